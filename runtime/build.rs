@@ -1,9 +1,12 @@
 use std::{env, fs, path::Path, process::Command};
 
 const GLSLC_PATH: &str = "D:\\VulkanSDK\\1.3.268.0\\Bin\\glslc.exe";
+/// Overrides `GLSLC_PATH`, e.g. `glslc` from the PATH on Linux CI.
+const GLSLC_ENV: &str = "GLSLC";
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:warning= start compiling GLSL shaders...");
+    let glslc_path = env::var(GLSLC_ENV).unwrap_or_else(|_| GLSLC_PATH.to_string());
 
     let project_dir = env::var("CARGO_MANIFEST_DIR")?;
     let shader_dir = Path::new(&project_dir)
@@ -37,7 +40,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("cargo:warning= compiling shader: {:?}", path);
                 let generated_file_name =
                     path.file_name().unwrap().to_str().unwrap().to_owned() + ".spv";
-                let result = Command::new(GLSLC_PATH)
+                let result = Command::new(&glslc_path)
                     .arg(&path)
                     .arg("-o")
                     .arg(spv_dir.join(&generated_file_name))
@@ -73,6 +76,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     fs::write(&shader_rs_path, shader_rs_content)?;
 
     println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed={GLSLC_ENV}");
     println!("cargo:rerun-if-changed=src/shader/glsl");
     println!("cargo:rerun-if-changed={}", shader_rs_path.display());
 
@@ -1,6 +1,9 @@
-use std::env;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
-use anyhow::anyhow;
+use anyhow::{Result, anyhow};
 use winit::{
     application::ApplicationHandler,
     event::{DeviceEvent, DeviceId, ElementState, KeyEvent, MouseButton, WindowEvent},
@@ -13,24 +16,42 @@ use crate::{
     function::{framework::scene::scene::SceneTrait, render::{render_system::RenderPipelineType, window_system::WindowCreateInfo}},
 };
 
+const S_HEADLESS_DELTA_TIME: f32 = 1.0 / 60.0;
+
 pub struct App {
     engine: Engine,
+    headless_frame_count: Option<u32>,
+    render_offscreen: bool,
+    capture_frame_path: Option<PathBuf>,
 }
 
 impl App {
     pub fn new() -> Self {
-        let _ = pretty_env_logger::try_init();
         let executable_path = env::current_exe().unwrap();
         let config_file_path = executable_path
             .parent()
             .ok_or_else(|| anyhow!("Failed to get parent directory"))
             .unwrap()
             .join("PiccoloEditor.ini");
+        Self::with_config(&config_file_path)
+    }
+
+    /// Creates the app from an explicit config file, e.g. `configs/PiccoloTest.ini` in tests
+    /// where the executable does not sit next to one.
+    pub fn with_config(config_file_path: &Path) -> Self {
+        let _ = pretty_env_logger::try_init();
         Self {
-            engine: Engine::new(&config_file_path),
+            engine: Engine::new(config_file_path),
+            headless_frame_count: None,
+            render_offscreen: false,
+            capture_frame_path: None,
         }
     }
 
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
     pub fn set_window_create_info(&mut self, window_create_info: WindowCreateInfo) {
         self.engine.set_window_create_info(window_create_info);
     }
@@ -39,12 +60,116 @@ impl App {
         self.engine.set_render_pipeline_type(render_pipeline_type);
     }
 
+    pub fn set_fixed_delta_time(&mut self, fixed_delta_time: Option<f32>) {
+        self.engine.set_fixed_delta_time(fixed_delta_time);
+    }
+
+    /// Records every frame's input and delta time to `path`, written on shutdown.
+    pub fn record_input(&mut self, path: &Path) {
+        self.engine.start_input_recording(path);
+    }
+
+    /// Drives the engine from a recording instead of live input, closing when it ends.
+    pub fn replay_input(&mut self, path: &Path) -> Result<()> {
+        self.engine.start_input_replay(path, true)
+    }
+
+    /// Applies `--record-input <path>`, `--replay-input <path>`,
+    /// `--fixed-delta-time <seconds>`, `--headless <frames>`, `--offscreen` and
+    /// `--capture-frame <path>` from the process arguments.
+    pub fn apply_command_line_args(&mut self) -> Result<()> {
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--record-input" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--record-input expects a path"))?;
+                    self.record_input(Path::new(&path));
+                }
+                "--replay-input" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--replay-input expects a path"))?;
+                    self.replay_input(Path::new(&path))?;
+                }
+                "--fixed-delta-time" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--fixed-delta-time expects seconds"))?;
+                    self.set_fixed_delta_time(Some(value.parse()?));
+                }
+                "--headless" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--headless expects a frame count"))?;
+                    self.headless_frame_count = Some(value.parse()?);
+                }
+                "--offscreen" => self.render_offscreen = true,
+                "--capture-frame" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--capture-frame expects a path"))?;
+                    self.capture_frame_path = Some(PathBuf::from(path));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
     pub fn run(&mut self) {
+        if let Some(frame_count) = self.headless_frame_count {
+            let delta_time = self
+                .engine
+                .fixed_delta_time()
+                .unwrap_or(S_HEADLESS_DELTA_TIME);
+            let frames = self.run_headless(frame_count, delta_time).unwrap();
+            log::info!("headless run finished frames={frames}");
+            return;
+        }
         let event_loop = EventLoop::new().unwrap();
         event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
         event_loop.run_app(self).unwrap();
     }
 
+    /// Brings the app up without a window or GPU. Frames are then driven by `step_frames`.
+    pub fn initialize_headless(&mut self) {
+        self.engine.initialize_headless();
+    }
+
+    /// Brings the app up without a window, rendering into an offscreen image.
+    pub fn initialize_offscreen(&mut self) {
+        self.engine.initialize_offscreen();
+    }
+
+    /// Writes the last offscreen frame as a PNG.
+    pub fn save_frame(&self, path: &Path) -> Result<()> {
+        self.engine.render_system().borrow().save_frame(path)
+    }
+
+    /// Ticks the headless app `frame_count` frames of `delta_time` seconds each and returns
+    /// how many frames ran before the engine asked to close.
+    pub fn step_frames(&mut self, frame_count: u32, delta_time: f32) -> Result<u32> {
+        self.engine.step_frames(frame_count, delta_time)
+    }
+
+    /// Runs the app without a window for `frame_count` frames and shuts it down. Renders
+    /// offscreen when `--offscreen` was given, saving the last frame to `--capture-frame`.
+    pub fn run_headless(&mut self, frame_count: u32, delta_time: f32) -> Result<u32> {
+        if self.render_offscreen {
+            self.initialize_offscreen();
+        } else {
+            self.initialize_headless();
+        }
+        let frames = self.step_frames(frame_count, delta_time)?;
+        if let Some(path) = self.capture_frame_path.clone() {
+            self.save_frame(&path)?;
+        }
+        self.engine.shutdown_engine();
+        Ok(frames)
+    }
+
     pub fn add_system<T>(&mut self, system: T)
     where
         T: System + 'static,
@@ -7,7 +7,10 @@ use crate::{
     function::{
         framework::world::world_manager::WorldManager,
         global::global_context::RuntimeGlobalContext,
-        input::{game_command_system::GameCommandInputSystem, input_system::InputSystem},
+        input::{
+            game_command_system::GameCommandInputSystem, gamepad_system::GamepadSystem,
+            input_record_system::InputRecordSystem, input_system::InputSystem,
+        },
         render::{
             render_system::{RenderPipelineType, RenderSystem},
             window_system::{WindowCreateInfo, WindowSystem},
@@ -37,6 +40,7 @@ struct EngineState {
     m_average_duration: f32,
     m_fps: u32,
     m_is_editor_mode: bool,
+    m_fixed_delta_time: Option<f32>,
     m_window_create_info: WindowCreateInfo,
     m_render_pipeline_type: RenderPipelineType,
 }
@@ -51,6 +55,7 @@ impl Engine {
                 m_average_duration: 0.0,
                 m_fps: 0,
                 m_is_editor_mode: false,
+                m_fixed_delta_time: None,
                 m_window_create_info: WindowCreateInfo::default(),
                 m_render_pipeline_type: RenderPipelineType::PBR,
             }),
@@ -66,6 +71,36 @@ impl Engine {
         self.m_state.borrow_mut().m_render_pipeline_type = render_pipeline_type;
     }
 
+    /// Replaces the measured frame time with a constant step, for reproducible runs.
+    pub fn set_fixed_delta_time(&mut self, fixed_delta_time: Option<f32>) {
+        self.m_state.borrow_mut().m_fixed_delta_time = fixed_delta_time;
+    }
+
+    pub fn fixed_delta_time(&self) -> Option<f32> {
+        self.m_state.borrow().m_fixed_delta_time
+    }
+
+    pub fn start_input_recording(&self, path: &Path) {
+        self.m_runtime_context
+            .input_record_system()
+            .borrow_mut()
+            .start_recording(path);
+    }
+
+    pub fn stop_input_recording(&self) -> Result<()> {
+        self.m_runtime_context
+            .input_record_system()
+            .borrow_mut()
+            .stop_recording()
+    }
+
+    pub fn start_input_replay(&self, path: &Path, close_on_end: bool) -> Result<()> {
+        self.m_runtime_context
+            .input_record_system()
+            .borrow_mut()
+            .start_replay(path, close_on_end)
+    }
+
     pub fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         self.m_runtime_context.resumed_instance(
             event_loop,
@@ -73,6 +108,20 @@ impl Engine {
             self.m_state.borrow().m_render_pipeline_type.clone(),
         );
     }
+    /// Brings the engine up without a window or Vulkan device, so scenes can be stepped
+    /// with `step_frames` in tests and simulations. The window create info only supplies
+    /// the viewport size.
+    pub fn initialize_headless(&mut self) {
+        self.initialize_without_window(false);
+    }
+
+    /// Like `initialize_headless`, but the render pipeline runs into an offscreen image that
+    /// `RenderSystem::capture_frame` reads back. Needs a Vulkan driver, which can be a
+    /// software one.
+    pub fn initialize_offscreen(&mut self) {
+        self.initialize_without_window(true);
+    }
+
     pub fn initialize(engine: &Engine) {
         engine.m_state.borrow_mut().m_last_tick_time_point = Instant::now();
         engine
@@ -91,8 +140,9 @@ impl Engine {
         let delta_time = now
             .duration_since(self.m_state.borrow().m_last_tick_time_point)
             .as_secs_f32();
-        self.m_state.borrow_mut().m_last_tick_time_point = now;
-        delta_time
+        let mut state = self.m_state.borrow_mut();
+        state.m_last_tick_time_point = now;
+        state.m_fixed_delta_time.unwrap_or(delta_time)
     }
 
     pub fn tick_one_frame(&self, delta_time: f32) -> Result<bool> {
@@ -100,6 +150,8 @@ impl Engine {
             .render_system()
             .borrow_mut()
             .swap_logic_render_data();
+        self.gamepad_tick();
+        let delta_time = self.input_record_tick(delta_time);
         self.logical_tick(delta_time);
         self.calculate_fps(delta_time);
         self.renderer_tick(delta_time)?;
@@ -114,6 +166,24 @@ impl Engine {
             .should_close())
     }
 
+    /// Ticks up to `frame_count` frames with a constant delta time, stopping early if the
+    /// engine asks to close. Returns the number of frames ticked.
+    pub fn step_frames(&self, frame_count: u32, delta_time: f32) -> Result<u32> {
+        for frame in 0..frame_count {
+            if !self.tick_one_frame(delta_time)? {
+                return Ok(frame + 1);
+            }
+        }
+        Ok(frame_count)
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.m_runtime_context
+            .render_system()
+            .borrow()
+            .is_headless()
+    }
+
     pub fn is_editor_mode(&self) -> bool {
         self.m_state.borrow().m_is_editor_mode
     }
@@ -124,6 +194,22 @@ impl Engine {
 }
 
 impl Engine {
+    fn initialize_without_window(&mut self, render_offscreen: bool) {
+        let (window_create_info, render_pipeline_type) = {
+            let state = self.m_state.borrow();
+            (
+                state.m_window_create_info.clone(),
+                state.m_render_pipeline_type.clone(),
+            )
+        };
+        self.m_runtime_context.headless_instance(
+            window_create_info,
+            render_pipeline_type,
+            render_offscreen,
+        );
+        Engine::initialize(self);
+    }
+
     fn renderer_tick(&self, delta_time: f32) -> Result<()> {
         let window_size = self
             .m_runtime_context
@@ -142,14 +228,52 @@ impl Engine {
         Ok(())
     }
 
+    fn gamepad_tick(&self) {
+        let events = self
+            .m_runtime_context
+            .gamepad_system()
+            .borrow_mut()
+            .poll_events();
+        let gamepad_system = self.m_runtime_context.gamepad_system().borrow();
+        events
+            .iter()
+            .for_each(|event| gamepad_system.on_event(self, event));
+    }
+
+    fn input_record_tick(&self, delta_time: f32) -> f32 {
+        let record_system = self.m_runtime_context.input_record_system();
+        if record_system.borrow().is_recording() {
+            record_system.borrow_mut().end_recorded_frame(delta_time);
+            return delta_time;
+        }
+        if !record_system.borrow().is_replaying() {
+            return delta_time;
+        }
+        let frame = record_system.borrow_mut().next_replay_frame();
+        match frame {
+            Some(frame) => {
+                frame.events.iter().for_each(|event| event.dispatch(self));
+                frame.delta_time
+            }
+            None => {
+                if record_system.borrow().close_on_replay_end() {
+                    self.m_runtime_context
+                        .window_system()
+                        .borrow()
+                        .request_close();
+                }
+                delta_time
+            }
+        }
+    }
+
     fn logical_tick(&self, delta_time: f32) {
-        let render_system = self.m_runtime_context.render_system().borrow();
-        let rhi = render_system.get_rhi();
-        let swapchain_info = rhi.get_swapchain_info();
-        let viewport = [
-            swapchain_info.extent.width as f32,
-            swapchain_info.extent.height as f32,
-        ];
+        let (width, height) = self
+            .m_runtime_context
+            .render_system()
+            .borrow()
+            .get_output_extent();
+        let viewport = [width as f32, height as f32];
         {
             let mut ui_runtime = self.m_runtime_context.ui_runtime().borrow_mut();
             ui_runtime.set_viewport(viewport);
@@ -191,6 +315,12 @@ impl Engine {
     pub fn input_system(&self) -> &RefCell<GameCommandInputSystem> {
         &self.m_runtime_context.input_system()
     }
+    pub fn gamepad_system(&self) -> &RefCell<GamepadSystem> {
+        &self.m_runtime_context.gamepad_system()
+    }
+    pub fn input_record_system(&self) -> &RefCell<InputRecordSystem> {
+        &self.m_runtime_context.input_record_system()
+    }
     pub fn asset_manager(&self) -> &AssetManager {
         &self.m_runtime_context.asset_manager()
     }
@@ -3,4 +3,6 @@ pub mod character_component;
 pub mod component;
 pub mod mesh;
 pub mod motor_component;
+pub mod point_light_component;
+pub mod spot_light_component;
 pub mod transform_component;
@@ -73,6 +73,9 @@ impl MeshComponent {
                     .to_str()
                     .unwrap()
                     .to_string();
+                mesh_component.m_material_desc.m_atlas_tile_count = material_res.m_atlas_tile_count;
+                mesh_component.m_material_desc.m_alpha_mode = material_res.m_alpha_mode;
+                mesh_component.m_material_desc.m_alpha_cutoff = material_res.m_alpha_cutoff;
             }
             mesh_component.m_transform_desc.m_transform_matrix = sub_mesh.m_transform.get_matrix();
         }
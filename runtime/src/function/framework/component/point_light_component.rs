@@ -0,0 +1,102 @@
+use std::any::Any;
+
+use crate::{
+    core::math::vector3::Vector3,
+    engine::Engine,
+    function::{
+        framework::{
+            component::{component::ComponentTrait, transform_component::TransformComponent},
+            object::{object::GObject, object_id_allocator::GObjectID},
+        },
+        render::render_swap_context::PointLightDesc,
+    },
+};
+
+#[derive(Clone, Default)]
+pub struct PointLightComponent {
+    pub m_flux: Vector3,
+    /// Offset from the owning object's transform, or the world position if it has none.
+    pub m_position: Vector3,
+
+    m_world_position: Vector3,
+    m_owner_id: Option<GObjectID>,
+    m_is_dirty: bool,
+}
+
+impl PointLightComponent {
+    pub fn new(position: Vector3, flux: Vector3) -> Self {
+        Self {
+            m_flux: flux,
+            m_position: position,
+            m_world_position: position,
+            m_owner_id: None,
+            m_is_dirty: true,
+        }
+    }
+
+    pub fn set_flux(&mut self, flux: Vector3) {
+        self.m_flux = flux;
+        self.m_is_dirty = true;
+    }
+
+    pub fn set_position(&mut self, position: Vector3) {
+        self.m_position = position;
+        self.m_is_dirty = true;
+    }
+
+    pub fn get_world_position(&self) -> &Vector3 {
+        &self.m_world_position
+    }
+}
+
+impl ComponentTrait for PointLightComponent {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn on_delete(&mut self, engine: &Engine) {
+        if let Some(owner_id) = self.m_owner_id.take() {
+            engine
+                .render_system()
+                .borrow()
+                .get_logic_swap_data()
+                .borrow_mut()
+                .add_delete_point_light(owner_id);
+        }
+    }
+
+    fn tick(&mut self, engine: &Engine, gobject: &GObject, _delta_time: f32) {
+        // the transform's dirty flag is cleared by the mesh component, so compare positions
+        let world_position = match gobject.get_component::<TransformComponent>() {
+            Some(transform) => Vector3::from_homogeneous(
+                &(transform.get_matrix() * self.m_position.to_homogeneous()),
+            ),
+            None => self.m_position,
+        };
+        if world_position != self.m_world_position {
+            self.m_world_position = world_position;
+            self.m_is_dirty = true;
+        }
+
+        if self.m_is_dirty {
+            self.m_is_dirty = false;
+            self.m_owner_id = Some(gobject.get_id());
+            engine
+                .render_system()
+                .borrow()
+                .get_logic_swap_data()
+                .borrow_mut()
+                .add_dirty_point_light(
+                    gobject.get_id(),
+                    PointLightDesc {
+                        m_position: self.m_world_position,
+                        m_flux: self.m_flux,
+                    },
+                );
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::any::Any;
+
+use crate::{
+    core::math::{vector3::Vector3, vector4::Vector4},
+    engine::Engine,
+    function::{
+        framework::{
+            component::{component::ComponentTrait, transform_component::TransformComponent},
+            object::{object::GObject, object_id_allocator::GObjectID},
+        },
+        render::render_swap_context::SpotLightDesc,
+    },
+};
+
+#[derive(Clone, Default)]
+pub struct SpotLightComponent {
+    pub m_flux: Vector3,
+    /// Offset from the owning object's transform, or the world position if it has none.
+    pub m_position: Vector3,
+    /// Direction in the owning object's space, rotated by its transform.
+    pub m_direction: Vector3,
+    /// Half angle in radians within which the light is at full strength.
+    pub m_inner_cone_angle: f32,
+    /// Half angle in radians beyond which the light contributes nothing.
+    pub m_outer_cone_angle: f32,
+    pub m_cast_shadow: bool,
+
+    m_world_position: Vector3,
+    m_world_direction: Vector3,
+    m_owner_id: Option<GObjectID>,
+    m_is_dirty: bool,
+}
+
+impl SpotLightComponent {
+    pub fn new(
+        position: Vector3,
+        direction: Vector3,
+        flux: Vector3,
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    ) -> Self {
+        Self {
+            m_flux: flux,
+            m_position: position,
+            m_direction: direction,
+            m_inner_cone_angle: inner_cone_angle,
+            m_outer_cone_angle: outer_cone_angle,
+            m_cast_shadow: true,
+            m_world_position: position,
+            m_world_direction: direction.normalize(),
+            m_owner_id: None,
+            m_is_dirty: true,
+        }
+    }
+
+    pub fn set_flux(&mut self, flux: Vector3) {
+        self.m_flux = flux;
+        self.m_is_dirty = true;
+    }
+
+    pub fn set_position(&mut self, position: Vector3) {
+        self.m_position = position;
+        self.m_is_dirty = true;
+    }
+
+    pub fn set_direction(&mut self, direction: Vector3) {
+        self.m_direction = direction;
+        self.m_is_dirty = true;
+    }
+
+    pub fn set_cone_angles(&mut self, inner_cone_angle: f32, outer_cone_angle: f32) {
+        self.m_inner_cone_angle = inner_cone_angle;
+        self.m_outer_cone_angle = outer_cone_angle;
+        self.m_is_dirty = true;
+    }
+
+    pub fn set_cast_shadow(&mut self, cast_shadow: bool) {
+        self.m_cast_shadow = cast_shadow;
+        self.m_is_dirty = true;
+    }
+
+    pub fn get_world_position(&self) -> &Vector3 {
+        &self.m_world_position
+    }
+
+    pub fn get_world_direction(&self) -> &Vector3 {
+        &self.m_world_direction
+    }
+}
+
+impl ComponentTrait for SpotLightComponent {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn on_delete(&mut self, engine: &Engine) {
+        if let Some(owner_id) = self.m_owner_id.take() {
+            engine
+                .render_system()
+                .borrow()
+                .get_logic_swap_data()
+                .borrow_mut()
+                .add_delete_spot_light(owner_id);
+        }
+    }
+
+    fn tick(&mut self, engine: &Engine, gobject: &GObject, _delta_time: f32) {
+        // the transform's dirty flag is cleared by the mesh component, so compare positions
+        let (world_position, world_direction) = match gobject.get_component::<TransformComponent>()
+        {
+            Some(transform) => {
+                let matrix = transform.get_matrix();
+                let direction = matrix
+                    * Vector4::new(
+                        self.m_direction.x,
+                        self.m_direction.y,
+                        self.m_direction.z,
+                        0.0,
+                    );
+                (
+                    Vector3::from_homogeneous(&(matrix * self.m_position.to_homogeneous())),
+                    Vector3::new(direction.x, direction.y, direction.z).normalize(),
+                )
+            }
+            None => (self.m_position, self.m_direction.normalize()),
+        };
+        if world_position != self.m_world_position || world_direction != self.m_world_direction {
+            self.m_world_position = world_position;
+            self.m_world_direction = world_direction;
+            self.m_is_dirty = true;
+        }
+
+        if self.m_is_dirty {
+            self.m_is_dirty = false;
+            self.m_owner_id = Some(gobject.get_id());
+            engine
+                .render_system()
+                .borrow()
+                .get_logic_swap_data()
+                .borrow_mut()
+                .add_dirty_spot_light(
+                    gobject.get_id(),
+                    SpotLightDesc {
+                        m_position: self.m_world_position,
+                        m_direction: self.m_world_direction,
+                        m_flux: self.m_flux,
+                        m_inner_cone_angle: self.m_inner_cone_angle,
+                        m_outer_cone_angle: self.m_outer_cone_angle,
+                        m_cast_shadow: self.m_cast_shadow,
+                    },
+                );
+        }
+    }
+}
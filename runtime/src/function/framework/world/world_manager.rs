@@ -3,10 +3,7 @@ use std::{cell::RefCell, collections::HashMap, rc::Rc};
 use crate::{
     engine::Engine,
     function::framework::scene::scene::SceneTrait,
-    resource::{
-        asset_manager::AssetManager, config_manager::ConfigManager,
-        res_type::common::world::WorldRes,
-    },
+    resource::{config_manager::ConfigManager, res_type::common::world::WorldRes},
 };
 use anyhow::Result;
 use log::info;
@@ -42,7 +39,7 @@ impl WorldManager {
 
     pub fn tick(&mut self, engine: &Engine, delta_time: f32) {
         if !self.m_is_world_loaded {
-            self.load_world(engine.asset_manager()).unwrap();
+            self.load_world(engine).unwrap();
         }
         if let Some(scene) = self.m_current_scene.as_ref() {
             let mut scene = scene.borrow_mut();
@@ -53,9 +50,19 @@ impl WorldManager {
         }
     }
 
-    fn load_world(&mut self, asset_manager: &AssetManager) -> Result<()> {
+    fn load_world(&mut self, engine: &Engine) -> Result<()> {
         info!("Loading world: {}", self.m_current_world_url);
-        let world_res: WorldRes = asset_manager.load_asset(&self.m_current_world_url)?;
+        let world_res: WorldRes = engine
+            .asset_manager()
+            .load_asset(&self.m_current_world_url)?;
+        if !world_res.m_render_pipeline_url.is_empty() {
+            engine
+                .render_system()
+                .borrow()
+                .get_logic_swap_data()
+                .borrow_mut()
+                .m_render_pipeline_url = Some(world_res.m_render_pipeline_url.clone());
+        }
         self.m_current_world_resource = world_res;
         self.m_is_world_loaded = true;
         info!("World load succeed!");
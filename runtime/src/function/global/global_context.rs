@@ -3,11 +3,20 @@ use std::{cell::RefCell, path::Path, rc::Rc};
 use winit::event_loop::ActiveEventLoop;
 
 use crate::{
+    engine::Engine,
     function::{
         framework::world::world_manager::WorldManager,
-        input::{game_command_system::GameCommandInputSystem, input_system::InputSystem},
+        input::{
+            game_command_system::GameCommandInputSystem,
+            gamepad_system::{GamepadEvent, GamepadSystem},
+            input_record_system::{InputRecordSystem, RecordedInputEvent},
+            input_system::InputSystem,
+        },
         render::{
-            render_system::{RenderPipelineType, RenderSystem, RenderSystemCreateInfo},
+            render_system::{
+                HeadlessRenderSystemCreateInfo, RenderPipelineType, RenderSystem,
+                RenderSystemCreateInfo,
+            },
             window_system::{WindowCreateInfo, WindowSystem},
         },
         ui::ui2::UiRuntime,
@@ -19,6 +28,8 @@ pub struct RuntimeGlobalContext {
     m_config_manager: ConfigManager,
     m_asset_manager: AssetManager,
     m_input_system: RefCell<GameCommandInputSystem>,
+    m_gamepad_system: RefCell<GamepadSystem>,
+    m_input_record_system: RefCell<InputRecordSystem>,
     m_world_manager: RefCell<WorldManager>,
     m_window_system: Rc<RefCell<WindowSystem>>,
     m_render_system: Option<RefCell<RenderSystem>>,
@@ -35,6 +46,8 @@ impl RuntimeGlobalContext {
             m_config_manager: config_manager,
             m_asset_manager: asset_manager,
             m_input_system: RefCell::new(GameCommandInputSystem::default()),
+            m_gamepad_system: RefCell::new(GamepadSystem::default()),
+            m_input_record_system: RefCell::new(InputRecordSystem::default()),
             m_world_manager: RefCell::new(WorldManager::default()),
             m_window_system: Rc::new(RefCell::new(WindowSystem::default())),
             m_render_system: None,
@@ -56,6 +69,7 @@ impl RuntimeGlobalContext {
             .borrow_mut()
             .initialize(event_loop, window_create_info)
             .unwrap();
+        self.m_gamepad_system.borrow_mut().initialize();
 
         self.register_input_system();
 
@@ -72,10 +86,55 @@ impl RuntimeGlobalContext {
         self.m_render_system = Some(RefCell::new(render_system));
     }
 
+    /// Starts the runtime without an event loop or window. Gamepads are not polled so only
+    /// injected input reaches the game. With `render_offscreen` the render pipeline still runs
+    /// on the GPU into an offscreen image, otherwise Vulkan is never touched.
+    pub fn headless_instance(
+        &mut self,
+        window_create_info: WindowCreateInfo,
+        render_pipeline_type: RenderPipelineType,
+        render_offscreen: bool,
+    ) {
+        let viewport_size = (window_create_info.width, window_create_info.height);
+        self.m_window_system
+            .borrow_mut()
+            .initialize_headless(window_create_info);
+
+        self.register_input_system();
+
+        let render_system = if render_offscreen {
+            RenderSystem::create(&RenderSystemCreateInfo {
+                window_system: &self.m_window_system.borrow(),
+                asset_manager: &self.m_asset_manager,
+                config_manager: &self.m_config_manager,
+                render_pipeline_type: render_pipeline_type,
+            })
+        } else {
+            RenderSystem::create_headless(&HeadlessRenderSystemCreateInfo {
+                viewport_size,
+                asset_manager: &self.m_asset_manager,
+                config_manager: &self.m_config_manager,
+            })
+        };
+        self.m_ui_runtime
+            .borrow_mut()
+            .load_font_texture(&self.m_config_manager)
+            .unwrap();
+        self.m_render_system = Some(RefCell::new(render_system));
+    }
+
     pub fn input_system(&self) -> &RefCell<GameCommandInputSystem> {
         &self.m_input_system
     }
 
+    pub fn gamepad_system(&self) -> &RefCell<GamepadSystem> {
+        &self.m_gamepad_system
+    }
+
+    pub fn input_record_system(&self) -> &RefCell<InputRecordSystem> {
+        &self.m_input_record_system
+    }
+
     pub fn window_system(&self) -> &Rc<RefCell<WindowSystem>> {
         &self.m_window_system
     }
@@ -101,14 +160,10 @@ impl RuntimeGlobalContext {
     }
 
     pub fn shutdown_systems(&self) {
+        if let Err(err) = self.m_input_record_system.borrow_mut().stop_recording() {
+            log::error!("failed to save input recording: {err}");
+        }
         self.m_ui_runtime.borrow_mut().destroy_textures();
-        self.m_render_system
-            .as_ref()
-            .unwrap()
-            .borrow()
-            .get_rhi()
-            .wait_idle()
-            .unwrap();
         self.m_render_system
             .as_ref()
             .unwrap()
@@ -122,36 +177,65 @@ impl RuntimeGlobalContext {
     fn register_input_system(&self) {
         let mut window_system = self.window_system().borrow_mut();
 
-        window_system.register_on_key_func(move |engine, device_id, event, is_synthetic| {
-            engine
-                .input_system()
-                .borrow_mut()
-                .on_key(engine, device_id, event, is_synthetic);
+        window_system.register_on_key_func(move |engine, _device_id, event, _is_synthetic| {
+            Self::on_live_input(engine, RecordedInputEvent::from_key_event(event));
         });
 
-        window_system.register_on_mouse_motion(move |engine, device_id, position| {
-            engine
-                .input_system()
-                .borrow_mut()
-                .on_mouse_motion(engine, device_id, position);
+        window_system.register_on_mouse_motion(move |engine, _device_id, position| {
+            Self::on_live_input(engine, RecordedInputEvent::MouseMotion { delta: position });
         });
 
-        window_system.register_on_cursor_pos_func(move |engine, device_id, position| {
-            engine
-                .input_system()
-                .borrow_mut()
-                .on_cursor_pos(device_id, position);
+        window_system.register_on_cursor_pos_func(move |engine, _device_id, position| {
+            Self::on_live_input(
+                engine,
+                RecordedInputEvent::CursorPos {
+                    x: position.x,
+                    y: position.y,
+                },
+            );
         });
 
-        window_system.register_on_mouse_button_func(move |engine, device_id, state, button| {
-            engine
-                .input_system()
-                .borrow_mut()
-                .on_mouse_button(device_id, state, button);
+        window_system.register_on_mouse_button_func(move |engine, _device_id, state, button| {
+            Self::on_live_input(engine, RecordedInputEvent::MouseButton { button, state });
         });
 
         window_system.register_on_ime_func(move |engine, ime| {
-            engine.input_system().borrow_mut().on_ime(ime);
+            Self::on_live_input(engine, RecordedInputEvent::Ime(ime.clone()));
+        });
+
+        let mut gamepad_system = self.gamepad_system().borrow_mut();
+
+        gamepad_system.register_on_button_func(move |engine, gamepad_id, button, state| {
+            Self::on_live_input(
+                engine,
+                RecordedInputEvent::Gamepad(GamepadEvent::Button {
+                    gamepad_id,
+                    button,
+                    state,
+                }),
+            );
+        });
+
+        gamepad_system.register_on_axis_func(move |engine, gamepad_id, axis, value| {
+            Self::on_live_input(
+                engine,
+                RecordedInputEvent::Gamepad(GamepadEvent::Axis {
+                    gamepad_id,
+                    axis,
+                    value,
+                }),
+            );
         });
     }
+
+    /// Live input goes through the same dispatch as a replay, and is dropped while one runs.
+    fn on_live_input(engine: &Engine, event: RecordedInputEvent) {
+        if engine
+            .input_record_system()
+            .borrow_mut()
+            .on_live_event(&event)
+        {
+            event.dispatch(engine);
+        }
+    }
 }
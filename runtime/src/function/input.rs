@@ -1,2 +1,4 @@
 pub mod game_command_system;
+pub mod gamepad_system;
+pub mod input_record_system;
 pub mod input_system;
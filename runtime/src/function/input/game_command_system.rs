@@ -1,14 +1,19 @@
+use std::collections::HashMap;
+
 use bitflags::bitflags;
 use winit::{
     dpi::PhysicalPosition,
-    event::{DeviceId, ElementState, Ime, KeyEvent, MouseButton},
+    event::{DeviceId, ElementState, Ime, MouseButton},
     keyboard::{KeyCode, PhysicalKey},
 };
 
 use crate::{
     engine::Engine,
     function::{
-        input::input_system::InputSystem,
+        input::{
+            gamepad_system::{GamepadAxis, GamepadButton, GamepadStickConfig},
+            input_system::{InputButtonState, InputSystem},
+        },
         render::{render_system::RenderSystem, window_system::WindowSystem},
         ui::ui2::UiInputSnapshot,
     },
@@ -16,7 +21,7 @@ use crate::{
 
 bitflags! {
     #[repr(transparent)]
-    #[derive(Default, Debug)]
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
     pub struct GameCommand: u32 {
         const forward       = 1 << 0;
         const backward      = 1 << 1;
@@ -33,6 +38,38 @@ bitflags! {
     }
 }
 
+/// Shaped stick magnitude above which the left stick counts as a movement command.
+const S_GAMEPAD_MOVE_THRESHOLD: f32 = 0.3;
+/// Commands driven by the left stick.
+const S_GAMEPAD_MOVE_COMMAND: GameCommand = GameCommand::forward
+    .union(GameCommand::backward)
+    .union(GameCommand::left)
+    .union(GameCommand::right);
+/// Hotbar slots reachable by the bumpers, matching the bound digit keys.
+const S_GAMEPAD_BLOCK_SLOT_COUNT: u8 = 8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct GamepadConfig {
+    pub move_stick: GamepadStickConfig,
+    pub look_stick: GamepadStickConfig,
+    /// Radians per second at full right-stick deflection.
+    pub look_yaw_speed: f32,
+    pub look_pitch_speed: f32,
+    pub invert_look_y: bool,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            move_stick: GamepadStickConfig::default(),
+            look_stick: GamepadStickConfig::default(),
+            look_yaw_speed: 3.0,
+            look_pitch_speed: 2.0,
+            invert_look_y: false,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct GameCommandInputSystem {
     m_cursor_delta_x: i32,
@@ -42,13 +79,22 @@ pub struct GameCommandInputSystem {
     m_cursor_delta_pitch: f32,
 
     m_game_command: GameCommand,
+    m_keyboard_command: GameCommand,
+    m_gamepad_command: GameCommand,
+    m_gamepad_config: GamepadConfig,
+    m_gamepad_left_stick: [f32; 2],
+    m_gamepad_right_stick: [f32; 2],
+    m_ui_nav_move: i32,
+    m_ui_nav_activate: bool,
     m_selected_block_slot: u8,
     m_cursor_pos: [f32; 2],
-    m_mouse_down: [bool; 3],
+    m_key_states: HashMap<KeyCode, InputButtonState>,
+    m_mouse_button_states: [InputButtonState; 3],
+    /// Set while a `UiRuntime` text input owns the keyboard; game commands and key
+    /// queries then ignore key input.
+    m_keyboard_captured_by_ui: bool,
     m_committed_text: String,
     m_ime_preedit: String,
-    m_key_backspace_pressed: bool,
-    m_key_enter_pressed: bool,
 }
 
 impl GameCommandInputSystem {
@@ -57,7 +103,65 @@ impl GameCommandInputSystem {
     }
 
     pub fn is_mouse_button_down(&self, button: usize) -> bool {
-        button < 3 && self.m_mouse_down[button]
+        self.mouse_button_state(button).down
+    }
+
+    /// True only in the frame the button went down.
+    pub fn is_mouse_button_pressed(&self, button: usize) -> bool {
+        self.mouse_button_state(button).pressed
+    }
+
+    /// True only in the frame the button went up.
+    pub fn is_mouse_button_released(&self, button: usize) -> bool {
+        self.mouse_button_state(button).released
+    }
+
+    pub fn mouse_button_held_duration(&self, button: usize) -> f32 {
+        self.mouse_button_state(button).held_duration
+    }
+
+    pub fn is_key_down(&self, key: KeyCode) -> bool {
+        self.key_state(key).down
+    }
+
+    /// True only in the frame the key went down; auto-repeat does not count.
+    pub fn is_key_pressed(&self, key: KeyCode) -> bool {
+        self.key_state(key).pressed
+    }
+
+    /// True only in the frame the key went up.
+    pub fn is_key_released(&self, key: KeyCode) -> bool {
+        self.key_state(key).released
+    }
+
+    pub fn key_held_duration(&self, key: KeyCode) -> f32 {
+        self.key_state(key).held_duration
+    }
+
+    /// Whether a UI text input currently receives keyboard input instead of the game.
+    pub fn is_keyboard_captured_by_ui(&self) -> bool {
+        self.m_keyboard_captured_by_ui
+    }
+
+    fn key_state(&self, key: KeyCode) -> InputButtonState {
+        if self.m_keyboard_captured_by_ui {
+            return InputButtonState::default();
+        }
+        self.m_key_states.get(&key).copied().unwrap_or_default()
+    }
+
+    fn raw_key_pressed_or_repeated(&self, key: KeyCode) -> bool {
+        self.m_key_states
+            .get(&key)
+            .map(|state| state.pressed || state.repeated)
+            .unwrap_or(false)
+    }
+
+    fn mouse_button_state(&self, button: usize) -> InputButtonState {
+        self.m_mouse_button_states
+            .get(button)
+            .copied()
+            .unwrap_or_default()
     }
 
     /// 1-based hotbar slot index (1..=9), default to 1.
@@ -67,53 +171,68 @@ impl GameCommandInputSystem {
 
     pub fn reset_game_command(&mut self) {
         self.m_game_command = GameCommand::empty();
+        self.m_keyboard_command = GameCommand::empty();
+        self.m_gamepad_command = GameCommand::empty();
+    }
+
+    pub fn get_gamepad_config(&self) -> &GamepadConfig {
+        &self.m_gamepad_config
+    }
+
+    pub fn set_gamepad_config(&mut self, config: GamepadConfig) {
+        self.m_gamepad_config = config;
+        self.update_gamepad_move_command();
+        self.refresh_game_command();
+    }
+
+    fn refresh_game_command(&mut self) {
+        self.m_game_command = (self.m_game_command & GameCommand::invalid)
+            | self.m_keyboard_command
+            | self.m_gamepad_command;
     }
 
     fn on_key_in_game_mode(
         &mut self,
         window_system: &WindowSystem,
-        _device_id: DeviceId,
-        event: &KeyEvent,
-        _is_synthetic: bool,
+        physical_key: PhysicalKey,
+        state: ElementState,
     ) {
-        self.m_game_command &= GameCommand::all() ^ GameCommand::jump;
-
-        match event.state {
-            ElementState::Pressed => match event.physical_key {
+        match state {
+            ElementState::Pressed => match physical_key {
                 PhysicalKey::Code(code) => match code {
                     KeyCode::KeyA => {
-                        self.m_game_command |= GameCommand::left;
+                        self.m_keyboard_command |= GameCommand::left;
                     }
                     KeyCode::KeyS => {
-                        self.m_game_command |= GameCommand::backward;
+                        self.m_keyboard_command |= GameCommand::backward;
                     }
                     KeyCode::KeyW => {
-                        self.m_game_command |= GameCommand::forward;
+                        self.m_keyboard_command |= GameCommand::forward;
                     }
                     KeyCode::KeyD => {
-                        self.m_game_command |= GameCommand::right;
+                        self.m_keyboard_command |= GameCommand::right;
                     }
                     KeyCode::Space => {
-                        self.m_game_command |= GameCommand::jump;
+                        self.m_keyboard_command |= GameCommand::jump;
                     }
                     KeyCode::KeyQ => {
-                        self.m_game_command |= GameCommand::up;
+                        self.m_keyboard_command |= GameCommand::up;
                     }
                     KeyCode::KeyE => {
-                        self.m_game_command |= GameCommand::down;
+                        self.m_keyboard_command |= GameCommand::down;
                     }
                     KeyCode::ControlLeft => {
-                        self.m_game_command |= GameCommand::squat;
+                        self.m_keyboard_command |= GameCommand::squat;
                     }
                     KeyCode::AltLeft => {
                         let mode = window_system.get_focus_mode();
                         window_system.set_focus_mode(!mode);
                     }
                     KeyCode::ShiftLeft => {
-                        self.m_game_command |= GameCommand::sprint;
+                        self.m_keyboard_command |= GameCommand::sprint;
                     }
                     KeyCode::KeyF => {
-                        self.m_game_command ^= GameCommand::free_camera;
+                        self.m_keyboard_command ^= GameCommand::free_camera;
                     }
                     KeyCode::Digit1 => self.m_selected_block_slot = 1,
                     KeyCode::Digit2 => self.m_selected_block_slot = 2,
@@ -122,35 +241,36 @@ impl GameCommandInputSystem {
                     KeyCode::Digit5 => self.m_selected_block_slot = 5,
                     KeyCode::Digit6 => self.m_selected_block_slot = 6,
                     KeyCode::Digit7 => self.m_selected_block_slot = 7,
+                    KeyCode::Digit8 => self.m_selected_block_slot = 8,
                     _ => {}
                 },
                 _ => {}
             },
-            ElementState::Released => match event.physical_key {
+            ElementState::Released => match physical_key {
                 PhysicalKey::Code(code) => match code {
                     KeyCode::KeyA => {
-                        self.m_game_command &= GameCommand::all() ^ GameCommand::left;
+                        self.m_keyboard_command &= GameCommand::all() ^ GameCommand::left;
                     }
                     KeyCode::KeyS => {
-                        self.m_game_command &= GameCommand::all() ^ GameCommand::backward;
+                        self.m_keyboard_command &= GameCommand::all() ^ GameCommand::backward;
                     }
                     KeyCode::KeyW => {
-                        self.m_game_command &= GameCommand::all() ^ GameCommand::forward;
+                        self.m_keyboard_command &= GameCommand::all() ^ GameCommand::forward;
                     }
                     KeyCode::KeyD => {
-                        self.m_game_command &= GameCommand::all() ^ GameCommand::right;
+                        self.m_keyboard_command &= GameCommand::all() ^ GameCommand::right;
                     }
                     KeyCode::KeyQ => {
-                        self.m_game_command &= GameCommand::all() ^ GameCommand::up;
+                        self.m_keyboard_command &= GameCommand::all() ^ GameCommand::up;
                     }
                     KeyCode::KeyE => {
-                        self.m_game_command &= GameCommand::all() ^ GameCommand::down;
+                        self.m_keyboard_command &= GameCommand::all() ^ GameCommand::down;
                     }
                     KeyCode::ControlLeft => {
-                        self.m_game_command &= GameCommand::squat;
+                        self.m_keyboard_command &= GameCommand::all() ^ GameCommand::squat;
                     }
                     KeyCode::ShiftLeft => {
-                        self.m_game_command &= GameCommand::sprint;
+                        self.m_keyboard_command &= GameCommand::all() ^ GameCommand::sprint;
                     }
                     _ => {}
                 },
@@ -159,6 +279,76 @@ impl GameCommandInputSystem {
         }
     }
 
+    fn set_keyboard_captured_by_ui(&mut self, captured: bool) {
+        if captured && !self.m_keyboard_captured_by_ui {
+            // Keys held when a text box takes focus must not keep driving the character.
+            self.m_keyboard_command &= GameCommand::free_camera;
+            self.m_gamepad_command &= GameCommand::all() ^ S_GAMEPAD_MOVE_COMMAND;
+            self.refresh_game_command();
+        }
+        self.m_keyboard_captured_by_ui = captured;
+    }
+
+    fn on_gamepad_button_in_game_mode(&mut self, button: GamepadButton, state: ElementState) {
+        let command = match button {
+            GamepadButton::South => GameCommand::jump,
+            GamepadButton::East => GameCommand::squat,
+            GamepadButton::LeftThumb => GameCommand::sprint,
+            GamepadButton::RightTrigger => GameCommand::fire,
+            GamepadButton::LeftBumper | GamepadButton::RightBumper => {
+                if state == ElementState::Pressed {
+                    let slot = self
+                        .get_selected_block_slot()
+                        .min(S_GAMEPAD_BLOCK_SLOT_COUNT);
+                    self.m_selected_block_slot = if button == GamepadButton::RightBumper {
+                        slot % S_GAMEPAD_BLOCK_SLOT_COUNT + 1
+                    } else if slot == 1 {
+                        S_GAMEPAD_BLOCK_SLOT_COUNT
+                    } else {
+                        slot - 1
+                    };
+                }
+                return;
+            }
+            _ => return,
+        };
+        match state {
+            ElementState::Pressed => self.m_gamepad_command |= command,
+            ElementState::Released => self.m_gamepad_command &= GameCommand::all() ^ command,
+        }
+    }
+
+    fn on_gamepad_button_in_ui_mode(&mut self, button: GamepadButton, state: ElementState) {
+        if state != ElementState::Pressed {
+            return;
+        }
+        match button {
+            GamepadButton::DPadUp | GamepadButton::DPadLeft => self.m_ui_nav_move -= 1,
+            GamepadButton::DPadDown | GamepadButton::DPadRight => self.m_ui_nav_move += 1,
+            GamepadButton::South => self.m_ui_nav_activate = true,
+            _ => {}
+        }
+    }
+
+    fn update_gamepad_move_command(&mut self) {
+        self.m_gamepad_command &= GameCommand::all() ^ S_GAMEPAD_MOVE_COMMAND;
+
+        let stick = self
+            .m_gamepad_config
+            .move_stick
+            .apply(self.m_gamepad_left_stick);
+        if stick[1] > S_GAMEPAD_MOVE_THRESHOLD {
+            self.m_gamepad_command |= GameCommand::forward;
+        } else if stick[1] < -S_GAMEPAD_MOVE_THRESHOLD {
+            self.m_gamepad_command |= GameCommand::backward;
+        }
+        if stick[0] > S_GAMEPAD_MOVE_THRESHOLD {
+            self.m_gamepad_command |= GameCommand::right;
+        } else if stick[0] < -S_GAMEPAD_MOVE_THRESHOLD {
+            self.m_gamepad_command |= GameCommand::left;
+        }
+    }
+
     fn clear(&mut self) {
         self.m_cursor_delta_x = 0;
         self.m_cursor_delta_y = 0;
@@ -168,6 +358,7 @@ impl GameCommandInputSystem {
         &mut self,
         window_system: &WindowSystem,
         render_system: &RenderSystem,
+        delta_time: f32,
     ) {
         let window_size = window_system.get_window_size();
 
@@ -185,6 +376,15 @@ impl GameCommandInputSystem {
 
         self.m_cursor_delta_yaw = cursor_delta_x * fov.x;
         self.m_cursor_delta_pitch = -cursor_delta_y * fov.y;
+
+        if window_system.get_focus_mode() {
+            let config = &self.m_gamepad_config;
+            let stick = config.look_stick.apply(self.m_gamepad_right_stick);
+            let pitch_sign = if config.invert_look_y { 1.0 } else { -1.0 };
+            self.m_cursor_delta_yaw -= stick[0] * config.look_yaw_speed * delta_time;
+            self.m_cursor_delta_pitch +=
+                pitch_sign * stick[1] * config.look_pitch_speed * delta_time;
+        }
     }
 
     pub fn cursor_delta_yaw(&self) -> f32 {
@@ -197,38 +397,42 @@ impl GameCommandInputSystem {
 }
 
 impl InputSystem for GameCommandInputSystem {
-    fn on_key(
+    fn on_key_input(
         &mut self,
         engine: &Engine,
-        device_id: DeviceId,
-        event: &KeyEvent,
-        is_synthetic: bool,
+        physical_key: PhysicalKey,
+        state: ElementState,
+        text: Option<&str>,
     ) {
-        if event.state == ElementState::Pressed {
-            if let PhysicalKey::Code(KeyCode::Escape) = event.physical_key {
-                engine.window_system().borrow().request_close();
+        // a text box focused during the last tick must see this key, not the game
+        self.set_keyboard_captured_by_ui(engine.ui_runtime().borrow().wants_keyboard_input());
+        let level_changed = match physical_key {
+            PhysicalKey::Code(code) => self.m_key_states.entry(code).or_default().on_event(state),
+            PhysicalKey::Unidentified(_) => true,
+        };
+        if state == ElementState::Pressed {
+            if let PhysicalKey::Code(KeyCode::Escape) = physical_key {
+                if self.m_keyboard_captured_by_ui {
+                    engine.ui_runtime().borrow_mut().clear_keyboard_focus();
+                } else {
+                    engine.window_system().borrow().request_close();
+                }
                 return;
             }
         }
-        if !engine.is_editor_mode() {
+        if !engine.is_editor_mode() && !self.m_keyboard_captured_by_ui && level_changed {
             let window_system = engine.window_system().borrow();
-            self.on_key_in_game_mode(&window_system, device_id, event, is_synthetic);
+            self.on_key_in_game_mode(&window_system, physical_key, state);
+            self.refresh_game_command();
         }
-        if event.state == ElementState::Pressed {
-            if let Some(text) = event.text.as_ref() {
+        if state == ElementState::Pressed {
+            if let Some(text) = text {
                 for ch in text.chars() {
                     if !ch.is_control() && ch != '\n' && ch != '\r' {
                         self.m_committed_text.push(ch);
                     }
                 }
             }
-            if let PhysicalKey::Code(code) = event.physical_key {
-                match code {
-                    KeyCode::Backspace => self.m_key_backspace_pressed = true,
-                    KeyCode::Enter | KeyCode::NumpadEnter => self.m_key_enter_pressed = true,
-                    _ => {}
-                }
-            }
         }
     }
 
@@ -252,7 +456,7 @@ impl InputSystem for GameCommandInputSystem {
             _ => None,
         };
         if let Some(idx) = idx {
-            self.m_mouse_down[idx] = state == ElementState::Pressed;
+            self.m_mouse_button_states[idx].on_event(state);
         }
     }
 
@@ -271,23 +475,80 @@ impl InputSystem for GameCommandInputSystem {
         }
     }
 
-    fn tick(&mut self, engine: &Engine, _delta_time: f32) {
+    fn on_gamepad_button(
+        &mut self,
+        engine: &Engine,
+        _gamepad_id: usize,
+        button: GamepadButton,
+        state: ElementState,
+    ) {
+        let window_system = engine.window_system().borrow();
+        if button == GamepadButton::Start && state == ElementState::Pressed {
+            let mode = window_system.get_focus_mode();
+            window_system.set_focus_mode(!mode);
+            return;
+        }
+        if window_system.get_focus_mode() && !engine.is_editor_mode() {
+            self.on_gamepad_button_in_game_mode(button, state);
+            self.refresh_game_command();
+        } else {
+            self.on_gamepad_button_in_ui_mode(button, state);
+        }
+    }
+
+    fn on_gamepad_axis(
+        &mut self,
+        engine: &Engine,
+        _gamepad_id: usize,
+        axis: GamepadAxis,
+        value: f32,
+    ) {
+        match axis {
+            GamepadAxis::LeftStickX => self.m_gamepad_left_stick[0] = value,
+            GamepadAxis::LeftStickY => self.m_gamepad_left_stick[1] = value,
+            GamepadAxis::RightStickX => self.m_gamepad_right_stick[0] = value,
+            GamepadAxis::RightStickY => self.m_gamepad_right_stick[1] = value,
+        }
+        // like keys, the stick only moves the character while the game owns the input
+        if !engine.is_editor_mode() && !self.m_keyboard_captured_by_ui {
+            self.update_gamepad_move_command();
+        } else {
+            self.m_gamepad_command &= GameCommand::all() ^ S_GAMEPAD_MOVE_COMMAND;
+        }
+        self.refresh_game_command();
+    }
+
+    fn tick(&mut self, engine: &Engine, delta_time: f32) {
         let ui_runtime = engine.ui_runtime();
         let window_system = &engine.window_system().borrow();
         let render_system = &engine.render_system().borrow();
         ui_runtime.borrow_mut().update_input(UiInputSnapshot {
             mouse_pos: self.m_cursor_pos,
-            mouse_down: self.m_mouse_down,
+            mouse_down: self.m_mouse_button_states.map(|state| state.down),
             mouse_wheel: 0.0,
             text_input: self.m_committed_text.clone(),
             ime_preedit: self.m_ime_preedit.clone(),
-            key_backspace_pressed: self.m_key_backspace_pressed,
-            key_enter_pressed: self.m_key_enter_pressed,
+            key_backspace_pressed: self.raw_key_pressed_or_repeated(KeyCode::Backspace),
+            key_enter_pressed: self.raw_key_pressed_or_repeated(KeyCode::Enter)
+                || self.raw_key_pressed_or_repeated(KeyCode::NumpadEnter),
+            nav_move: self.m_ui_nav_move.signum(),
+            nav_activate: self.m_ui_nav_activate,
         });
         self.m_committed_text.clear();
-        self.m_key_backspace_pressed = false;
-        self.m_key_enter_pressed = false;
-        self.calculate_cursor_delta_angles(window_system, render_system);
+        self.set_keyboard_captured_by_ui(ui_runtime.borrow().wants_keyboard_input());
+        self.m_key_states
+            .values_mut()
+            .for_each(|state| state.end_frame(delta_time));
+        self.m_mouse_button_states
+            .iter_mut()
+            .for_each(|state| state.end_frame(delta_time));
+        // jump fires in the frame its key or button went down, like `is_key_pressed`
+        self.m_keyboard_command &= GameCommand::all() ^ GameCommand::jump;
+        self.m_gamepad_command &= GameCommand::all() ^ GameCommand::jump;
+        self.refresh_game_command();
+        self.m_ui_nav_move = 0;
+        self.m_ui_nav_activate = false;
+        self.calculate_cursor_delta_angles(window_system, render_system, delta_time);
         self.clear();
 
         if window_system.get_focus_mode() {
@@ -0,0 +1,282 @@
+use std::collections::VecDeque;
+
+#[cfg(feature = "gamepad")]
+use gilrs::{Axis, Button, EventType, Gilrs};
+use serde::{Deserialize, Serialize};
+use winit::event::ElementState;
+
+use crate::engine::Engine;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GamepadEvent {
+    Button {
+        gamepad_id: usize,
+        button: GamepadButton,
+        state: ElementState,
+    },
+    Axis {
+        gamepad_id: usize,
+        axis: GamepadAxis,
+        value: f32,
+    },
+}
+
+/// Radial deadzone plus power response curve applied to a stick.
+#[derive(Clone, Copy, Debug)]
+pub struct GamepadStickConfig {
+    pub deadzone: f32,
+    pub outer_deadzone: f32,
+    pub response_exponent: f32,
+}
+
+impl Default for GamepadStickConfig {
+    fn default() -> Self {
+        Self {
+            deadzone: 0.15,
+            outer_deadzone: 0.95,
+            response_exponent: 2.0,
+        }
+    }
+}
+
+impl GamepadStickConfig {
+    /// Maps raw stick values to a shaped vector whose length is in `0.0..=1.0`.
+    pub fn apply(&self, raw: [f32; 2]) -> [f32; 2] {
+        let magnitude = (raw[0] * raw[0] + raw[1] * raw[1]).sqrt();
+        if magnitude <= self.deadzone {
+            return [0.0, 0.0];
+        }
+        let range = (self.outer_deadzone - self.deadzone).max(f32::EPSILON);
+        let normalized = ((magnitude - self.deadzone) / range).clamp(0.0, 1.0);
+        let scale = normalized.powf(self.response_exponent.max(f32::EPSILON)) / magnitude;
+        [raw[0] * scale, raw[1] * scale]
+    }
+}
+
+type OnGamepadButtonFunc = dyn Fn(&Engine, usize, GamepadButton, ElementState);
+type OnGamepadAxisFunc = dyn Fn(&Engine, usize, GamepadAxis, f32);
+
+/// Polls controllers through gilrs and forwards their events to registered callbacks.
+///
+/// Controllers are only read with the `gamepad` feature. On Linux any evdev device is
+/// picked up, so a uinput virtual gamepad can drive the engine in tests. Events can also
+/// be injected directly with `push_virtual_event`, with or without the feature.
+#[derive(Default)]
+pub struct GamepadSystem {
+    #[cfg(feature = "gamepad")]
+    m_gilrs: Option<Gilrs>,
+    m_virtual_events: VecDeque<GamepadEvent>,
+
+    m_on_button_func: Vec<Box<OnGamepadButtonFunc>>,
+    m_on_axis_func: Vec<Box<OnGamepadAxisFunc>>,
+}
+
+impl GamepadSystem {
+    #[cfg(not(feature = "gamepad"))]
+    pub fn initialize(&mut self) {
+        log::info!("gamepad support is disabled, build with the `gamepad` feature to enable it");
+    }
+
+    #[cfg(feature = "gamepad")]
+    pub fn initialize(&mut self) {
+        self.m_gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(gilrs::Error::NotImplemented(gilrs)) => {
+                log::warn!("gamepad backend is not supported on this platform");
+                Some(gilrs)
+            }
+            Err(err) => {
+                log::warn!("failed to initialize gamepad backend: {err}");
+                None
+            }
+        };
+    }
+
+    pub fn push_virtual_event(&mut self, event: GamepadEvent) {
+        self.m_virtual_events.push_back(event);
+    }
+
+    pub fn poll_events(&mut self) -> Vec<GamepadEvent> {
+        #[allow(unused_mut)]
+        let mut events: Vec<GamepadEvent> = self.m_virtual_events.drain(..).collect();
+        #[cfg(feature = "gamepad")]
+        if let Some(gilrs) = self.m_gilrs.as_mut() {
+            while let Some(event) = gilrs.next_event() {
+                let gamepad_id = usize::from(event.id);
+                let converted = match event.event {
+                    EventType::ButtonPressed(button, _) => {
+                        convert_button(button).map(|button| GamepadEvent::Button {
+                            gamepad_id,
+                            button,
+                            state: ElementState::Pressed,
+                        })
+                    }
+                    EventType::ButtonReleased(button, _) => {
+                        convert_button(button).map(|button| GamepadEvent::Button {
+                            gamepad_id,
+                            button,
+                            state: ElementState::Released,
+                        })
+                    }
+                    EventType::AxisChanged(axis, value, _) => {
+                        convert_axis(axis).map(|axis| GamepadEvent::Axis {
+                            gamepad_id,
+                            axis,
+                            value,
+                        })
+                    }
+                    EventType::Connected => {
+                        log::info!("gamepad {gamepad_id} connected");
+                        None
+                    }
+                    EventType::Disconnected => {
+                        log::info!("gamepad {gamepad_id} disconnected");
+                        None
+                    }
+                    _ => None,
+                };
+                events.extend(converted);
+            }
+        }
+        events
+    }
+
+    pub fn register_on_button_func<F>(&mut self, f: F)
+    where
+        F: 'static + Fn(&Engine, usize, GamepadButton, ElementState),
+    {
+        self.m_on_button_func.push(Box::new(f));
+    }
+
+    pub fn register_on_axis_func<F>(&mut self, f: F)
+    where
+        F: 'static + Fn(&Engine, usize, GamepadAxis, f32),
+    {
+        self.m_on_axis_func.push(Box::new(f));
+    }
+
+    pub fn on_event(&self, engine: &Engine, event: &GamepadEvent) {
+        match *event {
+            GamepadEvent::Button {
+                gamepad_id,
+                button,
+                state,
+            } => self
+                .m_on_button_func
+                .iter()
+                .for_each(|f| f(engine, gamepad_id, button, state)),
+            GamepadEvent::Axis {
+                gamepad_id,
+                axis,
+                value,
+            } => self
+                .m_on_axis_func
+                .iter()
+                .for_each(|f| f(engine, gamepad_id, axis, value)),
+        }
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn convert_button(button: Button) -> Option<GamepadButton> {
+    match button {
+        Button::South => Some(GamepadButton::South),
+        Button::East => Some(GamepadButton::East),
+        Button::North => Some(GamepadButton::North),
+        Button::West => Some(GamepadButton::West),
+        Button::LeftTrigger => Some(GamepadButton::LeftBumper),
+        Button::RightTrigger => Some(GamepadButton::RightBumper),
+        Button::LeftTrigger2 => Some(GamepadButton::LeftTrigger),
+        Button::RightTrigger2 => Some(GamepadButton::RightTrigger),
+        Button::Select => Some(GamepadButton::Select),
+        Button::Start => Some(GamepadButton::Start),
+        Button::LeftThumb => Some(GamepadButton::LeftThumb),
+        Button::RightThumb => Some(GamepadButton::RightThumb),
+        Button::DPadUp => Some(GamepadButton::DPadUp),
+        Button::DPadDown => Some(GamepadButton::DPadDown),
+        Button::DPadLeft => Some(GamepadButton::DPadLeft),
+        Button::DPadRight => Some(GamepadButton::DPadRight),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn convert_axis(axis: Axis) -> Option<GamepadAxis> {
+    match axis {
+        Axis::LeftStickX => Some(GamepadAxis::LeftStickX),
+        Axis::LeftStickY => Some(GamepadAxis::LeftStickY),
+        Axis::RightStickX => Some(GamepadAxis::RightStickX),
+        Axis::RightStickY => Some(GamepadAxis::RightStickY),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtual_events_are_polled_in_order_once() {
+        let mut gamepad_system = GamepadSystem::default();
+        let events = [
+            GamepadEvent::Button {
+                gamepad_id: 0,
+                button: GamepadButton::South,
+                state: ElementState::Pressed,
+            },
+            GamepadEvent::Axis {
+                gamepad_id: 0,
+                axis: GamepadAxis::LeftStickY,
+                value: 1.0,
+            },
+            GamepadEvent::Button {
+                gamepad_id: 1,
+                button: GamepadButton::South,
+                state: ElementState::Released,
+            },
+        ];
+        events
+            .iter()
+            .for_each(|event| gamepad_system.push_virtual_event(*event));
+
+        assert_eq!(gamepad_system.poll_events(), events);
+        assert!(gamepad_system.poll_events().is_empty());
+    }
+
+    #[test]
+    fn stick_config_applies_deadzones() {
+        let config = GamepadStickConfig::default();
+        assert_eq!(config.apply([0.1, 0.0]), [0.0, 0.0]);
+        assert_eq!(config.apply([0.0, -1.0]), [0.0, -1.0]);
+        let half = config.apply([0.55, 0.0]);
+        assert!(half[0] > 0.0 && half[0] < 0.55);
+    }
+}
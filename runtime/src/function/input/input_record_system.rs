@@ -0,0 +1,208 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::PhysicalPosition,
+    event::{DeviceId, ElementState, Ime, KeyEvent, MouseButton},
+    keyboard::PhysicalKey,
+};
+
+use crate::{
+    engine::Engine,
+    function::input::{gamepad_system::GamepadEvent, input_system::InputSystem},
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RecordedInputEvent {
+    Key {
+        physical_key: PhysicalKey,
+        state: ElementState,
+        text: Option<String>,
+    },
+    MouseButton {
+        button: MouseButton,
+        state: ElementState,
+    },
+    CursorPos {
+        x: f64,
+        y: f64,
+    },
+    MouseMotion {
+        delta: (f64, f64),
+    },
+    Ime(Ime),
+    Gamepad(GamepadEvent),
+}
+
+impl RecordedInputEvent {
+    pub fn from_key_event(event: &KeyEvent) -> Self {
+        Self::Key {
+            physical_key: event.physical_key,
+            state: event.state,
+            text: event.text.as_ref().map(|text| text.to_string()),
+        }
+    }
+
+    /// Feeds the event into the input system the same way the live callbacks do.
+    pub fn dispatch(&self, engine: &Engine) {
+        // SAFETY: the input systems ignore the device id, it only has to be a valid value.
+        let device_id = unsafe { DeviceId::dummy() };
+        let mut input_system = engine.input_system().borrow_mut();
+        match self {
+            RecordedInputEvent::Key {
+                physical_key,
+                state,
+                text,
+            } => input_system.on_key_input(engine, *physical_key, *state, text.as_deref()),
+            RecordedInputEvent::MouseButton { button, state } => {
+                input_system.on_mouse_button(device_id, *state, *button)
+            }
+            RecordedInputEvent::CursorPos { x, y } => {
+                input_system.on_cursor_pos(device_id, PhysicalPosition::new(*x, *y))
+            }
+            RecordedInputEvent::MouseMotion { delta } => {
+                input_system.on_mouse_motion(engine, device_id, *delta)
+            }
+            RecordedInputEvent::Ime(ime) => input_system.on_ime(ime),
+            RecordedInputEvent::Gamepad(GamepadEvent::Button {
+                gamepad_id,
+                button,
+                state,
+            }) => input_system.on_gamepad_button(engine, *gamepad_id, *button, *state),
+            RecordedInputEvent::Gamepad(GamepadEvent::Axis {
+                gamepad_id,
+                axis,
+                value,
+            }) => input_system.on_gamepad_axis(engine, *gamepad_id, *axis, *value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RecordedInputFrame {
+    pub delta_time: f32,
+    pub events: Vec<RecordedInputEvent>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub frames: Vec<RecordedInputFrame>,
+}
+
+#[derive(Default, PartialEq, Eq)]
+enum InputRecordMode {
+    #[default]
+    Idle,
+    Recording,
+    Replaying,
+}
+
+/// Records the per-frame input stream to a file and plays it back in place of live input.
+#[derive(Default)]
+pub struct InputRecordSystem {
+    m_mode: InputRecordMode,
+    m_recording: InputRecording,
+    m_pending_events: Vec<RecordedInputEvent>,
+    m_record_path: Option<PathBuf>,
+    m_replay_frame_index: usize,
+    m_close_on_replay_end: bool,
+}
+
+impl InputRecordSystem {
+    pub fn start_recording(&mut self, path: &Path) {
+        self.m_mode = InputRecordMode::Recording;
+        self.m_recording = InputRecording::default();
+        self.m_pending_events.clear();
+        self.m_record_path = Some(path.to_path_buf());
+    }
+
+    pub fn stop_recording(&mut self) -> Result<()> {
+        if self.m_mode != InputRecordMode::Recording {
+            return Ok(());
+        }
+        self.m_mode = InputRecordMode::Idle;
+        let recording = std::mem::take(&mut self.m_recording);
+        if let Some(path) = self.m_record_path.take() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, serde_json::to_string(&recording)?)?;
+            log::info!(
+                "input recording saved path='{}' frames={}",
+                path.display(),
+                recording.frames.len()
+            );
+        }
+        Ok(())
+    }
+
+    pub fn start_replay(&mut self, path: &Path, close_on_end: bool) -> Result<()> {
+        let text = fs::read_to_string(path)?;
+        self.m_recording = serde_json::from_str(&text)?;
+        self.m_mode = InputRecordMode::Replaying;
+        self.m_pending_events.clear();
+        self.m_replay_frame_index = 0;
+        self.m_close_on_replay_end = close_on_end;
+        Ok(())
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.m_mode == InputRecordMode::Recording
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.m_mode == InputRecordMode::Replaying
+    }
+
+    pub fn close_on_replay_end(&self) -> bool {
+        self.m_close_on_replay_end
+    }
+
+    /// Captures a live event while recording. Returns false if live input must be
+    /// dropped because a replay is driving the engine.
+    pub fn on_live_event(&mut self, event: &RecordedInputEvent) -> bool {
+        match self.m_mode {
+            InputRecordMode::Idle => true,
+            InputRecordMode::Recording => {
+                self.m_pending_events.push(event.clone());
+                true
+            }
+            InputRecordMode::Replaying => false,
+        }
+    }
+
+    /// Closes the current frame, attaching every event captured since the previous one.
+    pub fn end_recorded_frame(&mut self, delta_time: f32) {
+        if self.m_mode != InputRecordMode::Recording {
+            return;
+        }
+        self.m_recording.frames.push(RecordedInputFrame {
+            delta_time,
+            events: std::mem::take(&mut self.m_pending_events),
+        });
+    }
+
+    /// Returns the next frame to replay, or `None` once the recording is exhausted.
+    pub fn next_replay_frame(&mut self) -> Option<RecordedInputFrame> {
+        if self.m_mode != InputRecordMode::Replaying {
+            return None;
+        }
+        let frame = self
+            .m_recording
+            .frames
+            .get(self.m_replay_frame_index)
+            .cloned();
+        match frame {
+            Some(_) => self.m_replay_frame_index += 1,
+            None => {
+                log::info!("input replay finished frames={}", self.m_replay_frame_index);
+                self.m_mode = InputRecordMode::Idle;
+            }
+        }
+        frame
+    }
+}
@@ -1,17 +1,84 @@
 use winit::{
     dpi::PhysicalPosition,
     event::{DeviceId, ElementState, Ime, KeyEvent, MouseButton},
+    keyboard::PhysicalKey,
 };
 
-use crate::engine::Engine;
+use crate::{
+    engine::Engine,
+    function::input::gamepad_system::{GamepadAxis, GamepadButton},
+};
+
+/// Level and per-frame edge state of a single key or mouse button.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InputButtonState {
+    pub down: bool,
+    /// Went down since the last input tick.
+    pub pressed: bool,
+    /// Went up since the last input tick.
+    pub released: bool,
+    /// Received an auto-repeat press since the last input tick.
+    pub repeated: bool,
+    /// Seconds held so far; keeps the final value after release until the next press.
+    pub held_duration: f32,
+}
+
+impl InputButtonState {
+    /// Applies a press or release and returns false if it did not change the level,
+    /// i.e. for key auto-repeat.
+    pub fn on_event(&mut self, state: ElementState) -> bool {
+        match state {
+            ElementState::Pressed if self.down => {
+                self.repeated = true;
+                false
+            }
+            ElementState::Pressed => {
+                self.down = true;
+                self.pressed = true;
+                self.held_duration = 0.0;
+                true
+            }
+            ElementState::Released if self.down => {
+                self.down = false;
+                self.released = true;
+                true
+            }
+            ElementState::Released => false,
+        }
+    }
+
+    pub fn end_frame(&mut self, delta_time: f32) {
+        if self.down {
+            self.held_duration += delta_time;
+        }
+        self.pressed = false;
+        self.released = false;
+        self.repeated = false;
+    }
+}
 
 pub trait InputSystem {
     fn on_key(
         &mut self,
-        _engine: &Engine,
+        engine: &Engine,
         _device_id: DeviceId,
-        _event: &KeyEvent,
+        event: &KeyEvent,
         _is_synthetic: bool,
+    ) {
+        self.on_key_input(
+            engine,
+            event.physical_key,
+            event.state,
+            event.text.as_deref(),
+        );
+    }
+    /// Key input without the winit event wrapper, so recorded input can be replayed.
+    fn on_key_input(
+        &mut self,
+        _engine: &Engine,
+        _physical_key: PhysicalKey,
+        _state: ElementState,
+        _text: Option<&str>,
     ) {
     }
     fn on_mouse_motion(&mut self, _engine: &Engine, _device_id: DeviceId, _delta: (f64, f64)) {}
@@ -24,5 +91,21 @@ pub trait InputSystem {
     ) {
     }
     fn on_ime(&mut self, _ime: &Ime) {}
+    fn on_gamepad_button(
+        &mut self,
+        _engine: &Engine,
+        _gamepad_id: usize,
+        _button: GamepadButton,
+        _state: ElementState,
+    ) {
+    }
+    fn on_gamepad_axis(
+        &mut self,
+        _engine: &Engine,
+        _gamepad_id: usize,
+        _axis: GamepadAxis,
+        _value: f32,
+    ) {
+    }
     fn tick(&mut self, _engine: &Engine, _delta_time: f32) {}
 }
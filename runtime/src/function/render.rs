@@ -1,5 +1,6 @@
 pub mod debugdraw;
 pub mod font_atlas;
+pub mod ibl_precompute;
 pub mod interface;
 pub mod light;
 pub mod render_camera;
@@ -18,4 +19,7 @@ pub mod render_scene;
 pub mod render_swap_context;
 pub mod render_system;
 pub mod render_type;
+pub mod sky_atmosphere;
+pub mod texture_container;
+pub mod texture_decoder;
 pub mod window_system;
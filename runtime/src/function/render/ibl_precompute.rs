@@ -0,0 +1,482 @@
+use std::{f32::consts::PI, fs, path::PathBuf, thread};
+
+use anyhow::Result;
+use image::Rgba32FImage;
+use log::{info, warn};
+use vulkanalia::prelude::v1_0::*;
+
+use crate::{
+    core::math::vector3::Vector3,
+    function::render::{
+        render_type::{ImageType, TextureData},
+        texture_container::{load_texture_container, save_ktx2},
+    },
+    resource::asset_manager::AssetManager,
+};
+
+const IRRADIANCE_MAP_SIZE: u32 = 32;
+/// The shaders sample the specular map at `roughness * MAX_REFLECTION_LOD` with a
+/// MAX_REFLECTION_LOD of 8, so level i of the nine is prefiltered for a roughness of i / 8.
+const SPECULAR_MAP_SIZE: u32 = 256;
+const SPECULAR_MAP_LEVELS: u32 = 9;
+const SPECULAR_SAMPLE_COUNT: u32 = 128;
+const BRDF_MAP_SIZE: u32 = 128;
+const BRDF_SAMPLE_COUNT: u32 = 512;
+/// Part of the cache file names, bump it whenever the generated maps change.
+const CACHE_VERSION: u32 = 2;
+
+pub struct PrecomputedIBL {
+    pub m_irradiance_map: TextureData,
+    pub m_specular_map: TextureData,
+    pub m_brdf_map: TextureData,
+}
+
+/// Generates the irradiance map, the prefiltered specular map and the BRDF LUT from an
+/// equirectangular HDR image with +Z up, or loads them from the KTX2 files cached next to it.
+/// A cache older than the image is regenerated.
+pub fn load_or_precompute_ibl(
+    asset_manager: &AssetManager,
+    environment_map: &str,
+) -> Result<PrecomputedIBL> {
+    let source_path = asset_manager.get_full_path(environment_map);
+    let cache_paths = ["irradiance", "specular", "brdf"]
+        .map(|name| source_path.with_extension(format!("ibl{}.{}.ktx2", CACHE_VERSION, name)));
+
+    let source_modified = fs::metadata(&source_path)?.modified()?;
+    let is_cached = cache_paths.iter().all(|path| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified >= source_modified)
+    });
+    if is_cached {
+        match load_cache(&cache_paths) {
+            Ok(ibl) => return Ok(ibl),
+            Err(e) => warn!("Ignoring IBL cache of {}: {}", environment_map, e),
+        }
+    }
+
+    info!("Precomputing IBL maps of {}", environment_map);
+    let environment = image::open(&source_path)?.to_rgba32f();
+    let source_chain = source_cube_chain(&environment);
+    let ibl = PrecomputedIBL {
+        m_irradiance_map: irradiance_map(&source_chain),
+        m_specular_map: specular_map(&source_chain),
+        m_brdf_map: brdf_map(),
+    };
+
+    let maps = [&ibl.m_irradiance_map, &ibl.m_specular_map, &ibl.m_brdf_map];
+    for (path, texture) in cache_paths.iter().zip(maps) {
+        if let Err(e) = save_ktx2(texture).and_then(|bytes| Ok(fs::write(path, bytes)?)) {
+            warn!("Failed to cache {}: {}", path.display(), e);
+        }
+    }
+    Ok(ibl)
+}
+
+fn load_cache(cache_paths: &[PathBuf; 3]) -> Result<PrecomputedIBL> {
+    let [irradiance_map, specular_map, brdf_map] = cache_paths.each_ref().map(|path| {
+        fs::read(path)
+            .map_err(Into::into)
+            .and_then(|bytes| load_texture_container(&bytes))
+    });
+    Ok(PrecomputedIBL {
+        m_irradiance_map: irradiance_map?,
+        m_specular_map: specular_map?,
+        m_brdf_map: brdf_map?,
+    })
+}
+
+/// One mip level of a cube map, faces in layer order with rows top to bottom.
+struct CubeLevel {
+    m_size: u32,
+    m_texels: Vec<Vector3>,
+}
+
+impl CubeLevel {
+    fn new(size: u32, shade: impl Fn(usize, f32, f32) -> Vector3 + Sync) -> Self {
+        let face_texel_count = (size * size) as usize;
+        let mut texels = vec![Vector3::ZERO; face_texel_count * 6];
+        parallel_fill(&mut texels, |index| {
+            let (face, texel) = (index / face_texel_count, index % face_texel_count);
+            let (x, y) = (texel as u32 % size, texel as u32 / size);
+            shade(
+                face,
+                2.0 * (x as f32 + 0.5) / size as f32 - 1.0,
+                2.0 * (y as f32 + 0.5) / size as f32 - 1.0,
+            )
+        });
+        Self {
+            m_size: size,
+            m_texels: texels,
+        }
+    }
+
+    fn texel(&self, face: usize, x: u32, y: u32) -> Vector3 {
+        let size = self.m_size as usize;
+        self.m_texels[(face * size + y as usize) * size + x as usize]
+    }
+
+    /// Bilinear sample clamped to the face the direction points at.
+    fn sample(&self, direction: &Vector3) -> Vector3 {
+        let (face, s, t) = cube_face_coordinates(direction);
+        let size = self.m_size as f32;
+        let x = ((s + 1.0) * 0.5 * size - 0.5).clamp(0.0, size - 1.0);
+        let y = ((t + 1.0) * 0.5 * size - 0.5).clamp(0.0, size - 1.0);
+        let (x0, y0) = (x as u32, y as u32);
+        let (x1, y1) = ((x0 + 1).min(self.m_size - 1), (y0 + 1).min(self.m_size - 1));
+        let (fx, fy) = (x.fract(), y.fract());
+        let top = self.texel(face, x0, y0) * (1.0 - fx) + self.texel(face, x1, y0) * fx;
+        let bottom = self.texel(face, x0, y1) * (1.0 - fx) + self.texel(face, x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    fn downsample(&self) -> Self {
+        let size = (self.m_size / 2).max(1);
+        let scale = self.m_size / size;
+        let face_texel_count = (size * size) as usize;
+        let mut texels = Vec::with_capacity(face_texel_count * 6);
+        for face in 0..6 {
+            for y in 0..size {
+                for x in 0..size {
+                    let mut sum = Vector3::ZERO;
+                    for (dx, dy) in (0..scale).flat_map(|dx| (0..scale).map(move |dy| (dx, dy))) {
+                        sum += self.texel(face, x * scale + dx, y * scale + dy);
+                    }
+                    texels.push(sum / (scale * scale) as f32);
+                }
+            }
+        }
+        Self {
+            m_size: size,
+            m_texels: texels,
+        }
+    }
+
+    fn to_rgba16f(&self) -> Vec<u8> {
+        self.m_texels
+            .iter()
+            .flat_map(|texel| [texel.x, texel.y, texel.z, 1.0])
+            .flat_map(|channel| f32_to_half(channel).to_le_bytes())
+            .collect()
+    }
+}
+
+/// Fills `texels` on every core, `shade` maps a texel index to its value.
+fn parallel_fill<T: Send>(texels: &mut [T], shade: impl Fn(usize) -> T + Sync) {
+    let thread_count = thread::available_parallelism().map_or(1, |count| count.get());
+    let chunk_size = texels.len().div_ceil(thread_count).max(1);
+    let shade = &shade;
+    thread::scope(|scope| {
+        for (chunk_index, chunk) in texels.chunks_mut(chunk_size).enumerate() {
+            scope.spawn(move || {
+                for (index, texel) in chunk.iter_mut().enumerate() {
+                    *texel = shade(chunk_index * chunk_size + index);
+                }
+            });
+        }
+    });
+}
+
+/// Sampling direction of a texel at face coordinates `s`, `t` in [-1, 1], in the space the
+/// shaders sample cube maps in, which has the engine's Y and Z swapped.
+fn cube_face_direction(face: usize, s: f32, t: f32) -> Vector3 {
+    match face {
+        0 => Vector3::new(1.0, -t, -s),
+        1 => Vector3::new(-1.0, -t, s),
+        2 => Vector3::new(s, 1.0, t),
+        3 => Vector3::new(s, -1.0, -t),
+        4 => Vector3::new(s, -t, 1.0),
+        _ => Vector3::new(-s, -t, -1.0),
+    }
+    .normalize()
+}
+
+fn cube_face_coordinates(direction: &Vector3) -> (usize, f32, f32) {
+    let (x, y, z) = (direction.x.abs(), direction.y.abs(), direction.z.abs());
+    if x >= y && x >= z {
+        if direction.x > 0.0 {
+            (0, -direction.z / x, -direction.y / x)
+        } else {
+            (1, direction.z / x, -direction.y / x)
+        }
+    } else if y >= z {
+        if direction.y > 0.0 {
+            (2, direction.x / y, direction.z / y)
+        } else {
+            (3, direction.x / y, -direction.z / y)
+        }
+    } else if direction.z > 0.0 {
+        (4, direction.x / z, -direction.y / z)
+    } else {
+        (5, -direction.x / z, -direction.y / z)
+    }
+}
+
+fn texel_solid_angle(x: u32, y: u32, size: u32) -> f32 {
+    let area = |s: f32, t: f32| (s * t).atan2((s * s + t * t + 1.0).sqrt());
+    let texel_size = 2.0 / size as f32;
+    let (s0, t0) = (x as f32 * texel_size - 1.0, y as f32 * texel_size - 1.0);
+    let (s1, t1) = (s0 + texel_size, t0 + texel_size);
+    area(s0, t0) - area(s0, t1) - area(s1, t0) + area(s1, t1)
+}
+
+/// Bilinear sample of the equirectangular image along a direction in engine space.
+fn sample_equirectangular(environment: &Rgba32FImage, direction: &Vector3) -> Vector3 {
+    let (width, height) = (environment.width(), environment.height());
+    let u = 0.5 + direction.y.atan2(direction.x) / (2.0 * PI);
+    let v = direction.z.clamp(-1.0, 1.0).acos() / PI;
+    let x = u * width as f32 - 0.5;
+    let y = (v * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+    let (fx, fy) = (x - x.floor(), y.fract());
+    let x0 = (x.floor() as i64).rem_euclid(width as i64) as u32;
+    let x1 = (x0 + 1) % width;
+    let y0 = y as u32;
+    let y1 = (y0 + 1).min(height - 1);
+    let texel = |x: u32, y: u32| {
+        let [r, g, b, _] = environment.get_pixel(x, y).0;
+        Vector3::new(r, g, b)
+    };
+    let top = texel(x0, y0) * (1.0 - fx) + texel(x1, y0) * fx;
+    let bottom = texel(x0, y1) * (1.0 - fx) + texel(x1, y1) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+/// The environment resampled to the specular map size with a box filtered chain below it,
+/// which the prefiltering samples at the footprint of each importance sample.
+fn source_cube_chain(environment: &Rgba32FImage) -> Vec<CubeLevel> {
+    let sample_count = environment.width().div_ceil(4 * SPECULAR_MAP_SIZE).max(1);
+    let texel_size = 2.0 / SPECULAR_MAP_SIZE as f32;
+    let base = CubeLevel::new(SPECULAR_MAP_SIZE, |face, s, t| {
+        let mut sum = Vector3::ZERO;
+        for (i, j) in (0..sample_count).flat_map(|i| (0..sample_count).map(move |j| (i, j))) {
+            let offset = |k: u32| ((k as f32 + 0.5) / sample_count as f32 - 0.5) * texel_size;
+            let direction = cube_face_direction(face, s + offset(i), t + offset(j));
+            let engine_direction = Vector3::new(direction.x, direction.z, direction.y);
+            sum += sample_equirectangular(environment, &engine_direction);
+        }
+        sum / (sample_count * sample_count) as f32
+    });
+
+    let mut chain = vec![base];
+    while chain.last().unwrap().m_size > 1 {
+        let level = chain.last().unwrap().downsample();
+        chain.push(level);
+    }
+    chain
+}
+
+fn sample_chain(chain: &[CubeLevel], direction: &Vector3, lod: f32) -> Vector3 {
+    let lod = lod.clamp(0.0, (chain.len() - 1) as f32);
+    let level = lod as usize;
+    let color = chain[level].sample(direction);
+    if level + 1 < chain.len() {
+        let blend = lod.fract();
+        color * (1.0 - blend) + chain[level + 1].sample(direction) * blend
+    } else {
+        color
+    }
+}
+
+fn irradiance_map(source_chain: &[CubeLevel]) -> TextureData {
+    // the convolution runs over the source level as large as the irradiance map
+    let source = source_chain
+        .iter()
+        .find(|level| level.m_size <= IRRADIANCE_MAP_SIZE)
+        .unwrap();
+    let radiance = (0..6)
+        .flat_map(|face| {
+            (0..source.m_size * source.m_size).map(move |texel| {
+                let (x, y) = (texel % source.m_size, texel / source.m_size);
+                let size = source.m_size as f32;
+                let direction = cube_face_direction(
+                    face,
+                    2.0 * (x as f32 + 0.5) / size - 1.0,
+                    2.0 * (y as f32 + 0.5) / size - 1.0,
+                );
+                let weight = texel_solid_angle(x, y, source.m_size) / PI;
+                (direction, source.texel(face, x, y) * weight)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let level = CubeLevel::new(IRRADIANCE_MAP_SIZE, |face, s, t| {
+        let normal = cube_face_direction(face, s, t);
+        radiance
+            .iter()
+            .fold(Vector3::ZERO, |sum, (direction, radiance)| {
+                sum + *radiance * normal.dot(direction).max(0.0)
+            })
+    });
+    cube_texture(&[level])
+}
+
+fn specular_map(source_chain: &[CubeLevel]) -> TextureData {
+    let source_texel_solid_angle = 4.0 * PI / (6 * SPECULAR_MAP_SIZE * SPECULAR_MAP_SIZE) as f32;
+    let levels = (0..SPECULAR_MAP_LEVELS)
+        .map(|level| {
+            if level == 0 {
+                return CubeLevel {
+                    m_size: source_chain[0].m_size,
+                    m_texels: source_chain[0].m_texels.clone(),
+                };
+            }
+            let roughness = level as f32 / (SPECULAR_MAP_LEVELS - 1) as f32;
+            CubeLevel::new((SPECULAR_MAP_SIZE >> level).max(1), |face, s, t| {
+                // the view and reflection directions are both taken to be the normal
+                let normal = cube_face_direction(face, s, t);
+                let mut color = Vector3::ZERO;
+                let mut weight = 0.0;
+                for i in 0..SPECULAR_SAMPLE_COUNT {
+                    let half = importance_sample_ggx(
+                        hammersley(i, SPECULAR_SAMPLE_COUNT),
+                        &normal,
+                        roughness,
+                    );
+                    let n_dot_h = normal.dot(&half).max(0.0);
+                    let light = half * (2.0 * n_dot_h) - normal;
+                    let n_dot_l = normal.dot(&light);
+                    if n_dot_l > 0.0 {
+                        let pdf = distribution_ggx(n_dot_h, roughness) * 0.25 + 1e-4;
+                        let sample_solid_angle = 1.0 / (SPECULAR_SAMPLE_COUNT as f32 * pdf);
+                        let lod =
+                            0.5 * (sample_solid_angle / source_texel_solid_angle).log2() + 1.0;
+                        color += sample_chain(source_chain, &light, lod) * n_dot_l;
+                        weight += n_dot_l;
+                    }
+                }
+                color / weight.max(1e-4)
+            })
+        })
+        .collect::<Vec<_>>();
+    cube_texture(&levels)
+}
+
+fn cube_texture(levels: &[CubeLevel]) -> TextureData {
+    let mut texture = TextureData::default();
+    texture.m_width = levels[0].m_size;
+    texture.m_height = levels[0].m_size;
+    texture.m_depth = 1;
+    texture.m_mip_levels = levels.len() as u32;
+    texture.m_array_layers = 6;
+    // half floats can be filtered linearly on every device, unlike 32 bit ones
+    texture.m_format = vk::Format::R16G16B16A16_SFLOAT;
+    texture.m_type = ImageType::Cube;
+    texture.m_pixels = levels[0].to_rgba16f();
+    texture.m_mip_pixels = levels[1..].iter().map(CubeLevel::to_rgba16f).collect();
+    texture
+}
+
+/// Split sum BRDF LUT, the scale and bias of F0 in red and green, with N dot V along the
+/// width and roughness along the height.
+fn brdf_map() -> TextureData {
+    let mut texels = vec![[0.0; 2]; (BRDF_MAP_SIZE * BRDF_MAP_SIZE) as usize];
+    parallel_fill(&mut texels, |index| {
+        let n_dot_v = ((index as u32 % BRDF_MAP_SIZE) as f32 + 0.5) / BRDF_MAP_SIZE as f32;
+        let roughness = ((index as u32 / BRDF_MAP_SIZE) as f32 + 0.5) / BRDF_MAP_SIZE as f32;
+        let view = Vector3::new((1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v);
+        let (mut scale, mut bias) = (0.0, 0.0);
+        for i in 0..BRDF_SAMPLE_COUNT {
+            let half = importance_sample_ggx(
+                hammersley(i, BRDF_SAMPLE_COUNT),
+                &Vector3::UNIT_Z,
+                roughness,
+            );
+            let v_dot_h = view.dot(&half).max(0.0);
+            let light = half * (2.0 * v_dot_h) - view;
+            let n_dot_l = light.z;
+            if n_dot_l > 0.0 {
+                let n_dot_h = half.z.max(0.0);
+                let visibility = geometry_smith(n_dot_v, n_dot_l, roughness) * v_dot_h
+                    / (n_dot_h * n_dot_v).max(1e-4);
+                let fresnel = (1.0 - v_dot_h).powi(5);
+                scale += (1.0 - fresnel) * visibility;
+                bias += fresnel * visibility;
+            }
+        }
+        [
+            scale / BRDF_SAMPLE_COUNT as f32,
+            bias / BRDF_SAMPLE_COUNT as f32,
+        ]
+    });
+
+    let mut texture = TextureData::default();
+    texture.m_width = BRDF_MAP_SIZE;
+    texture.m_height = BRDF_MAP_SIZE;
+    texture.m_depth = 1;
+    texture.m_mip_levels = 1;
+    texture.m_array_layers = 1;
+    texture.m_format = vk::Format::R16G16_SFLOAT;
+    texture.m_type = ImageType::_2D;
+    texture.m_pixels = texels
+        .as_flattened()
+        .iter()
+        .flat_map(|&channel| f32_to_half(channel).to_le_bytes())
+        .collect();
+    texture
+}
+
+/// Rounds to the nearest half float, radiance past its range is clamped to the largest one.
+fn f32_to_half(value: f32) -> u16 {
+    const HALF_MAX: f32 = 65504.0;
+    let bits = value.clamp(-HALF_MAX, HALF_MAX).to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    let (half, rest, halfway) = if exponent > 0 {
+        (
+            ((exponent as u32) << 10) | (mantissa >> 13),
+            mantissa & 0x1fff,
+            0x1000,
+        )
+    } else if exponent >= -10 {
+        // subnormal, the implicit leading bit becomes part of the mantissa
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        (
+            mantissa >> shift,
+            mantissa & ((1 << shift) - 1),
+            1 << (shift - 1),
+        )
+    } else {
+        return sign;
+    };
+    // ties to even, a carry out of the mantissa moves up to the next exponent as it should
+    let round_up = rest > halfway || (rest == halfway && half & 1 != 0);
+    sign | (half + round_up as u32) as u16
+}
+
+fn hammersley(i: u32, count: u32) -> (f32, f32) {
+    (
+        i as f32 / count as f32,
+        i.reverse_bits() as f32 / (u32::MAX as f32 + 1.0),
+    )
+}
+
+fn importance_sample_ggx(xi: (f32, f32), normal: &Vector3, roughness: f32) -> Vector3 {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.0;
+    let cos_theta = ((1.0 - xi.1) / (1.0 + (a * a - 1.0) * xi.1)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+    let up = if normal.z.abs() < 0.999 {
+        Vector3::UNIT_Z
+    } else {
+        Vector3::UNIT_X
+    };
+    let tangent = up.cross(normal).normalize();
+    let bitangent = normal.cross(&tangent);
+    (tangent * (phi.cos() * sin_theta) + bitangent * (phi.sin() * sin_theta) + *normal * cos_theta)
+        .normalize()
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a2 = roughness.powi(4);
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness * 0.5;
+    let geometry_schlick_ggx = |n_dot: f32| n_dot / (n_dot * (1.0 - k) + k);
+    geometry_schlick_ggx(n_dot_v) * geometry_schlick_ggx(n_dot_l)
+}
@@ -30,6 +30,7 @@ use crate::function::render::{
         vulkan::vulkan_util::{self, create_image_view},
     },
     render_type::RHISamplerType,
+    texture_decoder::is_block_compressed,
 };
 
 const VALIDATION_LAYER: vk::ExtensionName =
@@ -38,6 +39,7 @@ const DEVICE_EXTENSIONS: &[vk::ExtensionName] = &[vk::KHR_SWAPCHAIN_EXTENSION.na
 const PORTABILITY_MACOS_VERSION: Version = Version::new(1, 3, 216);
 pub const K_MAX_FRAMES_IN_FLIGHT: usize = 3;
 pub const MAX_MATERIAL_COUNT: u32 = 256;
+pub const MAX_SHADER_FULLSCREEN_PASS_COUNT: u32 = 8;
 
 pub struct VulkanRHI {
     _m_entry: Entry,
@@ -80,6 +82,12 @@ pub struct VulkanRHIData {
     m_swapchain: vk::SwapchainKHR,
     m_swapchain_images: Vec<vk::Image>,
 
+    // Offscreen target standing in for the swapchain when there is no window.
+    m_is_offscreen: bool,
+    m_offscreen_image_memory: vk::DeviceMemory,
+    // `UNDEFINED` until a frame has been submitted into the offscreen target.
+    m_offscreen_image_layout: vk::ImageLayout,
+
     m_depth_image: vk::Image,
     m_depth_image_memory: vk::DeviceMemory,
 
@@ -104,6 +112,7 @@ pub struct VulkanRHIData {
     m_enable_validation_layers: bool,
     m_enable_debug_utils_label: bool,
     m_enable_point_light_shadow: bool,
+    m_enable_texture_compression_bc: bool,
 
     m_max_vertex_blending_mesh_count: u32,
 
@@ -116,7 +125,10 @@ impl VulkanRHI {
         data.m_max_vertex_blending_mesh_count = 256;
 
         let window_system = info.window_system;
-        data.m_window = Rc::downgrade(window_system.get_window());
+        data.m_is_offscreen = window_system.is_headless();
+        if !data.m_is_offscreen {
+            data.m_window = Rc::downgrade(window_system.get_window());
+        }
         let window_size = window_system.get_window_size();
 
         data.m_viewport = vk::Viewport {
@@ -136,8 +148,6 @@ impl VulkanRHI {
             },
         };
 
-        let window = info.window_system.get_window();
-
         let entry = unsafe {
             let loader = LibloadingLoader::new(LIBRARY).unwrap();
             Entry::new(loader).map_err(|b| anyhow!("{}", b)).unwrap()
@@ -155,16 +165,22 @@ impl VulkanRHI {
             data.m_enable_debug_utils_label = false;
         }
 
-        let instance = create_instance(window, &entry, &mut data);
+        let instance = create_instance(&entry, &mut data);
         initialize_debug_messenger(&instance, &mut data).unwrap();
-        create_window_surface(&instance, window, &mut data).unwrap();
+        if !data.m_is_offscreen {
+            create_window_surface(&instance, window_system.get_window(), &mut data).unwrap();
+        }
         initial_physical_device(&instance, &mut data).unwrap();
         let device = create_logical_device(&entry, &instance, &mut data).unwrap();
         create_command_pool(&device, &mut data).unwrap();
         create_command_buffers(&device, &mut data).unwrap();
         create_sync_objects(&device, &mut data).unwrap();
         create_descriptor_pool(&device, &mut data).unwrap();
-        create_swapchain(window, &instance, &device, &mut data).unwrap();
+        if data.m_is_offscreen {
+            create_offscreen_images(&instance, &device, window_size, &mut data).unwrap();
+        } else {
+            create_swapchain(window_system.get_window(), &instance, &device, &mut data).unwrap();
+        }
         create_swapchain_image_views(&device, &mut data).unwrap();
         create_depth_objects(&instance, &device, &mut data).unwrap();
 
@@ -196,8 +212,17 @@ impl VulkanRHI {
                 .m_swapchain_image_views
                 .iter()
                 .for_each(|v| self.m_device.destroy_image_view(*v, None));
-            self.m_device
-                .destroy_swapchain_khr(self.m_data.m_swapchain, None);
+            if self.m_data.m_is_offscreen {
+                self.m_data
+                    .m_swapchain_images
+                    .iter()
+                    .for_each(|i| self.m_device.destroy_image(*i, None));
+                self.m_device
+                    .free_memory(self.m_data.m_offscreen_image_memory, None);
+            } else {
+                self.m_device
+                    .destroy_swapchain_khr(self.m_data.m_swapchain, None);
+            }
             self.m_device
                 .destroy_descriptor_pool(self.m_data.m_descriptor_pool, None);
             self.m_data
@@ -219,8 +244,10 @@ impl VulkanRHI {
             self.m_device
                 .destroy_command_pool(self.m_data.m_command_pool, None);
             self.m_device.destroy_device(None);
-            self.m_instance
-                .destroy_surface_khr(self.m_data.m_surface, None);
+            if !self.m_data.m_is_offscreen {
+                self.m_instance
+                    .destroy_surface_khr(self.m_data.m_surface, None);
+            }
 
             if self.m_data.m_enable_validation_layers {
                 self.m_instance
@@ -245,6 +272,14 @@ impl VulkanRHI {
         Ok(descriptor_sets)
     }
 
+    pub fn free_descriptor_sets(&self, descriptor_sets: &[vk::DescriptorSet]) -> Result<()> {
+        unsafe {
+            self.m_device
+                .free_descriptor_sets(self.m_data.m_descriptor_pool, descriptor_sets)?
+        };
+        Ok(())
+    }
+
     fn recreate_swapchain(&mut self, window: &Window) -> Result<()> {
         unsafe {
             self.m_device.device_wait_idle()?;
@@ -289,6 +324,20 @@ impl VulkanRHI {
         Ok(())
     }
 
+    fn begin_offscreen_frame(&mut self, in_flight_fence: vk::Fence) -> Result<()> {
+        let image_in_flight =
+            self.m_data.m_images_in_flight[self.m_data.m_current_swapchain_image_index];
+        if !image_in_flight.is_null() {
+            unsafe {
+                self.m_device
+                    .wait_for_fences(&[image_in_flight], true, u64::MAX)
+            }?;
+        }
+        self.m_data.m_images_in_flight[self.m_data.m_current_swapchain_image_index] =
+            in_flight_fence;
+        self.prepare_frame()
+    }
+
     pub fn get_or_create_default_sampler(
         &self,
         sampler_type: RHISamplerType,
@@ -480,6 +529,26 @@ impl VulkanRHI {
         )?)
     }
 
+    pub fn create_multisampled_image(
+        &self,
+        width: u32,
+        height: u32,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        samples: vk::SampleCountFlags,
+    ) -> Result<(vk::Image, vk::DeviceMemory)> {
+        Ok(vulkan_util::create_multisampled_image(
+            &self.m_instance,
+            &self.m_device,
+            self.m_data.m_physical_device,
+            width,
+            height,
+            format,
+            usage,
+            samples,
+        )?)
+    }
+
     pub fn create_image_view(
         &self,
         image: vk::Image,
@@ -500,6 +569,22 @@ impl VulkanRHI {
         )?)
     }
 
+    pub fn create_image_layer_view(
+        &self,
+        image: vk::Image,
+        format: vk::Format,
+        aspect_flags: vk::ImageAspectFlags,
+        base_array_layer: u32,
+    ) -> Result<vk::ImageView> {
+        Ok(vulkan_util::create_image_layer_view(
+            &self.m_device,
+            image,
+            format,
+            aspect_flags,
+            base_array_layer,
+        )?)
+    }
+
     //mip_levels: 0 means auto
     pub fn create_texture_image(
         &self,
@@ -519,6 +604,48 @@ impl VulkanRHI {
         )?)
     }
 
+    /// `levels` holds every mip level, largest first.
+    pub fn create_texture_image_from_mips(
+        &self,
+        width: u32,
+        height: u32,
+        levels: &[&[u8]],
+        format: vk::Format,
+    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView)> {
+        Ok(vulkan_util::create_texture_image_from_mips(
+            self, width, height, levels, format,
+        )?)
+    }
+
+    /// `levels` holds every mip level, largest first, each with the six faces back to back.
+    pub fn create_cube_map_from_mips(
+        &self,
+        width: u32,
+        height: u32,
+        levels: &[&[u8]],
+        format: vk::Format,
+    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView)> {
+        Ok(vulkan_util::create_cube_map_from_mips(
+            self, width, height, levels, format,
+        )?)
+    }
+
+    /// Whether textures of `format` can be sampled with linear filtering. Block compressed
+    /// formats also need the device to support BC compression.
+    pub fn is_texture_format_supported(&self, format: vk::Format) -> bool {
+        if is_block_compressed(format) && !self.m_data.m_enable_texture_compression_bc {
+            return false;
+        }
+        let properties = unsafe {
+            self.m_instance
+                .get_physical_device_format_properties(self.m_data.m_physical_device, format)
+        };
+        properties.optimal_tiling_features.contains(
+            vk::FormatFeatureFlags::SAMPLED_IMAGE
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+        )
+    }
+
     pub fn create_descriptor_set_layout(
         &self,
         create_info: &vk::DescriptorSetLayoutCreateInfo,
@@ -656,6 +783,27 @@ impl VulkanRHI {
         }
     }
 
+    pub fn cmd_copy_image(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        src_image: vk::Image,
+        src_image_layout: vk::ImageLayout,
+        dst_image: vk::Image,
+        dst_image_layout: vk::ImageLayout,
+        regions: &[vk::ImageCopy],
+    ) {
+        unsafe {
+            self.m_device.cmd_copy_image(
+                command_buffer,
+                src_image,
+                src_image_layout,
+                dst_image,
+                dst_image_layout,
+                regions,
+            );
+        }
+    }
+
     pub fn cmd_copy_image_to_buffer(
         &self,
         command_buffer: vk::CommandBuffer,
@@ -824,6 +972,22 @@ impl VulkanRHI {
         }
     }
 
+    /// The MSAA sample count for `sample_count` samples per pixel. It has to be supported by
+    /// both color and depth attachments, as the forward lighting renders with depth.
+    pub fn get_msaa_sample_count(&self, sample_count: u32) -> Result<vk::SampleCountFlags> {
+        let limits = self.get_physical_device_properties().limits;
+        let supported =
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+        match vk::SampleCountFlags::from_bits(sample_count) {
+            Some(samples) if sample_count.is_power_of_two() && supported.contains(samples) => {
+                Ok(samples)
+            }
+            _ => Err(anyhow!(
+                "{sample_count}x MSAA is not supported, the device supports {supported:?}"
+            )),
+        }
+    }
+
     pub fn wait_idle(&self) -> Result<()> {
         unsafe {
             self.m_device.device_wait_idle()?;
@@ -927,10 +1091,15 @@ impl VulkanRHI {
 
     pub fn prepare_before_pass(
         &mut self,
-        pass_update_after_recreate_swapchain: &mut dyn FnMut(&VulkanRHI),
+        pass_update_after_recreate_swapchain: &mut dyn FnMut(&VulkanRHI) -> Result<()>,
     ) -> Result<bool> {
         let in_flight_fence = self.m_data.m_is_frame_in_flight_fences[self.m_current_frame_index];
 
+        if self.m_data.m_is_offscreen {
+            self.m_data.m_current_swapchain_image_index = 0;
+            return self.begin_offscreen_frame(in_flight_fence).map(|_| false);
+        }
+
         let result = unsafe {
             self.m_device.acquire_next_image_khr(
                 self.m_data.m_swapchain,
@@ -947,7 +1116,7 @@ impl VulkanRHI {
             Ok((image_index, _)) => image_index as usize,
             Err(vk::ErrorCode::OUT_OF_DATE_KHR) => {
                 self.recreate_swapchain(window)?;
-                pass_update_after_recreate_swapchain(&self);
+                pass_update_after_recreate_swapchain(&self)?;
                 return Ok(true);
             }
             Err(e) => return Err(anyhow!(e)),
@@ -977,8 +1146,14 @@ impl VulkanRHI {
 
     pub fn submit_rendering(
         &mut self,
-        pass_update_after_recreate_swapchain: &mut dyn FnMut(&VulkanRHI),
+        pass_update_after_recreate_swapchain: &mut dyn FnMut(&VulkanRHI) -> Result<()>,
     ) -> Result<()> {
+        if self.m_data.m_is_offscreen {
+            self.submit_frame()?;
+            self.m_data.m_offscreen_image_layout = vk::ImageLayout::PRESENT_SRC_KHR;
+            return Ok(());
+        }
+
         let command_buffer = self.m_data.m_current_command_buffer;
         unsafe {
             self.m_device.end_command_buffer(command_buffer)?;
@@ -1026,7 +1201,7 @@ impl VulkanRHI {
         match result {
             Ok(vk::SuccessCode::SUBOPTIMAL_KHR) | Err(vk::ErrorCode::OUT_OF_DATE_KHR) => {
                 self.recreate_swapchain(window)?;
-                pass_update_after_recreate_swapchain(&self);
+                pass_update_after_recreate_swapchain(&self)?;
             }
             Err(e) => {
                 return Err(anyhow!(e));
@@ -1038,6 +1213,126 @@ impl VulkanRHI {
         Ok(())
     }
 
+    pub fn is_offscreen(&self) -> bool {
+        self.m_data.m_is_offscreen
+    }
+
+    /// Copies the offscreen color target back to the host as tightly packed RGBA8 rows.
+    /// Waits for the device first, so the result is the last submitted frame.
+    pub fn read_back_offscreen_image(&self) -> Result<Vec<u8>> {
+        if !self.m_data.m_is_offscreen {
+            return Err(anyhow!("read back requires an offscreen rhi"));
+        }
+        let image_layout = self.m_data.m_offscreen_image_layout;
+        if image_layout == vk::ImageLayout::UNDEFINED {
+            return Err(anyhow!("no frame has been rendered offscreen yet"));
+        }
+        self.wait_idle()?;
+
+        let extent = self.m_data.m_swapchain_extent;
+        let image = self.m_data.m_swapchain_images[self.m_data.m_current_swapchain_image_index];
+        let buffer_size = (extent.width * extent.height * 4) as vk::DeviceSize;
+        let (staging_buffer, staging_buffer_mem) = self.create_buffer(
+            buffer_size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        let subresource_range = vk::ImageSubresourceRange::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1)
+            .build();
+        let graphics_family = self.m_data.m_queue_indices.graphics_family.unwrap();
+        let to_transfer_barrier = vk::ImageMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .old_layout(image_layout)
+            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .src_queue_family_index(graphics_family)
+            .dst_queue_family_index(graphics_family)
+            .image(image)
+            .subresource_range(subresource_range)
+            .build();
+        let restore_layout_barrier = vk::ImageMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .dst_access_mask(vk::AccessFlags::empty())
+            .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .new_layout(image_layout)
+            .src_queue_family_index(graphics_family)
+            .dst_queue_family_index(graphics_family)
+            .image(image)
+            .subresource_range(subresource_range)
+            .build();
+
+        let region = vk::BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(
+                vk::ImageSubresourceLayers::builder()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .mip_level(0)
+                    .base_array_layer(0)
+                    .layer_count(1)
+                    .build(),
+            )
+            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+            .image_extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .build();
+
+        let command_buffer = self.begin_single_time_commands()?;
+        self.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_transfer_barrier],
+        );
+        self.cmd_copy_image_to_buffer(
+            command_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            staging_buffer,
+            &[region],
+        );
+        self.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[restore_layout_barrier],
+        );
+        self.end_single_time_commands(command_buffer)?;
+
+        let data = self.map_memory(
+            staging_buffer_mem,
+            0,
+            buffer_size,
+            vk::MemoryMapFlags::empty(),
+        )? as *const u8;
+        let mut pixels = unsafe { std::slice::from_raw_parts(data, buffer_size as usize) }.to_vec();
+        self.unmap_memory(staging_buffer_mem);
+        self.destroy_buffer(staging_buffer);
+        self.free_memory(staging_buffer_mem);
+
+        // The target uses the swapchain's BGRA layout.
+        pixels
+            .chunks_exact_mut(4)
+            .for_each(|pixel| pixel.swap(0, 2));
+        Ok(pixels)
+    }
+
     pub fn prepare_frame(&self) -> Result<()> {
         let info = vk::CommandBufferBeginInfo::builder()
             .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
@@ -1194,13 +1489,13 @@ fn check_validation_layer_support(entry: &Entry, data: &VulkanRHIData) -> Result
 }
 
 fn get_required_extensions(entry: &Entry, data: &VulkanRHIData) -> Result<Vec<*const i8>> {
-    let binding = data.m_window.upgrade();
-    let window = binding.as_ref().unwrap();
-
-    let mut extensions = vk_window::get_required_instance_extensions(window)
-        .iter()
-        .map(|e| e.as_ptr())
-        .collect::<Vec<_>>();
+    let mut extensions = match data.m_window.upgrade() {
+        Some(window) => vk_window::get_required_instance_extensions(window.as_ref())
+            .iter()
+            .map(|e| e.as_ptr())
+            .collect::<Vec<_>>(),
+        None => vec![],
+    };
 
     if data.m_enable_validation_layers || data.m_enable_debug_utils_label {
         extensions.push(vk::EXT_DEBUG_UTILS_EXTENSION.name.as_ptr());
@@ -1253,7 +1548,7 @@ fn populate_debug_messenger_info(
         .user_callback(Some(debug_callback))
 }
 
-fn create_instance(_window: &Window, entry: &Entry, data: &mut VulkanRHIData) -> Instance {
+fn create_instance(entry: &Entry, data: &mut VulkanRHIData) -> Instance {
     if data.m_enable_validation_layers && !check_validation_layer_support(entry, data).unwrap() {
         error!("validation layers requested, but not available!");
     }
@@ -1330,6 +1625,15 @@ fn find_queue_families(
     for (index, properties) in properties.iter().enumerate() {
         if properties.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
             indices.graphics_family = Some(index as u32);
+            if data.m_is_offscreen {
+                indices.present_family = Some(index as u32);
+            }
+        }
+        if data.m_is_offscreen {
+            if indices.is_complete() {
+                break;
+            }
+            continue;
         }
         if unsafe {
             instance.get_physical_device_surface_support_khr(
@@ -1395,6 +1699,10 @@ fn is_device_suitable(
     if !check_device_extension_support(instance, data, physical_device)? {
         return Ok(false);
     }
+    if data.m_is_offscreen {
+        let features = unsafe { instance.get_physical_device_features(physical_device) };
+        return Ok(features.sampler_anisotropy == vk::TRUE);
+    }
     let swapchain_support_details = query_swapchain_support(instance, data, physical_device)?;
     let is_swapchain_adequate = !swapchain_support_details.formats.is_empty()
         && !swapchain_support_details.present_modes.is_empty();
@@ -1496,11 +1804,19 @@ fn create_logical_device(
     }
 
     let enable_geometry_shader = data.m_enable_point_light_shadow;
+    // BC textures fall back to CPU decoding when the device cannot sample them
+    data.m_enable_texture_compression_bc = unsafe {
+        instance
+            .get_physical_device_features(data.m_physical_device)
+            .texture_compression_bc
+            == vk::TRUE
+    };
 
     let features = vk::PhysicalDeviceFeatures::builder()
         .sampler_anisotropy(true)
         .fragment_stores_and_atomics(true)
         .geometry_shader(enable_geometry_shader)
+        .texture_compression_bc(data.m_enable_texture_compression_bc)
         .independent_blend(true)
         .sample_rate_shading(true);
 
@@ -1598,9 +1914,19 @@ fn create_descriptor_pool(device: &Device, data: &mut VulkanRHIData) -> Result<(
     .into_iter()
     .filter(|poolsize| poolsize.descriptor_count > 0)
     .collect::<Vec<_>>();
+    // shader passes are rebuilt whenever the render pipeline changes, so their sets are freed
     let info = vk::DescriptorPoolCreateInfo::builder()
+        .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
         .pool_sizes(&pool_sizes)
-        .max_sets(1 + 1 + 1 + MAX_MATERIAL_COUNT + data.m_max_vertex_blending_mesh_count + 1 + 1);
+        .max_sets(
+            1 + 1
+                + 1
+                + MAX_MATERIAL_COUNT
+                + data.m_max_vertex_blending_mesh_count
+                + 1
+                + 1
+                + MAX_SHADER_FULLSCREEN_PASS_COUNT,
+        );
 
     unsafe {
         data.m_descriptor_pool = device.create_descriptor_pool(&info, None)?;
@@ -1742,6 +2068,41 @@ fn create_swapchain(
     Ok(())
 }
 
+/// Creates the color target used instead of swapchain images when rendering offscreen.
+/// It has the swapchain's format and usage plus `TRANSFER_SRC` for read back. Passes still
+/// end in `PRESENT_SRC_KHR`, so the swapchain device extension stays required.
+fn create_offscreen_images(
+    instance: &Instance,
+    device: &Device,
+    window_size: (u32, u32),
+    data: &mut VulkanRHIData,
+) -> Result<()> {
+    let format = vk::Format::B8G8R8A8_SRGB;
+    let (image, memory) = vulkan_util::create_image(
+        instance,
+        device,
+        data.m_physical_device,
+        window_size.0,
+        window_size.1,
+        format,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        vk::ImageCreateFlags::empty(),
+        1,
+        1,
+    )?;
+    data.m_swapchain_images = vec![image];
+    data.m_offscreen_image_memory = memory;
+    data.m_offscreen_image_layout = vk::ImageLayout::UNDEFINED;
+    data.m_swapchain_image_format = format;
+    data.m_swapchain_extent = vk::Extent2D {
+        width: window_size.0,
+        height: window_size.1,
+    };
+    Ok(())
+}
+
 fn create_swapchain_image_views(device: &Device, data: &mut VulkanRHIData) -> Result<()> {
     data.m_swapchain_image_views = data
         .m_swapchain_images
@@ -1792,7 +2153,8 @@ fn create_depth_objects(
         vk::ImageTiling::OPTIMAL,
         vk::ImageUsageFlags::INPUT_ATTACHMENT
             | vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
-            | vk::ImageUsageFlags::TRANSFER_SRC,
+            | vk::ImageUsageFlags::TRANSFER_SRC
+            | vk::ImageUsageFlags::SAMPLED,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
         vk::ImageCreateFlags::empty(),
         1,
@@ -552,6 +552,154 @@ pub fn create_texture_image(
     Ok((texture_image, texture_image_memory, image_view))
 }
 
+/// Like `create_texture_image`, with every mip level supplied instead of blitted, largest first.
+pub fn create_texture_image_from_mips(
+    rhi: &VulkanRHI,
+    width: u32,
+    height: u32,
+    levels: &[&[u8]],
+    format: vk::Format,
+) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView)> {
+    create_image_from_levels(rhi, width, height, levels, format, 1)
+}
+
+/// Like `create_cube_map`, with every mip level supplied, each holding the six faces in order.
+pub fn create_cube_map_from_mips(
+    rhi: &VulkanRHI,
+    width: u32,
+    height: u32,
+    levels: &[&[u8]],
+    format: vk::Format,
+) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView)> {
+    create_image_from_levels(rhi, width, height, levels, format, 6)
+}
+
+/// Six layers make a cube map. Block compressed formats upload here too, as they cannot be
+/// blitted.
+fn create_image_from_levels(
+    rhi: &VulkanRHI,
+    width: u32,
+    height: u32,
+    levels: &[&[u8]],
+    format: vk::Format,
+    layers: u32,
+) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView)> {
+    let (image_flags, view_type) = if layers == 6 {
+        (
+            vk::ImageCreateFlags::CUBE_COMPATIBLE,
+            vk::ImageViewType::CUBE,
+        )
+    } else {
+        (vk::ImageCreateFlags::empty(), vk::ImageViewType::_2D)
+    };
+    let physical_device = rhi.m_data.m_physical_device;
+    let mip_levels = levels.len() as u32;
+    let size = levels.iter().map(|level| level.len()).sum::<usize>() as u64;
+    let (staging_buffer, staging_buffer_memory) = create_buffer(
+        &rhi.m_instance,
+        &rhi.m_device,
+        physical_device,
+        size,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )?;
+
+    let mut regions = Vec::with_capacity(levels.len());
+    unsafe {
+        let memory: *mut u8 = rhi
+            .m_device
+            .map_memory(staging_buffer_memory, 0, size, vk::MemoryMapFlags::empty())?
+            .cast();
+        let mut offset = 0;
+        for (mip_level, level) in levels.iter().enumerate() {
+            copy_nonoverlapping(level.as_ptr(), memory.add(offset), level.len());
+            regions.push(
+                vk::BufferImageCopy::builder()
+                    .buffer_offset(offset as u64)
+                    .image_subresource(
+                        vk::ImageSubresourceLayers::builder()
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .mip_level(mip_level as u32)
+                            .base_array_layer(0)
+                            .layer_count(layers),
+                    )
+                    .image_extent(vk::Extent3D {
+                        width: (width >> mip_level).max(1),
+                        height: (height >> mip_level).max(1),
+                        depth: 1,
+                    })
+                    .build(),
+            );
+            offset += level.len();
+        }
+        rhi.m_device.unmap_memory(staging_buffer_memory);
+    }
+
+    let (texture_image, texture_image_memory) = create_image(
+        &rhi.m_instance,
+        &rhi.m_device,
+        physical_device,
+        width,
+        height,
+        format,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        image_flags,
+        layers,
+        mip_levels,
+    )?;
+
+    transition_image_layout(
+        rhi,
+        texture_image,
+        vk::ImageLayout::UNDEFINED,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        layers,
+        mip_levels,
+        vk::ImageAspectFlags::COLOR,
+    )?;
+
+    let command_buffer = rhi.begin_single_time_commands()?;
+    unsafe {
+        rhi.m_device.cmd_copy_buffer_to_image(
+            command_buffer,
+            staging_buffer,
+            texture_image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &regions,
+        );
+    }
+    rhi.end_single_time_commands(command_buffer)?;
+
+    transition_image_layout(
+        rhi,
+        texture_image,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        layers,
+        mip_levels,
+        vk::ImageAspectFlags::COLOR,
+    )?;
+
+    unsafe {
+        rhi.m_device.destroy_buffer(staging_buffer, None);
+        rhi.m_device.free_memory(staging_buffer_memory, None);
+    }
+
+    let image_view = create_image_view(
+        &rhi.m_device,
+        texture_image,
+        format,
+        vk::ImageAspectFlags::COLOR,
+        view_type,
+        layers,
+        mip_levels,
+    )?;
+
+    Ok((texture_image, texture_image_memory, image_view))
+}
+
 pub fn create_image(
     instance: &Instance,
     device: &Device,
@@ -601,6 +749,51 @@ pub fn create_image(
     }
 }
 
+/// A device local attachment with `samples` samples per pixel, only ever resolved from.
+pub fn create_multisampled_image(
+    instance: &Instance,
+    device: &Device,
+    physical_device: vk::PhysicalDevice,
+    image_width: u32,
+    image_height: u32,
+    format: vk::Format,
+    image_usage_flags: vk::ImageUsageFlags,
+    samples: vk::SampleCountFlags,
+) -> Result<(vk::Image, vk::DeviceMemory)> {
+    let info = vk::ImageCreateInfo::builder()
+        .image_type(vk::ImageType::_2D)
+        .extent(vk::Extent3D {
+            width: image_width,
+            height: image_height,
+            depth: 1,
+        })
+        .mip_levels(1)
+        .array_layers(1)
+        .format(format)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .usage(image_usage_flags)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .samples(samples);
+
+    unsafe {
+        let image = device.create_image(&info, None)?;
+        let requirements = device.get_image_memory_requirements(image);
+        let info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(find_memory_type(
+                instance,
+                physical_device,
+                requirements.memory_type_bits,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?);
+
+        let image_memory = device.allocate_memory(&info, None)?;
+        device.bind_image_memory(image, image_memory, 0)?;
+        Ok((image, image_memory))
+    }
+}
+
 pub fn create_image_view(
     device: &Device,
     image: vk::Image,
@@ -625,3 +818,27 @@ pub fn create_image_view(
 
     Ok(unsafe { device.create_image_view(&info, None)? })
 }
+
+/// 2D view of a single layer of an array image, e.g. to render into one layer.
+pub fn create_image_layer_view(
+    device: &Device,
+    image: vk::Image,
+    format: vk::Format,
+    image_aspect_flags: vk::ImageAspectFlags,
+    base_array_layer: u32,
+) -> Result<vk::ImageView> {
+    let subresource_range = vk::ImageSubresourceRange::builder()
+        .aspect_mask(image_aspect_flags)
+        .base_mip_level(0)
+        .level_count(1)
+        .base_array_layer(base_array_layer)
+        .layer_count(1);
+
+    let info = vk::ImageViewCreateInfo::builder()
+        .image(image)
+        .view_type(vk::ImageViewType::_2D)
+        .format(format)
+        .subresource_range(subresource_range);
+
+    Ok(unsafe { device.create_image_view(&info, None)? })
+}
@@ -1,8 +1,8 @@
 use std::f32::consts::PI;
 
-use crate::{core::math::vector3::Vector3, function::render::render_type::BufferData};
-
+use crate::{core::math::{matrix4::Matrix4x4, vector3::Vector3}, function::render::{render_common::S_MAX_DIRECTIONAL_LIGHT_CASCADE_COUNT, render_type::BufferData}};
 
+#[derive(Clone)]
 pub struct PointLight {
     pub m_position: Vector3,
     pub m_flux: Vector3,
@@ -17,15 +17,39 @@ impl PointLight {
     }
 
     pub fn calculate_radius(&self) -> f32 {
-        const INTENSITY_CUTOFF: f32 = 1.0;
-        const ATTENTUATION_CUTOFF: f32 = 0.05;
-        let intensity = self.m_flux / (4.0 * PI);
-        let max_intensity = intensity.x.max(intensity.y).max(intensity.z);
-        let attenuation = INTENSITY_CUTOFF.max(ATTENTUATION_CUTOFF * max_intensity) / max_intensity;
-        1.0 / attenuation.sqrt()
+        calculate_light_radius(&self.m_flux)
     } 
 }
 
+/// Distance at which a light of the given flux falls below the shading cutoff.
+fn calculate_light_radius(flux: &Vector3) -> f32 {
+    const INTENSITY_CUTOFF: f32 = 1.0;
+    const ATTENTUATION_CUTOFF: f32 = 0.05;
+    let intensity = *flux / (4.0 * PI);
+    let max_intensity = intensity.x.max(intensity.y).max(intensity.z);
+    let attenuation = INTENSITY_CUTOFF.max(ATTENTUATION_CUTOFF * max_intensity) / max_intensity;
+    1.0 / attenuation.sqrt()
+}
+
+/// A point light masked to a cone; it is as bright as a point light of the same flux inside
+/// `m_inner_cone_angle` and fades out towards `m_outer_cone_angle`. Angles are half angles in
+/// radians.
+#[derive(Clone)]
+pub struct SpotLight {
+    pub m_position: Vector3,
+    pub m_direction: Vector3,
+    pub m_flux: Vector3,
+    pub m_inner_cone_angle: f32,
+    pub m_outer_cone_angle: f32,
+    pub m_cast_shadow: bool,
+}
+
+impl SpotLight {
+    pub fn calculate_radius(&self) -> f32 {
+        calculate_light_radius(&self.m_flux)
+    }
+}
+
 #[derive(Default)]
 pub struct AmbientLight {
     pub m_irradiance: Vector3,
@@ -35,6 +59,55 @@ pub struct AmbientLight {
 pub struct DirectionalLight {
     pub m_direction: Vector3,
     pub m_color: Vector3,
+    pub m_cascade_count: u32,
+    pub m_cascade_split_lambda: f32,
+    pub m_shadow_distance: f32,
+    pub m_cascade_blend_ratio: f32,
+}
+
+impl DirectionalLight {
+    pub fn cascade_count(&self) -> usize {
+        (self.m_cascade_count as usize).clamp(1, S_MAX_DIRECTIONAL_LIGHT_CASCADE_COUNT)
+    }
+}
+
+/// Exponential height fog, see `Fog` in the global rendering resource.
+#[derive(Clone)]
+pub struct HeightFog {
+    pub m_density: f32,
+    pub m_base_height: f32,
+    pub m_height_falloff: f32,
+    pub m_start_distance: f32,
+    pub m_max_opacity: f32,
+}
+
+/// Screen-space ambient occlusion, see `AmbientOcclusion` in the global rendering resource.
+#[derive(Clone)]
+pub struct ScreenSpaceAmbientOcclusion {
+    pub m_sample_count: u32,
+    pub m_radius: f32,
+    pub m_bias: f32,
+    pub m_intensity: f32,
+    pub m_blur_radius: u32,
+}
+
+/// Bloom of the HDR scene, see `Bloom` in the global rendering resource.
+#[derive(Clone)]
+pub struct HDRBloom {
+    pub m_threshold: f32,
+    pub m_knee: f32,
+    pub m_intensity: f32,
+}
+
+/// Temporal anti-aliasing, see `TemporalAntiAliasing` in the global rendering resource.
+#[derive(Clone, Default)]
+pub struct TemporalAntiAliasing {
+    /// Whether the render pipeline has a TAA pass, the camera is only jittered then.
+    pub m_enable: bool,
+    pub m_history_weight: f32,
+    pub m_camera_cut_distance: f32,
+    /// Drops the history for this frame, after a camera cut or when asked to.
+    pub m_reset_history: bool,
 }
 
 pub struct PointLightVertex {
@@ -48,4 +121,11 @@ pub struct PointLightVertex {
 pub struct PointLightList {
     pub m_lights: Vec<PointLight>,
     pub m_buffer: BufferData,
-}
\ No newline at end of file
+}
+#[derive(Default)]
+pub struct SpotLightList {
+    pub m_lights: Vec<SpotLight>,
+    /// Index into `m_lights` of the light rendered into each spot light shadow map layer.
+    pub m_shadow_light_indices: Vec<usize>,
+    pub m_shadow_proj_views: Vec<Matrix4x4>,
+}
@@ -20,6 +20,8 @@ pub struct RenderCamera{
     m_aspect: f32,
     m_fovx: f32,
     m_fovy: f32,
+    /// Offset of the projection in NDC, moved every frame while TAA runs.
+    m_jitter: Vector2,
     m_view_matrix_mutex: Mutex<()>,
 }
 
@@ -37,6 +39,7 @@ impl Default for RenderCamera {
             m_aspect: 0.0,
             m_fovx: 89.0,
             m_fovy: 0.0,
+            m_jitter: Vector2::new(0.0, 0.0),
             m_view_matrix_mutex: Mutex::new(()),
         }
     }
@@ -85,6 +88,14 @@ impl RenderCamera {
         self.m_zfar = zfar;
     }
 
+    pub fn get_znear(&self) -> f32 {
+        self.m_znear
+    }
+
+    pub fn get_zfar(&self) -> f32 {
+        self.m_zfar
+    }
+
     pub fn get_view_matrix(&self) -> Matrix4x4 {
         let _guard = self.m_view_matrix_mutex.lock().unwrap();
         match self.m_current_type {
@@ -95,7 +106,18 @@ impl RenderCamera {
         }
     }
 
-    pub fn get_perspective_matrix(&self) -> Matrix4x4 { 
+    pub fn get_perspective_matrix(&self) -> Matrix4x4 {
+        let jitter_mat = Matrix4x4::from_columns(
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [self.m_jitter.x, self.m_jitter.y, 0.0, 1.0],
+        );
+        jitter_mat * self.get_unjittered_perspective_matrix()
+    }
+
+    /// The projection without the jitter, which the velocity of the meshes is measured with.
+    pub fn get_unjittered_perspective_matrix(&self) -> Matrix4x4 {
         let fix_mat = Matrix4x4::from_columns(
             [1.0, 0.0, 0.0, 0.0],
             [0.0, -1.0, 0.0, 0.0],
@@ -174,4 +196,8 @@ impl RenderCamera {
     pub fn set_fov_x(&mut self, fovx: f32) {
         self.m_fovx = fovx;
     }
+
+    pub fn set_jitter(&mut self, jitter: Vector2) {
+        self.m_jitter = jitter;
+    }
 }
\ No newline at end of file
@@ -1,11 +1,21 @@
-use std::{array, rc::{Rc, Weak}};
+use std::{array, cell::RefCell, ops::Deref, rc::{Rc, Weak}};
 
 use vulkanalia::{prelude::v1_0::*};
 
-use crate::{core::math::{matrix4::Matrix4x4, vector3::Vector3, vector4::Vector4}};
+use crate::{core::math::{matrix4::Matrix4x4, vector3::Vector3, vector4::Vector4}, resource::res_type::data::material::MaterialAlphaMode};
 
 pub const S_POINT_LIGHT_SHADOW_MAP_DIMENSION: u32 = 2048;
 pub const S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION: u32 = 4096;
+pub const S_MAX_DIRECTIONAL_LIGHT_CASCADE_COUNT: usize = 4;
+pub const S_SPOT_LIGHT_SHADOW_MAP_DIMENSION: u32 = 1024;
+pub const S_MAX_SPOT_LIGHT_SHADOW_COUNT: usize = 4;
+
+pub const S_LIGHT_CLUSTER_X: usize = 16;
+pub const S_LIGHT_CLUSTER_Y: usize = 9;
+pub const S_LIGHT_CLUSTER_Z: usize = 24;
+pub const S_LIGHT_CLUSTER_COUNT: usize = S_LIGHT_CLUSTER_X * S_LIGHT_CLUSTER_Y * S_LIGHT_CLUSTER_Z;
+pub const S_MAX_CLUSTERED_LIGHT_COUNT: usize = 1024;
+pub const S_MAX_LIGHT_CLUSTER_INDEX_COUNT: usize = S_LIGHT_CLUSTER_COUNT * 32;
 
 
 pub const MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT: usize = 64;
@@ -28,6 +38,75 @@ pub struct VulkanScenePointLight {
     pub _padding_intensity: f32,
 }
 
+pub const S_CLUSTERED_LIGHT_TYPE_POINT: u32 = 0;
+pub const S_CLUSTERED_LIGHT_TYPE_SPOT: u32 = 1;
+
+/// A point or spot light as the clustered shading loop sees it.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct VulkanClusteredLight {
+    pub position: Vector3,
+    pub radius: f32,
+    pub intensity: Vector3,
+    pub light_type: u32,
+    pub direction: Vector3,
+    pub cos_outer_cone: f32,
+    pub cos_inner_cone: f32,
+    /// Layer of the point or spot light shadow map, or -1 for an unshadowed light.
+    pub shadow_index: i32,
+    pub _padding_shadow_index_1: u32,
+    pub _padding_shadow_index_2: u32,
+}
+
+/// Lights binned into view-space froxels: `S_LIGHT_CLUSTER_X x S_LIGHT_CLUSTER_Y` screen tiles
+/// by `S_LIGHT_CLUSTER_Z` exponential depth slices. Each cluster grid entry is an
+/// `(offset, count)` range of `light_indices`.
+#[repr(C)]
+#[derive(Clone)]
+pub struct MeshLightClusterStorageBufferObject {
+    pub light_count: u32,
+    /// Depth slice of a view depth `d` is `ln(d / cluster_z_near) * cluster_z_scale`.
+    pub cluster_z_scale: f32,
+    pub cluster_z_near: f32,
+    pub _padding_cluster_z_near: u32,
+    pub spot_light_shadow_proj_view: [Matrix4x4; S_MAX_SPOT_LIGHT_SHADOW_COUNT],
+    pub lights: [VulkanClusteredLight; S_MAX_CLUSTERED_LIGHT_COUNT],
+    pub cluster_light_grids: [[u32; 2]; S_LIGHT_CLUSTER_COUNT],
+    pub light_indices: [u32; S_MAX_LIGHT_CLUSTER_INDEX_COUNT],
+}
+
+impl MeshLightClusterStorageBufferObject {
+    /// Allocates the buffer object zeroed on the heap, it is too large to build on the stack.
+    pub fn new_boxed() -> Box<Self> {
+        // SAFETY: every field is an integer or a float, for which all zero bits are valid
+        unsafe { Box::new_zeroed().assume_init() }
+    }
+
+    /// Number of leading `light_indices` the cluster grids reference.
+    pub fn light_index_count(&self) -> usize {
+        self.cluster_light_grids.iter().map(|[offset, count]| (offset + count) as usize).max().unwrap_or(0)
+    }
+}
+
+/// The light cluster buffer object the render resource fills and the main camera pass uploads,
+/// shared so the half megabyte is never copied between them.
+#[derive(Clone)]
+pub struct SharedMeshLightClusterStorageBufferObject(Rc<RefCell<Box<MeshLightClusterStorageBufferObject>>>);
+
+impl Default for SharedMeshLightClusterStorageBufferObject {
+    fn default() -> Self {
+        Self(Rc::new(RefCell::new(MeshLightClusterStorageBufferObject::new_boxed())))
+    }
+}
+
+impl Deref for SharedMeshLightClusterStorageBufferObject {
+    type Target = RefCell<Box<MeshLightClusterStorageBufferObject>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Default)]
 pub struct MeshPerframeStorageBufferObject {
@@ -42,7 +121,31 @@ pub struct MeshPerframeStorageBufferObject {
     pub _padding_point_light_num_3: u32,
     pub scene_point_lights: [VulkanScenePointLight; S_MAX_POINT_LIGHT_COUNT],
     pub scene_directional_light: VulkanSceneDirectionalLight,
-    pub directional_light_proj_view: Matrix4x4
+    pub directional_light_cascade_proj_view: [Matrix4x4; S_MAX_DIRECTIONAL_LIGHT_CASCADE_COUNT],
+    /// View-space depth where each cascade ends.
+    pub directional_light_cascade_splits: Vector4,
+    pub directional_light_cascade_count: u32,
+    pub directional_light_cascade_blend_ratio: f32,
+    pub _padding_directional_light_cascade_1: u32,
+    pub _padding_directional_light_cascade_2: u32,
+    /// Sunlight above the atmosphere, scaled for the procedural sky.
+    pub sky_sun_illuminance: Vector3,
+    /// Whether the sky is the procedural one instead of the skybox cube map.
+    pub sky_enable: u32,
+    /// Horizon color of the procedural sky the fog fades to, the skybox is sampled instead.
+    pub fog_sky_color: Vector3,
+    pub fog_enable: u32,
+    pub fog_density: f32,
+    pub fog_base_height: f32,
+    pub fog_height_falloff: f32,
+    pub fog_start_distance: f32,
+    pub fog_max_opacity: f32,
+    pub _padding_fog_1: u32,
+    pub _padding_fog_2: u32,
+    pub _padding_fog_3: u32,
+    /// Without the TAA jitter, so the velocity of the meshes leaves it out.
+    pub unjittered_proj_view_matrix: Matrix4x4,
+    pub previous_unjittered_proj_view_matrix: Matrix4x4,
 }
 #[repr(C)]
 #[derive(Clone, Default)]
@@ -52,6 +155,7 @@ pub struct VulkanMeshInstance {
     _padding_enable_vertex_blending_2: f32,
     _padding_enable_vertex_blending_3: f32,
     pub model_matrix: Matrix4x4,
+    pub previous_model_matrix: Matrix4x4,
 }
 
 #[repr(C)]
@@ -82,6 +186,12 @@ impl Default for MeshPerdrawcallVertexBlendingStorageBufferObject {
     }
 }
 
+/// How `calculateNormal` reads the normal texture: three channels in [0, 1], or two channels
+/// with z rebuilt from them, the signed ones already being in [-1, 1].
+pub const S_NORMAL_ENCODING_RGB: u32 = 0;
+pub const S_NORMAL_ENCODING_RG_UNORM: u32 = 1;
+pub const S_NORMAL_ENCODING_RG_SNORM: u32 = 2;
+
 #[repr(C)]
 pub struct MeshPerMaterialUniformBufferObject {
     pub base_color_factor: Vector4,
@@ -94,6 +204,10 @@ pub struct MeshPerMaterialUniformBufferObject {
     pub emissive_factor: Vector3,
     pub is_blend: u32,
     pub id_double_sided: u32,
+    /// Texels whose alpha is below it are discarded, 0 unless the material is masked.
+    pub alpha_cutoff: f32,
+    /// One of the `S_NORMAL_ENCODING_*` values, picked from the normal texture's source format.
+    pub normal_encoding: u32,
 }
 
 #[repr(C)]
@@ -256,11 +370,32 @@ pub struct VulkanPBRMaterial {
 #[derive(Clone, Default)]
 pub struct RenderMeshNode {
     pub model_matrix: Rc<Matrix4x4>,
+    /// Only set for the main camera, whose meshes write velocity.
+    pub previous_model_matrix: Rc<Matrix4x4>,
     pub joint_matrices: Vec<Matrix4x4>,
     pub ref_mesh: Weak<VulkanMesh>,
     pub ref_material: Weak<VulkanPBRMaterial>,
     pub node_id: u32,
     pub enable_vertex_blending: bool,
+    /// Picks the alpha-tested pipelines for masked meshes.
+    pub alpha_mode: MaterialAlphaMode,
+}
+
+/// Per-frame counters gathered while building the visible mesh lists.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStatistics {
+    pub main_camera_visible_mesh_count: usize,
+    pub main_camera_culled_mesh_count: usize,
+    pub directional_light_visible_mesh_count: usize,
+    pub directional_light_culled_mesh_count: usize,
+    pub visible_point_light_count: usize,
+    pub culled_point_light_count: usize,
+    pub visible_spot_light_count: usize,
+    pub culled_spot_light_count: usize,
+    /// Instanced mesh draws of the main camera pass.
+    pub main_camera_draw_call_count: usize,
+    /// Instanced mesh draws of the directional, point and spot light shadow passes.
+    pub shadow_draw_call_count: usize,
 }
 
 pub struct TextureDataToUpdate<'a>{
@@ -268,26 +403,36 @@ pub struct TextureDataToUpdate<'a>{
     pub base_color_image_width: u32,
     pub base_color_image_height: u32,
     pub base_color_image_format: vk::Format,
+    pub base_color_image_mip_levels: u32,
+    pub base_color_image_mip_pixels: &'a [Vec<u8>],
 
     pub metallic_roughness_image_pixels: &'a [u8],
     pub metallic_roughness_image_width: u32,
     pub metallic_roughness_image_height: u32,
     pub metallic_roughness_image_format: vk::Format,
+    pub metallic_roughness_image_mip_levels: u32,
+    pub metallic_roughness_image_mip_pixels: &'a [Vec<u8>],
 
     pub normal_roughness_image_pixels: &'a [u8],
     pub normal_roughness_image_width: u32,
     pub normal_roughness_image_height: u32,
     pub normal_roughness_image_format: vk::Format,
+    pub normal_roughness_image_mip_levels: u32,
+    pub normal_roughness_image_mip_pixels: &'a [Vec<u8>],
 
     pub occlusion_image_pixels: &'a [u8],
     pub occlusion_image_width: u32,
     pub occlusion_image_height: u32,
     pub occlusion_image_format: vk::Format,
+    pub occlusion_image_mip_levels: u32,
+    pub occlusion_image_mip_pixels: &'a [Vec<u8>],
 
     pub emissive_image_pixels: &'a [u8],
     pub emissive_image_width: u32,
     pub emissive_image_height: u32,
     pub emissive_image_format: vk::Format,
+    pub emissive_image_mip_levels: u32,
+    pub emissive_image_mip_pixels: &'a [Vec<u8>],
 
     pub now_material: &'a mut VulkanPBRMaterial,
 }
\ No newline at end of file
@@ -1,10 +1,12 @@
 use std::rc::Rc;
 
-use crate::core::math::{axis_aligned::AxisAlignedBox, matrix4::Matrix4x4, vector3::Vector3, vector4::Vector4};
+use crate::{core::math::{axis_aligned::AxisAlignedBox, matrix4::Matrix4x4, vector3::Vector3, vector4::Vector4}, resource::res_type::data::material::MaterialAlphaMode};
 
 pub struct RenderEntity {
     pub m_instance_id: u32,
     pub m_model_matrix: Rc<Matrix4x4>,
+    /// Where the entity was drawn last frame, for its velocity.
+    pub m_previous_model_matrix: Rc<Matrix4x4>,
     
     pub m_mesh_asset_id: usize,
     pub m_enable_vertex_blending: bool,
@@ -12,7 +14,8 @@ pub struct RenderEntity {
     pub m_bounding_box: AxisAlignedBox,
 
     pub m_material_asset_id: usize,
-    pub m_blend: bool,
+    pub m_alpha_mode: MaterialAlphaMode,
+    pub m_alpha_cutoff: f32,
     pub m_double_sided: bool,
     pub m_base_color_factor: Vector4,
     pub m_metallic_factor: f32,
@@ -27,6 +30,7 @@ impl Default for RenderEntity {
         Self {
             m_instance_id: 0,
             m_model_matrix: Rc::new(Matrix4x4::identity()),
+            m_previous_model_matrix: Rc::new(Matrix4x4::identity()),
             
             m_mesh_asset_id: 0,
             m_enable_vertex_blending: false,
@@ -34,14 +38,15 @@ impl Default for RenderEntity {
             m_bounding_box: Default::default(),
 
             m_material_asset_id: 0,
-            m_blend: false,
+            m_alpha_mode: MaterialAlphaMode::Opaque,
+            m_alpha_cutoff: 0.5,
             m_double_sided: false,
             m_base_color_factor: Vector4::new(1.0, 1.0, 1.0, 1.0),
             m_metallic_factor: 1.0,
             m_roughness_factor: 1.0,
             m_normal_scale: 1.0,
             m_occlusion_strength: 1.0,
-            m_emissive_factor: Vector3::new(0.0, 0.0, 0.0),
+            m_emissive_factor: Vector3::new(1.0, 1.0, 1.0),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,875 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Result, anyhow};
+use vulkanalia::prelude::v1_0::*;
+
+use crate::resource::res_type::data::render_pipeline::{
+    RenderLightingPath, RenderPipelineEdge, RenderPipelineNode, RenderPipelineNodeKind,
+    RenderPipelineRes, ShaderFullscreenDesc,
+};
+
+/// The node graph of the main camera pass as stored in `asset/render_pipeline/`.
+pub type RenderGraphAsset = RenderPipelineRes;
+
+/// What an edge carries between two nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderGraphPortType {
+    GBuffer,
+    Depth,
+    SceneColor,
+    UIColor,
+}
+
+/// A validated render graph with its nodes in the order they execute.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledRenderGraph {
+    pub lighting_path: RenderLightingPath,
+    pub execution_order: Vec<RenderPipelineNodeKind>,
+    /// The shaders of the `ShaderFullscreen` nodes, in execution order.
+    pub shader_nodes: Vec<ShaderFullscreenDesc>,
+}
+
+impl CompiledRenderGraph {
+    /// The post process nodes in execution order, which is the order they chain the scene color.
+    pub fn post_process_nodes(&self) -> impl Iterator<Item = RenderPipelineNodeKind> + '_ {
+        self.execution_order
+            .iter()
+            .copied()
+            .filter(|kind| kind.is_post_process())
+    }
+}
+
+/// Subpasses of the main camera pass run in a fixed stage order; an edge may only feed a later
+/// stage, or another post pass.
+fn node_stage(kind: RenderPipelineNodeKind) -> u32 {
+    match kind {
+        RenderPipelineNodeKind::BasePass => 0,
+        RenderPipelineNodeKind::DeferredLighting => 1,
+        RenderPipelineNodeKind::ForwardLighting => 2,
+        RenderPipelineNodeKind::ToneMapping
+        | RenderPipelineNodeKind::ColorGrading
+        | RenderPipelineNodeKind::FXAA
+        | RenderPipelineNodeKind::TAA
+        | RenderPipelineNodeKind::ShaderFullscreen
+        | RenderPipelineNodeKind::UI => 3,
+        RenderPipelineNodeKind::CombineUI => 4,
+    }
+}
+
+fn output_port_type(kind: RenderPipelineNodeKind, port: &str) -> Option<RenderGraphPortType> {
+    match kind {
+        RenderPipelineNodeKind::BasePass if port == "depth" => Some(RenderGraphPortType::Depth),
+        RenderPipelineNodeKind::BasePass => Some(RenderGraphPortType::GBuffer),
+        RenderPipelineNodeKind::UI => Some(RenderGraphPortType::UIColor),
+        RenderPipelineNodeKind::CombineUI => None,
+        _ => Some(RenderGraphPortType::SceneColor),
+    }
+}
+
+fn accepted_input_types(kind: RenderPipelineNodeKind) -> &'static [RenderGraphPortType] {
+    match kind {
+        RenderPipelineNodeKind::BasePass | RenderPipelineNodeKind::UI => &[],
+        RenderPipelineNodeKind::DeferredLighting => {
+            &[RenderGraphPortType::GBuffer, RenderGraphPortType::Depth]
+        }
+        RenderPipelineNodeKind::ForwardLighting => {
+            &[RenderGraphPortType::SceneColor, RenderGraphPortType::Depth]
+        }
+        RenderPipelineNodeKind::ToneMapping
+        | RenderPipelineNodeKind::ColorGrading
+        | RenderPipelineNodeKind::FXAA => &[RenderGraphPortType::SceneColor],
+        RenderPipelineNodeKind::TAA | RenderPipelineNodeKind::ShaderFullscreen => {
+            &[RenderGraphPortType::SceneColor, RenderGraphPortType::Depth]
+        }
+        RenderPipelineNodeKind::CombineUI => &[
+            RenderGraphPortType::SceneColor,
+            RenderGraphPortType::UIColor,
+        ],
+    }
+}
+
+/// Validates the graph and sorts its nodes topologically. Nodes that become ready at the same
+/// time run in id order, so the result does not depend on the order nodes are listed in.
+pub fn compile_render_graph(asset: &RenderGraphAsset) -> Result<CompiledRenderGraph> {
+    let mut nodes: HashMap<u32, &RenderPipelineNode> = HashMap::new();
+    for node in &asset.nodes {
+        if nodes.insert(node.id, node).is_some() {
+            return Err(anyhow!(
+                "render graph has more than one node with id {}",
+                node.id
+            ));
+        }
+        if node.kind == RenderPipelineNodeKind::ShaderFullscreen {
+            let shader = node.shader.as_ref().ok_or_else(|| {
+                anyhow!("{} is a ShaderFullscreen node without a shader", node.name)
+            })?;
+            validate_shader_bindings(&node.name, shader)?;
+        } else if asset
+            .nodes
+            .iter()
+            .filter(|other| other.kind == node.kind)
+            .count()
+            > 1
+        {
+            return Err(anyhow!(
+                "render graph has more than one {:?} node",
+                node.kind
+            ));
+        }
+    }
+
+    let find_kind =
+        |kind: RenderPipelineNodeKind| asset.nodes.iter().find(|node| node.kind == kind);
+    for required in [
+        RenderPipelineNodeKind::UI,
+        RenderPipelineNodeKind::CombineUI,
+    ] {
+        find_kind(required).ok_or_else(|| anyhow!("render graph has no {required:?} node"))?;
+    }
+    let lighting_kind = match asset.lighting_path {
+        RenderLightingPath::Forward => RenderPipelineNodeKind::ForwardLighting,
+        RenderLightingPath::Deferred => RenderPipelineNodeKind::DeferredLighting,
+    };
+    find_kind(lighting_kind).ok_or_else(|| {
+        anyhow!(
+            "{:?} render graph has no {lighting_kind:?} node",
+            asset.lighting_path
+        )
+    })?;
+
+    // the scene color input of every node, to check the post chain is linear below
+    let mut scene_color_sources: HashMap<u32, u32> = HashMap::new();
+    for edge in &asset.edges {
+        let (from, to) = edge_nodes(&nodes, edge)?;
+        let port_type = output_port_type(from.kind, &edge.from_port)
+            .ok_or_else(|| anyhow!("{} has no output port '{}'", from.name, edge.from_port))?;
+        if !accepted_input_types(to.kind).contains(&port_type) {
+            return Err(anyhow!(
+                "{}.{} carries {port_type:?}, which {}.{} does not accept",
+                from.name,
+                edge.from_port,
+                to.name,
+                edge.to_port
+            ));
+        }
+        let same_post_stage = from.kind.is_post_process() && to.kind.is_post_process();
+        if node_stage(from.kind) >= node_stage(to.kind) && !same_post_stage {
+            return Err(anyhow!("{} cannot run before {}", to.name, from.name));
+        }
+        if port_type == RenderGraphPortType::SceneColor
+            && scene_color_sources.insert(to.id, from.id).is_some()
+        {
+            return Err(anyhow!("{} has more than one scene color input", to.name));
+        }
+    }
+
+    validate_lighting_inputs(asset, &nodes)?;
+
+    let execution_order = topological_sort(asset, &nodes)?;
+
+    // post passes must form a single chain from the lighting output to CombineUI; a deferred
+    // graph may still draw blended meshes forward, which then makes the forward node the output
+    let lighting_output = execution_order
+        .iter()
+        .filter(|node| {
+            matches!(
+                node.kind,
+                RenderPipelineNodeKind::DeferredLighting | RenderPipelineNodeKind::ForwardLighting
+            )
+        })
+        .max_by_key(|node| node_stage(node.kind))
+        .unwrap();
+    let combine_ui = find_kind(RenderPipelineNodeKind::CombineUI).unwrap();
+    let mut expected_source = lighting_output.id;
+    for node in execution_order
+        .iter()
+        .filter(|node| node.kind.is_post_process())
+        .chain([&combine_ui])
+    {
+        let source = scene_color_sources
+            .get(&node.id)
+            .ok_or_else(|| anyhow!("{} has no scene color input", node.name))?;
+        if *source != expected_source {
+            return Err(anyhow!(
+                "{} does not continue the post process chain",
+                node.name
+            ));
+        }
+        expected_source = node.id;
+    }
+
+    Ok(CompiledRenderGraph {
+        lighting_path: asset.lighting_path,
+        execution_order: execution_order.iter().map(|node| node.kind).collect(),
+        shader_nodes: execution_order
+            .iter()
+            .filter(|node| node.kind == RenderPipelineNodeKind::ShaderFullscreen)
+            .filter_map(|node| node.shader.clone())
+            .collect(),
+    })
+}
+
+/// The base pass and the lighting subpasses run in render passes of their own, which are wired
+/// the same whatever the graph says, so the inputs of the lighting nodes must match them:
+/// deferred lighting reads the gbuffer and the depth, and forward lighting reads the depth and,
+/// in a deferred graph, the deferred result it draws blended meshes over.
+fn validate_lighting_inputs(
+    asset: &RenderGraphAsset,
+    nodes: &HashMap<u32, &RenderPipelineNode>,
+) -> Result<()> {
+    let has_node = |kind: RenderPipelineNodeKind| asset.nodes.iter().any(|node| node.kind == kind);
+    let mut expected_inputs = Vec::new();
+    match asset.lighting_path {
+        RenderLightingPath::Deferred => {
+            expected_inputs.extend([
+                (
+                    RenderPipelineNodeKind::BasePass,
+                    RenderGraphPortType::GBuffer,
+                    RenderPipelineNodeKind::DeferredLighting,
+                ),
+                (
+                    RenderPipelineNodeKind::BasePass,
+                    RenderGraphPortType::Depth,
+                    RenderPipelineNodeKind::DeferredLighting,
+                ),
+            ]);
+            if has_node(RenderPipelineNodeKind::ForwardLighting) {
+                expected_inputs.extend([
+                    (
+                        RenderPipelineNodeKind::DeferredLighting,
+                        RenderGraphPortType::SceneColor,
+                        RenderPipelineNodeKind::ForwardLighting,
+                    ),
+                    (
+                        RenderPipelineNodeKind::BasePass,
+                        RenderGraphPortType::Depth,
+                        RenderPipelineNodeKind::ForwardLighting,
+                    ),
+                ]);
+            }
+        }
+        RenderLightingPath::Forward => {
+            if has_node(RenderPipelineNodeKind::DeferredLighting) {
+                return Err(anyhow!(
+                    "Forward render graph cannot have a DeferredLighting node"
+                ));
+            }
+            expected_inputs.push((
+                RenderPipelineNodeKind::BasePass,
+                RenderGraphPortType::Depth,
+                RenderPipelineNodeKind::ForwardLighting,
+            ));
+        }
+    }
+
+    let mut inputs = Vec::new();
+    for edge in &asset.edges {
+        let (from, to) = edge_nodes(nodes, edge)?;
+        if !matches!(
+            to.kind,
+            RenderPipelineNodeKind::DeferredLighting | RenderPipelineNodeKind::ForwardLighting
+        ) {
+            continue;
+        }
+        // every edge has been checked to carry a port type by now
+        let input = (
+            from.kind,
+            output_port_type(from.kind, &edge.from_port).unwrap(),
+            to.kind,
+        );
+        if !expected_inputs.contains(&input) {
+            return Err(anyhow!(
+                "{}.{} -> {}.{} does not match the fixed {:?} lighting passes",
+                from.name,
+                edge.from_port,
+                to.name,
+                edge.to_port,
+                asset.lighting_path
+            ));
+        }
+        inputs.push(input);
+    }
+    if let Some((from, port_type, to)) =
+        expected_inputs.iter().find(|input| !inputs.contains(input))
+    {
+        return Err(anyhow!("{to:?} has no {port_type:?} input from {from:?}"));
+    }
+    Ok(())
+}
+
+fn validate_shader_bindings(name: &str, shader: &ShaderFullscreenDesc) -> Result<()> {
+    let mut bindings: Vec<u32> = shader.inputs.iter().map(|input| input.binding).collect();
+    if !shader.params.is_empty() {
+        bindings.push(shader.params_binding);
+    }
+    bindings.sort();
+    if bindings.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err(anyhow!("{name} binds two resources to the same binding"));
+    }
+    Ok(())
+}
+
+fn edge_nodes<'a>(
+    nodes: &HashMap<u32, &'a RenderPipelineNode>,
+    edge: &RenderPipelineEdge,
+) -> Result<(&'a RenderPipelineNode, &'a RenderPipelineNode)> {
+    let find_node = |id: u32| {
+        nodes
+            .get(&id)
+            .copied()
+            .ok_or_else(|| anyhow!("render graph edge references missing node {id}"))
+    };
+    Ok((find_node(edge.from_node)?, find_node(edge.to_node)?))
+}
+
+fn topological_sort<'a>(
+    asset: &'a RenderGraphAsset,
+    nodes: &HashMap<u32, &'a RenderPipelineNode>,
+) -> Result<Vec<&'a RenderPipelineNode>> {
+    let mut in_degrees: HashMap<u32, usize> = asset.nodes.iter().map(|node| (node.id, 0)).collect();
+    for edge in &asset.edges {
+        *in_degrees.get_mut(&edge.to_node).unwrap() += 1;
+    }
+
+    let mut order = Vec::with_capacity(asset.nodes.len());
+    while let Some(id) = in_degrees
+        .iter()
+        .filter(|(_, in_degree)| **in_degree == 0)
+        .map(|(id, _)| *id)
+        .min()
+    {
+        in_degrees.remove(&id);
+        for edge in asset.edges.iter().filter(|edge| edge.from_node == id) {
+            if let Some(in_degree) = in_degrees.get_mut(&edge.to_node) {
+                *in_degree -= 1;
+            }
+        }
+        order.push(nodes[&id]);
+    }
+
+    if !in_degrees.is_empty() {
+        let mut cycle: Vec<_> = in_degrees
+            .keys()
+            .map(|id| nodes[id].name.as_str())
+            .collect();
+        cycle.sort();
+        return Err(anyhow!(
+            "render graph has a cycle through {}",
+            cycle.join(", ")
+        ));
+    }
+    Ok(order)
+}
+
+/// A transient image the subpasses of a render graph read and write. It is placed in the first
+/// of its candidate attachments that no other resource alive at the same time occupies.
+#[derive(Debug, Clone)]
+pub struct RenderGraphResourceDesc {
+    pub candidate_attachments: Vec<usize>,
+}
+
+/// The resources a subpass reads as input attachments and writes as color or depth.
+#[derive(Debug, Clone, Default)]
+pub struct RenderGraphSubpassDesc {
+    pub input_attachments: Vec<usize>,
+    /// The input attachments the subpass samples rather than loads with `subpassLoad`. A
+    /// sampler may read other pixels than the one being shaded, so the writes it waits for
+    /// cannot be synchronized by region.
+    pub sampled_inputs: Vec<usize>,
+    pub color_attachments: Vec<usize>,
+    pub depth_attachment: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenderGraphAccess {
+    Input,
+    Sampled,
+    Color,
+    Depth,
+}
+
+impl RenderGraphAccess {
+    fn is_write(self) -> bool {
+        !matches!(self, Self::Input | Self::Sampled)
+    }
+
+    fn stage_mask(self) -> vk::PipelineStageFlags {
+        match self {
+            Self::Input | Self::Sampled => vk::PipelineStageFlags::FRAGMENT_SHADER,
+            Self::Color => vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            Self::Depth => {
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+            }
+        }
+    }
+
+    fn write_access_mask(self) -> vk::AccessFlags {
+        match self {
+            Self::Input | Self::Sampled => vk::AccessFlags::empty(),
+            Self::Color => vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            Self::Depth => vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        }
+    }
+
+    fn access_mask(self) -> vk::AccessFlags {
+        match self {
+            Self::Input => vk::AccessFlags::INPUT_ATTACHMENT_READ | vk::AccessFlags::SHADER_READ,
+            Self::Sampled => vk::AccessFlags::SHADER_READ,
+            Self::Color => {
+                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            }
+            Self::Depth => {
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+            }
+        }
+    }
+}
+
+impl RenderGraphSubpassDesc {
+    fn accesses(&self) -> impl Iterator<Item = (usize, RenderGraphAccess)> + '_ {
+        self.input_attachments
+            .iter()
+            .map(|resource| {
+                let access = if self.sampled_inputs.contains(resource) {
+                    RenderGraphAccess::Sampled
+                } else {
+                    RenderGraphAccess::Input
+                };
+                (*resource, access)
+            })
+            .chain(
+                self.color_attachments
+                    .iter()
+                    .map(|resource| (*resource, RenderGraphAccess::Color)),
+            )
+            .chain(
+                self.depth_attachment
+                    .map(|resource| (resource, RenderGraphAccess::Depth)),
+            )
+    }
+}
+
+/// Subpasses in execution order and the resources they use, before any attachment is chosen.
+#[derive(Debug, Clone, Default)]
+pub struct RenderGraphAttachmentLayout {
+    pub resources: Vec<RenderGraphResourceDesc>,
+    pub subpasses: Vec<RenderGraphSubpassDesc>,
+}
+
+/// Where each resource lives, and the synchronization the subpasses need between them.
+#[derive(Debug, Clone)]
+pub struct RenderGraphAttachmentPlan {
+    pub resource_attachments: Vec<usize>,
+    pub preserve_attachments: Vec<Vec<u32>>,
+    /// Dependencies between subpasses; the one from outside the render pass is left to the
+    /// caller.
+    pub dependencies: Vec<vk::SubpassDependency>,
+}
+
+impl RenderGraphAttachmentLayout {
+    pub fn add_resource(&mut self, candidate_attachments: &[usize]) -> usize {
+        self.resources.push(RenderGraphResourceDesc {
+            candidate_attachments: candidate_attachments.to_vec(),
+        });
+        self.resources.len() - 1
+    }
+
+    pub fn add_subpass(&mut self, subpass: RenderGraphSubpassDesc) -> u32 {
+        self.subpasses.push(subpass);
+        (self.subpasses.len() - 1) as u32
+    }
+
+    /// Places every resource, aliasing attachments between resources whose lifetimes do not
+    /// overlap, then derives the dependencies and preserved attachments of each subpass.
+    pub fn allocate(&self) -> Result<RenderGraphAttachmentPlan> {
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.resources.len()];
+        for (subpass_index, subpass) in self.subpasses.iter().enumerate() {
+            for (resource, _) in subpass.accesses() {
+                let lifetime = lifetimes
+                    .get_mut(resource)
+                    .ok_or_else(|| anyhow!("render graph resource {resource} is not declared"))?;
+                *lifetime = Some(match *lifetime {
+                    Some((first, _)) => (first, subpass_index),
+                    None => (subpass_index, subpass_index),
+                });
+            }
+        }
+        let lifetimes = lifetimes
+            .into_iter()
+            .enumerate()
+            .map(|(resource, lifetime)| {
+                lifetime.ok_or_else(|| anyhow!("render graph resource {resource} is never used"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut allocation_order: Vec<usize> = (0..self.resources.len()).collect();
+        allocation_order.sort_by_key(|resource| lifetimes[*resource].0);
+        let mut resource_attachments = vec![usize::MAX; self.resources.len()];
+        for (i, &resource) in allocation_order.iter().enumerate() {
+            let (first, last) = lifetimes[resource];
+            let attachment = self.resources[resource]
+                .candidate_attachments
+                .iter()
+                .copied()
+                .find(|attachment| {
+                    allocation_order[..i].iter().all(|&other| {
+                        let (other_first, other_last) = lifetimes[other];
+                        resource_attachments[other] != *attachment
+                            || other_last < first
+                            || last < other_first
+                    })
+                })
+                .ok_or_else(|| {
+                    anyhow!("no free attachment for render graph resource {resource}")
+                })?;
+            resource_attachments[resource] = attachment;
+        }
+
+        let preserve_attachments = (0..self.subpasses.len())
+            .map(|subpass_index| {
+                let used: Vec<usize> = self.subpasses[subpass_index]
+                    .accesses()
+                    .map(|(resource, _)| resource_attachments[resource])
+                    .collect();
+                let mut preserved: Vec<u32> = lifetimes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (first, last))| *first < subpass_index && subpass_index < *last)
+                    .map(|(resource, _)| resource_attachments[resource])
+                    .filter(|attachment| !used.contains(attachment))
+                    .map(|attachment| attachment as u32)
+                    .collect();
+                preserved.sort();
+                preserved.dedup();
+                preserved
+            })
+            .collect();
+
+        Ok(RenderGraphAttachmentPlan {
+            dependencies: self.hazard_dependencies(&resource_attachments),
+            resource_attachments,
+            preserve_attachments,
+        })
+    }
+
+    /// Tracks every attachment through the subpasses: reads wait for the last write, writes
+    /// wait for the last write and for every read since. Dependencies are by region unless a
+    /// sampled read is on either side of them.
+    fn hazard_dependencies(&self, resource_attachments: &[usize]) -> Vec<vk::SubpassDependency> {
+        let mut last_write: HashMap<usize, (usize, RenderGraphAccess)> = HashMap::new();
+        let mut reads_since_write: HashMap<usize, Vec<(usize, RenderGraphAccess)>> = HashMap::new();
+        let mut dependencies: BTreeMap<(usize, usize), vk::SubpassDependency> = BTreeMap::new();
+        let mut add_dependency =
+            |src: (usize, RenderGraphAccess), dst: (usize, RenderGraphAccess)| {
+                if src.0 == dst.0 {
+                    return;
+                }
+                let dependency = dependencies.entry((src.0, dst.0)).or_insert_with(|| {
+                    vk::SubpassDependency::builder()
+                        .src_subpass(src.0 as u32)
+                        .dst_subpass(dst.0 as u32)
+                        .dependency_flags(vk::DependencyFlags::BY_REGION)
+                        .build()
+                });
+                if src.1 == RenderGraphAccess::Sampled || dst.1 == RenderGraphAccess::Sampled {
+                    dependency.dependency_flags = vk::DependencyFlags::empty();
+                }
+                dependency.src_stage_mask |= src.1.stage_mask();
+                dependency.dst_stage_mask |= dst.1.stage_mask();
+                dependency.src_access_mask |= src.1.write_access_mask();
+                dependency.dst_access_mask |= dst.1.access_mask();
+            };
+
+        for (subpass_index, subpass) in self.subpasses.iter().enumerate() {
+            for (resource, access) in subpass.accesses() {
+                let attachment = resource_attachments[resource];
+                let current = (subpass_index, access);
+                if let Some(write) = last_write.get(&attachment) {
+                    add_dependency(*write, current);
+                }
+                if access.is_write() {
+                    for read in reads_since_write.remove(&attachment).unwrap_or_default() {
+                        add_dependency(read, current);
+                    }
+                    last_write.insert(attachment, current);
+                } else {
+                    reads_since_write
+                        .entry(attachment)
+                        .or_default()
+                        .push(current);
+                }
+            }
+        }
+        dependencies.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u32, kind: RenderPipelineNodeKind) -> RenderPipelineNode {
+        RenderPipelineNode {
+            id,
+            kind,
+            name: format!("{kind:?}"),
+            position: [0.0; 2],
+            shader: None,
+        }
+    }
+
+    fn edge(from_node: u32, from_port: &str, to_node: u32) -> RenderPipelineEdge {
+        RenderPipelineEdge {
+            from_node,
+            from_port: from_port.to_string(),
+            to_node,
+            to_port: from_port.to_string(),
+            framebuffer: String::new(),
+        }
+    }
+
+    /// BasePass -> DeferredLighting -> ToneMapping -> CombineUI, with UI composited on top.
+    fn deferred_graph() -> RenderGraphAsset {
+        RenderGraphAsset {
+            lighting_path: RenderLightingPath::Deferred,
+            nodes: vec![
+                node(0, RenderPipelineNodeKind::BasePass),
+                node(1, RenderPipelineNodeKind::DeferredLighting),
+                node(2, RenderPipelineNodeKind::ToneMapping),
+                node(3, RenderPipelineNodeKind::UI),
+                node(4, RenderPipelineNodeKind::CombineUI),
+            ],
+            edges: vec![
+                edge(0, "gbuffer", 1),
+                edge(0, "depth", 1),
+                edge(1, "lit", 2),
+                edge(2, "tone_mapped", 4),
+                edge(3, "ui_color", 4),
+            ],
+        }
+    }
+
+    fn compile_error(asset: &RenderGraphAsset) -> String {
+        compile_render_graph(asset).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn compiles_nodes_in_dependency_then_id_order() {
+        let compiled = compile_render_graph(&deferred_graph()).unwrap();
+        assert_eq!(
+            compiled.execution_order,
+            [
+                RenderPipelineNodeKind::BasePass,
+                RenderPipelineNodeKind::DeferredLighting,
+                RenderPipelineNodeKind::ToneMapping,
+                RenderPipelineNodeKind::UI,
+                RenderPipelineNodeKind::CombineUI,
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_a_cycle() {
+        let mut asset = deferred_graph();
+        asset.nodes.push(node(5, RenderPipelineNodeKind::FXAA));
+        asset.edges.retain(|edge| edge.to_node != 2);
+        asset.edges.push(edge(5, "antialiased", 2));
+        asset.edges.push(edge(2, "tone_mapped", 5));
+        assert_eq!(
+            compile_error(&asset),
+            "render graph has a cycle through CombineUI, FXAA, ToneMapping"
+        );
+    }
+
+    #[test]
+    fn rejects_a_missing_scene_color_input() {
+        let mut asset = deferred_graph();
+        asset.edges.retain(|edge| edge.to_node != 2);
+        assert_eq!(
+            compile_error(&asset),
+            "ToneMapping has no scene color input"
+        );
+    }
+
+    #[test]
+    fn rejects_a_post_chain_that_skips_the_lighting_output() {
+        // the forward node draws blended meshes over the deferred result, so the post chain
+        // has to start from it rather than from the deferred lighting
+        let mut asset = deferred_graph();
+        asset
+            .nodes
+            .push(node(5, RenderPipelineNodeKind::ForwardLighting));
+        asset.edges.push(edge(1, "deferred_lit", 5));
+        asset.edges.push(edge(0, "depth", 5));
+        assert_eq!(
+            compile_error(&asset),
+            "ToneMapping does not continue the post process chain"
+        );
+
+        asset.edges.retain(|edge| edge.to_node != 2);
+        asset.edges.push(edge(5, "lit_hdr", 2));
+        assert!(compile_render_graph(&asset).is_ok());
+    }
+
+    #[test]
+    fn rejects_lighting_inputs_the_fixed_passes_do_not_read() {
+        let mut asset = deferred_graph();
+        asset.edges.retain(|edge| edge.from_port != "depth");
+        assert_eq!(
+            compile_error(&asset),
+            "DeferredLighting has no Depth input from BasePass"
+        );
+
+        let mut asset = deferred_graph();
+        asset
+            .nodes
+            .push(node(5, RenderPipelineNodeKind::ForwardLighting));
+        asset.edges.retain(|edge| edge.to_node != 2);
+        asset.edges.push(edge(0, "depth", 5));
+        asset.edges.push(edge(5, "lit_hdr", 2));
+        // the lighting pass always feeds the forward one in a deferred graph
+        assert_eq!(
+            compile_error(&asset),
+            "ForwardLighting has no SceneColor input from DeferredLighting"
+        );
+
+        asset.edges.push(edge(1, "deferred_lit", 5));
+        assert!(compile_render_graph(&asset).is_ok());
+        asset.lighting_path = RenderLightingPath::Forward;
+        assert_eq!(
+            compile_error(&asset),
+            "Forward render graph cannot have a DeferredLighting node"
+        );
+    }
+
+    /// Three subpasses that each read the previous one's color, over two attachments.
+    fn ping_pong_layout() -> RenderGraphAttachmentLayout {
+        let mut layout = RenderGraphAttachmentLayout::default();
+        let resources: Vec<_> = (0..3).map(|_| layout.add_resource(&[0, 1])).collect();
+        layout.add_subpass(RenderGraphSubpassDesc {
+            color_attachments: vec![resources[0]],
+            ..Default::default()
+        });
+        for pair in resources.windows(2) {
+            layout.add_subpass(RenderGraphSubpassDesc {
+                input_attachments: vec![pair[0]],
+                color_attachments: vec![pair[1]],
+                ..Default::default()
+            });
+        }
+        layout
+    }
+
+    #[test]
+    fn reuses_an_attachment_once_its_resource_is_dead() {
+        let plan = ping_pong_layout().allocate().unwrap();
+        assert_eq!(plan.resource_attachments, [0, 1, 0]);
+        assert!(plan.preserve_attachments.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn fails_when_no_candidate_attachment_is_free() {
+        let mut layout = ping_pong_layout();
+        layout.resources[1].candidate_attachments = vec![0];
+        assert_eq!(
+            layout.allocate().unwrap_err().to_string(),
+            "no free attachment for render graph resource 1"
+        );
+    }
+
+    #[test]
+    fn orders_reads_after_writes_and_writes_after_reads() {
+        let plan = ping_pong_layout().allocate().unwrap();
+        let masks: Vec<_> = plan
+            .dependencies
+            .iter()
+            .map(|dependency| {
+                (
+                    (dependency.src_subpass, dependency.dst_subpass),
+                    (dependency.src_stage_mask, dependency.dst_stage_mask),
+                    (dependency.src_access_mask, dependency.dst_access_mask),
+                )
+            })
+            .collect();
+
+        let input_read = vk::AccessFlags::INPUT_ATTACHMENT_READ | vk::AccessFlags::SHADER_READ;
+        let color_read_write =
+            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE;
+        let color_output = vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
+        let fragment_shader = vk::PipelineStageFlags::FRAGMENT_SHADER;
+        assert_eq!(
+            masks,
+            [
+                // subpass 1 reads what subpass 0 wrote
+                (
+                    (0, 1),
+                    (color_output, fragment_shader),
+                    (vk::AccessFlags::COLOR_ATTACHMENT_WRITE, input_read),
+                ),
+                // subpass 2 overwrites the attachment subpass 0 wrote
+                (
+                    (0, 2),
+                    (color_output, color_output),
+                    (vk::AccessFlags::COLOR_ATTACHMENT_WRITE, color_read_write),
+                ),
+                // subpass 2 reads subpass 1's output and overwrites what subpass 1 read
+                (
+                    (1, 2),
+                    (
+                        color_output | fragment_shader,
+                        fragment_shader | color_output
+                    ),
+                    (
+                        vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                        input_read | color_read_write
+                    ),
+                ),
+            ]
+        );
+        assert!(
+            plan.dependencies
+                .iter()
+                .all(|dependency| dependency.dependency_flags == vk::DependencyFlags::BY_REGION)
+        );
+    }
+
+    #[test]
+    fn does_not_sync_a_sampled_input_by_region() {
+        let mut layout = ping_pong_layout();
+        // subpass 2 samples the output of subpass 1, as FXAA and TAA do
+        layout.subpasses[2].sampled_inputs = layout.subpasses[2].input_attachments.clone();
+        let plan = layout.allocate().unwrap();
+        let flags: Vec<_> = plan
+            .dependencies
+            .iter()
+            .map(|dependency| {
+                (
+                    (dependency.src_subpass, dependency.dst_subpass),
+                    dependency.dependency_flags,
+                    dependency.dst_access_mask,
+                )
+            })
+            .collect();
+
+        let color_read_write =
+            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE;
+        assert_eq!(
+            flags,
+            [
+                (
+                    (0, 1),
+                    vk::DependencyFlags::BY_REGION,
+                    vk::AccessFlags::INPUT_ATTACHMENT_READ | vk::AccessFlags::SHADER_READ,
+                ),
+                ((0, 2), vk::DependencyFlags::BY_REGION, color_read_write),
+                (
+                    (1, 2),
+                    vk::DependencyFlags::empty(),
+                    vk::AccessFlags::SHADER_READ | color_read_write,
+                ),
+            ]
+        );
+    }
+}
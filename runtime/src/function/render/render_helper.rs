@@ -1,6 +1,13 @@
 
 
-use crate::{core::math::{self, bounding_box::{BoundingBox, bounding_box_transform}, matrix4::Matrix4x4, vector3::Vector3, vector4::Vector4}, function::render::{render_camera::RenderCamera, render_scene::RenderScene}};
+use std::f32::consts::PI;
+
+use crate::{core::math::{self, bounding_box::{BoundingBox, bounding_box_transform}, matrix4::Matrix4x4, vector3::Vector3, vector4::Vector4}, function::render::{light::SpotLight, render_camera::RenderCamera, render_common::{MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT, RenderMeshNode, S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION, S_LIGHT_CLUSTER_X, S_LIGHT_CLUSTER_Y, S_LIGHT_CLUSTER_Z, VulkanClusteredLight}, render_scene::RenderScene}, resource::res_type::data::material::MaterialAlphaMode};
+
+/// Levels of a full mip chain, down to 1x1.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
 
 #[inline]
 pub fn round_up(value: u32, alignment: u32) -> u32 {
@@ -8,7 +15,38 @@ pub fn round_up(value: u32, alignment: u32) -> u32 {
     return temp - temp % alignment;
 }
 
-struct ClusterFrustum {
+/// Orders nodes so those sharing a material, and within it a mesh, are adjacent, which is what
+/// `mesh_node_batches` groups on. Masked nodes follow the opaque ones, as they draw with their own
+/// pipelines. Node ids break ties so the order is stable between frames.
+pub fn sort_mesh_nodes_for_batching(nodes: &mut [RenderMeshNode]) {
+    nodes.sort_by_key(|node| {
+        (
+            node.alpha_mode == MaterialAlphaMode::Mask,
+            node.ref_material.as_ptr() as usize,
+            node.ref_mesh.as_ptr() as usize,
+            node.node_id,
+        )
+    });
+}
+
+/// Runs of sorted nodes sharing a mesh and material; each run is drawn instanced.
+pub fn mesh_node_batches(nodes: &[RenderMeshNode]) -> impl Iterator<Item = &[RenderMeshNode]> {
+    nodes.chunk_by(|a, b| a.ref_mesh.ptr_eq(&b.ref_mesh) && a.ref_material.ptr_eq(&b.ref_material))
+}
+
+/// The opaque and the masked nodes of nodes sorted by `sort_mesh_nodes_for_batching`.
+pub fn split_masked_mesh_nodes(nodes: &[RenderMeshNode]) -> (&[RenderMeshNode], &[RenderMeshNode]) {
+    nodes.split_at(nodes.partition_point(|node| node.alpha_mode != MaterialAlphaMode::Mask))
+}
+
+/// Instanced draws needed for sorted nodes, `MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT` per draw.
+pub fn mesh_node_draw_call_count(nodes: &[RenderMeshNode]) -> usize {
+    mesh_node_batches(nodes)
+        .map(|batch| batch.len().div_ceil(MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT))
+        .sum()
+}
+
+pub struct ClusterFrustum {
     m_plane_right: Vector4,
     m_plane_left: Vector4,
     m_plane_top: Vector4,
@@ -17,12 +55,118 @@ struct ClusterFrustum {
     m_plane_far: Vector4,
 }
 
-pub fn calculate_directional_light_camera(scene: &RenderScene, camera: &RenderCamera) -> Matrix4x4 {
+impl ClusterFrustum {
+    fn planes(&self) -> [&Vector4; 6] {
+        [
+            &self.m_plane_right,
+            &self.m_plane_left,
+            &self.m_plane_top,
+            &self.m_plane_bottom,
+            &self.m_plane_near,
+            &self.m_plane_far,
+        ]
+    }
+}
+
+/// Extracts the planes of the clip-space box `[x_left, x_right] x [y_top, y_bottom] x
+/// [z_near, z_far]` from a view-projection matrix (Gribb/Hartmann). The planes face inwards,
+/// in the space the matrix transforms from. The z range is Vulkan's `0..1` for a full frustum.
+pub fn create_cluster_frustum_from_matrix(
+    mat: &Matrix4x4,
+    x_left: f32,
+    x_right: f32,
+    y_top: f32,
+    y_bottom: f32,
+    z_near: f32,
+    z_far: f32,
+) -> ClusterFrustum {
+    let row = |r: usize| Vector4::new(mat[0][r], mat[1][r], mat[2][r], mat[3][r]);
+    let (row_x, row_y, row_z, row_w) = (row(0), row(1), row(2), row(3));
+    // plane = a * row + b * row_w
+    let plane = |a: f32, row: &Vector4, b: f32| {
+        Vector4::new(
+            a * row.x + b * row_w.x,
+            a * row.y + b * row_w.y,
+            a * row.z + b * row_w.z,
+            a * row.w + b * row_w.w,
+        )
+    };
+
+    ClusterFrustum {
+        m_plane_right: plane(-1.0, &row_x, x_right),
+        m_plane_left: plane(1.0, &row_x, -x_left),
+        m_plane_top: plane(1.0, &row_y, -y_top),
+        m_plane_bottom: plane(-1.0, &row_y, y_bottom),
+        m_plane_near: plane(1.0, &row_z, -z_near),
+        m_plane_far: plane(-1.0, &row_z, z_far),
+    }
+}
+
+/// Conservative frustum/box test: false only if the box lies fully outside one plane.
+pub fn tiled_frustum_intersect_box(f: &ClusterFrustum, b: &BoundingBox) -> bool {
+    f.planes().iter().all(|plane| {
+        let positive_vertex = Vector3::new(
+            if plane.x >= 0.0 { b.max_bound.x } else { b.min_bound.x },
+            if plane.y >= 0.0 { b.max_bound.y } else { b.min_bound.y },
+            if plane.z >= 0.0 { b.max_bound.z } else { b.min_bound.z },
+        );
+        plane.x * positive_vertex.x + plane.y * positive_vertex.y + plane.z * positive_vertex.z + plane.w >= 0.0
+    })
+}
+
+/// Sphere/frustum test; the planes are normalized so `radius` is a world-space distance.
+pub fn tiled_frustum_intersect_sphere(f: &ClusterFrustum, center: &Vector3, radius: f32) -> bool {
+    f.planes().iter().all(|plane| {
+        let normal_length = (plane.x * plane.x + plane.y * plane.y + plane.z * plane.z).sqrt();
+        plane.x * center.x + plane.y * center.y + plane.z * center.z + plane.w >= -radius * normal_length
+    })
+}
+
+pub fn box_intersect_sphere(b: &BoundingBox, center: &Vector3, radius: f32) -> bool {
+    let closest_point = Vector3::new(
+        center.x.clamp(b.min_bound.x, b.max_bound.x),
+        center.y.clamp(b.min_bound.y, b.max_bound.y),
+        center.z.clamp(b.min_bound.z, b.max_bound.z),
+    );
+    (closest_point - center).length() <= radius
+}
+
+/// View-space depths where each directional light cascade ends, using the practical split
+/// scheme: `lambda` blends the logarithmic split (1.0) with the uniform one (0.0).
+pub fn calculate_directional_light_cascade_splits(
+    z_near: f32,
+    z_far: f32,
+    cascade_count: usize,
+    lambda: f32,
+) -> Vec<f32> {
+    (1..=cascade_count)
+        .map(|i| {
+            let ratio = i as f32 / cascade_count as f32;
+            let log_split = z_near * (z_far / z_near).powf(ratio);
+            let uniform_split = z_near + (z_far - z_near) * ratio;
+            lambda * log_split + (1.0 - lambda) * uniform_split
+        })
+        .collect()
+}
+
+/// Fits the directional shadow projection to the part of the scene the camera can see between
+/// the view depths `split_near` and `split_far`, which is one cascade.
+///
+/// The projection covers the bounding sphere of that slice of the camera frustum, with its
+/// center snapped to whole shadow map texels, so the map does not shimmer when the camera moves
+/// or turns. The depth range reaches back to the farthest caster so off-screen casters still
+/// shadow visible receivers.
+pub fn calculate_directional_light_camera(
+    scene: &RenderScene,
+    camera: &RenderCamera,
+    split_near: f32,
+    split_far: f32,
+) -> Matrix4x4 {
     let proj_view_matrix = {
         camera.get_perspective_matrix() * camera.get_view_matrix()
     };
 
-    let frustum_bounding_box = {
+    let frustum_points = {
         let g_frustum_points_ndc_space = [
             Vector3::new(-1.0, -1.0, 1.0),
             Vector3::new( 1.0, -1.0, 1.0),
@@ -34,34 +178,160 @@ pub fn calculate_directional_light_camera(scene: &RenderScene, camera: &RenderCa
             Vector3::new(-1.0,  1.0, 0.0),
         ];
         let inverse_proj_view_matrix = proj_view_matrix.inverse();
+        let points = g_frustum_points_ndc_space.map(|point| {
+            Vector3::from_homogeneous(&(inverse_proj_view_matrix * point.to_homogeneous()))
+        });
 
-        let mut frustum_bounding_box = BoundingBox::default();
-
-        for i in 0..8 {
-            let frustum_point_with_w = inverse_proj_view_matrix * g_frustum_points_ndc_space[i].to_homogeneous();
-            let frustum_point = Vector3::from_homogeneous(&frustum_point_with_w);
-            frustum_bounding_box.merge(frustum_point);
+        // frustum edges are straight lines, so depth is linear along each of them
+        let depth_range = camera.get_zfar() - camera.get_znear();
+        let t_near = (split_near - camera.get_znear()) / depth_range;
+        let t_far = (split_far - camera.get_znear()) / depth_range;
+        let mut cascade_points = points;
+        for i in 0..4 {
+            let (far_point, near_point) = (points[i], points[i + 4]);
+            cascade_points[i] = near_point + (far_point - near_point) * t_far;
+            cascade_points[i + 4] = near_point + (far_point - near_point) * t_near;
         }
-        frustum_bounding_box
+        cascade_points
     };
 
-    let scene_bounding_box = scene.calc_scene_bounding_box();
+    // The bounding sphere of the slice has the same radius however the camera moves or turns,
+    // so the shadow map texels keep their world size; rounding hides float noise in the corners.
+    let sphere_center = frustum_points.iter().fold(Vector3::ZERO, |sum, point| sum + *point) * (1.0 / 8.0);
+    let sphere_radius = frustum_points.iter().map(|point| (*point - sphere_center).length()).fold(0.0_f32, f32::max);
+    let sphere_radius = (sphere_radius * 16.0).ceil() / 16.0;
+
+    // The light view only depends on the light direction, so snapping in it is stable too.
+    let light_direction = scene.m_directional_light.m_direction.normalize();
+    let up = if light_direction.z.abs() > 0.99 { Vector3::UNIT_Y } else { Vector3::UNIT_Z };
+    let light_view = math::look_at(&light_direction, &Vector3::ZERO, &up);
 
-    let box_center = (frustum_bounding_box.min_bound + frustum_bounding_box.max_bound) *0.5;
-    let box_extent = (frustum_bounding_box.max_bound - frustum_bounding_box.min_bound) *0.5;
-    let eye = box_center + scene.m_directional_light.m_direction * box_extent.length();
-    let center = box_center;
-    let light_view = math::look_at(&eye, &center,&Vector3::new(0.0, 0.0, 1.0));
+    let texel_size = sphere_radius * 2.0 / S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION as f32;
+    let sphere_center_light_view = Vector3::from_homogeneous(&(light_view * sphere_center.to_homogeneous()));
+    let center_x = (sphere_center_light_view.x / texel_size).floor() * texel_size;
+    let center_y = (sphere_center_light_view.y / texel_size).floor() * texel_size;
+
+    let scene_bounding_box = scene.calc_scene_bounding_box();
+    let has_scene_bounds = scene_bounding_box.min_bound.x <= scene_bounding_box.max_bound.x;
+    let sphere_min_z = sphere_center_light_view.z - sphere_radius;
+    let sphere_max_z = sphere_center_light_view.z + sphere_radius;
+    let (z_min, z_max) = if has_scene_bounds {
+        let scene_bounding_box_light_view = bounding_box_transform(&scene_bounding_box, &light_view);
+        (sphere_min_z.max(scene_bounding_box_light_view.min_bound.z), sphere_max_z.max(scene_bounding_box_light_view.max_bound.z))
+    } else {
+        (sphere_min_z, sphere_max_z)
+    };
 
-    let frustum_bounding_box_light_view = bounding_box_transform(&frustum_bounding_box, &light_view);
-    let scene_bounding_box_light_view = bounding_box_transform(&scene_bounding_box, &light_view);
     let light_proj = math::orthographic_projection_01(
-        frustum_bounding_box_light_view.min_bound.x.max(scene_bounding_box_light_view.min_bound.x),
-        frustum_bounding_box_light_view.max_bound.x.min(scene_bounding_box_light_view.max_bound.x),
-        frustum_bounding_box_light_view.min_bound.y.max(scene_bounding_box_light_view.min_bound.y),
-        frustum_bounding_box_light_view.max_bound.y.min(scene_bounding_box_light_view.max_bound.y),
-        -scene_bounding_box_light_view.max_bound.z,
-        -(frustum_bounding_box_light_view.min_bound.z.max(scene_bounding_box_light_view.min_bound.z))
+        center_x - sphere_radius,
+        center_x + sphere_radius,
+        center_y - sphere_radius,
+        center_y + sphere_radius,
+        -z_max,
+        -z_min,
     );
     light_proj * light_view
-}
\ No newline at end of file
+}
+
+
+/// Perspective shadow projection covering a spot light's outer cone out to its radius.
+pub fn calculate_spot_light_camera(light: &SpotLight) -> Matrix4x4 {
+    let direction = light.m_direction.normalize();
+    let up = if direction.z.abs() > 0.99 { Vector3::UNIT_Y } else { Vector3::UNIT_Z };
+    let light_view = math::look_at(&light.m_position, &(light.m_position + direction), &up);
+
+    let radius = light.calculate_radius();
+    let fovy = (light.m_outer_cone_angle * 2.0).clamp(0.01, PI - 0.01);
+    let light_proj = math::perspective(fovy, 1.0, 0.1_f32.min(radius * 0.5), radius);
+    light_proj * light_view
+}
+
+/// Bins lights into the camera's froxels: `S_LIGHT_CLUSTER_X x S_LIGHT_CLUSTER_Y` screen tiles
+/// by `S_LIGHT_CLUSTER_Z` depth slices spaced exponentially between the near and far planes.
+///
+/// Fills `cluster_light_grids` with the `(offset, count)` range of `light_indices` holding each
+/// cluster's lights and returns the depth slice scale, so a view depth `d` falls into slice
+/// `ln(d / z_near) * scale`. Each cluster lists its lights in the order of `lights`; when
+/// `light_indices` runs out the farthest clusters lose theirs first.
+pub fn calculate_light_clusters(
+    camera: &RenderCamera,
+    lights: &[VulkanClusteredLight],
+    cluster_light_grids: &mut [[u32; 2]],
+    light_indices: &mut [u32],
+) -> f32 {
+    let view_matrix = camera.get_view_matrix();
+    let proj_matrix = camera.get_perspective_matrix();
+    let (z_near, z_far) = (camera.get_znear(), camera.get_zfar());
+    let z_scale = S_LIGHT_CLUSTER_Z as f32 / (z_far / z_near).ln();
+
+    let slice_depth = |slice: usize| z_near * (z_far / z_near).powf(slice as f32 / S_LIGHT_CLUSTER_Z as f32);
+    let depth_slice = |depth: f32| {
+        ((depth.max(z_near) / z_near).ln() * z_scale).floor().clamp(0.0, (S_LIGHT_CLUSTER_Z - 1) as f32) as usize
+    };
+    let tile_ndc = |tile: usize, count: usize| tile as f32 / count as f32 * 2.0 - 1.0;
+    let ndc_tile = |ndc: f32, count: usize| ((ndc * 0.5 + 0.5) * count as f32).floor().clamp(0.0, (count - 1) as f32) as usize;
+    // view-space x and y of an ndc coordinate at unit depth; y is flipped by the projection
+    let (x_scale, y_scale) = (1.0 / proj_matrix[0][0], 1.0 / proj_matrix[1][1]);
+
+    // (cluster, light) pairs, binned into ranges once every light has been placed
+    let mut cluster_lights: Vec<(u32, u32)> = Vec::new();
+    for (light_index, light) in lights.iter().enumerate() {
+        let center = Vector3::from_homogeneous(&(view_matrix * light.position.to_homogeneous()));
+        let depth = -center.z;
+        let radius = light.radius;
+        if depth + radius < z_near || depth - radius > z_far {
+            continue;
+        }
+
+        // screen bounds of the sphere's view-space box; a sphere crossing the near plane covers the whole screen
+        let (mut tile_x_range, mut tile_y_range) = ((0, S_LIGHT_CLUSTER_X - 1), (0, S_LIGHT_CLUSTER_Y - 1));
+        if depth - radius > z_near {
+            let ndc_range = |coordinate: f32, scale: f32| {
+                [depth - radius, depth + radius]
+                    .iter()
+                    .flat_map(|d| [(coordinate - radius) / (d * scale), (coordinate + radius) / (d * scale)])
+                    .fold((f32::MAX, f32::MIN), |(low, high), ndc| (low.min(ndc), high.max(ndc)))
+            };
+            let (x_low, x_high) = ndc_range(center.x, x_scale);
+            let (y_low, y_high) = ndc_range(center.y, y_scale);
+            if x_high < -1.0 || x_low > 1.0 || y_high < -1.0 || y_low > 1.0 {
+                continue;
+            }
+            tile_x_range = (ndc_tile(x_low, S_LIGHT_CLUSTER_X), ndc_tile(x_high, S_LIGHT_CLUSTER_X));
+            tile_y_range = (ndc_tile(y_low, S_LIGHT_CLUSTER_Y), ndc_tile(y_high, S_LIGHT_CLUSTER_Y));
+        }
+
+        for z in depth_slice(depth - radius)..=depth_slice(depth + radius) {
+            let (near_depth, far_depth) = (slice_depth(z), slice_depth(z + 1));
+            for y in tile_y_range.0..=tile_y_range.1 {
+                for x in tile_x_range.0..=tile_x_range.1 {
+                    let mut cluster_box = BoundingBox::default();
+                    for depth in [near_depth, far_depth] {
+                        for ndc_x in [tile_ndc(x, S_LIGHT_CLUSTER_X), tile_ndc(x + 1, S_LIGHT_CLUSTER_X)] {
+                            for ndc_y in [tile_ndc(y, S_LIGHT_CLUSTER_Y), tile_ndc(y + 1, S_LIGHT_CLUSTER_Y)] {
+                                cluster_box.merge(Vector3::new(ndc_x * x_scale * depth, ndc_y * y_scale * depth, -depth));
+                            }
+                        }
+                    }
+                    if box_intersect_sphere(&cluster_box, &center, radius) {
+                        let cluster_index = x + S_LIGHT_CLUSTER_X * (y + S_LIGHT_CLUSTER_Y * z);
+                        cluster_lights.push((cluster_index as u32, light_index as u32));
+                    }
+                }
+            }
+        }
+    }
+
+    // the sort is stable, so each cluster keeps its lights in order
+    cluster_lights.sort_by_key(|(cluster_index, _)| *cluster_index);
+    cluster_light_grids.fill([0, 0]);
+    for (index_offset, (cluster_index, light_index)) in cluster_lights.into_iter().take(light_indices.len()).enumerate() {
+        let grid = &mut cluster_light_grids[cluster_index as usize];
+        if grid[1] == 0 {
+            grid[0] = index_offset as u32;
+        }
+        grid[1] += 1;
+        light_indices[index_offset] = light_index;
+    }
+    z_scale
+}
@@ -2,7 +2,7 @@ use std::{cell::RefCell, rc::Rc};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{core::math::{matrix4::Matrix4x4, vector4::Vector4}, function::{framework::object::object_id_allocator::{GObjectID, K_INVALID_GOBJECT_ID}, render::render_type::MeshVertexDataDefinition}};
+use crate::{core::math::{matrix4::Matrix4x4, vector4::Vector4}, function::{framework::object::object_id_allocator::{GObjectID, K_INVALID_GOBJECT_ID}, render::render_type::MeshVertexDataDefinition}, resource::res_type::data::material::MaterialAlphaMode};
 
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct GameObjectLazyMeshDesc {
@@ -67,6 +67,9 @@ pub struct GameObjectMaterialDesc{
     pub m_normal_texture_file: String,
     pub m_occlusion_texture_file: String,
     pub m_emissive_texture_file: String,
+    pub m_atlas_tile_count: u32,
+    pub m_alpha_mode: MaterialAlphaMode,
+    pub m_alpha_cutoff: f32,
     pub m_with_texture: bool
 }
 
@@ -1,14 +1,25 @@
 pub mod pbr_pipeline;
 pub mod ui_pipeline;
 
-use crate::function::{
-    render::{
-        interface::vulkan::vulkan_rhi::VulkanRHI,
-        render_pass::DescriptorLayoutRegistry,
-        render_resource::{GlobalRenderResource, RenderResource},
-        render_scene::RenderScene,
+use anyhow::{Result, anyhow};
+
+use crate::{
+    function::{
+        render::{
+            interface::vulkan::vulkan_rhi::{MAX_SHADER_FULLSCREEN_PASS_COUNT, VulkanRHI},
+            render_graph::{CompiledRenderGraph, compile_render_graph},
+            render_pass::DescriptorLayoutRegistry,
+            render_resource::{GlobalRenderResource, RenderResource},
+            render_scene::RenderScene,
+        },
+        ui::ui2::UiRuntime,
+    },
+    resource::{
+        asset_manager::AssetManager,
+        res_type::data::render_pipeline::{
+            RenderLightingPath, RenderPipelineNodeKind, RenderPipelineRes, ShaderFullscreenInput,
+        },
     },
-    ui::ui2::UiRuntime,
 };
 
 pub trait RenderPipelineTrait {
@@ -22,12 +33,187 @@ pub trait RenderPipelineTrait {
         render_resource: &mut GlobalRenderResource,
         ui_runtime: &UiRuntime,
     );
-    fn recreate_after_swapchain(&mut self, rhi: &VulkanRHI, render_resource: &GlobalRenderResource);
+    fn recreate_after_swapchain(
+        &mut self,
+        rhi: &VulkanRHI,
+        render_resource: &GlobalRenderResource,
+    ) -> Result<()>;
+    /// Takes effect on the next `recreate_after_swapchain`, which rebuilds whatever the new
+    /// configuration changes. Pipelines without a configurable main camera pass ignore it.
+    fn set_config(&mut self, _config: &RenderPipelineConfig) {}
+    /// The configuration last passed to `set_config`, `None` if the pipeline ignores it.
+    fn config(&self) -> Option<&RenderPipelineConfig> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostProcessPassType {
+    ToneMapping,
+    ColorGrading,
+    FXAA,
+    TAA,
+    /// Index into `RenderPipelineConfig::shader_passes`.
+    ShaderFullscreen(usize),
+}
+
+impl PostProcessPassType {
+    pub fn from_node_kind(kind: RenderPipelineNodeKind) -> Option<Self> {
+        match kind {
+            RenderPipelineNodeKind::ToneMapping => Some(Self::ToneMapping),
+            RenderPipelineNodeKind::ColorGrading => Some(Self::ColorGrading),
+            RenderPipelineNodeKind::FXAA => Some(Self::FXAA),
+            RenderPipelineNodeKind::TAA => Some(Self::TAA),
+            _ => None,
+        }
+    }
+
+    pub fn node_kind(self) -> RenderPipelineNodeKind {
+        match self {
+            Self::ToneMapping => RenderPipelineNodeKind::ToneMapping,
+            Self::ColorGrading => RenderPipelineNodeKind::ColorGrading,
+            Self::FXAA => RenderPipelineNodeKind::FXAA,
+            Self::TAA => RenderPipelineNodeKind::TAA,
+            Self::ShaderFullscreen(_) => RenderPipelineNodeKind::ShaderFullscreen,
+        }
+    }
+}
+
+/// Largest parameter block a `ShaderFullscreen` pass uploads, in floats.
+pub const MAX_SHADER_FULLSCREEN_PARAM_COUNT: usize = 16;
+/// Input attachments a `ShaderFullscreen` pass may read; the descriptor pool reserves this many
+/// per pass.
+pub const MAX_SHADER_FULLSCREEN_INPUT_COUNT: usize = 2;
+
+/// A `ShaderFullscreen` node with its SPIR-V loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderFullscreenPassConfig {
+    pub name: String,
+    pub spirv: Vec<u8>,
+    pub entry: String,
+    pub inputs: Vec<ShaderFullscreenInput>,
+    pub params: Vec<f32>,
+    pub params_binding: u32,
+}
+
+/// How the main camera pass renders: the lighting path and the post passes in the order they run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderPipelineConfig {
+    pub lighting_path: RenderLightingPath,
+    pub post_process_passes: Vec<PostProcessPassType>,
+    pub shader_passes: Vec<ShaderFullscreenPassConfig>,
+}
+
+impl RenderPipelineConfig {
+    /// The fixed deferred layout used when no preset is configured.
+    pub fn with_fxaa(enable_fxaa: bool) -> Self {
+        let mut post_process_passes = vec![
+            PostProcessPassType::ToneMapping,
+            PostProcessPassType::ColorGrading,
+        ];
+        if enable_fxaa {
+            post_process_passes.push(PostProcessPassType::FXAA);
+        }
+        Self {
+            lighting_path: RenderLightingPath::Deferred,
+            post_process_passes,
+            shader_passes: Vec::new(),
+        }
+    }
+
+    pub fn from_preset(preset: &RenderPipelineRes, asset_manager: &AssetManager) -> Result<Self> {
+        Self::from_render_graph(&compile_render_graph(preset)?, asset_manager)
+    }
+
+    /// Loads the SPIR-V of every `ShaderFullscreen` node.
+    pub fn from_render_graph(
+        render_graph: &CompiledRenderGraph,
+        asset_manager: &AssetManager,
+    ) -> Result<Self> {
+        let shader_passes = render_graph
+            .shader_nodes
+            .iter()
+            .map(|shader| {
+                Ok(ShaderFullscreenPassConfig {
+                    name: shader.spirv_path.clone(),
+                    spirv: asset_manager.load_binary(&shader.spirv_path)?,
+                    entry: shader.entry.clone(),
+                    inputs: shader.inputs.clone(),
+                    params: shader.params.iter().map(|param| param.value).collect(),
+                    params_binding: shader.params_binding,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut shader_pass_count = 0;
+        let post_process_passes = render_graph
+            .post_process_nodes()
+            .map(|kind| {
+                PostProcessPassType::from_node_kind(kind).unwrap_or_else(|| {
+                    shader_pass_count += 1;
+                    PostProcessPassType::ShaderFullscreen(shader_pass_count - 1)
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            lighting_path: render_graph.lighting_path,
+            post_process_passes,
+            shader_passes,
+        })
+    }
+
+    /// Whether a `TAA` pass runs, which needs the camera jittered from frame to frame.
+    pub fn uses_taa(&self) -> bool {
+        self.post_process_passes.contains(&PostProcessPassType::TAA)
+    }
+
+    /// The main camera pass has one subpass per post pass, so each may run at most once.
+    pub fn validate(&self) -> Result<()> {
+        for (i, pass) in self.post_process_passes.iter().enumerate() {
+            if self.post_process_passes[..i].contains(pass) {
+                return Err(anyhow!(
+                    "post process pass {pass:?} is configured more than once"
+                ));
+            }
+            if let PostProcessPassType::ShaderFullscreen(index) = pass {
+                if *index >= self.shader_passes.len() {
+                    return Err(anyhow!("shader pass {index} is not configured"));
+                }
+            }
+        }
+        if self.shader_passes.len() > MAX_SHADER_FULLSCREEN_PASS_COUNT as usize {
+            return Err(anyhow!(
+                "at most {MAX_SHADER_FULLSCREEN_PASS_COUNT} shader passes are supported"
+            ));
+        }
+        if let Some(shader_pass) = self
+            .shader_passes
+            .iter()
+            .find(|shader_pass| shader_pass.params.len() > MAX_SHADER_FULLSCREEN_PARAM_COUNT)
+        {
+            return Err(anyhow!(
+                "{} has more than {MAX_SHADER_FULLSCREEN_PARAM_COUNT} params",
+                shader_pass.name
+            ));
+        }
+        if let Some(shader_pass) = self
+            .shader_passes
+            .iter()
+            .find(|shader_pass| shader_pass.inputs.len() > MAX_SHADER_FULLSCREEN_INPUT_COUNT)
+        {
+            return Err(anyhow!(
+                "{} has more than {MAX_SHADER_FULLSCREEN_INPUT_COUNT} inputs",
+                shader_pass.name
+            ));
+        }
+        Ok(())
+    }
 }
 
 pub struct RenderPipelineCreateInfo<'a> {
     pub rhi: &'a VulkanRHI,
     pub render_resource: &'a RenderResource,
     pub descriptor_layout_registry: &'a DescriptorLayoutRegistry,
-    pub enable_fxaa: bool,
+    pub config: RenderPipelineConfig,
 }
@@ -1,66 +1,80 @@
+pub mod bloom_pass;
 pub mod color_grading_pass;
 pub mod combine_ui_pass;
-pub mod directional_light_pass;
 pub mod fxaa_pass;
+pub mod layered_shadow_pass;
 pub mod main_camera_pass;
+pub mod msaa_resolve_pass;
 pub mod pick_pass;
 pub mod point_light_pass;
+pub mod shader_fullscreen_pass;
+pub mod ssao_pass;
+pub mod taa_pass;
 pub mod tone_mapping_pass;
 pub mod ui_pass;
 
-use crate::function::{
-    render::{
-        interface::vulkan::vulkan_rhi::VulkanRHI,
-        render_pass::DescriptorLayoutRegistry,
-        render_pipeline::{
-            RenderPipelineTrait,
-            pbr_pipeline::{
-                directional_light_pass::{
-                    DirectionalLightShadowPass, DirectionalLightShadowPassInitInfo,
+use crate::{
+    function::{
+        render::{
+            interface::vulkan::vulkan_rhi::VulkanRHI,
+            render_pass::DescriptorLayoutRegistry,
+            render_pipeline::{
+                RenderPipelineConfig, RenderPipelineTrait,
+                pbr_pipeline::{
+                    layered_shadow_pass::{
+                        LayeredShadowPass, LayeredShadowPassInitInfo, LayeredShadowSource,
+                    },
+                    main_camera_pass::{
+                        MainCameraPass, MainCameraPassInitInfo, PerMeshDescriptorLayout,
+                    },
+                    pick_pass::{PickPass, PickPassInitInfo},
+                    point_light_pass::{PointLightShadowPass, PointLightShadowPassInitInfo},
                 },
-                main_camera_pass::{
-                    MainCameraPass, MainCameraPassInitInfo, PerMeshDescriptorLayout,
-                },
-                pick_pass::{PickPass, PickPassInitInfo},
-                point_light_pass::{PointLightShadowPass, PointLightShadowPassInitInfo},
             },
+            render_resource::{GlobalRenderResource, RenderResource},
+            render_scene::RenderScene,
         },
-        render_resource::{GlobalRenderResource, RenderResource},
-        render_scene::RenderScene,
-        render_type::RenderPipelineType,
+        ui::ui2::UiRuntime,
     },
-    ui::ui2::UiRuntime,
+    resource::res_type::data::render_pipeline::RenderLightingPath,
 };
 use anyhow::Result;
+use vulkanalia::prelude::v1_0::*;
 
 pub struct PBRRenderPipelineCreateInfo<'a> {
     pub rhi: &'a VulkanRHI,
     pub render_resource: &'a RenderResource,
     pub descriptor_layout_registry: &'a DescriptorLayoutRegistry,
-    pub enable_fxaa: bool,
+    pub config: RenderPipelineConfig,
+    pub directional_light_cascade_count: usize,
+    pub msaa_samples: vk::SampleCountFlags,
 }
 
 pub struct PBRRenderPipeline {
-    m_render_pipeline_type: RenderPipelineType,
-    m_directional_light_pass: DirectionalLightShadowPass,
+    m_config: RenderPipelineConfig,
+    m_directional_light_pass: LayeredShadowPass,
     m_point_light_pass: PointLightShadowPass,
+    m_spot_light_pass: LayeredShadowPass,
     m_main_camera_pass: MainCameraPass,
     m_pick_pass: PickPass,
 }
 
 impl PBRRenderPipeline {
     pub fn create(create_info: &PBRRenderPipelineCreateInfo) -> Result<Self> {
-        let mut m_directional_light_pass = DirectionalLightShadowPass::default();
+        let mut m_directional_light_pass = LayeredShadowPass::default();
         let mut m_point_light_pass = PointLightShadowPass::default();
+        let mut m_spot_light_pass = LayeredShadowPass::default();
         let mut m_main_camera_pass = MainCameraPass::default();
         let mut m_pick_pass = PickPass::default();
 
         let global_render_resource = &create_info.render_resource.m_global_render_resource;
 
-        m_directional_light_pass.initialize(&DirectionalLightShadowPassInitInfo {
+        m_directional_light_pass.initialize(&LayeredShadowPassInitInfo {
             rhi: create_info.rhi,
             descriptor_layout_registry: &create_info.descriptor_layout_registry,
             global_render_resource: &global_render_resource,
+            source: LayeredShadowSource::DirectionalLightCascades,
+            layer_count: create_info.directional_light_cascade_count,
         })?;
 
         m_point_light_pass.initialize(&PointLightShadowPassInitInfo {
@@ -69,18 +83,31 @@ impl PBRRenderPipeline {
             global_render_resource: &global_render_resource,
         })?;
 
-        m_main_camera_pass.m_directional_light_shadow_color_image_view = m_directional_light_pass
-            .m_directional_light_shadow_attachment
-            .view;
+        // unused spot light layers are still cleared so the whole array is ready to be sampled
+        m_spot_light_pass.initialize(&LayeredShadowPassInitInfo {
+            rhi: create_info.rhi,
+            descriptor_layout_registry: &create_info.descriptor_layout_registry,
+            global_render_resource: &global_render_resource,
+            source: LayeredShadowSource::SpotLights,
+            layer_count: LayeredShadowSource::SpotLights.max_layer_count(),
+        })?;
+
+        m_main_camera_pass.m_directional_light_shadow_color_image_view =
+            m_directional_light_pass.m_shadow_attachment.view;
 
         m_main_camera_pass.m_point_light_shadow_color_image_view =
             m_point_light_pass.m_point_light_shadow_attachment.view;
 
+        m_main_camera_pass.m_spot_light_shadow_color_image_view =
+            m_spot_light_pass.m_shadow_attachment.view;
+
         m_main_camera_pass.initialize(&MainCameraPassInitInfo {
             rhi: create_info.rhi,
-            enable_fxaa: create_info.enable_fxaa,
+            post_process_passes: &create_info.config.post_process_passes,
+            shader_passes: &create_info.config.shader_passes,
             global_render_resource: &global_render_resource,
             descriptor_layout_manager: &create_info.descriptor_layout_registry,
+            msaa_samples: create_info.msaa_samples,
         })?;
 
         m_pick_pass.initialize(&PickPassInitInfo {
@@ -92,9 +119,10 @@ impl PBRRenderPipeline {
         })?;
 
         Ok(PBRRenderPipeline {
-            m_render_pipeline_type: RenderPipelineType::DeferredPipeline,
+            m_config: create_info.config.clone(),
             m_directional_light_pass,
             m_point_light_pass,
+            m_spot_light_pass,
             m_main_camera_pass,
             m_pick_pass,
         })
@@ -110,6 +138,7 @@ impl RenderPipelineTrait for PBRRenderPipeline {
         self.m_directional_light_pass
             .prepare_pass_data(render_resource);
         self.m_point_light_pass.prepare_pass_data(render_resource);
+        self.m_spot_light_pass.prepare_pass_data(render_resource);
         self.m_main_camera_pass.prepare_pass_data(render_resource);
         self.m_pick_pass.prepare_pass_data(rhi, render_resource);
     }
@@ -128,16 +157,19 @@ impl RenderPipelineTrait for PBRRenderPipeline {
     ) {
         self.m_directional_light_pass
             .draw(rhi, render_scene, render_resource);
-        self.m_point_light_pass.draw(rhi);
+        self.m_point_light_pass
+            .draw(rhi, render_scene, render_resource);
+        self.m_spot_light_pass
+            .draw(rhi, render_scene, render_resource);
         self.m_main_camera_pass
             .draw(
                 rhi,
                 render_scene,
                 render_resource,
                 ui_runtime,
-                match self.m_render_pipeline_type {
-                    RenderPipelineType::ForwardPipeline => true,
-                    RenderPipelineType::DeferredPipeline => false,
+                match self.m_config.lighting_path {
+                    RenderLightingPath::Forward => true,
+                    RenderLightingPath::Deferred => false,
                 },
             )
             .unwrap();
@@ -146,9 +178,18 @@ impl RenderPipelineTrait for PBRRenderPipeline {
         &mut self,
         rhi: &VulkanRHI,
         render_resource: &GlobalRenderResource,
-    ) {
+    ) -> Result<()> {
         self.m_main_camera_pass
             .recreate_after_swapchain(rhi, render_resource)
-            .unwrap();
+    }
+    fn set_config(&mut self, config: &RenderPipelineConfig) {
+        // the lighting path only changes which subpasses draw, the post passes reshape the
+        // render pass
+        self.m_main_camera_pass
+            .set_post_process_passes(&config.post_process_passes, &config.shader_passes);
+        self.m_config = config.clone();
+    }
+    fn config(&self) -> Option<&RenderPipelineConfig> {
+        Some(&self.m_config)
     }
 }
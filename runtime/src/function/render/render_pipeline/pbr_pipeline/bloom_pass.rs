@@ -0,0 +1,587 @@
+use anyhow::Result;
+use linkme::distributed_slice;
+use vulkanalia::{
+    prelude::v1_0::*,
+    vk::{VertexInputAttributeDescription, VertexInputBindingDescription},
+};
+
+use crate::{
+    function::render::{
+        interface::vulkan::vulkan_rhi::{VULKAN_RHI_DESCRIPTOR_COMBINED_IMAGE_SAMPLER, VulkanRHI},
+        light::HDRBloom,
+        render_pass::{
+            Descriptor, DescriptorLayout, DescriptorLayoutRegistry, FrameBufferAttachment,
+            RenderPass, RenderPipelineBase,
+        },
+        render_type::RHISamplerType,
+    },
+    shader::generated::shader::{BLOOM_DOWNSAMPLE_FRAG, BLOOM_UPSAMPLE_FRAG, POST_PROCESS_VERT},
+};
+
+/// Levels of the chain, each half the size of the one before; the first is half the screen.
+const BLOOM_PASS_MIP_COUNT: usize = 6;
+const BLOOM_PASS_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+const _BLOOM_PASS_DOWNSAMPLE: usize = 0;
+const _BLOOM_PASS_UPSAMPLE: usize = 1;
+
+/// Descriptor set `i` feeds the downsample into level `i`, set `BLOOM_PASS_MIP_COUNT + i` the
+/// upsample into level `i`, and the last one the composite onto the scene.
+const _BLOOM_PASS_UPSAMPLE_DESCRIPTOR_SET: usize = BLOOM_PASS_MIP_COUNT;
+const _BLOOM_PASS_COMPOSITE_DESCRIPTOR_SET: usize = 2 * BLOOM_PASS_MIP_COUNT - 1;
+
+pub struct BloomPassInitInfo<'a> {
+    pub rhi: &'a VulkanRHI,
+    pub descriptor_layout_manager: &'a DescriptorLayoutRegistry,
+    pub scene_color_attachment: vk::ImageView,
+}
+
+/// Keep in sync with the push constants of shader/glsl/bloom.h.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct BloomPushConstantObject {
+    target_size: [f32; 2],
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    prefilter: u32,
+}
+
+/// Bloom of the lit HDR scene, between the lighting and the tone mapping. The bright parts are
+/// downsampled through a chain of ever smaller levels, blurred back up level by level, then added
+/// onto the scene color. Each step is a render pass of its own since the levels differ in size.
+#[derive(Default)]
+pub struct BloomPass {
+    pub m_render_pass: RenderPass,
+    /// Keeps what the target holds and blends onto it, for the upsample steps and the composite.
+    m_blend_render_pass: vk::RenderPass,
+    m_mip_framebuffers: Vec<vk::Framebuffer>,
+    m_scene_framebuffer: vk::Framebuffer,
+}
+
+impl BloomPass {
+    pub fn initialize(&mut self, info: &BloomPassInitInfo) -> Result<()> {
+        let rhi = info.rhi;
+        self.setup_attachments(rhi)?;
+        self.setup_render_pass(rhi)?;
+        self.setup_framebuffer(rhi, info.scene_color_attachment)?;
+        self.setup_descriptor_layout(rhi, info.descriptor_layout_manager)?;
+        self.setup_pipelines(rhi)?;
+        self.setup_descriptor_set(rhi)?;
+        self.update_descriptor_set(rhi, info.scene_color_attachment)?;
+        Ok(())
+    }
+
+    pub fn draw(&self, rhi: &VulkanRHI, bloom: &HDRBloom) {
+        let command_buffer = rhi.get_current_command_buffer();
+        rhi.push_event(command_buffer, "Bloom\0", [1.0; 4]);
+
+        let extent = rhi.get_swapchain_info().extent;
+        let push_constants =
+            |target: vk::Extent2D, intensity: f32, prefilter: bool| BloomPushConstantObject {
+                target_size: [target.width as f32, target.height as f32],
+                threshold: bloom.m_threshold,
+                knee: bloom.m_knee,
+                intensity,
+                prefilter: prefilter as u32,
+            };
+        for mip in 0..BLOOM_PASS_MIP_COUNT {
+            let target = Self::mip_extent(extent, mip);
+            self.draw_step(
+                rhi,
+                self.m_render_pass.m_framebuffer.render_pass,
+                self.m_mip_framebuffers[mip],
+                target,
+                _BLOOM_PASS_DOWNSAMPLE,
+                mip,
+                &push_constants(target, 1.0, mip == 0),
+            );
+        }
+        for mip in (0..BLOOM_PASS_MIP_COUNT - 1).rev() {
+            let target = Self::mip_extent(extent, mip);
+            self.draw_step(
+                rhi,
+                self.m_blend_render_pass,
+                self.m_mip_framebuffers[mip],
+                target,
+                _BLOOM_PASS_UPSAMPLE,
+                _BLOOM_PASS_UPSAMPLE_DESCRIPTOR_SET + mip,
+                &push_constants(target, 1.0, false),
+            );
+        }
+        self.draw_step(
+            rhi,
+            self.m_blend_render_pass,
+            self.m_scene_framebuffer,
+            extent,
+            _BLOOM_PASS_UPSAMPLE,
+            _BLOOM_PASS_COMPOSITE_DESCRIPTOR_SET,
+            &push_constants(extent, bloom.m_intensity, false),
+        );
+
+        rhi.pop_event(command_buffer);
+    }
+
+    pub fn update_after_framebuffer_recreate(
+        &mut self,
+        rhi: &VulkanRHI,
+        scene_color_attachment: vk::ImageView,
+    ) -> Result<()> {
+        for framebuffer in self.m_mip_framebuffers.drain(..) {
+            rhi.destroy_framebuffer(framebuffer);
+        }
+        rhi.destroy_framebuffer(self.m_scene_framebuffer);
+        self.destroy_attachments(rhi);
+        self.setup_attachments(rhi)?;
+        self.setup_framebuffer(rhi, scene_color_attachment)?;
+        self.update_descriptor_set(rhi, scene_color_attachment)
+    }
+
+    pub fn destroy(&self, rhi: &VulkanRHI) {
+        for framebuffer in &self.m_mip_framebuffers {
+            rhi.destroy_framebuffer(*framebuffer);
+        }
+        rhi.destroy_framebuffer(self.m_scene_framebuffer);
+        self.destroy_attachments(rhi);
+        for pipeline in &self.m_render_pass.m_render_pipeline {
+            rhi.destroy_pipeline(pipeline.pipeline);
+            rhi.destroy_pipeline_layout(pipeline.layout);
+        }
+        rhi.destroy_render_pass(self.m_blend_render_pass);
+        rhi.destroy_render_pass(self.m_render_pass.m_framebuffer.render_pass);
+    }
+
+    fn mip_extent(extent: vk::Extent2D, mip: usize) -> vk::Extent2D {
+        vk::Extent2D {
+            width: (extent.width >> (mip + 1)).max(1),
+            height: (extent.height >> (mip + 1)).max(1),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_step(
+        &self,
+        rhi: &VulkanRHI,
+        render_pass: vk::RenderPass,
+        framebuffer: vk::Framebuffer,
+        target: vk::Extent2D,
+        pipeline_index: usize,
+        descriptor_set_index: usize,
+        push_constants: &BloomPushConstantObject,
+    ) {
+        let command_buffer = rhi.get_current_command_buffer();
+
+        let render_area = vk::Rect2D::builder()
+            .offset(vk::Offset2D::default())
+            .extent(target);
+        let info = vk::RenderPassBeginInfo::builder()
+            .render_pass(render_pass)
+            .framebuffer(framebuffer)
+            .render_area(render_area);
+
+        let viewport = vk::Viewport::builder()
+            .x(0.0)
+            .y(0.0)
+            .width(target.width as f32)
+            .height(target.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0)
+            .build();
+        let scissor = vk::Rect2D::builder()
+            .offset(vk::Offset2D::default())
+            .extent(target)
+            .build();
+
+        rhi.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+        let pipeline = &self.m_render_pass.m_render_pipeline[pipeline_index];
+        rhi.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline.pipeline,
+        );
+        rhi.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        rhi.cmd_set_scissor(command_buffer, 0, &[scissor]);
+        rhi.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline.layout,
+            0,
+            &[self.m_render_pass.m_descriptor_infos[descriptor_set_index].descriptor_set],
+            &[],
+        );
+        rhi.cmd_push_constants(
+            command_buffer,
+            pipeline.layout,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            unsafe {
+                std::slice::from_raw_parts(
+                    push_constants as *const BloomPushConstantObject as *const u8,
+                    std::mem::size_of::<BloomPushConstantObject>(),
+                )
+            },
+        );
+        rhi.cmd_draw(command_buffer, 3, 1, 0, 0);
+        rhi.cmd_end_render_pass(command_buffer);
+    }
+}
+
+pub struct BloomDescriptorLayout;
+impl DescriptorLayout for BloomDescriptorLayout {
+    fn new(rhi: &VulkanRHI) -> Result<vk::DescriptorSetLayout> {
+        let layout_bindings = [vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build()];
+
+        let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&layout_bindings);
+        let layout = rhi.create_descriptor_set_layout(&create_info)?;
+        Ok(layout)
+    }
+}
+
+#[distributed_slice(VULKAN_RHI_DESCRIPTOR_COMBINED_IMAGE_SAMPLER)]
+static COMBINED_IMAGE_SAMPLER_COUNT: u32 = 2 * BLOOM_PASS_MIP_COUNT as u32;
+
+impl BloomPass {
+    fn setup_attachments(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let extent = rhi.get_swapchain_info().extent;
+        self.m_render_pass.m_framebuffer.attachments = (0..BLOOM_PASS_MIP_COUNT)
+            .map(|mip| {
+                let target = Self::mip_extent(extent, mip);
+                let (image, mem) = rhi.create_image(
+                    target.width,
+                    target.height,
+                    BLOOM_PASS_FORMAT,
+                    vk::ImageTiling::OPTIMAL,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    vk::ImageCreateFlags::empty(),
+                    1,
+                    1,
+                )?;
+                let view = rhi.create_image_view(
+                    image,
+                    BLOOM_PASS_FORMAT,
+                    vk::ImageAspectFlags::COLOR,
+                    vk::ImageViewType::_2D,
+                    1,
+                    1,
+                )?;
+                Ok(FrameBufferAttachment {
+                    image,
+                    mem,
+                    view,
+                    format: BLOOM_PASS_FORMAT,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(())
+    }
+
+    fn destroy_attachments(&self, rhi: &VulkanRHI) {
+        for attachment in &self.m_render_pass.m_framebuffer.attachments {
+            rhi.destroy_image_view(attachment.view);
+            rhi.destroy_image(attachment.image);
+            rhi.free_memory(attachment.mem);
+        }
+    }
+
+    /// The downsample overwrites its whole target, the upsample and the composite add onto it.
+    /// Both passes stay compatible, so one framebuffer per level serves either.
+    fn setup_render_pass(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let color_attachment_refs = [vk::AttachmentReference::builder()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
+        let subpasses = [vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_refs)
+            .build()];
+        let dependencies = [
+            // the previous step wrote the source, and the last frame may still be reading the
+            // target
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                )
+                .dst_stage_mask(
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                )
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(
+                    vk::AccessFlags::SHADER_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )
+                .build(),
+            vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                )
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(
+                    vk::AccessFlags::SHADER_READ
+                        | vk::AccessFlags::INPUT_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )
+                .build(),
+        ];
+
+        let render_pass = |load_op: vk::AttachmentLoadOp, initial_layout: vk::ImageLayout| {
+            let attachments = [vk::AttachmentDescription::builder()
+                .format(BLOOM_PASS_FORMAT)
+                .samples(vk::SampleCountFlags::_1)
+                .load_op(load_op)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(initial_layout)
+                .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .build()];
+            let create_info = vk::RenderPassCreateInfo::builder()
+                .attachments(&attachments)
+                .subpasses(&subpasses)
+                .dependencies(&dependencies);
+            rhi.create_render_pass(&create_info)
+        };
+
+        self.m_render_pass.m_framebuffer.render_pass =
+            render_pass(vk::AttachmentLoadOp::DONT_CARE, vk::ImageLayout::UNDEFINED)?;
+        self.m_blend_render_pass = render_pass(
+            vk::AttachmentLoadOp::LOAD,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )?;
+
+        Ok(())
+    }
+
+    fn setup_framebuffer(
+        &mut self,
+        rhi: &VulkanRHI,
+        scene_color_attachment: vk::ImageView,
+    ) -> Result<()> {
+        let extent = rhi.get_swapchain_info().extent;
+        let create_framebuffer =
+            |render_pass: vk::RenderPass, view: vk::ImageView, target: vk::Extent2D| {
+                let attachments = [view];
+                let create_info = vk::FramebufferCreateInfo::builder()
+                    .render_pass(render_pass)
+                    .attachments(&attachments)
+                    .width(target.width)
+                    .height(target.height)
+                    .layers(1);
+                rhi.create_framebuffer(&create_info)
+            };
+        self.m_mip_framebuffers = self
+            .m_render_pass
+            .m_framebuffer
+            .attachments
+            .iter()
+            .enumerate()
+            .map(|(mip, attachment)| {
+                create_framebuffer(
+                    self.m_render_pass.m_framebuffer.render_pass,
+                    attachment.view,
+                    Self::mip_extent(extent, mip),
+                )
+            })
+            .collect::<Result<_, _>>()?;
+        self.m_scene_framebuffer =
+            create_framebuffer(self.m_blend_render_pass, scene_color_attachment, extent)?;
+        Ok(())
+    }
+
+    fn setup_descriptor_layout(
+        &mut self,
+        rhi: &VulkanRHI,
+        descriptor_layout_manager: &DescriptorLayoutRegistry,
+    ) -> Result<()> {
+        let layout = descriptor_layout_manager.acquire::<BloomDescriptorLayout>(rhi)?;
+        self.m_render_pass.m_descriptor_infos = (0..2 * BLOOM_PASS_MIP_COUNT)
+            .map(|_| Descriptor {
+                layout,
+                descriptor_set: Default::default(),
+            })
+            .collect();
+        Ok(())
+    }
+
+    fn setup_pipelines(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        self.m_render_pass.m_render_pipeline.clear();
+        for (frag_shader, render_pass, blend) in [
+            (
+                BLOOM_DOWNSAMPLE_FRAG,
+                self.m_render_pass.m_framebuffer.render_pass,
+                false,
+            ),
+            (BLOOM_UPSAMPLE_FRAG, self.m_blend_render_pass, true),
+        ] {
+            let vert_shader_module = rhi.create_shader_module(&POST_PROCESS_VERT)?;
+            let frag_shader_module = rhi.create_shader_module(frag_shader)?;
+
+            let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vert_shader_module)
+                .name(b"main\0");
+
+            let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag_shader_module)
+                .name(b"main\0");
+
+            let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+                .vertex_binding_descriptions(&[] as &[VertexInputBindingDescription])
+                .vertex_attribute_descriptions(&[] as &[VertexInputAttributeDescription]);
+
+            let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+                .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+                .primitive_restart_enable(false);
+
+            let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+                .viewport_count(1)
+                .scissor_count(1);
+
+            let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+                .depth_clamp_enable(false)
+                .rasterizer_discard_enable(false)
+                .polygon_mode(vk::PolygonMode::FILL)
+                .line_width(1.0)
+                .cull_mode(vk::CullModeFlags::BACK)
+                .front_face(vk::FrontFace::CLOCKWISE)
+                .depth_bias_enable(false);
+
+            let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+                .sample_shading_enable(false)
+                .rasterization_samples(vk::SampleCountFlags::_1);
+
+            let attachment = vk::PipelineColorBlendAttachmentState::builder()
+                .color_write_mask(vk::ColorComponentFlags::all())
+                .blend_enable(blend)
+                .src_color_blend_factor(vk::BlendFactor::ONE)
+                .dst_color_blend_factor(vk::BlendFactor::ONE)
+                .color_blend_op(vk::BlendOp::ADD)
+                .src_alpha_blend_factor(vk::BlendFactor::ONE)
+                .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+                .alpha_blend_op(vk::BlendOp::ADD);
+
+            let attachments = &[attachment];
+            let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+                .logic_op_enable(false)
+                .logic_op(vk::LogicOp::COPY)
+                .attachments(attachments)
+                .blend_constants([0.0, 0.0, 0.0, 0.0]);
+
+            let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+                .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+            let set_layouts = &[self.m_render_pass.m_descriptor_infos[0].layout];
+            let push_constant_ranges = [vk::PushConstantRange::builder()
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .offset(0)
+                .size(std::mem::size_of::<BloomPushConstantObject>() as u32)];
+            let layout_info = vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(set_layouts)
+                .push_constant_ranges(&push_constant_ranges);
+
+            let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
+
+            let stages = &[vert_stage, frag_stage];
+            let info = vk::GraphicsPipelineCreateInfo::builder()
+                .stages(stages)
+                .vertex_input_state(&vertex_input_state)
+                .input_assembly_state(&input_assembly_state)
+                .viewport_state(&viewport_state)
+                .rasterization_state(&rasterization_state)
+                .multisample_state(&multisample_state)
+                .color_blend_state(&color_blend_state)
+                .dynamic_state(&dynamic_state)
+                .layout(pipeline_layout)
+                .render_pass(render_pass)
+                .subpass(0)
+                .build();
+
+            let pipeline = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
+
+            rhi.destroy_shader_module(vert_shader_module);
+            rhi.destroy_shader_module(frag_shader_module);
+
+            self.m_render_pass
+                .m_render_pipeline
+                .push(RenderPipelineBase {
+                    layout: pipeline_layout,
+                    pipeline,
+                });
+        }
+        Ok(())
+    }
+
+    fn setup_descriptor_set(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        for descriptor_info in &mut self.m_render_pass.m_descriptor_infos {
+            let set_layouts = [descriptor_info.layout];
+            let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(rhi.get_descriptor_pool())
+                .set_layouts(&set_layouts);
+            descriptor_info.descriptor_set = rhi.allocate_descriptor_sets(&alloc_info)?[0];
+        }
+        Ok(())
+    }
+
+    fn update_descriptor_set(
+        &self,
+        rhi: &VulkanRHI,
+        scene_color_attachment: vk::ImageView,
+    ) -> Result<()> {
+        let sampler = *rhi.get_or_create_default_sampler(RHISamplerType::Linear)?;
+        let mip_views = self
+            .m_render_pass
+            .m_framebuffer
+            .attachments
+            .iter()
+            .map(|attachment| attachment.view);
+        // the downsamples read the scene then each level in turn, the upsamples and the composite
+        // read the level below their target
+        let source_image_infos = std::iter::once(scene_color_attachment)
+            .chain(mip_views.clone().take(BLOOM_PASS_MIP_COUNT - 1))
+            .chain(mip_views.skip(1))
+            .chain(std::iter::once(
+                self.m_render_pass.m_framebuffer.attachments[0].view,
+            ))
+            .map(|image_view| {
+                [vk::DescriptorImageInfo::builder()
+                    .sampler(sampler)
+                    .image_view(image_view)
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .build()]
+            })
+            .collect::<Vec<_>>();
+
+        let descriptor_writes = self
+            .m_render_pass
+            .m_descriptor_infos
+            .iter()
+            .zip(&source_image_infos)
+            .map(|(descriptor_info, source_image_info)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(descriptor_info.descriptor_set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(source_image_info)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        rhi.update_descriptor_sets(&descriptor_writes)?;
+        Ok(())
+    }
+}
@@ -14,7 +14,6 @@ use crate::{
         render_pass::{
             Descriptor, DescriptorLayout, DescriptorLayoutRegistry, RenderPass, RenderPipelineBase,
         },
-        render_pipeline::pbr_pipeline::main_camera_pass::MainCameraSubPass,
         render_resource::GlobalRenderResource,
         render_type::RHISamplerType,
     },
@@ -25,6 +24,7 @@ pub struct ColorGradingPassInitInfo<'a> {
     pub global_render_resource: &'a GlobalRenderResource,
     pub descriptor_layout_manager: &'a DescriptorLayoutRegistry,
     pub render_pass: vk::RenderPass,
+    pub subpass: u32,
     pub rhi: &'a VulkanRHI,
     pub input_attachment: vk::ImageView,
 }
@@ -32,11 +32,13 @@ pub struct ColorGradingPassInitInfo<'a> {
 #[derive(Default)]
 pub struct ColorGradingPass {
     pub m_render_pass: RenderPass,
+    m_subpass: u32,
 }
 
 impl ColorGradingPass {
     pub fn initialize(&mut self, info: &ColorGradingPassInitInfo) -> Result<()> {
         self.m_render_pass.m_framebuffer.render_pass = info.render_pass;
+        self.m_subpass = info.subpass;
         self.setup_descriptor_layout(info.rhi, &info.descriptor_layout_manager)?;
         self.setup_pipelines(info.rhi)?;
         self.setup_descriptor_set(info.rhi)?;
@@ -70,6 +72,22 @@ impl ColorGradingPass {
         rhi.cmd_draw(command_buffer, 3, 1, 0, 0);
         rhi.pop_event(command_buffer);
     }
+    /// Rebuilds the pipeline for a recreated main camera render pass, which may also have moved
+    /// this pass to another subpass.
+    pub fn update_after_render_pass_recreate(
+        &mut self,
+        rhi: &VulkanRHI,
+        render_pass: vk::RenderPass,
+        subpass: u32,
+    ) -> Result<()> {
+        for pipeline in self.m_render_pass.m_render_pipeline.drain(..) {
+            rhi.destroy_pipeline(pipeline.pipeline);
+            rhi.destroy_pipeline_layout(pipeline.layout);
+        }
+        self.m_render_pass.m_framebuffer.render_pass = render_pass;
+        self.m_subpass = subpass;
+        self.setup_pipelines(rhi)
+    }
     pub fn update_after_framebuffer_recreate(
         &mut self,
         rhi: &VulkanRHI,
@@ -226,7 +244,7 @@ impl ColorGradingPass {
             .dynamic_state(&dynamic_state)
             .layout(pipeline_layout)
             .render_pass(self.m_render_pass.m_framebuffer.render_pass)
-            .subpass(MainCameraSubPass::ColorGrading as u32)
+            .subpass(self.m_subpass)
             .build();
 
         let pipeline = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
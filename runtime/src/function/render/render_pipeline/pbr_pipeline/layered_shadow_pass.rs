@@ -1,28 +1,32 @@
-use std::{collections::HashMap, os::raw::c_void};
+use std::os::raw::c_void;
 
 use crate::{
-    core::math::matrix4::Matrix4x4,
     function::render::{
         interface::vulkan::vulkan_rhi::{VULKAN_RHI_DESCRIPTOR_STORAGE_BUFFER_DYNAMIC, VulkanRHI},
         render_common::{
             MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT,
             MeshDirectionalLightShadowPerdrawcallStorageBufferObject,
             MeshDirectionalLightShadowPerdrawcallVertexBlendingStorageBufferObject,
-            MeshDirectionalLightShadowPerframeStorageBufferObject,
-            S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION,
+            MeshDirectionalLightShadowPerframeStorageBufferObject, RenderMeshNode,
+            S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION, S_MAX_DIRECTIONAL_LIGHT_CASCADE_COUNT,
+            S_MAX_SPOT_LIGHT_SHADOW_COUNT, S_SPOT_LIGHT_SHADOW_MAP_DIMENSION,
         },
-        render_helper::round_up,
+        render_helper::{mesh_node_batches, round_up, split_masked_mesh_nodes},
         render_mesh::MeshVertex,
         render_pass::{
             DescriptorLayout, DescriptorLayoutRegistry, FrameBufferAttachment, RenderPass,
             RenderPipelineBase,
         },
-        render_pipeline::pbr_pipeline::main_camera_pass::PerMeshDescriptorLayout,
+        render_pipeline::pbr_pipeline::main_camera_pass::{
+            MeshPerMaterialDescriptorLayout, PerMeshDescriptorLayout,
+        },
         render_resource::{GlobalRenderResource, RenderResource},
         render_scene::RenderScene,
     },
+    resource::res_type::data::material::MaterialAlphaMode,
     shader::generated::shader::{
-        MESH_DIRECTIONAL_LIGHT_SHADOW_FRAG, MESH_DIRECTIONAL_LIGHT_SHADOW_VERT,
+        MESH_DIRECTIONAL_LIGHT_SHADOW_ALPHA_TEST_FRAG, MESH_DIRECTIONAL_LIGHT_SHADOW_FRAG,
+        MESH_DIRECTIONAL_LIGHT_SHADOW_VERT,
     },
 };
 
@@ -30,27 +34,99 @@ use anyhow::Result;
 use linkme::distributed_slice;
 use vulkanalia::prelude::v1_0::*;
 
-pub struct DirectionalLightShadowPassInitInfo<'a> {
+/// The lights a `LayeredShadowPass` draws, one layer of the shadow map array each.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum LayeredShadowSource {
+    /// The cascades of the directional light, with an orthographic projection each.
+    #[default]
+    DirectionalLightCascades,
+    /// The shadowed spot lights, with a perspective projection each.
+    SpotLights,
+}
+
+impl LayeredShadowSource {
+    pub fn max_layer_count(self) -> usize {
+        match self {
+            Self::DirectionalLightCascades => S_MAX_DIRECTIONAL_LIGHT_CASCADE_COUNT,
+            Self::SpotLights => S_MAX_SPOT_LIGHT_SHADOW_COUNT,
+        }
+    }
+
+    fn map_dimension(self) -> u32 {
+        match self {
+            Self::DirectionalLightCascades => S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION,
+            Self::SpotLights => S_SPOT_LIGHT_SHADOW_MAP_DIMENSION,
+        }
+    }
+
+    fn event_name(self) -> &'static str {
+        match self {
+            Self::DirectionalLightCascades => "Directional Light Shadow\0",
+            Self::SpotLights => "Spot Light Shadow\0",
+        }
+    }
+
+    fn perframe_storage_buffer_objects(
+        self,
+        render_resource: &RenderResource,
+    ) -> &[MeshDirectionalLightShadowPerframeStorageBufferObject] {
+        match self {
+            Self::DirectionalLightCascades => {
+                &render_resource.m_mesh_directional_light_shadow_perframe_storage_buffer_objects
+            }
+            Self::SpotLights => {
+                &render_resource.m_mesh_spot_light_shadow_perframe_storage_buffer_objects
+            }
+        }
+    }
+
+    fn visible_mesh_nodes(self, render_scene: &RenderScene, layer: usize) -> &[RenderMeshNode] {
+        match self {
+            Self::DirectionalLightCascades => {
+                render_scene.get_directional_light_visible_mesh_nodes(layer)
+            }
+            Self::SpotLights => render_scene.get_spot_light_visible_mesh_nodes(layer),
+        }
+    }
+}
+
+pub struct LayeredShadowPassInitInfo<'a> {
     pub rhi: &'a VulkanRHI,
     pub global_render_resource: &'a GlobalRenderResource,
     pub descriptor_layout_registry: &'a DescriptorLayoutRegistry,
+    pub source: LayeredShadowSource,
+    /// Clamped to at least one and at most the source's `max_layer_count`.
+    pub layer_count: usize,
 }
 
+/// Renders depth into the layers of one shadow map array, with the light projection of each
+/// layer taken from its `LayeredShadowSource`.
 #[derive(Default)]
-pub struct DirectionalLightShadowPass {
+pub struct LayeredShadowPass {
     m_render_pass: RenderPass,
     m_per_mesh_layout: vk::DescriptorSetLayout,
-    m_mesh_directional_light_shadow_perframe_storage_buffer_object:
-        MeshDirectionalLightShadowPerframeStorageBufferObject,
-    pub m_directional_light_shadow_attachment: FrameBufferAttachment,
+    m_per_material_layout: vk::DescriptorSetLayout,
+    m_source: LayeredShadowSource,
+    m_layer_count: usize,
+    m_map_dimension: u32,
+    m_perframe_storage_buffer_objects: Vec<MeshDirectionalLightShadowPerframeStorageBufferObject>,
+    /// One layer per cascade or light; `view` covers all layers for sampling.
+    pub m_shadow_attachment: FrameBufferAttachment,
+    m_layer_views: Vec<vk::ImageView>,
+    m_layer_framebuffers: Vec<vk::Framebuffer>,
 }
 
+// three per pass, one pass for the directional light and one for the spot lights
 #[distributed_slice(VULKAN_RHI_DESCRIPTOR_STORAGE_BUFFER_DYNAMIC)]
-static STORAGE_BUFFER_DYNAMIC_COUNT: u32 = 3;
+static STORAGE_BUFFER_DYNAMIC_COUNT: u32 = 3 * 2;
 
-impl DirectionalLightShadowPass {
-    pub fn initialize(&mut self, info: &DirectionalLightShadowPassInitInfo) -> Result<()> {
+impl LayeredShadowPass {
+    pub fn initialize(&mut self, info: &LayeredShadowPassInitInfo) -> Result<()> {
         let rhi = info.rhi;
+        self.m_source = info.source;
+        self.m_layer_count = info.layer_count.clamp(1, info.source.max_layer_count());
+        self.m_map_dimension = info.source.map_dimension();
+        self.m_perframe_storage_buffer_objects = vec![Default::default(); self.m_layer_count];
 
         self.setup_attachments(&rhi)?;
         self.setup_render_pass(&rhi)?;
@@ -63,9 +139,11 @@ impl DirectionalLightShadowPass {
     }
 
     pub fn prepare_pass_data(&mut self, render_resource: &RenderResource) {
-        self.m_mesh_directional_light_shadow_perframe_storage_buffer_object = render_resource
-            .m_mesh_directional_light_shadow_perframe_storage_buffer_object
-            .clone();
+        let source_objects = self
+            .m_source
+            .perframe_storage_buffer_objects(render_resource);
+        self.m_perframe_storage_buffer_objects
+            .clone_from_slice(&source_objects[..self.m_layer_count]);
     }
 
     pub fn draw(
@@ -74,12 +152,14 @@ impl DirectionalLightShadowPass {
         render_scene: &RenderScene,
         resource: &mut GlobalRenderResource,
     ) {
-        self.draw_model(rhi, render_scene, resource);
+        for layer_index in 0..self.m_layer_count {
+            self.draw_model(rhi, render_scene, resource, layer_index);
+        }
     }
 }
 
-pub struct DirectionalLightShadowDescriptorLayout;
-impl DescriptorLayout for DirectionalLightShadowDescriptorLayout {
+pub struct LayeredShadowDescriptorLayout;
+impl DescriptorLayout for LayeredShadowDescriptorLayout {
     fn new(rhi: &VulkanRHI) -> Result<vk::DescriptorSetLayout> {
         let layout_bindings = [
             vk::DescriptorSetLayoutBinding::builder()
@@ -108,20 +188,20 @@ impl DescriptorLayout for DirectionalLightShadowDescriptorLayout {
     }
 }
 
-impl DirectionalLightShadowPass {
+impl LayeredShadowPass {
     fn setup_attachments(&mut self, rhi: &VulkanRHI) -> Result<()> {
         self.m_render_pass.m_framebuffer.attachments.clear();
 
         let format = vk::Format::R32_SFLOAT;
         let (image, mem) = rhi.create_image(
-            S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION,
-            S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION,
+            self.m_map_dimension,
+            self.m_map_dimension,
             format,
             vk::ImageTiling::OPTIMAL,
             vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
             vk::ImageCreateFlags::empty(),
-            1,
+            self.m_layer_count as u32,
             1,
         )?;
 
@@ -129,12 +209,18 @@ impl DirectionalLightShadowPass {
             image,
             format,
             vk::ImageAspectFlags::COLOR,
-            vk::ImageViewType::_2D,
-            1,
+            vk::ImageViewType::_2D_ARRAY,
+            self.m_layer_count as u32,
             1,
         )?;
 
-        self.m_directional_light_shadow_attachment = FrameBufferAttachment {
+        self.m_layer_views = (0..self.m_layer_count as u32)
+            .map(|layer| {
+                rhi.create_image_layer_view(image, format, vk::ImageAspectFlags::COLOR, layer)
+            })
+            .collect::<Result<_>>()?;
+
+        self.m_shadow_attachment = FrameBufferAttachment {
             image,
             mem,
             view,
@@ -143,8 +229,8 @@ impl DirectionalLightShadowPass {
 
         let format = rhi.get_depth_image_info().format;
         let (image, mem) = rhi.create_image(
-            S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION,
-            S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION,
+            self.m_map_dimension,
+            self.m_map_dimension,
             format,
             vk::ImageTiling::OPTIMAL,
             vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
@@ -180,7 +266,7 @@ impl DirectionalLightShadowPass {
     fn setup_render_pass(&mut self, rhi: &VulkanRHI) -> Result<()> {
         let attachements = [
             vk::AttachmentDescription::builder()
-                .format(self.m_directional_light_shadow_attachment.format)
+                .format(self.m_shadow_attachment.format)
                 .samples(vk::SampleCountFlags::_1)
                 .load_op(vk::AttachmentLoadOp::CLEAR)
                 .store_op(vk::AttachmentStoreOp::STORE)
@@ -213,14 +299,28 @@ impl DirectionalLightShadowPass {
             .color_attachments(&color_attachment_refs)
             .depth_stencil_attachment(&depth_attachment_ref)
             .build()];
-        let dependencies = [vk::SubpassDependency::builder()
-            .src_subpass(0)
-            .dst_subpass(vk::SUBPASS_EXTERNAL)
-            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .dst_stage_mask(vk::PipelineStageFlags::BOTTOM_OF_PIPE)
-            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-            .dst_access_mask(vk::AccessFlags::empty())
-            .build()];
+        let dependencies = [
+            // the depth buffer is shared by all layers
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+                .dst_stage_mask(vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+                .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+                .dst_access_mask(
+                    vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )
+                .build(),
+            vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(vk::PipelineStageFlags::BOTTOM_OF_PIPE)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(vk::AccessFlags::empty())
+                .build(),
+        ];
 
         let create_info = vk::RenderPassCreateInfo::builder()
             .attachments(&attachements)
@@ -233,21 +333,26 @@ impl DirectionalLightShadowPass {
     }
 
     fn setup_framebuffer(&mut self, rhi: &VulkanRHI) -> Result<()> {
-        let attachments = [
-            self.m_directional_light_shadow_attachment.view,
-            self.m_render_pass.m_framebuffer.attachments[0].view,
-        ];
-
-        let framebuffer_create_info = vk::FramebufferCreateInfo::builder()
-            .render_pass(self.m_render_pass.m_framebuffer.render_pass)
-            .attachments(&attachments)
-            .width(S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION)
-            .height(S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION)
-            .layers(1)
-            .build();
-
-        self.m_render_pass.m_framebuffer.framebuffer =
-            rhi.create_framebuffer(&framebuffer_create_info)?;
+        self.m_layer_framebuffers.clear();
+
+        for &layer_view in &self.m_layer_views {
+            let attachments = [
+                layer_view,
+                self.m_render_pass.m_framebuffer.attachments[0].view,
+            ];
+
+            let framebuffer_create_info = vk::FramebufferCreateInfo::builder()
+                .render_pass(self.m_render_pass.m_framebuffer.render_pass)
+                .attachments(&attachments)
+                .width(self.m_map_dimension)
+                .height(self.m_map_dimension)
+                .layers(1)
+                .build();
+
+            self.m_layer_framebuffers
+                .push(rhi.create_framebuffer(&framebuffer_create_info)?);
+        }
+        self.m_render_pass.m_framebuffer.framebuffer = self.m_layer_framebuffers[0];
 
         Ok(())
     }
@@ -262,37 +367,48 @@ impl DirectionalLightShadowPass {
             .resize_with(1, Default::default);
 
         self.m_render_pass.m_descriptor_infos[0].layout =
-            descriptor_layout_manager.acquire::<DirectionalLightShadowDescriptorLayout>(rhi)?;
+            descriptor_layout_manager.acquire::<LayeredShadowDescriptorLayout>(rhi)?;
 
         self.m_per_mesh_layout =
             descriptor_layout_manager.acquire::<PerMeshDescriptorLayout>(rhi)?;
+        self.m_per_material_layout =
+            descriptor_layout_manager.acquire::<MeshPerMaterialDescriptorLayout>(rhi)?;
 
         Ok(())
     }
 
     fn setup_pipelines(&mut self, rhi: &VulkanRHI) -> Result<()> {
-        self.m_render_pass
-            .m_render_pipeline
-            .resize_with(1, Default::default);
-
         let vert_shader_module = rhi.create_shader_module(&MESH_DIRECTIONAL_LIGHT_SHADOW_VERT)?;
         let frag_shader_module = rhi.create_shader_module(&MESH_DIRECTIONAL_LIGHT_SHADOW_FRAG)?;
+        let alpha_test_frag_shader_module =
+            rhi.create_shader_module(&MESH_DIRECTIONAL_LIGHT_SHADOW_ALPHA_TEST_FRAG)?;
 
         let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
             .stage(vk::ShaderStageFlags::VERTEX)
             .module(vert_shader_module)
-            .name(b"main\0");
+            .name(b"main\0")
+            .build();
 
         let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
             .stage(vk::ShaderStageFlags::FRAGMENT)
             .module(frag_shader_module)
-            .name(b"main\0");
+            .name(b"main\0")
+            .build();
+
+        let alpha_test_frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(alpha_test_frag_shader_module)
+            .name(b"main\0")
+            .build();
 
-        let binding_descriptions = &MeshVertex::get_binding_descriptions()[0..1];
-        let attribute_descriptions = &MeshVertex::get_attribute_descriptions()[0..1];
+        // position, and the texcoord the alpha test samples with
+        let binding_descriptions = MeshVertex::get_binding_descriptions();
+        let attribute_descriptions = MeshVertex::get_attribute_descriptions();
+        let binding_descriptions = [binding_descriptions[0], binding_descriptions[2]];
+        let attribute_descriptions = [attribute_descriptions[0], attribute_descriptions[3]];
         let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
-            .vertex_binding_descriptions(binding_descriptions)
-            .vertex_attribute_descriptions(attribute_descriptions);
+            .vertex_binding_descriptions(&binding_descriptions)
+            .vertex_attribute_descriptions(&attribute_descriptions);
 
         let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
             .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
@@ -301,16 +417,16 @@ impl DirectionalLightShadowPass {
         let viewports = [vk::Viewport::builder()
             .x(0.0)
             .y(0.0)
-            .width(S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION as f32)
-            .height(S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION as f32)
+            .width(self.m_map_dimension as f32)
+            .height(self.m_map_dimension as f32)
             .min_depth(0.0)
             .max_depth(1.0)];
 
         let scissors = [vk::Rect2D::builder()
             .offset(vk::Offset2D { x: 0, y: 0 })
             .extent(vk::Extent2D {
-                width: S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION,
-                height: S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION,
+                width: self.m_map_dimension,
+                height: self.m_map_dimension,
             })];
 
         let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
@@ -347,38 +463,53 @@ impl DirectionalLightShadowPass {
             .attachments(&attachments)
             .blend_constants([0.0, 0.0, 0.0, 0.0]);
 
+        // the material set is only bound for masked meshes
         let set_layouts = &[
             self.m_render_pass.m_descriptor_infos[0].layout,
             self.m_per_mesh_layout,
+            self.m_per_material_layout,
         ];
         let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(set_layouts);
 
         let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
 
-        let stages = &[vert_stage, frag_stage];
-        let info = vk::GraphicsPipelineCreateInfo::builder()
-            .stages(stages)
-            .vertex_input_state(&vertex_input_state)
-            .input_assembly_state(&input_assembly_state)
-            .viewport_state(&viewport_state)
-            .rasterization_state(&rasterization_state)
-            .multisample_state(&multisample_state)
-            .depth_stencil_state(&depth_stencil_state)
-            .color_blend_state(&color_blend_state)
-            .layout(pipeline_layout)
-            .render_pass(self.m_render_pass.m_framebuffer.render_pass)
-            .subpass(0)
-            .build();
-
-        let pipeline = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
+        // opaque meshes first, then masked ones
+        let stages = [
+            [vert_stage, frag_stage],
+            [vert_stage, alpha_test_frag_stage],
+        ];
+        let infos: Vec<_> = stages
+            .iter()
+            .map(|stages| {
+                vk::GraphicsPipelineCreateInfo::builder()
+                    .stages(stages)
+                    .vertex_input_state(&vertex_input_state)
+                    .input_assembly_state(&input_assembly_state)
+                    .viewport_state(&viewport_state)
+                    .rasterization_state(&rasterization_state)
+                    .multisample_state(&multisample_state)
+                    .depth_stencil_state(&depth_stencil_state)
+                    .color_blend_state(&color_blend_state)
+                    .layout(pipeline_layout)
+                    .render_pass(self.m_render_pass.m_framebuffer.render_pass)
+                    .subpass(0)
+                    .build()
+            })
+            .collect();
+
+        let pipelines = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &infos)?;
 
         rhi.destroy_shader_module(vert_shader_module);
         rhi.destroy_shader_module(frag_shader_module);
-
-        self.m_render_pass.m_render_pipeline[0] = RenderPipelineBase {
-            layout: pipeline_layout,
-            pipeline,
-        };
+        rhi.destroy_shader_module(alpha_test_frag_shader_module);
+
+        self.m_render_pass.m_render_pipeline = pipelines
+            .into_iter()
+            .map(|pipeline| RenderPipelineBase {
+                layout: pipeline_layout,
+                pipeline,
+            })
+            .collect();
         Ok(())
     }
 
@@ -450,6 +581,7 @@ impl DirectionalLightShadowPass {
         rhi: &VulkanRHI,
         render_scene: &RenderScene,
         render_resource: &mut GlobalRenderResource,
+        layer_index: usize,
     ) {
         let command_buffer = rhi.get_current_command_buffer();
 
@@ -462,27 +594,20 @@ impl DirectionalLightShadowPass {
 
         let begin_info = vk::RenderPassBeginInfo::builder()
             .render_pass(self.m_render_pass.m_framebuffer.render_pass)
-            .framebuffer(self.m_render_pass.m_framebuffer.framebuffer)
+            .framebuffer(self.m_layer_framebuffers[layer_index])
             .render_area(
                 vk::Rect2D::builder()
                     .offset(vk::Offset2D { x: 0, y: 0 })
                     .extent(vk::Extent2D {
-                        width: S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION,
-                        height: S_DIRECTIONAL_LIGHT_SHADOW_MAP_DIMENSION,
+                        width: self.m_map_dimension,
+                        height: self.m_map_dimension,
                     })
                     .build(),
             )
             .clear_values(&clear_values);
 
         rhi.cmd_begin_render_pass(command_buffer, &begin_info, vk::SubpassContents::INLINE);
-        rhi.push_event(command_buffer, "Directional Light Shadow\0", [1.0; 4]);
-
-        let pipeline = &self.m_render_pass.m_render_pipeline[0];
-        rhi.cmd_bind_pipeline(
-            command_buffer,
-            vk::PipelineBindPoint::GRAPHICS,
-            pipeline.pipeline,
-        );
+        rhi.push_event(command_buffer, self.m_source.event_name(), [1.0; 4]);
 
         let perframe_dynamic_offset = round_up(
             render_resource
@@ -499,8 +624,7 @@ impl DirectionalLightShadowPass {
             + std::mem::size_of::<MeshDirectionalLightShadowPerframeStorageBufferObject>() as u32;
         unsafe {
             std::ptr::copy_nonoverlapping(
-                &self.m_mesh_directional_light_shadow_perframe_storage_buffer_object as *const _
-                    as *const c_void,
+                &self.m_perframe_storage_buffer_objects[layer_index] as *const _ as *const c_void,
                 render_resource
                     ._storage_buffer
                     ._global_upload_ringbuffer_pointer
@@ -509,39 +633,53 @@ impl DirectionalLightShadowPass {
             );
         }
 
-        struct MeshNode<'a> {
-            model_matrix: &'a Matrix4x4,
-        }
-
-        let visiable_nodes = render_scene.get_directional_light_visible_mesh_nodes();
+        let visiable_nodes = self.m_source.visible_mesh_nodes(render_scene, layer_index);
+        let (opaque_nodes, masked_nodes) = split_masked_mesh_nodes(visiable_nodes);
 
-        let mut main_camera_mesh_drawcall_batch: HashMap<_, HashMap<_, Vec<_>>> = HashMap::new();
-
-        for node in visiable_nodes.iter() {
-            let mesh_instanced = main_camera_mesh_drawcall_batch
-                .entry(node.ref_material.as_ptr())
-                .or_default();
-            let mesh_nodes = mesh_instanced.entry(node.ref_mesh.as_ptr()).or_default();
+        for (pipeline, nodes) in self
+            .m_render_pass
+            .m_render_pipeline
+            .iter()
+            .zip([opaque_nodes, masked_nodes])
+        {
+            if nodes.is_empty() {
+                continue;
+            }
+            rhi.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline,
+            );
 
-            mesh_nodes.push(MeshNode {
-                model_matrix: &node.model_matrix,
-            });
-        }
+            for batch in mesh_node_batches(nodes) {
+                if batch[0].alpha_mode == MaterialAlphaMode::Mask {
+                    let ref_material = unsafe { &*batch[0].ref_material.as_ptr() };
+                    rhi.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        pipeline.layout,
+                        2,
+                        &[ref_material.material_descriptor_set],
+                        &[],
+                    );
+                }
 
-        for (_material, mesh_instanced) in &main_camera_mesh_drawcall_batch {
-            for (mesh, mesh_nodes) in mesh_instanced {
-                let ref_mesh = unsafe { &**mesh };
+                let ref_mesh = unsafe { &*batch[0].ref_mesh.as_ptr() };
 
                 rhi.cmd_bind_descriptor_sets(
                     command_buffer,
                     vk::PipelineBindPoint::GRAPHICS,
-                    self.m_render_pass.m_render_pipeline[0].layout,
+                    pipeline.layout,
                     1,
                     &[ref_mesh.mesh_vertex_blending_descriptor_set],
                     &[],
                 );
 
-                let buffers = [ref_mesh.mesh_vertex_position_buffer];
+                let buffers = [
+                    ref_mesh.mesh_vertex_position_buffer,
+                    ref_mesh.mesh_vertex_varying_enable_blending_buffer,
+                    ref_mesh.mesh_vertex_varying_buffer,
+                ];
 
                 rhi.cmd_bind_vertex_buffers(command_buffer, 0, &buffers, &[0, 0, 0]);
                 rhi.cmd_bind_index_buffer(
@@ -551,57 +689,20 @@ impl DirectionalLightShadowPass {
                     ref_mesh.mesh_index_type,
                 );
 
-                let instance_count = mesh_nodes.len();
-                let max_drawcall_instance = MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT;
-                let drawcall_count = instance_count.div_ceil(max_drawcall_instance);
-
-                for index in 0..drawcall_count {
-                    let current_count =
-                        max_drawcall_instance.min(instance_count - index * max_drawcall_instance);
-
-                    let mut object =
-                        MeshDirectionalLightShadowPerdrawcallStorageBufferObject::default();
-
-                    for i in 0..current_count {
-                        object.mesh_instances[i].model_matrix = mesh_nodes
-                            [index * max_drawcall_instance + i]
-                            .model_matrix
-                            .clone();
-                    }
-
-                    let perdrawcall_dynamic_offset = round_up(
-                        render_resource
-                            ._storage_buffer
-                            ._global_upload_ringbuffers_end[rhi.get_current_frame_index()],
-                        render_resource
-                            ._storage_buffer
-                            ._min_storage_buffer_offset_alignment,
-                    );
-                    render_resource
-                        ._storage_buffer
-                        ._global_upload_ringbuffers_end[rhi.get_current_frame_index()] =
-                        perdrawcall_dynamic_offset
-                            + std::mem::size_of::<
-                                MeshDirectionalLightShadowPerdrawcallStorageBufferObject,
-                            >() as u32;
-
-                    unsafe {
-                        std::ptr::copy_nonoverlapping(
-                            &object as *const _ as *const c_void,
-                            render_resource
-                                ._storage_buffer
-                                ._global_upload_ringbuffer_pointer
-                                .add(perdrawcall_dynamic_offset as usize),
-                            std::mem::size_of::<
-                                MeshDirectionalLightShadowPerdrawcallStorageBufferObject,
-                            >(),
-                        );
-                    }
+                let perdrawcall_dynamic_offsets = render_resource
+                    ._storage_buffer
+                    .upload_mesh_instances(rhi.get_current_frame_index(), batch);
+
+                for (index, perdrawcall_dynamic_offset) in
+                    perdrawcall_dynamic_offsets.into_iter().enumerate()
+                {
+                    let current_count = MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT
+                        .min(batch.len() - index * MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT);
 
                     rhi.cmd_bind_descriptor_sets(
                         command_buffer,
                         vk::PipelineBindPoint::GRAPHICS,
-                        self.m_render_pass.m_render_pipeline[0].layout,
+                        pipeline.layout,
                         0,
                         &[self.m_render_pass.m_descriptor_infos[0].descriptor_set],
                         &[perframe_dynamic_offset, perdrawcall_dynamic_offset, 0],
@@ -1,7 +1,10 @@
-use std::{collections::HashMap, os::raw::c_void, slice};
+use std::{
+    mem::{self, offset_of},
+    os::raw::c_void,
+    slice,
+};
 
 use crate::{
-    core::math::matrix4::Matrix4x4,
     function::{
         render::{
             interface::vulkan::vulkan_rhi::{
@@ -11,20 +14,33 @@ use crate::{
                 VulkanRHI,
             },
             render_common::{
-                MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT, MeshPerdrawcallStorageBufferObject,
+                MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT, MeshLightClusterStorageBufferObject,
+                MeshPerdrawcallStorageBufferObject,
                 MeshPerdrawcallVertexBlendingStorageBufferObject, MeshPerframeStorageBufferObject,
+                RenderMeshNode, SharedMeshLightClusterStorageBufferObject, VulkanClusteredLight,
+            },
+            render_graph::{
+                RenderGraphAttachmentLayout, RenderGraphAttachmentPlan, RenderGraphSubpassDesc,
             },
-            render_helper::round_up,
+            render_helper::{mesh_node_batches, round_up, split_masked_mesh_nodes},
             render_mesh::MeshVertex,
             render_pass::{
                 DescriptorLayout, DescriptorLayoutRegistry, RenderPass, RenderPipelineBase,
             },
-            render_pipeline::pbr_pipeline::{
-                color_grading_pass::{ColorGradingPass, ColorGradingPassInitInfo},
-                combine_ui_pass::{CombineUIPass, CombineUIPassInitInfo},
-                fxaa_pass::{FXAAPass, FXAAPassInitInfo},
-                tone_mapping_pass::{ToneMappingInitInfo, ToneMappingPass},
-                ui_pass::{UIPass, UIPassInitInfo},
+            render_pipeline::{
+                PostProcessPassType, ShaderFullscreenPassConfig,
+                pbr_pipeline::{
+                    bloom_pass::{BloomPass, BloomPassInitInfo},
+                    color_grading_pass::{ColorGradingPass, ColorGradingPassInitInfo},
+                    combine_ui_pass::{CombineUIPass, CombineUIPassInitInfo},
+                    fxaa_pass::{FXAAPass, FXAAPassInitInfo},
+                    msaa_resolve_pass::{MSAAResolvePass, MSAAResolvePassInitInfo},
+                    shader_fullscreen_pass::{ShaderFullscreenInitInfo, ShaderFullscreenPass},
+                    ssao_pass::{SSAOPass, SSAOPassInitInfo},
+                    taa_pass::{TAAPass, TAAPassInitInfo},
+                    tone_mapping_pass::{ToneMappingInitInfo, ToneMappingPass},
+                    ui_pass::{UIPass, UIPassInitInfo},
+                },
             },
             render_resource::{GlobalRenderResource, RenderResource},
             render_scene::RenderScene,
@@ -32,6 +48,9 @@ use crate::{
         },
         ui::ui2::UiRuntime,
     },
+    resource::res_type::data::render_pipeline::{
+        RenderPipelineNodeKind, ShaderFullscreenInputSource,
+    },
     shader::generated::shader::{
         DEFERRED_LIGHTING_FRAG, DEFERRED_LIGHTING_VERT, MESH_FRAG, MESH_GBUFFER_FRAG, MESH_VERT,
         SKYBOX_FRAG, SKYBOX_VERT,
@@ -49,29 +68,35 @@ pub const _MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD: usize = 3;
 pub const _MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN: usize = 4;
 pub const _MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_ODD: usize = 5;
 pub const _MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_EVEN: usize = 6;
-pub const _MAIN_CAMERA_PASS_DEPTH: usize = 7;
-pub const _MAIN_CAMERA_PASS_SWAPCHAIN_IMAGE: usize = 8;
+pub const _MAIN_CAMERA_PASS_TAA_RESOLVE: usize = 7;
+pub const _MAIN_CAMERA_PASS_VELOCITY: usize = 8;
+pub const _MAIN_CAMERA_PASS_DEPTH: usize = 9;
+pub const _MAIN_CAMERA_PASS_SWAPCHAIN_IMAGE: usize = 10;
 pub const _MAIN_CAMERA_PASS_CUSTOM_ATTACHMENT_COUNT: usize = 5;
 pub const _MAIN_CAMERA_PASS_POST_PROCESS_ATTACHMENT_COUNT: usize = 2;
-pub const _MAIN_CAMERA_PASS_ATTACHMENT_COUNT: usize = 9;
-
+pub const _MAIN_CAMERA_PASS_TEMPORAL_ATTACHMENT_COUNT: usize = 2;
+pub const _MAIN_CAMERA_PASS_ATTACHMENT_COUNT: usize = 11;
+/// The backup buffer the lighting writes the scene color to, and bloom adds onto.
+const _MAIN_CAMERA_PASS_LIGHTING_ATTACHMENT: usize = _MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD;
+
+/// The subpasses of the lighting render pass. The base pass runs in a render pass of its own
+/// before it, so SSAO can sample the GBuffer in between, and bloom samples the lit scene after it.
+/// The main render pass then runs the post process subpasses in the configured order, then UI
+/// and combine UI. Only the multisampled lighting render pass has the depth resolve subpass.
 pub enum MainCameraSubPass {
-    BasePass = 0,
-    DeferredLighting = 1,
-    ForwardLighting = 2,
-    ToneMapping = 3,
-    ColorGrading = 4,
-    FXAA = 5,
-    UI = 6,
-    CombineUI = 7,
-    EnumCount = 8,
+    DeferredLighting = 0,
+    ForwardLighting = 1,
+    DepthResolve = 2,
 }
 
 pub struct MainCameraPassInitInfo<'a> {
     pub rhi: &'a VulkanRHI,
-    pub enable_fxaa: bool,
+    pub post_process_passes: &'a [PostProcessPassType],
+    pub shader_passes: &'a [ShaderFullscreenPassConfig],
     pub global_render_resource: &'a GlobalRenderResource,
     pub descriptor_layout_manager: &'a DescriptorLayoutRegistry,
+    /// Above one sample the forward lighting draws to multisampled targets.
+    pub msaa_samples: vk::SampleCountFlags,
 }
 
 pub enum LayoutType {
@@ -87,69 +112,338 @@ pub enum LayoutType {
 
 enum RenderPipelineType {
     MeshGBuffer,
+    MeshGBufferAlphaTest,
     DeferredLighting,
     MeshLighting,
+    MeshLightingAlphaTest,
+    /// Blended meshes in the forward subpass, multisampled along with it.
+    MeshTransparent,
+    /// Blended meshes in the forward subpass of the deferred path.
+    DeferredMeshTransparent,
     SkyBox,
     Axis,
     Partical,
     RenderPipelineTypeCount,
 }
 
+/// The attachments images passed from subpass to subpass may be placed in; they share one format.
+const _MAIN_CAMERA_PASS_COLOR_ATTACHMENTS: [usize; 4] = [
+    _MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD,
+    _MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN,
+    _MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_ODD,
+    _MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_EVEN,
+];
+
+/// The builtin post passes are always created, so a disabled one still needs a subpass.
+const _MAIN_CAMERA_PASS_BUILTIN_POST_PROCESS_PASSES: [PostProcessPassType; 4] = [
+    PostProcessPassType::ToneMapping,
+    PostProcessPassType::ColorGrading,
+    PostProcessPassType::FXAA,
+    PostProcessPassType::TAA,
+];
+
+/// The subpasses of the main render pass for a given post pass order, declared by the images
+/// they read and write, and the attachment the render graph placed each image in. The scene color
+/// comes lit and bloomed into the backup buffer the lighting renders to. TAA always writes the
+/// resolve attachment, which is copied into its history once the render pass ends. When a builtin
+/// post pass is disabled one more subpass follows the configured ones; it reads the result and
+/// writes a scratch image nothing reads.
+struct MainCameraRenderGraph {
+    m_post_process_passes: Vec<PostProcessPassType>,
+    m_post_process_subpass_count: usize,
+    m_layout: RenderGraphAttachmentLayout,
+    m_plan: RenderGraphAttachmentPlan,
+    /// The images each post process subpass reads, in the order its pass binds them.
+    m_post_process_inputs: Vec<Vec<usize>>,
+    m_scene_color: usize,
+    m_ui_color: usize,
+}
+
+impl MainCameraRenderGraph {
+    fn new(
+        post_process_passes: &[PostProcessPassType],
+        shader_passes: &[ShaderFullscreenPassConfig],
+    ) -> Result<Self> {
+        let mut layout = RenderGraphAttachmentLayout::default();
+        let swapchain = layout.add_resource(&[_MAIN_CAMERA_PASS_SWAPCHAIN_IMAGE]);
+        let mut scene_color = layout.add_resource(&[_MAIN_CAMERA_PASS_LIGHTING_ATTACHMENT]);
+        // only there when a shader pass reads it, as every resource must be used
+        let mut depth = None;
+
+        let post_process_subpass_count = post_process_passes.len()
+            + if _MAIN_CAMERA_PASS_BUILTIN_POST_PROCESS_PASSES
+                .iter()
+                .all(|pass| post_process_passes.contains(pass))
+            {
+                0
+            } else {
+                1
+            };
+        let post_process_inputs = (0..post_process_subpass_count)
+            .map(|slot| {
+                let pass = post_process_passes.get(slot);
+                // a left out TAA still builds its pipeline in the parking subpass, where its
+                // shader loads the depth as the second input attachment
+                let reads_depth_for_taa = match pass {
+                    Some(pass) => *pass == PostProcessPassType::TAA,
+                    None => !post_process_passes.contains(&PostProcessPassType::TAA),
+                };
+                let inputs = match pass {
+                    Some(PostProcessPassType::ShaderFullscreen(index)) => shader_passes[*index]
+                        .inputs
+                        .iter()
+                        .map(|input| match input.source {
+                            ShaderFullscreenInputSource::SceneColor => scene_color,
+                            ShaderFullscreenInputSource::Depth => *depth.get_or_insert_with(|| {
+                                layout.add_resource(&[_MAIN_CAMERA_PASS_DEPTH])
+                            }),
+                        })
+                        .collect(),
+                    _ if reads_depth_for_taa => vec![
+                        scene_color,
+                        *depth
+                            .get_or_insert_with(|| layout.add_resource(&[_MAIN_CAMERA_PASS_DEPTH])),
+                    ],
+                    _ => vec![scene_color],
+                };
+                let output = if pass == Some(&PostProcessPassType::TAA) {
+                    layout.add_resource(&[_MAIN_CAMERA_PASS_TAA_RESOLVE])
+                } else {
+                    layout.add_resource(&_MAIN_CAMERA_PASS_COLOR_ATTACHMENTS)
+                };
+                // FXAA and TAA sample the scene color around the pixel; parked ones still do
+                let sampled_inputs = match pass {
+                    Some(PostProcessPassType::ShaderFullscreen(index)) => shader_passes[*index]
+                        .inputs
+                        .iter()
+                        .zip(&inputs)
+                        .filter(|(input, _)| input.sampled)
+                        .map(|(_, resource)| *resource)
+                        .collect(),
+                    Some(PostProcessPassType::FXAA | PostProcessPassType::TAA) | None => {
+                        vec![inputs[0]]
+                    }
+                    _ => Vec::new(),
+                };
+                if slot < post_process_passes.len() {
+                    scene_color = output;
+                }
+                layout.add_subpass(RenderGraphSubpassDesc {
+                    input_attachments: inputs.clone(),
+                    sampled_inputs,
+                    color_attachments: vec![output],
+                    ..Default::default()
+                });
+                inputs
+            })
+            .collect();
+        let ui_color = layout.add_resource(&_MAIN_CAMERA_PASS_COLOR_ATTACHMENTS);
+        layout.add_subpass(RenderGraphSubpassDesc {
+            color_attachments: vec![ui_color],
+            ..Default::default()
+        });
+        layout.add_subpass(RenderGraphSubpassDesc {
+            input_attachments: vec![scene_color, ui_color],
+            color_attachments: vec![swapchain],
+            ..Default::default()
+        });
+
+        let plan = layout.allocate()?;
+        Ok(Self {
+            m_post_process_passes: post_process_passes.to_vec(),
+            m_post_process_subpass_count: post_process_subpass_count,
+            m_layout: layout,
+            m_plan: plan,
+            m_post_process_inputs: post_process_inputs,
+            m_scene_color: scene_color,
+            m_ui_color: ui_color,
+        })
+    }
+
+    /// Disabled passes are parked in the first unused subpass, where they never draw.
+    fn slot(&self, pass: PostProcessPassType) -> usize {
+        self.m_post_process_passes
+            .iter()
+            .position(|p| *p == pass)
+            .unwrap_or(self.m_post_process_passes.len())
+    }
+
+    fn subpass(&self, pass: PostProcessPassType) -> u32 {
+        self.slot(pass) as u32
+    }
+
+    fn input_attachment(&self, pass: PostProcessPassType) -> usize {
+        self.m_plan.resource_attachments[self.m_post_process_inputs[self.slot(pass)][0]]
+    }
+
+    fn input_attachments(&self, pass: PostProcessPassType) -> Vec<usize> {
+        self.m_post_process_inputs[self.slot(pass)]
+            .iter()
+            .map(|resource| self.m_plan.resource_attachments[*resource])
+            .collect()
+    }
+
+    fn ui_subpass(&self) -> u32 {
+        self.m_post_process_subpass_count as u32
+    }
+
+    fn combine_ui_subpass(&self) -> u32 {
+        self.ui_subpass() + 1
+    }
+
+    fn scene_attachment(&self) -> usize {
+        self.m_plan.resource_attachments[self.m_scene_color]
+    }
+
+    fn ui_attachment(&self) -> usize {
+        self.m_plan.resource_attachments[self.m_ui_color]
+    }
+
+    /// The node each subpass runs, by subpass index. Unused post process subpasses run none.
+    fn subpass_schedule(&self) -> Vec<Option<RenderPipelineNodeKind>> {
+        let mut schedule: Vec<_> = (0..self.m_post_process_subpass_count)
+            .map(|slot| {
+                self.m_post_process_passes
+                    .get(slot)
+                    .map(|pass| pass.node_kind())
+            })
+            .collect();
+        schedule.extend([
+            Some(RenderPipelineNodeKind::UI),
+            Some(RenderPipelineNodeKind::CombineUI),
+        ]);
+        schedule
+    }
+}
+
 #[derive(Default)]
 pub struct MainCameraPass {
     pub m_directional_light_shadow_color_image_view: vk::ImageView,
     pub m_point_light_shadow_color_image_view: vk::ImageView,
+    pub m_spot_light_shadow_color_image_view: vk::ImageView,
     m_render_pass: RenderPass,
-    m_enable_fxaa: bool,
+    /// The base pass alone, writing the GBuffer, the emissive light and the depth the lighting loads.
+    m_gbuffer_render_pass: vk::RenderPass,
+    m_gbuffer_framebuffer: vk::Framebuffer,
+    /// The deferred and forward lighting, writing the scene color bloom and the main render pass
+    /// read.
+    m_lighting_render_pass: vk::RenderPass,
+    m_lighting_framebuffer: vk::Framebuffer,
+    /// The lighting render pass the forward path uses with MSAA on, resolving into the same
+    /// attachments.
+    m_msaa_lighting_render_pass: vk::RenderPass,
+    m_msaa_lighting_framebuffer: vk::Framebuffer,
+    m_msaa_samples: vk::SampleCountFlags,
+    m_post_process_passes: Vec<PostProcessPassType>,
+    m_shader_fullscreen_configs: Vec<ShaderFullscreenPassConfig>,
+    m_pending_post_process_passes:
+        Option<(Vec<PostProcessPassType>, Vec<ShaderFullscreenPassConfig>)>,
+    m_subpass_schedule: Vec<Option<RenderPipelineNodeKind>>,
     m_mesh_perframe_storage_buffer_object: MeshPerframeStorageBufferObject,
+    m_mesh_light_cluster_storage_buffer_object: SharedMeshLightClusterStorageBufferObject,
     m_swapchain_framebuffers: Vec<vk::Framebuffer>,
 
+    m_ssao_pass: SSAOPass,
+    m_bloom_pass: BloomPass,
     m_tone_mapping_pass: ToneMappingPass,
     m_color_grading_pass: ColorGradingPass,
     m_fxaa_pass: FXAAPass,
+    m_taa_pass: TAAPass,
+    m_msaa_resolve_pass: MSAAResolvePass,
     m_ui_pass: UIPass,
     m_combine_ui_pass: CombineUIPass,
+    m_shader_fullscreen_passes: Vec<ShaderFullscreenPass>,
 }
 
 impl MainCameraPass {
     pub fn initialize(&mut self, info: &MainCameraPassInitInfo) -> Result<()> {
-        self.m_enable_fxaa = info.enable_fxaa;
+        self.m_post_process_passes = info.post_process_passes.to_vec();
+        self.m_shader_fullscreen_configs = info.shader_passes.to_vec();
+        self.m_subpass_schedule = self.render_graph()?.subpass_schedule();
+        self.m_msaa_samples = info.msaa_samples;
         let rhi = info.rhi;
         let descriptor_layout_manager = info.descriptor_layout_manager;
         self.setup_attachments(rhi)?;
+        self.setup_gbuffer_render_pass(rhi)?;
+        self.setup_lighting_render_pass(rhi)?;
         self.setup_render_pass(rhi)?;
+        self.m_ssao_pass.initialize(&SSAOPassInitInfo {
+            rhi,
+            descriptor_layout_manager,
+            gbuffer_normal_attachment: self.m_render_pass.m_framebuffer.attachments
+                [_MAIN_CAMERA_PASS_GBUFFER_A]
+                .view,
+        })?;
+        self.m_bloom_pass.initialize(&BloomPassInitInfo {
+            rhi,
+            descriptor_layout_manager,
+            scene_color_attachment: self.m_render_pass.m_framebuffer.attachments
+                [_MAIN_CAMERA_PASS_LIGHTING_ATTACHMENT]
+                .view,
+        })?;
+        if self.is_multisampled() {
+            self.m_msaa_resolve_pass
+                .initialize(&MSAAResolvePassInitInfo {
+                    render_pass: self.m_msaa_lighting_render_pass,
+                    subpass: MainCameraSubPass::DepthResolve as u32,
+                    rhi,
+                    descriptor_layout_manager,
+                    samples: self.m_msaa_samples,
+                    color_formats: [
+                        self.m_render_pass.m_framebuffer.attachments
+                            [_MAIN_CAMERA_PASS_LIGHTING_ATTACHMENT]
+                            .format,
+                        self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_VELOCITY]
+                            .format,
+                    ],
+                })?;
+        }
         self.setup_descriptor_layout(rhi, descriptor_layout_manager)?;
         self.setup_pipelines(rhi)?;
         self.setup_descriptor_set(rhi, info.global_render_resource)?;
         self.setup_framebuffer_descriptor_set(rhi)?;
         self.setup_framebuffer(rhi)?;
 
+        let render_graph = self.render_graph()?;
+        let image_views = self.m_render_pass.get_framebuffer_image_views();
+
         self.m_tone_mapping_pass.initialize(&ToneMappingInitInfo {
             render_pass: *self.m_render_pass.get_render_pass(),
+            subpass: render_graph.subpass(PostProcessPassType::ToneMapping),
             descriptor_layout_manager: descriptor_layout_manager,
             rhi: rhi,
             global_render_resource: info.global_render_resource,
-            input_attachment: self.m_render_pass.get_framebuffer_image_views()
-                [_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD],
+            input_attachment: image_views
+                [render_graph.input_attachment(PostProcessPassType::ToneMapping)],
         })?;
 
         self.m_color_grading_pass
             .initialize(&ColorGradingPassInitInfo {
                 render_pass: *self.m_render_pass.get_render_pass(),
+                subpass: render_graph.subpass(PostProcessPassType::ColorGrading),
                 descriptor_layout_manager: descriptor_layout_manager,
                 rhi: rhi,
                 global_render_resource: info.global_render_resource,
-                input_attachment: self.m_render_pass.get_framebuffer_image_views()
-                    [_MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN],
+                input_attachment: image_views
+                    [render_graph.input_attachment(PostProcessPassType::ColorGrading)],
             })?;
 
         self.m_fxaa_pass.initialize(&FXAAPassInitInfo {
             render_pass: *self.m_render_pass.get_render_pass(),
+            subpass: render_graph.subpass(PostProcessPassType::FXAA),
             descriptor_layout_manager: descriptor_layout_manager,
             rhi: rhi,
             global_render_resource: info.global_render_resource,
-            input_attachment: self.m_render_pass.get_framebuffer_image_views()
-                [_MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_ODD],
+            input_attachment: image_views[render_graph.input_attachment(PostProcessPassType::FXAA)],
+        })?;
+
+        self.m_taa_pass.initialize(&TAAPassInitInfo {
+            render_pass: *self.m_render_pass.get_render_pass(),
+            subpass: render_graph.subpass(PostProcessPassType::TAA),
+            descriptor_layout_manager,
+            rhi,
+            input_attachment: image_views[render_graph.input_attachment(PostProcessPassType::TAA)],
+            velocity_attachment: image_views[_MAIN_CAMERA_PASS_VELOCITY],
         })?;
 
         self.m_ui_pass.initialize(&UIPassInitInfo {
@@ -157,6 +451,7 @@ impl MainCameraPass {
             descriptor_layout_manager: descriptor_layout_manager,
             global_render_resource: info.global_render_resource,
             render_pass: *self.m_render_pass.get_render_pass(),
+            subpass: render_graph.ui_subpass(),
         })?;
 
         self.m_combine_ui_pass.initialize(&CombineUIPassInitInfo {
@@ -164,12 +459,13 @@ impl MainCameraPass {
             descriptor_layout_manager: descriptor_layout_manager,
             global_render_resource: info.global_render_resource,
             render_pass: *self.m_render_pass.get_render_pass(),
-            scene_input_attachment: self.m_render_pass.get_framebuffer_image_views()
-                [_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD],
-            ui_input_attachment: self.m_render_pass.get_framebuffer_image_views()
-                [_MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN],
+            subpass: render_graph.combine_ui_subpass(),
+            scene_input_attachment: image_views[render_graph.scene_attachment()],
+            ui_input_attachment: image_views[render_graph.ui_attachment()],
         })?;
 
+        self.setup_shader_fullscreen_passes(rhi, &render_graph)?;
+
         Ok(())
     }
 
@@ -177,6 +473,28 @@ impl MainCameraPass {
         self.m_mesh_perframe_storage_buffer_object = render_resource
             .m_mesh_perframe_storage_buffer_object
             .clone();
+        // the cluster data is large, so it is shared rather than copied
+        self.m_mesh_light_cluster_storage_buffer_object = render_resource
+            .m_mesh_light_cluster_storage_buffer_object
+            .clone();
+        self.m_taa_pass.prepare_pass_data();
+    }
+
+    /// Reorders or disables post passes. The render pass is rebuilt for the new order by the
+    /// next `recreate_after_swapchain`; until then the current order keeps drawing.
+    pub fn set_post_process_passes(
+        &mut self,
+        post_process_passes: &[PostProcessPassType],
+        shader_passes: &[ShaderFullscreenPassConfig],
+    ) {
+        if self.m_post_process_passes != post_process_passes
+            || self.m_shader_fullscreen_configs != shader_passes
+        {
+            self.m_pending_post_process_passes =
+                Some((post_process_passes.to_vec(), shader_passes.to_vec()));
+        } else {
+            self.m_pending_post_process_passes = None;
+        }
     }
 
     pub fn recreate_after_swapchain(
@@ -196,44 +514,194 @@ impl MainCameraPass {
         for framebuffer in self.m_swapchain_framebuffers.drain(..) {
             rhi.destroy_framebuffer(framebuffer);
         }
+        // taken, so a rebuild that fails before recreating them does not destroy them twice
+        rhi.destroy_framebuffer(mem::take(&mut self.m_gbuffer_framebuffer));
+        rhi.destroy_framebuffer(mem::take(&mut self.m_lighting_framebuffer));
         self.setup_attachments(rhi)?;
+        if self.is_multisampled() {
+            rhi.destroy_framebuffer(mem::take(&mut self.m_msaa_lighting_framebuffer));
+            self.m_msaa_resolve_pass
+                .update_after_framebuffer_recreate(rhi)?;
+        }
+        self.m_ssao_pass.update_after_framebuffer_recreate(
+            rhi,
+            self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_GBUFFER_A].view,
+        )?;
+        self.m_bloom_pass.update_after_framebuffer_recreate(
+            rhi,
+            self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_LIGHTING_ATTACHMENT]
+                .view,
+        )?;
+        if let Some((post_process_passes, shader_passes)) =
+            self.m_pending_post_process_passes.take()
+        {
+            self.m_post_process_passes = post_process_passes;
+            self.m_shader_fullscreen_configs = shader_passes;
+            self.m_subpass_schedule = self.render_graph()?.subpass_schedule();
+            self.recreate_render_pass(rhi)?;
+        }
         self.setup_framebuffer_descriptor_set(rhi)?;
         self.setup_framebuffer(rhi)?;
 
+        let render_graph = self.render_graph()?;
         let image_views = self.m_render_pass.get_framebuffer_image_views();
-        self.m_tone_mapping_pass
-            .update_after_framebuffer_recreate(
-                rhi,
-                image_views[_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD],
-            )
-            .unwrap();
+        self.m_tone_mapping_pass.update_after_framebuffer_recreate(
+            rhi,
+            image_views[render_graph.input_attachment(PostProcessPassType::ToneMapping)],
+        )?;
         self.m_color_grading_pass
             .update_after_framebuffer_recreate(
                 rhi,
                 resource,
-                image_views[_MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN],
-            )
-            .unwrap();
-        self.m_fxaa_pass
-            .update_after_framebuffer_recreate(
+                image_views[render_graph.input_attachment(PostProcessPassType::ColorGrading)],
+            )?;
+        self.m_fxaa_pass.update_after_framebuffer_recreate(
+            rhi,
+            image_views[render_graph.input_attachment(PostProcessPassType::FXAA)],
+        )?;
+        self.m_taa_pass.update_after_framebuffer_recreate(
+            rhi,
+            image_views[render_graph.input_attachment(PostProcessPassType::TAA)],
+            image_views[_MAIN_CAMERA_PASS_VELOCITY],
+        )?;
+        self.m_combine_ui_pass.update_after_framebuffer_recreate(
+            rhi,
+            image_views[render_graph.scene_attachment()],
+            image_views[render_graph.ui_attachment()],
+        )?;
+        for (index, shader_pass) in self.m_shader_fullscreen_passes.iter_mut().enumerate() {
+            let input_attachments = render_graph
+                .input_attachments(PostProcessPassType::ShaderFullscreen(index))
+                .into_iter()
+                .map(|attachment| Self::attachment_view(rhi, &image_views, attachment))
+                .collect::<Vec<_>>();
+            shader_pass.update_after_framebuffer_recreate(rhi, &input_attachments)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the render pass for a new post pass order, along with every pipeline created
+    /// against it. The caller has already waited for the device to go idle.
+    fn recreate_render_pass(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        for pipeline in self.m_render_pass.m_render_pipeline.drain(..) {
+            rhi.destroy_pipeline(pipeline.pipeline);
+            rhi.destroy_pipeline_layout(pipeline.layout);
+        }
+        rhi.destroy_render_pass(mem::take(&mut self.m_render_pass.m_framebuffer.render_pass));
+        self.setup_render_pass(rhi)?;
+        self.setup_pipelines(rhi)?;
+
+        let render_pass = self.m_render_pass.m_framebuffer.render_pass;
+        let render_graph = self.render_graph()?;
+        self.m_tone_mapping_pass.update_after_render_pass_recreate(
+            rhi,
+            render_pass,
+            render_graph.subpass(PostProcessPassType::ToneMapping),
+        )?;
+        self.m_color_grading_pass
+            .update_after_render_pass_recreate(
                 rhi,
-                image_views[_MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_ODD],
-            )
-            .unwrap();
-        self.m_combine_ui_pass
-            .update_after_framebuffer_recreate(
+                render_pass,
+                render_graph.subpass(PostProcessPassType::ColorGrading),
+            )?;
+        self.m_fxaa_pass.update_after_render_pass_recreate(
+            rhi,
+            render_pass,
+            render_graph.subpass(PostProcessPassType::FXAA),
+        )?;
+        self.m_taa_pass.update_after_render_pass_recreate(
+            rhi,
+            render_pass,
+            render_graph.subpass(PostProcessPassType::TAA),
+        )?;
+        self.m_ui_pass.update_after_render_pass_recreate(
+            rhi,
+            render_pass,
+            render_graph.ui_subpass(),
+        )?;
+        self.m_combine_ui_pass.update_after_render_pass_recreate(
+            rhi,
+            render_pass,
+            render_graph.combine_ui_subpass(),
+        )?;
+
+        // the shader passes may have changed along with the order, so they are built anew
+        for shader_pass in self.m_shader_fullscreen_passes.drain(..) {
+            shader_pass.destroy(rhi)?;
+        }
+        self.setup_shader_fullscreen_passes(rhi, &render_graph)?;
+        Ok(())
+    }
+
+    fn render_graph(&self) -> Result<MainCameraRenderGraph> {
+        MainCameraRenderGraph::new(
+            &self.m_post_process_passes,
+            &self.m_shader_fullscreen_configs,
+        )
+    }
+
+    /// The depth image belongs to the rhi, not to this pass's framebuffer attachments.
+    fn attachment_view(
+        rhi: &VulkanRHI,
+        image_views: &[vk::ImageView],
+        attachment: usize,
+    ) -> vk::ImageView {
+        if attachment == _MAIN_CAMERA_PASS_DEPTH {
+            *rhi.get_depth_image_info().image_view
+        } else {
+            image_views[attachment]
+        }
+    }
+
+    fn setup_shader_fullscreen_passes(
+        &mut self,
+        rhi: &VulkanRHI,
+        render_graph: &MainCameraRenderGraph,
+    ) -> Result<()> {
+        let image_views = self.m_render_pass.get_framebuffer_image_views();
+        for (index, config) in self.m_shader_fullscreen_configs.iter().enumerate() {
+            let pass = PostProcessPassType::ShaderFullscreen(index);
+            let input_attachments = render_graph
+                .input_attachments(pass)
+                .into_iter()
+                .map(|attachment| Self::attachment_view(rhi, &image_views, attachment))
+                .collect::<Vec<_>>();
+            let mut shader_pass = ShaderFullscreenPass::default();
+            let initialized = shader_pass.initialize(&ShaderFullscreenInitInfo {
+                render_pass: *self.m_render_pass.get_render_pass(),
+                subpass: render_graph.subpass(pass),
                 rhi,
-                image_views[_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD],
-                image_views[_MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN],
-            )
-            .unwrap();
+                config,
+                input_attachments: &input_attachments,
+            });
+            if let Err(err) = initialized {
+                shader_pass.destroy(rhi)?;
+                return Err(err.context(format!("shader pass '{}'", config.name)));
+            }
+            self.m_shader_fullscreen_passes.push(shader_pass);
+        }
         Ok(())
     }
 
     pub fn destroy(&self, rhi: &VulkanRHI) {
+        for shader_pass in &self.m_shader_fullscreen_passes {
+            shader_pass.destroy(rhi).unwrap();
+        }
+        self.m_ssao_pass.destroy(rhi);
+        self.m_bloom_pass.destroy(rhi);
+        self.m_taa_pass.destroy(rhi);
         self.m_swapchain_framebuffers
             .iter()
             .for_each(|f| rhi.destroy_framebuffer(*f));
+        rhi.destroy_framebuffer(self.m_gbuffer_framebuffer);
+        rhi.destroy_render_pass(self.m_gbuffer_render_pass);
+        rhi.destroy_framebuffer(self.m_lighting_framebuffer);
+        rhi.destroy_render_pass(self.m_lighting_render_pass);
+        if self.is_multisampled() {
+            self.m_msaa_resolve_pass.destroy(rhi);
+            rhi.destroy_framebuffer(self.m_msaa_lighting_framebuffer);
+            rhi.destroy_render_pass(self.m_msaa_lighting_render_pass);
+        }
         rhi.destroy_pipeline(self.m_render_pass.m_render_pipeline[0].pipeline);
         rhi.destroy_pipeline_layout(self.m_render_pass.m_render_pipeline[0].layout);
         // rhi.destroy_descriptor_set_layout(self.m_base.m_descriptor_infos[0].layout);
@@ -255,13 +723,43 @@ impl MainCameraPass {
             .offset(vk::Offset2D::default())
             .extent(swapchain_info.extent);
 
+        // uploaded once and shared by every draw that binds the mesh global set
+        let light_cluster_dynamic_offset = self.upload_light_cluster(rhi, render_resource);
+
+        // the forward path only needs the cleared depth
+        self.draw_gbuffer_pass(
+            rhi,
+            render_scene,
+            render_resource,
+            light_cluster_dynamic_offset,
+            forward_draw,
+        )?;
+        if !forward_draw {
+            self.m_ssao_pass.draw(
+                rhi,
+                render_scene.m_ambient_occlusion.as_ref(),
+                &self.m_mesh_perframe_storage_buffer_object,
+            );
+        }
+        self.draw_lighting_pass(
+            rhi,
+            render_scene,
+            render_resource,
+            light_cluster_dynamic_offset,
+            forward_draw,
+        )?;
+        if let Some(bloom) = &render_scene.m_bloom {
+            self.m_bloom_pass.draw(rhi, bloom);
+        }
+        let taa_enabled = self
+            .m_subpass_schedule
+            .contains(&Some(RenderPipelineNodeKind::TAA));
+        if taa_enabled {
+            self.m_taa_pass.prepare_history(rhi);
+        }
+
+        // the lit scene is loaded, so only the other attachments clear
         let mut clear_values = [vk::ClearValue::default(); _MAIN_CAMERA_PASS_ATTACHMENT_COUNT];
-        clear_values[_MAIN_CAMERA_PASS_GBUFFER_A].color.float32 = [0.0, 0.0, 0.0, 0.0];
-        clear_values[_MAIN_CAMERA_PASS_GBUFFER_B].color.float32 = [0.0, 0.0, 0.0, 0.0];
-        clear_values[_MAIN_CAMERA_PASS_GBUFFER_C].color.float32 = [0.0, 0.0, 0.0, 0.0];
-        clear_values[_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD]
-            .color
-            .float32 = [0.0, 0.0, 0.0, 0.0];
         clear_values[_MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN]
             .color
             .float32 = [0.0, 0.0, 0.0, 0.0];
@@ -271,14 +769,19 @@ impl MainCameraPass {
         clear_values[_MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_EVEN]
             .color
             .float32 = [0.0, 0.0, 0.0, 1.0];
-        clear_values[_MAIN_CAMERA_PASS_DEPTH].depth_stencil = vk::ClearDepthStencilValue {
-            depth: 1.0,
-            stencil: 0,
-        };
         clear_values[_MAIN_CAMERA_PASS_SWAPCHAIN_IMAGE]
             .color
             .float32 = [0.0, 0.0, 0.0, 0.0];
 
+        // the history is reprojected from where the camera saw it last frame, without the jitter
+        let reprojection_matrix = self
+            .m_mesh_perframe_storage_buffer_object
+            .previous_unjittered_proj_view_matrix
+            * self
+                .m_mesh_perframe_storage_buffer_object
+                .unjittered_proj_view_matrix
+                .inverse();
+
         let current_swapchain_image_index = rhi.get_current_swapchain_image_index();
 
         let info = vk::RenderPassBeginInfo::builder()
@@ -289,44 +792,214 @@ impl MainCameraPass {
 
         rhi.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
 
-        if forward_draw {
-            rhi.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+        for (subpass, node) in self.m_subpass_schedule.iter().enumerate() {
+            if subpass > 0 {
+                rhi.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+            }
+            match node {
+                Some(RenderPipelineNodeKind::ToneMapping) => self.m_tone_mapping_pass.draw(rhi),
+                Some(RenderPipelineNodeKind::ColorGrading) => self.m_color_grading_pass.draw(rhi),
+                Some(RenderPipelineNodeKind::FXAA) => self.m_fxaa_pass.draw(rhi),
+                Some(RenderPipelineNodeKind::TAA) => {
+                    self.m_taa_pass
+                        .draw(rhi, &render_scene.m_temporal_aa, &reprojection_matrix)
+                }
+                Some(RenderPipelineNodeKind::ShaderFullscreen) => {
+                    if let PostProcessPassType::ShaderFullscreen(index) =
+                        self.m_post_process_passes[subpass]
+                    {
+                        self.m_shader_fullscreen_passes[index].draw(rhi);
+                    }
+                }
+                Some(RenderPipelineNodeKind::UI) => self.draw_ui(rhi, ui_runtime),
+                Some(RenderPipelineNodeKind::CombineUI) => self.m_combine_ui_pass.draw(rhi),
+                _ => {}
+            }
+        }
 
-            rhi.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+        rhi.cmd_end_render_pass(command_buffer);
 
-            rhi.push_event(command_buffer, "Forward Lighting\0", [1.0; 4]);
-            self.draw_mesh_lighting(rhi, render_scene, render_resource)?;
-            self.draw_skybox(rhi, render_resource)?;
-            rhi.pop_event(command_buffer);
-        } else {
+        if taa_enabled {
+            self.m_taa_pass.store_history(
+                rhi,
+                self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_TAA_RESOLVE].image,
+            );
+        }
+
+        Ok(())
+    }
+
+    fn draw_gbuffer_pass(
+        &self,
+        rhi: &VulkanRHI,
+        render_scene: &RenderScene,
+        render_resource: &mut GlobalRenderResource,
+        light_cluster_dynamic_offset: u32,
+        forward_draw: bool,
+    ) -> Result<()> {
+        let command_buffer = rhi.get_current_command_buffer();
+
+        let render_area = vk::Rect2D::builder()
+            .offset(vk::Offset2D::default())
+            .extent(rhi.get_swapchain_info().extent);
+
+        let mut clear_values = [vk::ClearValue::default(); 6];
+        clear_values[_MAIN_CAMERA_PASS_GBUFFER_A].color.float32 = [0.0, 0.0, 0.0, 0.0];
+        clear_values[_MAIN_CAMERA_PASS_GBUFFER_B].color.float32 = [0.0, 0.0, 0.0, 0.0];
+        clear_values[_MAIN_CAMERA_PASS_GBUFFER_C].color.float32 = [0.0, 0.0, 0.0, 0.0];
+        clear_values[3].color.float32 = [0.0, 0.0, 0.0, 1.0];
+        clear_values[4].color.float32 = [0.0, 0.0, 0.0, 0.0];
+        clear_values[5].depth_stencil = vk::ClearDepthStencilValue {
+            depth: 1.0,
+            stencil: 0,
+        };
+
+        let info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.m_gbuffer_render_pass)
+            .framebuffer(self.m_gbuffer_framebuffer)
+            .render_area(render_area)
+            .clear_values(&clear_values);
+
+        rhi.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+        if !forward_draw {
             rhi.push_event(command_buffer, "BasePass\0", [1.0; 4]);
-            self.draw_mesh_gbuffer(rhi, render_scene, render_resource)?;
+            self.draw_mesh_gbuffer(
+                rhi,
+                render_scene,
+                render_resource,
+                light_cluster_dynamic_offset,
+            )?;
             rhi.pop_event(command_buffer);
+        }
+        rhi.cmd_end_render_pass(command_buffer);
+
+        Ok(())
+    }
+
+    /// The lighting path decides which of the lighting subpasses draw.
+    fn draw_lighting_pass(
+        &self,
+        rhi: &VulkanRHI,
+        render_scene: &RenderScene,
+        render_resource: &mut GlobalRenderResource,
+        light_cluster_dynamic_offset: u32,
+        forward_draw: bool,
+    ) -> Result<()> {
+        if forward_draw && self.is_multisampled() {
+            return self.draw_msaa_lighting_pass(
+                rhi,
+                render_scene,
+                render_resource,
+                light_cluster_dynamic_offset,
+            );
+        }
+
+        let command_buffer = rhi.get_current_command_buffer();
+
+        let render_area = vk::Rect2D::builder()
+            .offset(vk::Offset2D::default())
+            .extent(rhi.get_swapchain_info().extent);
 
-            rhi.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+        let info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.m_lighting_render_pass)
+            .framebuffer(self.m_lighting_framebuffer)
+            .render_area(render_area);
 
+        rhi.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+        if !forward_draw {
             rhi.push_event(command_buffer, "DeferredLighting\0", [1.0; 4]);
-            self.draw_deferred_lighting(rhi, render_resource)?;
+            self.draw_deferred_lighting(rhi, render_resource, light_cluster_dynamic_offset)?;
             rhi.pop_event(command_buffer);
-
-            rhi.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
         }
-
         rhi.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+        if forward_draw {
+            rhi.push_event(command_buffer, "Forward Lighting\0", [1.0; 4]);
+            self.draw_mesh_lighting(
+                rhi,
+                render_scene,
+                render_resource,
+                light_cluster_dynamic_offset,
+            )?;
+            self.draw_skybox(rhi, render_resource)?;
+            rhi.pop_event(command_buffer);
+        }
+        rhi.push_event(command_buffer, "Transparent\0", [1.0; 4]);
+        self.draw_mesh_transparent(
+            rhi,
+            render_scene,
+            render_resource,
+            light_cluster_dynamic_offset,
+            if forward_draw {
+                RenderPipelineType::MeshTransparent
+            } else {
+                RenderPipelineType::DeferredMeshTransparent
+            },
+        )?;
+        rhi.pop_event(command_buffer);
+        rhi.cmd_end_render_pass(command_buffer);
+
+        Ok(())
+    }
 
-        self.m_tone_mapping_pass.draw(rhi);
+    /// The forward lighting into the multisampled targets, then the depth resolve.
+    fn draw_msaa_lighting_pass(
+        &self,
+        rhi: &VulkanRHI,
+        render_scene: &RenderScene,
+        render_resource: &mut GlobalRenderResource,
+        light_cluster_dynamic_offset: u32,
+    ) -> Result<()> {
+        let command_buffer = rhi.get_current_command_buffer();
 
-        rhi.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+        let render_area = vk::Rect2D::builder()
+            .offset(vk::Offset2D::default())
+            .extent(rhi.get_swapchain_info().extent);
+
+        // only the multisampled targets clear, the resolved ones are overwritten
+        let mut clear_values = [vk::ClearValue::default(); 9];
+        clear_values[6].color.float32 = [0.0, 0.0, 0.0, 1.0];
+        clear_values[7].color.float32 = [0.0, 0.0, 0.0, 0.0];
+        clear_values[8].depth_stencil = vk::ClearDepthStencilValue {
+            depth: 1.0,
+            stencil: 0,
+        };
 
-        self.m_color_grading_pass.draw(rhi);
+        let info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.m_msaa_lighting_render_pass)
+            .framebuffer(self.m_msaa_lighting_framebuffer)
+            .render_area(render_area)
+            .clear_values(&clear_values);
 
+        rhi.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
         rhi.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+        rhi.push_event(command_buffer, "Forward Lighting\0", [1.0; 4]);
+        self.draw_mesh_lighting(
+            rhi,
+            render_scene,
+            render_resource,
+            light_cluster_dynamic_offset,
+        )?;
+        self.draw_skybox(rhi, render_resource)?;
+        rhi.pop_event(command_buffer);
+        rhi.push_event(command_buffer, "Transparent\0", [1.0; 4]);
+        self.draw_mesh_transparent(
+            rhi,
+            render_scene,
+            render_resource,
+            light_cluster_dynamic_offset,
+            RenderPipelineType::MeshTransparent,
+        )?;
+        rhi.pop_event(command_buffer);
+        rhi.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+        self.m_msaa_resolve_pass.draw(rhi);
+        rhi.cmd_end_render_pass(command_buffer);
 
-        if self.m_enable_fxaa {
-            self.m_fxaa_pass.draw(rhi);
-        }
+        Ok(())
+    }
 
-        rhi.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+    fn draw_ui(&self, rhi: &VulkanRHI, ui_runtime: &UiRuntime) {
+        let command_buffer = rhi.get_current_command_buffer();
+        let swapchain_info = rhi.get_swapchain_info();
 
         let mut clear_value = vk::ClearValue::default();
         clear_value.color.float32 = [0.0, 0.0, 0.0, 0.0];
@@ -348,14 +1021,6 @@ impl MainCameraPass {
         rhi.cmd_clear_attachments(command_buffer, &clear_attachments, &clear_rects);
 
         self.m_ui_pass.draw(rhi, ui_runtime);
-
-        rhi.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
-
-        self.m_combine_ui_pass.draw(rhi);
-
-        rhi.cmd_end_render_pass(command_buffer);
-
-        Ok(())
     }
 }
 
@@ -364,9 +1029,9 @@ static STORAGE_BUFFER_COUNT: u32 = 1;
 #[distributed_slice(VULKAN_RHI_DESCRIPTOR_UNIFORM_BUFFER)]
 static UNIFORM_BUFFER_COUNT: u32 = vulkan_rhi::MAX_MATERIAL_COUNT;
 #[distributed_slice(VULKAN_RHI_DESCRIPTOR_STORAGE_BUFFER_DYNAMIC)]
-static STORAGE_BUFFER_DYNAMIC_COUNT: u32 = 3 + 1;
+static STORAGE_BUFFER_DYNAMIC_COUNT: u32 = 4 + 1;
 #[distributed_slice(VULKAN_RHI_DESCRIPTOR_COMBINED_IMAGE_SAMPLER)]
-static COMBINED_IMAGE_SAMPLER_COUNT: u32 = 5 + 5 * vulkan_rhi::MAX_MATERIAL_COUNT + 1;
+static COMBINED_IMAGE_SAMPLER_COUNT: u32 = 6 + 5 * vulkan_rhi::MAX_MATERIAL_COUNT + 2;
 #[distributed_slice(VULKAN_RHI_DESCRIPTOR_INPUT_ATTACHMENT)]
 static INPUT_ATTACHMENT_COUNT: u32 = 4;
 
@@ -441,6 +1106,18 @@ impl DescriptorLayout for MeshGlobalDescriptorLayout {
                 .descriptor_count(1)
                 .stage_flags(vk::ShaderStageFlags::FRAGMENT)
                 .build(),
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(8)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(9)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
         ];
         let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
             .bindings(&mesh_global_layout_bindings)
@@ -508,7 +1185,7 @@ impl DescriptorLayout for SkyboxDescriptorLayout {
                 .binding(0)
                 .descriptor_type(vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
                 .descriptor_count(1)
-                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
                 .build(),
             vk::DescriptorSetLayoutBinding::builder()
                 .binding(1)
@@ -555,6 +1232,12 @@ impl DescriptorLayout for DeferredLightingDescriptorLayout {
                 .descriptor_count(1)
                 .stage_flags(vk::ShaderStageFlags::FRAGMENT)
                 .build(),
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(4)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
         ];
 
         let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
@@ -571,7 +1254,8 @@ impl MainCameraPass {
         let swapchain_info = rhi.get_swapchain_info();
 
         let attachment_count = _MAIN_CAMERA_PASS_CUSTOM_ATTACHMENT_COUNT
-            + _MAIN_CAMERA_PASS_POST_PROCESS_ATTACHMENT_COUNT;
+            + _MAIN_CAMERA_PASS_POST_PROCESS_ATTACHMENT_COUNT
+            + _MAIN_CAMERA_PASS_TEMPORAL_ATTACHMENT_COUNT;
 
         self.m_render_pass
             .m_framebuffer
@@ -592,19 +1276,38 @@ impl MainCameraPass {
             .format = vk::Format::R16G16B16A16_SFLOAT;
         self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_EVEN]
             .format = vk::Format::R16G16B16A16_SFLOAT;
+        self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_TAA_RESOLVE].format =
+            vk::Format::R16G16B16A16_SFLOAT;
+        self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_VELOCITY].format =
+            vk::Format::R16G16_SFLOAT;
         for buffer_index in 0..attachment_count {
+            // any post process subpass may feed FXAA or TAA, which sample their input, and SSAO
+            // samples the GBuffer normals. The TAA resolve is copied into the history, and TAA
+            // samples the velocity
             let usage = match buffer_index {
-                _MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_ODD
+                _MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD
+                | _MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN
+                | _MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_ODD
                 | _MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_EVEN => {
                     vk::ImageUsageFlags::COLOR_ATTACHMENT
                         | vk::ImageUsageFlags::INPUT_ATTACHMENT
                         | vk::ImageUsageFlags::SAMPLED
                 }
-                _ => {
+                _MAIN_CAMERA_PASS_GBUFFER_A => {
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT
+                        | vk::ImageUsageFlags::INPUT_ATTACHMENT
+                        | vk::ImageUsageFlags::SAMPLED
+                }
+                _MAIN_CAMERA_PASS_TAA_RESOLVE => {
                     vk::ImageUsageFlags::COLOR_ATTACHMENT
                         | vk::ImageUsageFlags::INPUT_ATTACHMENT
-                        | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT
+                        | vk::ImageUsageFlags::SAMPLED
+                        | vk::ImageUsageFlags::TRANSFER_SRC
+                }
+                _MAIN_CAMERA_PASS_VELOCITY => {
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED
                 }
+                _ => vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::INPUT_ATTACHMENT,
             };
             (
                 self.m_render_pass.m_framebuffer.attachments[buffer_index].image,
@@ -637,16 +1340,17 @@ impl MainCameraPass {
         let mut attachments =
             [vk::AttachmentDescription::default(); _MAIN_CAMERA_PASS_ATTACHMENT_COUNT];
 
+        // the GBuffer is done with by now, and stays unused
         attachments[_MAIN_CAMERA_PASS_GBUFFER_A] = vk::AttachmentDescription::builder()
             .format(
                 self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_GBUFFER_A].format,
             )
             .samples(vk::SampleCountFlags::_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .initial_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .build();
 
@@ -655,11 +1359,11 @@ impl MainCameraPass {
                 self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_GBUFFER_B].format,
             )
             .samples(vk::SampleCountFlags::_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
             .store_op(vk::AttachmentStoreOp::DONT_CARE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .initial_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .build();
 
@@ -668,11 +1372,11 @@ impl MainCameraPass {
                 self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_GBUFFER_C].format,
             )
             .samples(vk::SampleCountFlags::_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
             .store_op(vk::AttachmentStoreOp::DONT_CARE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .initial_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .build();
 
@@ -682,11 +1386,11 @@ impl MainCameraPass {
                     .format,
             )
             .samples(vk::SampleCountFlags::_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .load_op(vk::AttachmentLoadOp::LOAD)
             .store_op(vk::AttachmentStoreOp::DONT_CARE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .initial_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .build();
 
@@ -736,14 +1440,40 @@ impl MainCameraPass {
                 .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
                 .build();
 
+        // left for the copy into the TAA history once the render pass ends
+        attachments[_MAIN_CAMERA_PASS_TAA_RESOLVE] = vk::AttachmentDescription::builder()
+            .format(
+                self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_TAA_RESOLVE].format,
+            )
+            .samples(vk::SampleCountFlags::_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .build();
+
+        // written by the base pass or the forward lighting, TAA only samples it
+        attachments[_MAIN_CAMERA_PASS_VELOCITY] = vk::AttachmentDescription::builder()
+            .format(self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_VELOCITY].format)
+            .samples(vk::SampleCountFlags::_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .build();
+
         attachments[_MAIN_CAMERA_PASS_DEPTH] = vk::AttachmentDescription::builder()
             .format(rhi.get_depth_image_info().format)
             .samples(vk::SampleCountFlags::_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .load_op(vk::AttachmentLoadOp::LOAD)
             .store_op(vk::AttachmentStoreOp::STORE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .initial_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
             .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
             .build();
 
@@ -757,318 +1487,535 @@ impl MainCameraPass {
             .initial_layout(vk::ImageLayout::UNDEFINED)
             .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
             .build();
-        let mut subpasses =
-            [vk::SubpassDescription::default(); MainCameraSubPass::EnumCount as usize];
 
-        //gbuffer subpass
-        let depth_stencil_attachment_ref = vk::AttachmentReference::builder()
-            .attachment(_MAIN_CAMERA_PASS_DEPTH as u32)
-            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
-        let color_attachment_refs = [
-            vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_GBUFFER_A as u32)
-                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-                .build(),
-            vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_GBUFFER_B as u32)
-                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-                .build(),
-            vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_GBUFFER_C as u32)
-                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-                .build(),
-        ];
-        subpasses[MainCameraSubPass::BasePass as usize] = vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_refs)
-            .depth_stencil_attachment(&depth_stencil_attachment_ref)
-            .build();
+        // the render graph places every image and works out what each subpass waits for
+        let render_graph = self.render_graph()?;
+        let attachment_refs = |resources: &[usize], layout: vk::ImageLayout| {
+            resources
+                .iter()
+                .map(|resource| {
+                    vk::AttachmentReference::builder()
+                        .attachment(render_graph.m_plan.resource_attachments[*resource] as u32)
+                        .layout(layout)
+                        .build()
+                })
+                .collect::<Vec<_>>()
+        };
+        let subpass_descs = &render_graph.m_layout.subpasses;
+        let input_attachment_refs: Vec<_> = subpass_descs
+            .iter()
+            .map(|desc| {
+                attachment_refs(
+                    &desc.input_attachments,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                )
+            })
+            .collect();
+        let color_attachment_refs: Vec<_> = subpass_descs
+            .iter()
+            .map(|desc| {
+                attachment_refs(
+                    &desc.color_attachments,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                )
+            })
+            .collect();
+        let depth_stencil_attachment_refs: Vec<_> = subpass_descs
+            .iter()
+            .map(|desc| {
+                attachment_refs(
+                    desc.depth_attachment.as_slice(),
+                    vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                )
+            })
+            .collect();
+        let subpasses: Vec<_> = (0..subpass_descs.len())
+            .map(|subpass| {
+                let mut description = vk::SubpassDescription::builder()
+                    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                    .color_attachments(&color_attachment_refs[subpass])
+                    .input_attachments(&input_attachment_refs[subpass])
+                    .preserve_attachments(&render_graph.m_plan.preserve_attachments[subpass]);
+                if let Some(depth_stencil_attachment_ref) =
+                    depth_stencil_attachment_refs[subpass].first()
+                {
+                    description =
+                        description.depth_stencil_attachment(depth_stencil_attachment_ref);
+                }
+                description.build()
+            })
+            .collect();
 
-        //deferred lighting subpass
-        let input_attachment_refs = [
-            vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_GBUFFER_A as u32)
-                .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                .build(),
-            vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_GBUFFER_B as u32)
-                .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                .build(),
-            vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_GBUFFER_C as u32)
-                .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                .build(),
-            vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_DEPTH as u32)
-                .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        // the scene color and the depth are written before this pass
+        let mut dependencies = vec![
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                )
+                .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .src_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )
+                .dst_access_mask(
+                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::INPUT_ATTACHMENT_READ,
+                )
                 .build(),
         ];
-        let color_attachment_refs = [vk::AttachmentReference::builder()
-            .attachment(_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD as u32)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .build()];
-        subpasses[MainCameraSubPass::DeferredLighting as usize] = vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_refs)
-            .input_attachments(&input_attachment_refs)
-            .build();
-
-        //forward lighting subpass
-        let color_attachment_refs = [vk::AttachmentReference::builder()
-            .attachment(_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD as u32)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .build()];
-        let depth_stencil_attachment_ref = vk::AttachmentReference::builder()
-            .attachment(_MAIN_CAMERA_PASS_DEPTH as u32)
-            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
-        subpasses[MainCameraSubPass::ForwardLighting as usize] = vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_refs)
-            .depth_stencil_attachment(&depth_stencil_attachment_ref)
-            .build();
-
-        //tone mapping subpass
-        let input_attachment_ref = [vk::AttachmentReference::builder()
-            .attachment(_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD as u32)
-            .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)];
-
-        let color_attachment_ref = [vk::AttachmentReference::builder()
-            .attachment(_MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN as u32)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
-
-        subpasses[MainCameraSubPass::ToneMapping as usize] = vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_ref)
-            .input_attachments(&input_attachment_ref)
-            .build();
+        dependencies.extend_from_slice(&render_graph.m_plan.dependencies);
+        // the TAA resolve is copied into the history after the last subpass
+        dependencies.push(
+            vk::SubpassDependency::builder()
+                .src_subpass(subpass_descs.len() as u32 - 1)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                )
+                .dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+                .build(),
+        );
 
-        //color grading subpass
-        let input_attachment_ref = [vk::AttachmentReference::builder()
-            .attachment(_MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN as u32)
-            .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)];
+        let info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies);
 
-        let color_attachment_ref = if self.m_enable_fxaa {
-            [vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_ODD as u32)
-                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)]
-        } else {
-            [vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD as u32)
-                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)]
-        };
+        self.m_render_pass.m_framebuffer.render_pass = rhi.create_render_pass(&info)?;
 
-        subpasses[MainCameraSubPass::ColorGrading as usize] = vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_ref)
-            .input_attachments(&input_attachment_ref)
-            .build();
+        Ok(())
+    }
 
-        //fxaa subpass
-        let input_attachment_ref = if self.m_enable_fxaa {
-            [vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_POST_PROCESS_BUFFER_ODD as u32)
-                .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)]
-        } else {
-            [vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN as u32)
-                .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)]
+    fn setup_gbuffer_render_pass(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let gbuffer_attachment = |index: usize| {
+            vk::AttachmentDescription::builder()
+                .format(self.m_render_pass.m_framebuffer.attachments[index].format)
+                .samples(vk::SampleCountFlags::_1)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .build()
         };
+        // the emissive light goes straight to the scene color, which the lighting adds onto, and
+        // the velocity stays an attachment for the forward subpass to draw over. The depth is
+        // left read only for SSAO, the lighting turns it back into an attachment for the forward
+        // subpass
+        let attachments = [
+            gbuffer_attachment(_MAIN_CAMERA_PASS_GBUFFER_A),
+            gbuffer_attachment(_MAIN_CAMERA_PASS_GBUFFER_B),
+            gbuffer_attachment(_MAIN_CAMERA_PASS_GBUFFER_C),
+            vk::AttachmentDescription::builder()
+                .format(
+                    self.m_render_pass.m_framebuffer.attachments
+                        [_MAIN_CAMERA_PASS_LIGHTING_ATTACHMENT]
+                        .format,
+                )
+                .samples(vk::SampleCountFlags::_1)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .build(),
+            vk::AttachmentDescription::builder()
+                .format(
+                    self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_VELOCITY].format,
+                )
+                .samples(vk::SampleCountFlags::_1)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .build(),
+            vk::AttachmentDescription::builder()
+                .format(rhi.get_depth_image_info().format)
+                .samples(vk::SampleCountFlags::_1)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+                .build(),
+        ];
 
-        let color_attachment_ref = [vk::AttachmentReference::builder()
-            .attachment(_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD as u32)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .build()];
+        let color_attachment_refs = [0, 1, 2, 3, 4].map(|attachment| {
+            vk::AttachmentReference::builder()
+                .attachment(attachment)
+                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .build()
+        });
+        let depth_attachment_ref = vk::AttachmentReference::builder()
+            .attachment(5)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
 
-        subpasses[MainCameraSubPass::FXAA as usize] = vk::SubpassDescription::builder()
+        let subpasses = [vk::SubpassDescription::builder()
             .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_ref)
-            .input_attachments(&input_attachment_ref)
-            .build();
-
-        //ui subpass
-        let color_attachment_ref = [vk::AttachmentReference::builder()
-            .attachment(_MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN as u32)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .color_attachments(&color_attachment_refs)
+            .depth_stencil_attachment(&depth_attachment_ref)
             .build()];
-        let preserve_attachments = [_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD as u32];
-        subpasses[MainCameraSubPass::UI as usize] = vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_ref)
-            .preserve_attachments(&preserve_attachments)
-            .build();
 
-        //combine ui subpass
-        let input_attachment_ref = [
-            vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_BACKUP_BUFFER_ODD as u32)
-                .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
-            vk::AttachmentReference::builder()
-                .attachment(_MAIN_CAMERA_PASS_BACKUP_BUFFER_EVEN as u32)
-                .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
-        ];
-        let color_attachment_ref = [vk::AttachmentReference::builder()
-            .attachment(_MAIN_CAMERA_PASS_SWAPCHAIN_IMAGE as u32)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .build()];
-        subpasses[MainCameraSubPass::CombineUI as usize] = vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_ref)
-            .input_attachments(&input_attachment_ref)
-            .build();
+        // the last frame still reads the GBuffer, the scene color, the velocity and the depth, then
+        // SSAO and the lighting read what this pass writes
         let dependencies = [
             vk::SubpassDependency::builder()
                 .src_subpass(vk::SUBPASS_EXTERNAL)
-                .dst_subpass(MainCameraSubPass::BasePass as u32)
-                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-                .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
-                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-                .dst_access_mask(vk::AccessFlags::SHADER_READ)
-                .build(),
-            vk::SubpassDependency::builder()
-                .src_subpass(MainCameraSubPass::BasePass as u32)
-                .dst_subpass(MainCameraSubPass::DeferredLighting as u32)
+                .dst_subpass(0)
                 .src_stage_mask(
-                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
                 )
                 .dst_stage_mask(
                     vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
-                )
-                .src_access_mask(
-                    vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                        | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
                 )
+                .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
                 .dst_access_mask(
-                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::COLOR_ATTACHMENT_READ,
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
                 )
-                .dependency_flags(vk::DependencyFlags::BY_REGION)
                 .build(),
             vk::SubpassDependency::builder()
-                .src_subpass(MainCameraSubPass::DeferredLighting as u32)
-                .dst_subpass(MainCameraSubPass::ForwardLighting as u32)
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
                 .src_stage_mask(
                     vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
                 )
                 .dst_stage_mask(
-                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
                 )
                 .src_access_mask(
-                    vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
                 )
                 .dst_access_mask(
-                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::COLOR_ATTACHMENT_READ,
+                    vk::AccessFlags::SHADER_READ
+                        | vk::AccessFlags::INPUT_ATTACHMENT_READ
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
                 )
-                .dependency_flags(vk::DependencyFlags::BY_REGION)
                 .build(),
-            vk::SubpassDependency::builder()
-                .src_subpass(MainCameraSubPass::ForwardLighting as u32)
-                .dst_subpass(MainCameraSubPass::ToneMapping as u32)
-                .src_stage_mask(
-                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
-                )
-                .dst_stage_mask(
-                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
-                )
-                .src_access_mask(
-                    vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        ];
+
+        let info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies);
+
+        self.m_gbuffer_render_pass = rhi.create_render_pass(&info)?;
+
+        Ok(())
+    }
+
+    /// Shares the attachments of the base pass: the deferred subpass reads the GBuffer and adds
+    /// onto the scene color, the forward subpass draws over it with depth.
+    fn setup_lighting_render_pass(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let gbuffer_attachment = |index: usize| {
+            vk::AttachmentDescription::builder()
+                .format(self.m_render_pass.m_framebuffer.attachments[index].format)
+                .samples(vk::SampleCountFlags::_1)
+                .load_op(vk::AttachmentLoadOp::LOAD)
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .build()
+        };
+        let attachments = [
+            gbuffer_attachment(_MAIN_CAMERA_PASS_GBUFFER_A),
+            gbuffer_attachment(_MAIN_CAMERA_PASS_GBUFFER_B),
+            gbuffer_attachment(_MAIN_CAMERA_PASS_GBUFFER_C),
+            vk::AttachmentDescription::builder()
+                .format(
+                    self.m_render_pass.m_framebuffer.attachments
+                        [_MAIN_CAMERA_PASS_LIGHTING_ATTACHMENT]
+                        .format,
                 )
-                .dst_access_mask(
-                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::COLOR_ATTACHMENT_READ,
+                .samples(vk::SampleCountFlags::_1)
+                .load_op(vk::AttachmentLoadOp::LOAD)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .build(),
+            vk::AttachmentDescription::builder()
+                .format(
+                    self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_VELOCITY].format,
                 )
-                .dependency_flags(vk::DependencyFlags::BY_REGION)
+                .samples(vk::SampleCountFlags::_1)
+                .load_op(vk::AttachmentLoadOp::LOAD)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .build(),
+            vk::AttachmentDescription::builder()
+                .format(rhi.get_depth_image_info().format)
+                .samples(vk::SampleCountFlags::_1)
+                .load_op(vk::AttachmentLoadOp::LOAD)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+                .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                .build(),
+        ];
+
+        let input_attachment_refs = [0, 1, 2, 5].map(|attachment| {
+            vk::AttachmentReference::builder()
+                .attachment(attachment)
+                .layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .build()
+        });
+        let deferred_color_attachment_refs = [vk::AttachmentReference::builder()
+            .attachment(3)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
+        // the forward meshes write their velocity over what the base pass left
+        let forward_color_attachment_refs = [3, 4].map(|attachment| {
+            vk::AttachmentReference::builder()
+                .attachment(attachment)
+                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .build()
+        });
+        let preserve_attachments = [4];
+        let depth_attachment_ref = vk::AttachmentReference::builder()
+            .attachment(5)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+        let subpasses = [
+            vk::SubpassDescription::builder()
+                .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                .input_attachments(&input_attachment_refs)
+                .color_attachments(&deferred_color_attachment_refs)
+                .preserve_attachments(&preserve_attachments)
                 .build(),
+            vk::SubpassDescription::builder()
+                .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                .color_attachments(&forward_color_attachment_refs)
+                .depth_stencil_attachment(&depth_attachment_ref)
+                .build(),
+        ];
+
+        let dependencies = [
+            // the shadow maps, the GBuffer, the emissive light, the depth and the SSAO are all
+            // written before this pass
             vk::SubpassDependency::builder()
-                .src_subpass(MainCameraSubPass::ToneMapping as u32)
-                .dst_subpass(MainCameraSubPass::ColorGrading as u32)
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(MainCameraSubPass::DeferredLighting as u32)
                 .src_stage_mask(
                     vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
                 )
                 .dst_stage_mask(
-                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
                 )
                 .src_access_mask(
-                    vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
                 )
                 .dst_access_mask(
-                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::COLOR_ATTACHMENT_READ,
+                    vk::AccessFlags::SHADER_READ
+                        | vk::AccessFlags::INPUT_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
                 )
-                .dependency_flags(vk::DependencyFlags::BY_REGION)
                 .build(),
+            // the forward subpass writes the depth the deferred one read, and draws over its
+            // color
             vk::SubpassDependency::builder()
-                .src_subpass(MainCameraSubPass::ColorGrading as u32)
-                .dst_subpass(MainCameraSubPass::FXAA as u32)
+                .src_subpass(MainCameraSubPass::DeferredLighting as u32)
+                .dst_subpass(MainCameraSubPass::ForwardLighting as u32)
                 .src_stage_mask(
-                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
                 )
                 .dst_stage_mask(
-                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
-                )
-                .src_access_mask(
-                    vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
                 )
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
                 .dst_access_mask(
-                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::COLOR_ATTACHMENT_READ,
+                    vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+                        | vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
                 )
                 .dependency_flags(vk::DependencyFlags::BY_REGION)
                 .build(),
+            // bloom and the main render pass read the scene color, the velocity and the depth
             vk::SubpassDependency::builder()
-                .src_subpass(MainCameraSubPass::FXAA as u32)
-                .dst_subpass(MainCameraSubPass::UI as u32)
+                .src_subpass(MainCameraSubPass::ForwardLighting as u32)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
                 .src_stage_mask(
                     vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
                 )
                 .dst_stage_mask(
-                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
                 )
                 .src_access_mask(
-                    vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
                 )
                 .dst_access_mask(
-                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::COLOR_ATTACHMENT_READ,
+                    vk::AccessFlags::SHADER_READ
+                        | vk::AccessFlags::INPUT_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
                 )
-                .dependency_flags(vk::DependencyFlags::BY_REGION)
                 .build(),
+        ];
+
+        let info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies);
+
+        self.m_lighting_render_pass = rhi.create_render_pass(&info)?;
+
+        if !self.is_multisampled() {
+            return Ok(());
+        }
+
+        // the forward subpass draws to the multisampled targets and resolves the color and the
+        // velocity, the depth is resolved by a subpass of its own
+        let msaa_attachment = |format, final_layout| {
+            vk::AttachmentDescription::builder()
+                .format(format)
+                .samples(self.m_msaa_samples)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(final_layout)
+                .build()
+        };
+        let msaa_attachments = [
+            attachments[0],
+            attachments[1],
+            attachments[2],
+            attachments[3],
+            attachments[4],
+            attachments[5],
+            msaa_attachment(
+                attachments[3].format,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ),
+            msaa_attachment(
+                attachments[4].format,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ),
+            msaa_attachment(
+                attachments[5].format,
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ),
+        ];
+
+        let msaa_color_attachment_refs = [6, 7].map(|attachment| {
+            vk::AttachmentReference::builder()
+                .attachment(attachment)
+                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .build()
+        });
+        let msaa_depth_attachment_ref = vk::AttachmentReference::builder()
+            .attachment(8)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let msaa_depth_input_attachment_refs = [vk::AttachmentReference::builder()
+            .attachment(8)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+            .build()];
+        let resolved_preserve_attachments = [3, 4];
+
+        let msaa_subpasses = [
+            subpasses[0],
+            vk::SubpassDescription::builder()
+                .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                .color_attachments(&msaa_color_attachment_refs)
+                .resolve_attachments(&forward_color_attachment_refs)
+                .depth_stencil_attachment(&msaa_depth_attachment_ref)
+                .build(),
+            vk::SubpassDescription::builder()
+                .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                .input_attachments(&msaa_depth_input_attachment_refs)
+                .depth_stencil_attachment(&depth_attachment_ref)
+                .preserve_attachments(&resolved_preserve_attachments)
+                .build(),
+        ];
+
+        let msaa_dependencies = [
+            dependencies[0],
+            dependencies[1],
+            // the depth resolve reads the samples the forward subpass wrote
             vk::SubpassDependency::builder()
-                .src_subpass(MainCameraSubPass::UI as u32)
-                .dst_subpass(MainCameraSubPass::CombineUI as u32)
-                .src_stage_mask(
-                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
-                )
+                .src_subpass(MainCameraSubPass::ForwardLighting as u32)
+                .dst_subpass(MainCameraSubPass::DepthResolve as u32)
+                .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
                 .dst_stage_mask(
-                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
-                )
-                .src_access_mask(
-                    vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
                 )
+                .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
                 .dst_access_mask(
-                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::COLOR_ATTACHMENT_READ,
+                    vk::AccessFlags::INPUT_ATTACHMENT_READ
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
                 )
                 .dependency_flags(vk::DependencyFlags::BY_REGION)
                 .build(),
+            // the resolved color and velocity are written by the forward subpass, the resolved
+            // depth by the last one
+            dependencies[2],
+            vk::SubpassDependency {
+                src_subpass: MainCameraSubPass::DepthResolve as u32,
+                ..dependencies[2]
+            },
         ];
 
         let info = vk::RenderPassCreateInfo::builder()
-            .attachments(&attachments)
-            .subpasses(&subpasses)
-            .dependencies(&dependencies);
+            .attachments(&msaa_attachments)
+            .subpasses(&msaa_subpasses)
+            .dependencies(&msaa_dependencies);
 
-        self.m_render_pass.m_framebuffer.render_pass = rhi.create_render_pass(&info)?;
+        self.m_msaa_lighting_render_pass = rhi.create_render_pass(&info)?;
 
         Ok(())
     }
 
+    fn is_multisampled(&self) -> bool {
+        self.m_msaa_samples != vk::SampleCountFlags::_1
+    }
+
+    /// The render pass the forward pipelines are created against.
+    fn forward_lighting_render_pass(&self) -> vk::RenderPass {
+        if self.is_multisampled() {
+            self.m_msaa_lighting_render_pass
+        } else {
+            self.m_lighting_render_pass
+        }
+    }
+
     fn setup_framebuffer(&mut self, rhi: &VulkanRHI) -> Result<()> {
         let swapchain_info = rhi.get_swapchain_info();
         let depth_image_info = rhi.get_depth_image_info();
@@ -1096,6 +2043,43 @@ impl MainCameraPass {
             })
             .collect::<Result<Vec<_>, _>>()?;
         self.m_swapchain_framebuffers = framebuffers;
+
+        // the base pass and the lighting use the same attachments
+        let attachments = [
+            self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_GBUFFER_A].view,
+            self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_GBUFFER_B].view,
+            self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_GBUFFER_C].view,
+            self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_LIGHTING_ATTACHMENT]
+                .view,
+            self.m_render_pass.m_framebuffer.attachments[_MAIN_CAMERA_PASS_VELOCITY].view,
+            *depth_image_info.image_view,
+        ];
+        let create_framebuffer = |render_pass| {
+            let create_info = vk::FramebufferCreateInfo::builder()
+                .render_pass(render_pass)
+                .attachments(&attachments)
+                .width(swapchain_info.extent.width)
+                .height(swapchain_info.extent.height)
+                .layers(1);
+            rhi.create_framebuffer(&create_info)
+        };
+        self.m_gbuffer_framebuffer = create_framebuffer(self.m_gbuffer_render_pass)?;
+        self.m_lighting_framebuffer = create_framebuffer(self.m_lighting_render_pass)?;
+
+        if self.is_multisampled() {
+            let attachments = [
+                attachments.as_slice(),
+                &self.m_msaa_resolve_pass.get_attachment_views(),
+            ]
+            .concat();
+            let create_info = vk::FramebufferCreateInfo::builder()
+                .render_pass(self.m_msaa_lighting_render_pass)
+                .attachments(&attachments)
+                .width(swapchain_info.extent.width)
+                .height(swapchain_info.extent.height)
+                .layers(1);
+            self.m_msaa_lighting_framebuffer = rhi.create_framebuffer(&create_info)?;
+        }
         Ok(())
     }
 
@@ -1155,12 +2139,32 @@ impl MainCameraPass {
             let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
                 .stage(vk::ShaderStageFlags::VERTEX)
                 .module(vert_shader_module)
-                .name(b"main\0");
+                .name(b"main\0")
+                .build();
 
             let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
                 .stage(vk::ShaderStageFlags::FRAGMENT)
                 .module(frag_shader_module)
-                .name(b"main\0");
+                .name(b"main\0")
+                .build();
+
+            // constant 0 turns on the alpha test of the fragment shader
+            let alpha_test_map_entries = [vk::SpecializationMapEntry::builder()
+                .constant_id(0)
+                .offset(0)
+                .size(std::mem::size_of::<vk::Bool32>())
+                .build()];
+            let alpha_test_data = vk::TRUE.to_ne_bytes();
+            let alpha_test_specialization_info = vk::SpecializationInfo::builder()
+                .map_entries(&alpha_test_map_entries)
+                .data(&alpha_test_data);
+
+            let alpha_test_frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag_shader_module)
+                .name(b"main\0")
+                .specialization_info(&alpha_test_specialization_info)
+                .build();
 
             let binding_descriptions = &MeshVertex::get_binding_descriptions();
             let attribute_descriptions = &MeshVertex::get_attribute_descriptions();
@@ -1211,6 +2215,14 @@ impl MainCameraPass {
                     .color_write_mask(vk::ColorComponentFlags::all())
                     .blend_enable(false)
                     .build(),
+                vk::PipelineColorBlendAttachmentState::builder()
+                    .color_write_mask(vk::ColorComponentFlags::all())
+                    .blend_enable(false)
+                    .build(),
+                vk::PipelineColorBlendAttachmentState::builder()
+                    .color_write_mask(vk::ColorComponentFlags::all())
+                    .blend_enable(false)
+                    .build(),
             ];
             let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
                 .logic_op_enable(false)
@@ -1228,34 +2240,43 @@ impl MainCameraPass {
             ];
             let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(set_layouts);
 
-            let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
-
-            let stages = &[vert_stage, frag_stage];
-            let info = vk::GraphicsPipelineCreateInfo::builder()
-                .stages(stages)
-                .vertex_input_state(&vertex_input_state)
-                .input_assembly_state(&input_assembly_state)
-                .viewport_state(&viewport_state)
-                .rasterization_state(&rasterization_state)
-                .multisample_state(&multisample_state)
-                .depth_stencil_state(&depth_stencil_state)
-                .color_blend_state(&color_blend_state)
-                .dynamic_state(&dynamic_state)
-                .layout(pipeline_layout)
-                .render_pass(self.m_render_pass.m_framebuffer.render_pass)
-                .subpass(MainCameraSubPass::BasePass as u32)
-                .build();
-
-            let pipeline = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
-
-            rhi.destroy_shader_module(vert_shader_module);
-            rhi.destroy_shader_module(frag_shader_module);
-
-            self.m_render_pass.m_render_pipeline[RenderPipelineType::MeshGBuffer as usize] =
-                RenderPipelineBase {
+            // masked meshes draw with the alpha-tested variant
+            let variants = [
+                (RenderPipelineType::MeshGBuffer, [vert_stage, frag_stage]),
+                (
+                    RenderPipelineType::MeshGBufferAlphaTest,
+                    [vert_stage, alpha_test_frag_stage],
+                ),
+            ];
+            for (pipeline_type, stages) in variants {
+                let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
+
+                let info = vk::GraphicsPipelineCreateInfo::builder()
+                    .stages(&stages)
+                    .vertex_input_state(&vertex_input_state)
+                    .input_assembly_state(&input_assembly_state)
+                    .viewport_state(&viewport_state)
+                    .rasterization_state(&rasterization_state)
+                    .multisample_state(&multisample_state)
+                    .depth_stencil_state(&depth_stencil_state)
+                    .color_blend_state(&color_blend_state)
+                    .dynamic_state(&dynamic_state)
+                    .layout(pipeline_layout)
+                    .render_pass(self.m_gbuffer_render_pass)
+                    .subpass(0)
+                    .build();
+
+                let pipeline =
+                    rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
+
+                self.m_render_pass.m_render_pipeline[pipeline_type as usize] = RenderPipelineBase {
                     layout: pipeline_layout,
                     pipeline,
                 };
+            }
+
+            rhi.destroy_shader_module(vert_shader_module);
+            rhi.destroy_shader_module(frag_shader_module);
         }
 
         // deferred lighting
@@ -1303,9 +2324,17 @@ impl MainCameraPass {
                     .depth_compare_op(vk::CompareOp::ALWAYS)
                     .stencil_test_enable(false);
 
+            // adds onto the emissive light of the base pass, which the fog fades by the source
+            // alpha; the scene color keeps its own alpha
             let attachment = vk::PipelineColorBlendAttachmentState::builder()
                 .color_write_mask(vk::ColorComponentFlags::all())
-                .blend_enable(false);
+                .blend_enable(true)
+                .src_color_blend_factor(vk::BlendFactor::ONE)
+                .dst_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+                .color_blend_op(vk::BlendOp::ADD)
+                .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+                .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+                .alpha_blend_op(vk::BlendOp::ADD);
 
             let attachments = &[attachment];
             let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
@@ -1338,7 +2367,7 @@ impl MainCameraPass {
                 .color_blend_state(&color_blend_state)
                 .dynamic_state(&dynamic_state)
                 .layout(pipeline_layout)
-                .render_pass(self.m_render_pass.m_framebuffer.render_pass)
+                .render_pass(self.m_lighting_render_pass)
                 .subpass(MainCameraSubPass::DeferredLighting as u32)
                 .build();
 
@@ -1362,12 +2391,32 @@ impl MainCameraPass {
             let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
                 .stage(vk::ShaderStageFlags::VERTEX)
                 .module(vert_shader_module)
-                .name(b"main\0");
+                .name(b"main\0")
+                .build();
 
             let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
                 .stage(vk::ShaderStageFlags::FRAGMENT)
                 .module(frag_shader_module)
-                .name(b"main\0");
+                .name(b"main\0")
+                .build();
+
+            // constant 0 turns on the alpha test of the fragment shader
+            let alpha_test_map_entries = [vk::SpecializationMapEntry::builder()
+                .constant_id(0)
+                .offset(0)
+                .size(std::mem::size_of::<vk::Bool32>())
+                .build()];
+            let alpha_test_data = vk::TRUE.to_ne_bytes();
+            let alpha_test_specialization_info = vk::SpecializationInfo::builder()
+                .map_entries(&alpha_test_map_entries)
+                .data(&alpha_test_data);
+
+            let alpha_test_frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag_shader_module)
+                .name(b"main\0")
+                .specialization_info(&alpha_test_specialization_info)
+                .build();
 
             let binding_descriptions = &MeshVertex::get_binding_descriptions();
             let attribute_descriptions = &MeshVertex::get_attribute_descriptions();
@@ -1396,19 +2445,147 @@ impl MainCameraPass {
 
             let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
                 .sample_shading_enable(false)
-                .rasterization_samples(vk::SampleCountFlags::_1);
+                .rasterization_samples(self.m_msaa_samples);
+
+            let depth_stencil_state: vk::PipelineDepthStencilStateCreateInfoBuilder =
+                vk::PipelineDepthStencilStateCreateInfo::builder()
+                    .depth_test_enable(true)
+                    .depth_write_enable(true)
+                    .depth_compare_op(vk::CompareOp::LESS)
+                    .stencil_test_enable(false);
+
+            let attachments = [
+                vk::PipelineColorBlendAttachmentState::builder()
+                    .color_write_mask(vk::ColorComponentFlags::all())
+                    .blend_enable(false)
+                    .build(),
+                vk::PipelineColorBlendAttachmentState::builder()
+                    .color_write_mask(vk::ColorComponentFlags::all())
+                    .blend_enable(false)
+                    .build(),
+            ];
+            let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+                .logic_op_enable(false)
+                .logic_op(vk::LogicOp::COPY)
+                .attachments(&attachments)
+                .blend_constants([0.0, 0.0, 0.0, 0.0]);
+
+            let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+                .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+            let set_layouts = &[
+                self.m_render_pass.m_descriptor_infos[LayoutType::MeshGlobal as usize].layout,
+                self.m_render_pass.m_descriptor_infos[LayoutType::PerMesh as usize].layout,
+                self.m_render_pass.m_descriptor_infos[LayoutType::MeshPerMaterial as usize].layout,
+            ];
+            let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(set_layouts);
+
+            // masked meshes draw with the alpha-tested variant
+            let variants = [
+                (RenderPipelineType::MeshLighting, [vert_stage, frag_stage]),
+                (
+                    RenderPipelineType::MeshLightingAlphaTest,
+                    [vert_stage, alpha_test_frag_stage],
+                ),
+            ];
+            for (pipeline_type, stages) in variants {
+                let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
+
+                let info = vk::GraphicsPipelineCreateInfo::builder()
+                    .stages(&stages)
+                    .vertex_input_state(&vertex_input_state)
+                    .input_assembly_state(&input_assembly_state)
+                    .viewport_state(&viewport_state)
+                    .rasterization_state(&rasterization_state)
+                    .multisample_state(&multisample_state)
+                    .depth_stencil_state(&depth_stencil_state)
+                    .color_blend_state(&color_blend_state)
+                    .dynamic_state(&dynamic_state)
+                    .layout(pipeline_layout)
+                    .render_pass(self.forward_lighting_render_pass())
+                    .subpass(MainCameraSubPass::ForwardLighting as u32)
+                    .build();
+
+                let pipeline =
+                    rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
+
+                self.m_render_pass.m_render_pipeline[pipeline_type as usize] = RenderPipelineBase {
+                    layout: pipeline_layout,
+                    pipeline,
+                };
+            }
+
+            rhi.destroy_shader_module(vert_shader_module);
+            rhi.destroy_shader_module(frag_shader_module);
+        }
+
+        // mesh transparent
+        {
+            let vert_shader_module = rhi.create_shader_module(&MESH_VERT)?;
+            let frag_shader_module = rhi.create_shader_module(&MESH_FRAG)?;
+
+            let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vert_shader_module)
+                .name(b"main\0")
+                .build();
+
+            let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag_shader_module)
+                .name(b"main\0")
+                .build();
+
+            let binding_descriptions = &MeshVertex::get_binding_descriptions();
+            let attribute_descriptions = &MeshVertex::get_attribute_descriptions();
+            let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+                .vertex_binding_descriptions(binding_descriptions)
+                .vertex_attribute_descriptions(attribute_descriptions);
+
+            let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+                .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+                .primitive_restart_enable(false);
+
+            let swapchain_info = rhi.get_swapchain_info();
+
+            let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+                .viewports(std::slice::from_ref(swapchain_info.viewport))
+                .scissors(std::slice::from_ref(swapchain_info.scissor));
+
+            let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+                .depth_clamp_enable(false)
+                .rasterizer_discard_enable(false)
+                .polygon_mode(vk::PolygonMode::FILL)
+                .line_width(1.0)
+                .cull_mode(vk::CullModeFlags::BACK)
+                .front_face(vk::FrontFace::CLOCKWISE)
+                .depth_bias_enable(false);
 
+            // tested against the opaque depth, but not written, as the meshes behind still show
             let depth_stencil_state: vk::PipelineDepthStencilStateCreateInfoBuilder =
                 vk::PipelineDepthStencilStateCreateInfo::builder()
                     .depth_test_enable(true)
-                    .depth_write_enable(true)
+                    .depth_write_enable(false)
                     .depth_compare_op(vk::CompareOp::LESS)
                     .stencil_test_enable(false);
 
-            let attachments = [vk::PipelineColorBlendAttachmentState::builder()
-                .color_write_mask(vk::ColorComponentFlags::all())
-                .blend_enable(false)
-                .build()];
+            // blended over the lit scene keeping its alpha, the velocity stays the opaque one
+            let attachments = [
+                vk::PipelineColorBlendAttachmentState::builder()
+                    .color_write_mask(vk::ColorComponentFlags::all())
+                    .blend_enable(true)
+                    .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+                    .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                    .color_blend_op(vk::BlendOp::ADD)
+                    .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+                    .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+                    .alpha_blend_op(vk::BlendOp::ADD)
+                    .build(),
+                vk::PipelineColorBlendAttachmentState::builder()
+                    .color_write_mask(vk::ColorComponentFlags::empty())
+                    .blend_enable(false)
+                    .build(),
+            ];
             let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
                 .logic_op_enable(false)
                 .logic_op(vk::LogicOp::COPY)
@@ -1425,34 +2602,53 @@ impl MainCameraPass {
             ];
             let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(set_layouts);
 
-            let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
-
-            let stages = &[vert_stage, frag_stage];
-            let info = vk::GraphicsPipelineCreateInfo::builder()
-                .stages(stages)
-                .vertex_input_state(&vertex_input_state)
-                .input_assembly_state(&input_assembly_state)
-                .viewport_state(&viewport_state)
-                .rasterization_state(&rasterization_state)
-                .multisample_state(&multisample_state)
-                .depth_stencil_state(&depth_stencil_state)
-                .color_blend_state(&color_blend_state)
-                .dynamic_state(&dynamic_state)
-                .layout(pipeline_layout)
-                .render_pass(self.m_render_pass.m_framebuffer.render_pass)
-                .subpass(MainCameraSubPass::ForwardLighting as u32)
-                .build();
-
-            let pipeline = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
-
-            rhi.destroy_shader_module(vert_shader_module);
-            rhi.destroy_shader_module(frag_shader_module);
-
-            self.m_render_pass.m_render_pipeline[RenderPipelineType::MeshLighting as usize] =
-                RenderPipelineBase {
+            // the deferred path draws them in its own forward subpass, never multisampled
+            let variants = [
+                (
+                    RenderPipelineType::MeshTransparent,
+                    self.forward_lighting_render_pass(),
+                    self.m_msaa_samples,
+                ),
+                (
+                    RenderPipelineType::DeferredMeshTransparent,
+                    self.m_lighting_render_pass,
+                    vk::SampleCountFlags::_1,
+                ),
+            ];
+            for (pipeline_type, render_pass, samples) in variants {
+                let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+                    .sample_shading_enable(false)
+                    .rasterization_samples(samples);
+
+                let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
+
+                let stages = &[vert_stage, frag_stage];
+                let info = vk::GraphicsPipelineCreateInfo::builder()
+                    .stages(stages)
+                    .vertex_input_state(&vertex_input_state)
+                    .input_assembly_state(&input_assembly_state)
+                    .viewport_state(&viewport_state)
+                    .rasterization_state(&rasterization_state)
+                    .multisample_state(&multisample_state)
+                    .depth_stencil_state(&depth_stencil_state)
+                    .color_blend_state(&color_blend_state)
+                    .dynamic_state(&dynamic_state)
+                    .layout(pipeline_layout)
+                    .render_pass(render_pass)
+                    .subpass(MainCameraSubPass::ForwardLighting as u32)
+                    .build();
+
+                let pipeline =
+                    rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
+
+                self.m_render_pass.m_render_pipeline[pipeline_type as usize] = RenderPipelineBase {
                     layout: pipeline_layout,
                     pipeline,
                 };
+            }
+
+            rhi.destroy_shader_module(vert_shader_module);
+            rhi.destroy_shader_module(frag_shader_module);
         }
 
         // skybox
@@ -1491,7 +2687,7 @@ impl MainCameraPass {
 
             let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
                 .sample_shading_enable(false)
-                .rasterization_samples(vk::SampleCountFlags::_1);
+                .rasterization_samples(self.m_msaa_samples);
 
             let depth_stencil_state: vk::PipelineDepthStencilStateCreateInfoBuilder =
                 vk::PipelineDepthStencilStateCreateInfo::builder()
@@ -1500,10 +2696,17 @@ impl MainCameraPass {
                     .depth_compare_op(vk::CompareOp::LESS)
                     .stencil_test_enable(false);
 
-            let attachments = [vk::PipelineColorBlendAttachmentState::builder()
-                .color_write_mask(vk::ColorComponentFlags::all())
-                .blend_enable(false)
-                .build()];
+            // the sky keeps the cleared velocity, TAA reprojects it by the depth instead
+            let attachments = [
+                vk::PipelineColorBlendAttachmentState::builder()
+                    .color_write_mask(vk::ColorComponentFlags::all())
+                    .blend_enable(false)
+                    .build(),
+                vk::PipelineColorBlendAttachmentState::builder()
+                    .color_write_mask(vk::ColorComponentFlags::empty())
+                    .blend_enable(false)
+                    .build(),
+            ];
             let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
                 .logic_op_enable(false)
                 .logic_op(vk::LogicOp::COPY)
@@ -1531,7 +2734,7 @@ impl MainCameraPass {
                 .color_blend_state(&color_blend_state)
                 .dynamic_state(&dynamic_state)
                 .layout(pipeline_layout)
-                .render_pass(self.m_render_pass.m_framebuffer.render_pass)
+                .render_pass(self.forward_lighting_render_pass())
                 .subpass(MainCameraSubPass::ForwardLighting as u32)
                 .build();
 
@@ -1606,6 +2809,16 @@ impl MainCameraPass {
                 )
                 .build()];
 
+        let mesh_light_cluster_storage_buffer_info = [vk::DescriptorBufferInfo::builder()
+            .offset(0)
+            .range(size_of::<MeshLightClusterStorageBufferObject>() as u64)
+            .buffer(
+                global_render_resource
+                    ._storage_buffer
+                    ._global_upload_ringbuffer,
+            )
+            .build()];
+
         let brdf_texture_image_info = [vk::DescriptorImageInfo::builder()
             .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .image_view(
@@ -1660,6 +2873,12 @@ impl MainCameraPass {
             .sampler(*rhi.get_or_create_default_sampler(RHISamplerType::Nearest)?)
             .build()];
 
+        let spot_light_shadow_texture_image_info = [vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(self.m_spot_light_shadow_color_image_view)
+            .sampler(*rhi.get_or_create_default_sampler(RHISamplerType::Nearest)?)
+            .build()];
+
         let mesh_descriptor_writes_info = [
             vk::WriteDescriptorSet::builder()
                 .dst_set(
@@ -1733,6 +2952,24 @@ impl MainCameraPass {
                 .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
                 .image_info(&directional_light_shadow_texture_image_info)
                 .build(),
+            vk::WriteDescriptorSet::builder()
+                .dst_set(
+                    self.m_render_pass.m_descriptor_infos[LayoutType::MeshGlobal as usize]
+                        .descriptor_set,
+                )
+                .dst_binding(8)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER_DYNAMIC)
+                .buffer_info(&mesh_light_cluster_storage_buffer_info)
+                .build(),
+            vk::WriteDescriptorSet::builder()
+                .dst_set(
+                    self.m_render_pass.m_descriptor_infos[LayoutType::MeshGlobal as usize]
+                        .descriptor_set,
+                )
+                .dst_binding(9)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(&spot_light_shadow_texture_image_info)
+                .build(),
         ];
 
         rhi.update_descriptor_sets(&mesh_descriptor_writes_info)?;
@@ -1835,6 +3072,11 @@ impl MainCameraPass {
             .image_view(*rhi.get_depth_image_info().image_view)
             .sampler(vk::Sampler::null())
             .build()];
+        let ambient_occlusion_image_info = [vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(self.m_ssao_pass.get_ambient_occlusion_image_view())
+            .sampler(*rhi.get_or_create_default_sampler(RHISamplerType::Nearest)?)
+            .build()];
         let deferred_lighting_descriptor_writes_info = [
             vk::WriteDescriptorSet::builder()
                 .dst_set(
@@ -1872,6 +3114,15 @@ impl MainCameraPass {
                 .descriptor_type(vk::DescriptorType::INPUT_ATTACHMENT)
                 .image_info(&depth_input_attachment_info)
                 .build(),
+            vk::WriteDescriptorSet::builder()
+                .dst_set(
+                    self.m_render_pass.m_descriptor_infos[LayoutType::DeferredLighting as usize]
+                        .descriptor_set,
+                )
+                .dst_binding(4)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(&ambient_occlusion_image_info)
+                .build(),
         ];
         rhi.update_descriptor_sets(&deferred_lighting_descriptor_writes_info)?;
         Ok(())
@@ -1882,6 +3133,7 @@ impl MainCameraPass {
         rhi: &VulkanRHI,
         render_scene: &RenderScene,
         render_resource: &mut GlobalRenderResource,
+        light_cluster_dynamic_offset: u32,
     ) -> Result<()> {
         let command_buffer = rhi.get_current_command_buffer();
 
@@ -1889,14 +3141,6 @@ impl MainCameraPass {
         rhi.cmd_set_viewport(command_buffer, 0, slice::from_ref(info.viewport));
         rhi.cmd_set_scissor(command_buffer, 0, slice::from_ref(info.scissor));
 
-        let pipeline =
-            &self.m_render_pass.m_render_pipeline[RenderPipelineType::MeshGBuffer as usize];
-        rhi.cmd_bind_pipeline(
-            command_buffer,
-            vk::PipelineBindPoint::GRAPHICS,
-            pipeline.pipeline,
-        );
-
         let perframe_dynamic_offset = round_up(
             render_resource
                 ._storage_buffer
@@ -1921,127 +3165,24 @@ impl MainCameraPass {
             );
         }
 
-        struct MeshNode<'a> {
-            model_matrix: &'a Matrix4x4,
-        }
-
-        let visiable_nodes = render_scene.get_main_camera_visible_mesh_nodes();
-
-        let mut main_camera_mesh_drawcall_batch: HashMap<_, HashMap<_, Vec<_>>> = HashMap::new();
-
-        for node in visiable_nodes.iter() {
-            let mesh_instanced = main_camera_mesh_drawcall_batch
-                .entry(node.ref_material.as_ptr())
-                .or_default();
-            let mesh_nodes = mesh_instanced.entry(node.ref_mesh.as_ptr()).or_default();
-
-            mesh_nodes.push(MeshNode {
-                model_matrix: &node.model_matrix,
-            });
-        }
-
-        for (material, mesh_instanced) in &main_camera_mesh_drawcall_batch {
-            rhi.cmd_bind_descriptor_sets(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.m_render_pass.m_render_pipeline[RenderPipelineType::MeshGBuffer as usize]
-                    .layout,
-                2,
-                &[unsafe { &**material }.material_descriptor_set],
-                &[],
-            );
-
-            for (mesh, mesh_nodes) in mesh_instanced {
-                let ref_mesh = unsafe { &**mesh };
-
-                rhi.cmd_bind_descriptor_sets(
-                    command_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    self.m_render_pass.m_render_pipeline[RenderPipelineType::MeshGBuffer as usize]
-                        .layout,
-                    1,
-                    &[ref_mesh.mesh_vertex_blending_descriptor_set],
-                    &[],
-                );
-
-                let buffers = [
-                    ref_mesh.mesh_vertex_position_buffer,
-                    ref_mesh.mesh_vertex_varying_enable_blending_buffer,
-                    ref_mesh.mesh_vertex_varying_buffer,
-                ];
-
-                rhi.cmd_bind_vertex_buffers(command_buffer, 0, &buffers, &[0, 0, 0]);
-                rhi.cmd_bind_index_buffer(
-                    command_buffer,
-                    ref_mesh.mesh_index_buffer,
-                    0,
-                    ref_mesh.mesh_index_type,
-                );
-
-                let instance_count = mesh_nodes.len();
-                let max_drawcall_instance = MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT;
-                let drawcall_count = instance_count.div_ceil(max_drawcall_instance);
-
-                for index in 0..drawcall_count {
-                    let current_count =
-                        max_drawcall_instance.min(instance_count - index * max_drawcall_instance);
-
-                    let mut object = MeshPerdrawcallStorageBufferObject::default();
-
-                    for i in 0..current_count {
-                        object.mesh_instances[i].model_matrix = mesh_nodes
-                            [index * max_drawcall_instance + i]
-                            .model_matrix
-                            .clone();
-                    }
-
-                    let perdrawcall_dynamic_offset = round_up(
-                        render_resource
-                            ._storage_buffer
-                            ._global_upload_ringbuffers_end[rhi.get_current_frame_index()],
-                        render_resource
-                            ._storage_buffer
-                            ._min_storage_buffer_offset_alignment,
-                    );
-                    render_resource
-                        ._storage_buffer
-                        ._global_upload_ringbuffers_end[rhi.get_current_frame_index()] =
-                        perdrawcall_dynamic_offset
-                            + std::mem::size_of::<MeshPerdrawcallStorageBufferObject>() as u32;
-
-                    unsafe {
-                        std::ptr::copy_nonoverlapping(
-                            &object as *const _ as *const c_void,
-                            render_resource
-                                ._storage_buffer
-                                ._global_upload_ringbuffer_pointer
-                                .add(perdrawcall_dynamic_offset as usize),
-                            std::mem::size_of::<MeshPerdrawcallStorageBufferObject>(),
-                        );
-                    }
-
-                    rhi.cmd_bind_descriptor_sets(
-                        command_buffer,
-                        vk::PipelineBindPoint::GRAPHICS,
-                        self.m_render_pass.m_render_pipeline[0].layout,
-                        0,
-                        &[
-                            self.m_render_pass.m_descriptor_infos[LayoutType::MeshGlobal as usize]
-                                .descriptor_set,
-                        ],
-                        &[perframe_dynamic_offset, perdrawcall_dynamic_offset, 0],
-                    );
-                    rhi.cmd_draw_indexed(
-                        command_buffer,
-                        ref_mesh.mesh_index_count,
-                        current_count as u32,
-                        0,
-                        0,
-                        0,
-                    );
-                }
-            }
-        }
+        let (opaque_nodes, masked_nodes) =
+            split_masked_mesh_nodes(render_scene.get_main_camera_visible_mesh_nodes());
+        self.draw_mesh_nodes(
+            rhi,
+            render_resource,
+            opaque_nodes,
+            RenderPipelineType::MeshGBuffer,
+            perframe_dynamic_offset,
+            light_cluster_dynamic_offset,
+        );
+        self.draw_mesh_nodes(
+            rhi,
+            render_resource,
+            masked_nodes,
+            RenderPipelineType::MeshGBufferAlphaTest,
+            perframe_dynamic_offset,
+            light_cluster_dynamic_offset,
+        );
         Ok(())
     }
 
@@ -2049,6 +3190,7 @@ impl MainCameraPass {
         &self,
         rhi: &VulkanRHI,
         render_resource: &mut GlobalRenderResource,
+        light_cluster_dynamic_offset: u32,
     ) -> Result<()> {
         let command_buffer = rhi.get_current_command_buffer();
 
@@ -2101,7 +3243,13 @@ impl MainCameraPass {
                     .descriptor_set,
                 self.m_render_pass.m_descriptor_infos[LayoutType::Skybox as usize].descriptor_set,
             ],
-            &[perframe_dynamic_offset, perframe_dynamic_offset, 0, 0],
+            &[
+                perframe_dynamic_offset,
+                perframe_dynamic_offset,
+                0,
+                light_cluster_dynamic_offset,
+                0,
+            ],
         );
 
         rhi.cmd_draw(command_buffer, 3, 1, 0, 0);
@@ -2114,6 +3262,7 @@ impl MainCameraPass {
         rhi: &VulkanRHI,
         render_scene: &RenderScene,
         render_resource: &mut GlobalRenderResource,
+        light_cluster_dynamic_offset: u32,
     ) -> Result<()> {
         let command_buffer = rhi.get_current_command_buffer();
 
@@ -2121,14 +3270,6 @@ impl MainCameraPass {
         rhi.cmd_set_viewport(command_buffer, 0, slice::from_ref(info.viewport));
         rhi.cmd_set_scissor(command_buffer, 0, slice::from_ref(info.scissor));
 
-        let pipeline =
-            &self.m_render_pass.m_render_pipeline[RenderPipelineType::MeshLighting as usize];
-        rhi.cmd_bind_pipeline(
-            command_buffer,
-            vk::PipelineBindPoint::GRAPHICS,
-            pipeline.pipeline,
-        );
-
         let perframe_dynamic_offset = round_up(
             render_resource
                 ._storage_buffer
@@ -2153,128 +3294,224 @@ impl MainCameraPass {
             );
         }
 
-        struct MeshNode<'a> {
-            model_matrix: &'a Matrix4x4,
+        let (opaque_nodes, masked_nodes) =
+            split_masked_mesh_nodes(render_scene.get_main_camera_visible_mesh_nodes());
+        self.draw_mesh_nodes(
+            rhi,
+            render_resource,
+            opaque_nodes,
+            RenderPipelineType::MeshLighting,
+            perframe_dynamic_offset,
+            light_cluster_dynamic_offset,
+        );
+        self.draw_mesh_nodes(
+            rhi,
+            render_resource,
+            masked_nodes,
+            RenderPipelineType::MeshLightingAlphaTest,
+            perframe_dynamic_offset,
+            light_cluster_dynamic_offset,
+        );
+        Ok(())
+    }
+
+    /// The blended meshes, back to front over the lit scene.
+    fn draw_mesh_transparent(
+        &self,
+        rhi: &VulkanRHI,
+        render_scene: &RenderScene,
+        render_resource: &mut GlobalRenderResource,
+        light_cluster_dynamic_offset: u32,
+        pipeline_type: RenderPipelineType,
+    ) -> Result<()> {
+        let visiable_nodes = render_scene.get_main_camera_visible_transparent_mesh_nodes();
+        if visiable_nodes.is_empty() {
+            return Ok(());
         }
 
-        let visiable_nodes = render_scene.get_main_camera_visible_mesh_nodes();
+        let command_buffer = rhi.get_current_command_buffer();
 
-        let mut main_camera_mesh_drawcall_batch: HashMap<_, HashMap<_, Vec<_>>> = HashMap::new();
+        let info = rhi.get_swapchain_info();
+        rhi.cmd_set_viewport(command_buffer, 0, slice::from_ref(info.viewport));
+        rhi.cmd_set_scissor(command_buffer, 0, slice::from_ref(info.scissor));
 
-        for node in visiable_nodes.iter() {
-            let mesh_instanced = main_camera_mesh_drawcall_batch
-                .entry(node.ref_material.as_ptr())
-                .or_default();
-            let mesh_nodes = mesh_instanced.entry(node.ref_mesh.as_ptr()).or_default();
+        let perframe_dynamic_offset = round_up(
+            render_resource
+                ._storage_buffer
+                ._global_upload_ringbuffers_end[rhi.get_current_frame_index()],
+            render_resource
+                ._storage_buffer
+                ._min_storage_buffer_offset_alignment,
+        );
 
-            mesh_nodes.push(MeshNode {
-                model_matrix: &node.model_matrix,
-            });
+        render_resource
+            ._storage_buffer
+            ._global_upload_ringbuffers_end[rhi.get_current_frame_index()] =
+            perframe_dynamic_offset + std::mem::size_of::<MeshPerframeStorageBufferObject>() as u32;
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                &self.m_mesh_perframe_storage_buffer_object as *const _ as *const c_void,
+                render_resource
+                    ._storage_buffer
+                    ._global_upload_ringbuffer_pointer
+                    .add(perframe_dynamic_offset as usize),
+                std::mem::size_of::<MeshPerframeStorageBufferObject>(),
+            );
+        }
+
+        self.draw_mesh_nodes(
+            rhi,
+            render_resource,
+            visiable_nodes,
+            pipeline_type,
+            perframe_dynamic_offset,
+            light_cluster_dynamic_offset,
+        );
+        Ok(())
+    }
+
+    /// Draws the nodes in batches with one of the mesh pipelines, which share their set layouts.
+    fn draw_mesh_nodes(
+        &self,
+        rhi: &VulkanRHI,
+        render_resource: &mut GlobalRenderResource,
+        visiable_nodes: &[RenderMeshNode],
+        pipeline_type: RenderPipelineType,
+        perframe_dynamic_offset: u32,
+        light_cluster_dynamic_offset: u32,
+    ) {
+        if visiable_nodes.is_empty() {
+            return;
         }
+        let command_buffer = rhi.get_current_command_buffer();
+
+        let pipeline = &self.m_render_pass.m_render_pipeline[pipeline_type as usize];
+        rhi.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline.pipeline,
+        );
+
+        let mut last_material = std::ptr::null();
+        for batch in mesh_node_batches(visiable_nodes) {
+            let material = batch[0].ref_material.as_ptr();
+            if material != last_material {
+                rhi.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.layout,
+                    2,
+                    &[unsafe { &*material }.material_descriptor_set],
+                    &[],
+                );
+                last_material = material;
+            }
+
+            let ref_mesh = unsafe { &*batch[0].ref_mesh.as_ptr() };
 
-        for (material, mesh_instanced) in &main_camera_mesh_drawcall_batch {
             rhi.cmd_bind_descriptor_sets(
                 command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                self.m_render_pass.m_render_pipeline[RenderPipelineType::MeshGBuffer as usize]
-                    .layout,
-                2,
-                &[unsafe { &**material }.material_descriptor_set],
+                pipeline.layout,
+                1,
+                &[ref_mesh.mesh_vertex_blending_descriptor_set],
                 &[],
             );
 
-            for (mesh, mesh_nodes) in mesh_instanced {
-                let ref_mesh = unsafe { &**mesh };
+            let buffers = [
+                ref_mesh.mesh_vertex_position_buffer,
+                ref_mesh.mesh_vertex_varying_enable_blending_buffer,
+                ref_mesh.mesh_vertex_varying_buffer,
+            ];
+
+            rhi.cmd_bind_vertex_buffers(command_buffer, 0, &buffers, &[0, 0, 0]);
+            rhi.cmd_bind_index_buffer(
+                command_buffer,
+                ref_mesh.mesh_index_buffer,
+                0,
+                ref_mesh.mesh_index_type,
+            );
+
+            let perdrawcall_dynamic_offsets = render_resource
+                ._storage_buffer
+                .upload_mesh_instances(rhi.get_current_frame_index(), batch);
+
+            for (index, perdrawcall_dynamic_offset) in
+                perdrawcall_dynamic_offsets.into_iter().enumerate()
+            {
+                let current_count = MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT
+                    .min(batch.len() - index * MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT);
 
                 rhi.cmd_bind_descriptor_sets(
                     command_buffer,
                     vk::PipelineBindPoint::GRAPHICS,
-                    self.m_render_pass.m_render_pipeline[RenderPipelineType::MeshGBuffer as usize]
-                        .layout,
-                    1,
-                    &[ref_mesh.mesh_vertex_blending_descriptor_set],
-                    &[],
+                    pipeline.layout,
+                    0,
+                    &[
+                        self.m_render_pass.m_descriptor_infos[LayoutType::MeshGlobal as usize]
+                            .descriptor_set,
+                    ],
+                    &[
+                        perframe_dynamic_offset,
+                        perdrawcall_dynamic_offset,
+                        0,
+                        light_cluster_dynamic_offset,
+                    ],
                 );
-
-                let buffers = [
-                    ref_mesh.mesh_vertex_position_buffer,
-                    ref_mesh.mesh_vertex_varying_enable_blending_buffer,
-                    ref_mesh.mesh_vertex_varying_buffer,
-                ];
-
-                rhi.cmd_bind_vertex_buffers(command_buffer, 0, &buffers, &[0, 0, 0]);
-                rhi.cmd_bind_index_buffer(
+                rhi.cmd_draw_indexed(
                     command_buffer,
-                    ref_mesh.mesh_index_buffer,
+                    ref_mesh.mesh_index_count,
+                    current_count as u32,
+                    0,
+                    0,
                     0,
-                    ref_mesh.mesh_index_type,
                 );
+            }
+        }
+    }
 
-                let instance_count = mesh_nodes.len();
-                let max_drawcall_instance = MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT;
-                let drawcall_count = instance_count.div_ceil(max_drawcall_instance);
-
-                for index in 0..drawcall_count {
-                    let current_count =
-                        max_drawcall_instance.min(instance_count - index * max_drawcall_instance);
-
-                    let mut object = MeshPerdrawcallStorageBufferObject::default();
-
-                    for i in 0..current_count {
-                        object.mesh_instances[i].model_matrix = mesh_nodes
-                            [index * max_drawcall_instance + i]
-                            .model_matrix
-                            .clone();
-                    }
+    fn upload_light_cluster(
+        &self,
+        rhi: &VulkanRHI,
+        render_resource: &mut GlobalRenderResource,
+    ) -> u32 {
+        let light_cluster_dynamic_offset = round_up(
+            render_resource
+                ._storage_buffer
+                ._global_upload_ringbuffers_end[rhi.get_current_frame_index()],
+            render_resource
+                ._storage_buffer
+                ._min_storage_buffer_offset_alignment,
+        );
 
-                    let perdrawcall_dynamic_offset = round_up(
-                        render_resource
-                            ._storage_buffer
-                            ._global_upload_ringbuffers_end[rhi.get_current_frame_index()],
-                        render_resource
-                            ._storage_buffer
-                            ._min_storage_buffer_offset_alignment,
-                    );
+        // the descriptor range covers the whole object, so all of it is reserved
+        render_resource
+            ._storage_buffer
+            ._global_upload_ringbuffers_end[rhi.get_current_frame_index()] =
+            light_cluster_dynamic_offset
+                + std::mem::size_of::<MeshLightClusterStorageBufferObject>() as u32;
+
+        // the shaders only read the first `light_count` lights and the light indices the grids
+        // reference, so the rest of the reserved range is left as it was
+        let light_cluster = self.m_mesh_light_cluster_storage_buffer_object.borrow();
+        let lights_end = offset_of!(MeshLightClusterStorageBufferObject, lights)
+            + light_cluster.light_count as usize * size_of::<VulkanClusteredLight>();
+        let grids_start = offset_of!(MeshLightClusterStorageBufferObject, cluster_light_grids);
+        let indices_end = offset_of!(MeshLightClusterStorageBufferObject, light_indices)
+            + light_cluster.light_index_count() * size_of::<u32>();
+        for (start, end) in [(0, lights_end), (grids_start, indices_end)] {
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    (&**light_cluster as *const _ as *const c_void).add(start),
                     render_resource
                         ._storage_buffer
-                        ._global_upload_ringbuffers_end[rhi.get_current_frame_index()] =
-                        perdrawcall_dynamic_offset
-                            + std::mem::size_of::<MeshPerdrawcallStorageBufferObject>() as u32;
-
-                    unsafe {
-                        std::ptr::copy_nonoverlapping(
-                            &object as *const _ as *const c_void,
-                            render_resource
-                                ._storage_buffer
-                                ._global_upload_ringbuffer_pointer
-                                .add(perdrawcall_dynamic_offset as usize),
-                            std::mem::size_of::<MeshPerdrawcallStorageBufferObject>(),
-                        );
-                    }
-
-                    rhi.cmd_bind_descriptor_sets(
-                        command_buffer,
-                        vk::PipelineBindPoint::GRAPHICS,
-                        self.m_render_pass.m_render_pipeline[0].layout,
-                        0,
-                        &[
-                            self.m_render_pass.m_descriptor_infos[LayoutType::MeshGlobal as usize]
-                                .descriptor_set,
-                        ],
-                        &[perframe_dynamic_offset, perdrawcall_dynamic_offset, 0],
-                    );
-                    rhi.cmd_draw_indexed(
-                        command_buffer,
-                        ref_mesh.mesh_index_count,
-                        current_count as u32,
-                        0,
-                        0,
-                        0,
-                    );
-                }
+                        ._global_upload_ringbuffer_pointer
+                        .add(light_cluster_dynamic_offset as usize + start),
+                    end - start,
+                );
             }
         }
-        Ok(())
+        light_cluster_dynamic_offset
     }
 
     fn draw_skybox(
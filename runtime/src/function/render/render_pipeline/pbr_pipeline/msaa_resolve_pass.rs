@@ -0,0 +1,334 @@
+use anyhow::Result;
+use linkme::distributed_slice;
+use vulkanalia::{
+    prelude::v1_0::*,
+    vk::{VertexInputAttributeDescription, VertexInputBindingDescription},
+};
+
+use crate::{
+    function::render::{
+        interface::vulkan::vulkan_rhi::{VULKAN_RHI_DESCRIPTOR_INPUT_ATTACHMENT, VulkanRHI},
+        render_pass::{
+            Descriptor, DescriptorLayout, DescriptorLayoutRegistry, FrameBufferAttachment,
+            RenderPass, RenderPipelineBase,
+        },
+    },
+    shader::generated::shader::{DEPTH_RESOLVE_FRAG, POST_PROCESS_VERT},
+};
+
+pub struct MSAAResolvePassInitInfo<'a> {
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+    pub rhi: &'a VulkanRHI,
+    pub descriptor_layout_manager: &'a DescriptorLayoutRegistry,
+    pub samples: vk::SampleCountFlags,
+    /// The formats of the scene color and the velocity the targets resolve into.
+    pub color_formats: [vk::Format; 2],
+}
+
+/// Keep in sync with the push constants of shader/glsl/depth_resolve.frag.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct DepthResolvePushConstantObject {
+    sample_count: i32,
+}
+
+/// The multisampled targets of the forward lighting with MSAA on. The color and the velocity are
+/// resolved by the render pass, the depth has no resolve attachment in Vulkan 1.0, so a subpass
+/// of its own writes the nearest sample of each pixel into the depth the post passes read.
+#[derive(Default)]
+pub struct MSAAResolvePass {
+    pub m_render_pass: RenderPass,
+    m_subpass: u32,
+    m_samples: vk::SampleCountFlags,
+    m_color_formats: [vk::Format; 2],
+}
+
+impl MSAAResolvePass {
+    pub fn initialize(&mut self, info: &MSAAResolvePassInitInfo) -> Result<()> {
+        self.m_render_pass.m_framebuffer.render_pass = info.render_pass;
+        self.m_subpass = info.subpass;
+        self.m_samples = info.samples;
+        self.m_color_formats = info.color_formats;
+        self.setup_attachments(info.rhi)?;
+        self.setup_descriptor_layout(info.rhi, info.descriptor_layout_manager)?;
+        self.setup_pipelines(info.rhi)?;
+        self.setup_descriptor_set(info.rhi)?;
+        self.update_descriptor_set(info.rhi)?;
+        Ok(())
+    }
+
+    /// The multisampled color, velocity and depth, in the order the lighting render pass takes
+    /// them.
+    pub fn get_attachment_views(&self) -> Vec<vk::ImageView> {
+        self.m_render_pass
+            .m_framebuffer
+            .attachments
+            .iter()
+            .map(|attachment| attachment.view)
+            .collect()
+    }
+
+    pub fn draw(&self, rhi: &VulkanRHI) {
+        let command_buffer = rhi.get_current_command_buffer();
+        rhi.push_event(command_buffer, "Depth Resolve\0", [1.0; 4]);
+        let info = rhi.get_swapchain_info();
+        let pipeline = &self.m_render_pass.m_render_pipeline[0];
+        rhi.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline.pipeline,
+        );
+        rhi.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(info.viewport));
+        rhi.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(info.scissor));
+        rhi.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline.layout,
+            0,
+            &[self.m_render_pass.m_descriptor_infos[0].descriptor_set],
+            &[],
+        );
+        let push_constants = DepthResolvePushConstantObject {
+            sample_count: self.m_samples.bits() as i32,
+        };
+        rhi.cmd_push_constants(
+            command_buffer,
+            pipeline.layout,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            unsafe {
+                std::slice::from_raw_parts(
+                    &push_constants as *const DepthResolvePushConstantObject as *const u8,
+                    std::mem::size_of::<DepthResolvePushConstantObject>(),
+                )
+            },
+        );
+        rhi.cmd_draw(command_buffer, 3, 1, 0, 0);
+        rhi.pop_event(command_buffer);
+    }
+
+    /// The targets follow the size of the swapchain.
+    pub fn update_after_framebuffer_recreate(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        self.destroy_attachments(rhi);
+        self.setup_attachments(rhi)?;
+        self.update_descriptor_set(rhi)
+    }
+
+    pub fn destroy(&self, rhi: &VulkanRHI) {
+        self.destroy_attachments(rhi);
+        for pipeline in &self.m_render_pass.m_render_pipeline {
+            rhi.destroy_pipeline(pipeline.pipeline);
+            rhi.destroy_pipeline_layout(pipeline.layout);
+        }
+    }
+}
+
+pub struct DepthResolveDescriptorLayout;
+impl DescriptorLayout for DepthResolveDescriptorLayout {
+    fn new(rhi: &VulkanRHI) -> Result<vk::DescriptorSetLayout> {
+        let layout_bindings = [vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::INPUT_ATTACHMENT)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build()];
+        let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&layout_bindings);
+        let layout = rhi.create_descriptor_set_layout(&create_info)?;
+        Ok(layout)
+    }
+}
+
+#[distributed_slice(VULKAN_RHI_DESCRIPTOR_INPUT_ATTACHMENT)]
+static INPUT_ATTACHMENT_COUNT: u32 = 1;
+
+impl MSAAResolvePass {
+    fn setup_attachments(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let extent = rhi.get_swapchain_info().extent;
+        // only the depth is read after the samples are drawn, the rest never leaves the tile
+        let targets = [
+            (
+                self.m_color_formats[0],
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                vk::ImageAspectFlags::COLOR,
+            ),
+            (
+                self.m_color_formats[1],
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                vk::ImageAspectFlags::COLOR,
+            ),
+            (
+                rhi.get_depth_image_info().format,
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+                    | vk::ImageUsageFlags::INPUT_ATTACHMENT,
+                vk::ImageAspectFlags::DEPTH,
+            ),
+        ];
+        self.m_render_pass.m_framebuffer.attachments = targets
+            .into_iter()
+            .map(|(format, usage, aspect)| {
+                let (image, mem) = rhi.create_multisampled_image(
+                    extent.width,
+                    extent.height,
+                    format,
+                    usage,
+                    self.m_samples,
+                )?;
+                let view =
+                    rhi.create_image_view(image, format, aspect, vk::ImageViewType::_2D, 1, 1)?;
+                Ok(FrameBufferAttachment {
+                    image,
+                    mem,
+                    view,
+                    format,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(())
+    }
+
+    fn destroy_attachments(&self, rhi: &VulkanRHI) {
+        for attachment in &self.m_render_pass.m_framebuffer.attachments {
+            rhi.destroy_image_view(attachment.view);
+            rhi.destroy_image(attachment.image);
+            rhi.free_memory(attachment.mem);
+        }
+    }
+
+    fn setup_descriptor_layout(
+        &mut self,
+        rhi: &VulkanRHI,
+        descriptor_layout_manager: &DescriptorLayoutRegistry,
+    ) -> Result<()> {
+        self.m_render_pass.m_descriptor_infos.clear();
+        let layout = descriptor_layout_manager.acquire::<DepthResolveDescriptorLayout>(rhi)?;
+        self.m_render_pass.m_descriptor_infos.push(Descriptor {
+            layout,
+            descriptor_set: Default::default(),
+        });
+        Ok(())
+    }
+
+    fn setup_pipelines(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        self.m_render_pass.m_render_pipeline.clear();
+        let vert_shader_module = rhi.create_shader_module(&POST_PROCESS_VERT)?;
+        let frag_shader_module = rhi.create_shader_module(&DEPTH_RESOLVE_FRAG)?;
+
+        let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vert_shader_module)
+            .name(b"main\0");
+
+        let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(frag_shader_module)
+            .name(b"main\0");
+
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&[] as &[VertexInputBindingDescription])
+            .vertex_attribute_descriptions(&[] as &[VertexInputAttributeDescription]);
+
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+            .primitive_restart_enable(false);
+
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.0)
+            .cull_mode(vk::CullModeFlags::BACK)
+            .front_face(vk::FrontFace::CLOCKWISE)
+            .depth_bias_enable(false);
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+            .sample_shading_enable(false)
+            .rasterization_samples(vk::SampleCountFlags::_1);
+
+        // the depth is written as is, there is nothing to test it against
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(vk::CompareOp::ALWAYS)
+            .stencil_test_enable(false);
+
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .logic_op_enable(false)
+            .logic_op(vk::LogicOp::COPY)
+            .blend_constants([0.0, 0.0, 0.0, 0.0]);
+
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+        let set_layouts = &[self.m_render_pass.m_descriptor_infos[0].layout];
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(std::mem::size_of::<DepthResolvePushConstantObject>() as u32)];
+        let layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+
+        let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
+
+        let stages = &[vert_stage, frag_stage];
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state)
+            .layout(pipeline_layout)
+            .render_pass(self.m_render_pass.m_framebuffer.render_pass)
+            .subpass(self.m_subpass)
+            .build();
+
+        let pipeline = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
+
+        rhi.destroy_shader_module(vert_shader_module);
+        rhi.destroy_shader_module(frag_shader_module);
+
+        self.m_render_pass
+            .m_render_pipeline
+            .push(RenderPipelineBase {
+                layout: pipeline_layout,
+                pipeline,
+            });
+
+        Ok(())
+    }
+
+    fn setup_descriptor_set(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let set_layouts = [self.m_render_pass.m_descriptor_infos[0].layout];
+        let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(rhi.get_descriptor_pool())
+            .set_layouts(&set_layouts);
+
+        self.m_render_pass.m_descriptor_infos[0].descriptor_set =
+            rhi.allocate_descriptor_sets(&alloc_info)?[0];
+        Ok(())
+    }
+
+    fn update_descriptor_set(&self, rhi: &VulkanRHI) -> Result<()> {
+        let image_info = [vk::DescriptorImageInfo::builder()
+            .image_view(self.m_render_pass.m_framebuffer.attachments[2].view)
+            .image_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+            .build()];
+        let descriptor_writes = [vk::WriteDescriptorSet::builder()
+            .dst_set(self.m_render_pass.m_descriptor_infos[0].descriptor_set)
+            .dst_binding(0)
+            .descriptor_type(vk::DescriptorType::INPUT_ATTACHMENT)
+            .image_info(&image_info)
+            .build()];
+        rhi.update_descriptor_sets(&descriptor_writes)?;
+        Ok(())
+    }
+}
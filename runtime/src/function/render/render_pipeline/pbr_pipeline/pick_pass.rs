@@ -216,9 +216,13 @@ impl PickPass {
                 );
             }
 
-            let visiable_nodes = render_scene.get_main_camera_visible_mesh_nodes();
+            // blended meshes can be picked as well
+            let visiable_nodes = render_scene
+                .get_main_camera_visible_mesh_nodes()
+                .iter()
+                .chain(render_scene.get_main_camera_visible_transparent_mesh_nodes());
 
-            for render_mesh_node in visiable_nodes.iter() {
+            for render_mesh_node in visiable_nodes {
                 let mut object = MeshInefficientPickPerdrawcallStorageBufferObject::default();
                 object.model_matrix[0] = *render_mesh_node.model_matrix;
                 object.node_ids[0] = render_mesh_node.node_id;
@@ -1,15 +1,28 @@
+use std::os::raw::c_void;
+
 use crate::{
     function::render::{
-        interface::vulkan::vulkan_rhi::{VULKAN_RHI_DESCRIPTOR_STORAGE_BUFFER_DYNAMIC, VulkanRHI}, render_common::{
+        interface::vulkan::vulkan_rhi::{VULKAN_RHI_DESCRIPTOR_STORAGE_BUFFER_DYNAMIC, VulkanRHI},
+        render_common::{
+            MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT,
             MeshPointLightShadowPerdrawcallStorageBufferObject,
             MeshPointLightShadowPerdrawcallVertexBlendingStorageBufferObject,
             MeshPointLightShadowPerframeStorageBufferObject, S_MAX_POINT_LIGHT_COUNT,
             S_POINT_LIGHT_SHADOW_MAP_DIMENSION,
-        }, render_mesh::MeshVertex, render_pass::{
+        },
+        render_helper::{mesh_node_batches, round_up, split_masked_mesh_nodes},
+        render_mesh::MeshVertex,
+        render_pass::{
             DescriptorLayout, DescriptorLayoutRegistry, FrameBufferAttachment, RenderPass,
             RenderPipelineBase,
-        }, render_pipeline::pbr_pipeline::main_camera_pass::PerMeshDescriptorLayout, render_resource::{GlobalRenderResource, RenderResource}
+        },
+        render_pipeline::pbr_pipeline::main_camera_pass::{
+            MeshPerMaterialDescriptorLayout, PerMeshDescriptorLayout,
+        },
+        render_resource::{GlobalRenderResource, RenderResource},
+        render_scene::RenderScene,
     },
+    resource::res_type::data::material::MaterialAlphaMode,
     shader::generated::shader::{
         MESH_POINT_LIGHT_SHADOW_FRAG, MESH_POINT_LIGHT_SHADOW_GEOM, MESH_POINT_LIGHT_SHADOW_VERT,
     },
@@ -29,6 +42,7 @@ pub struct PointLightShadowPassInitInfo<'a> {
 pub struct PointLightShadowPass {
     m_render_pass: RenderPass,
     m_per_mesh_layout: vk::DescriptorSetLayout,
+    m_per_material_layout: vk::DescriptorSetLayout,
     m_mesh_point_light_shadow_perframe_storage_buffer_object:
         MeshPointLightShadowPerframeStorageBufferObject,
     pub m_point_light_shadow_attachment: FrameBufferAttachment,
@@ -87,8 +101,13 @@ impl PointLightShadowPass {
             .clone();
     }
 
-    pub fn draw(&self, rhi: &VulkanRHI) {
-        self.draw_model(rhi);
+    pub fn draw(
+        &self,
+        rhi: &VulkanRHI,
+        render_scene: &RenderScene,
+        resource: &mut GlobalRenderResource,
+    ) {
+        self.draw_model(rhi, render_scene, resource);
     }
 }
 
@@ -249,15 +268,13 @@ impl PointLightShadowPass {
             descriptor_layout_manager.acquire::<PointLightShadowDescriptorLayout>(rhi)?;
         self.m_per_mesh_layout =
             descriptor_layout_manager.acquire::<PerMeshDescriptorLayout>(rhi)?;
+        self.m_per_material_layout =
+            descriptor_layout_manager.acquire::<MeshPerMaterialDescriptorLayout>(rhi)?;
 
         Ok(())
     }
 
     fn setup_pipelines(&mut self, rhi: &VulkanRHI) -> Result<()> {
-        self.m_render_pass
-            .m_render_pipeline
-            .resize_with(1, Default::default);
-
         let vert_shader_module = rhi.create_shader_module(&MESH_POINT_LIGHT_SHADOW_VERT)?;
         let geom_shader_module = rhi.create_shader_module(&MESH_POINT_LIGHT_SHADOW_GEOM)?;
         let frag_shader_module = rhi.create_shader_module(&MESH_POINT_LIGHT_SHADOW_FRAG)?;
@@ -265,23 +282,47 @@ impl PointLightShadowPass {
         let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
             .stage(vk::ShaderStageFlags::VERTEX)
             .module(vert_shader_module)
-            .name(b"main\0");
+            .name(b"main\0")
+            .build();
 
         let geom_stage = vk::PipelineShaderStageCreateInfo::builder()
             .stage(vk::ShaderStageFlags::GEOMETRY)
             .module(geom_shader_module)
-            .name(b"main\0");
+            .name(b"main\0")
+            .build();
 
         let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
             .stage(vk::ShaderStageFlags::FRAGMENT)
             .module(frag_shader_module)
-            .name(b"main\0");
+            .name(b"main\0")
+            .build();
+
+        // constant 0 turns on the alpha test of the fragment shader
+        let alpha_test_map_entries = [vk::SpecializationMapEntry::builder()
+            .constant_id(0)
+            .offset(0)
+            .size(std::mem::size_of::<vk::Bool32>())
+            .build()];
+        let alpha_test_data = vk::TRUE.to_ne_bytes();
+        let alpha_test_specialization_info = vk::SpecializationInfo::builder()
+            .map_entries(&alpha_test_map_entries)
+            .data(&alpha_test_data);
+
+        let alpha_test_frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(frag_shader_module)
+            .name(b"main\0")
+            .specialization_info(&alpha_test_specialization_info)
+            .build();
 
-        let binding_descriptions = &MeshVertex::get_binding_descriptions()[0..1];
-        let attribute_descriptions = &MeshVertex::get_attribute_descriptions()[0..1];
+        // position, and the texcoord the alpha test samples with
+        let binding_descriptions = MeshVertex::get_binding_descriptions();
+        let attribute_descriptions = MeshVertex::get_attribute_descriptions();
+        let binding_descriptions = [binding_descriptions[0], binding_descriptions[2]];
+        let attribute_descriptions = [attribute_descriptions[0], attribute_descriptions[3]];
         let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
-            .vertex_binding_descriptions(binding_descriptions)
-            .vertex_attribute_descriptions(attribute_descriptions);
+            .vertex_binding_descriptions(&binding_descriptions)
+            .vertex_attribute_descriptions(&attribute_descriptions);
 
         let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
             .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
@@ -336,38 +377,52 @@ impl PointLightShadowPass {
             .attachments(&attachments)
             .blend_constants([0.0, 0.0, 0.0, 0.0]);
 
+        // the material set is only bound for masked meshes
         let set_layouts = &[
             self.m_render_pass.m_descriptor_infos[0].layout,
             self.m_per_mesh_layout,
+            self.m_per_material_layout,
         ];
         let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(set_layouts);
 
         let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
 
-        let stages = &[vert_stage, geom_stage, frag_stage];
-        let info = vk::GraphicsPipelineCreateInfo::builder()
-            .stages(stages)
-            .vertex_input_state(&vertex_input_state)
-            .input_assembly_state(&input_assembly_state)
-            .viewport_state(&viewport_state)
-            .rasterization_state(&rasterization_state)
-            .multisample_state(&multisample_state)
-            .depth_stencil_state(&depth_stencil_state)
-            .color_blend_state(&color_blend_state)
-            .layout(pipeline_layout)
-            .render_pass(self.m_render_pass.m_framebuffer.render_pass)
-            .subpass(0)
-            .build();
-
-        let pipeline = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
+        // opaque meshes first, then masked ones
+        let stages = [
+            [vert_stage, geom_stage, frag_stage],
+            [vert_stage, geom_stage, alpha_test_frag_stage],
+        ];
+        let infos: Vec<_> = stages
+            .iter()
+            .map(|stages| {
+                vk::GraphicsPipelineCreateInfo::builder()
+                    .stages(stages)
+                    .vertex_input_state(&vertex_input_state)
+                    .input_assembly_state(&input_assembly_state)
+                    .viewport_state(&viewport_state)
+                    .rasterization_state(&rasterization_state)
+                    .multisample_state(&multisample_state)
+                    .depth_stencil_state(&depth_stencil_state)
+                    .color_blend_state(&color_blend_state)
+                    .layout(pipeline_layout)
+                    .render_pass(self.m_render_pass.m_framebuffer.render_pass)
+                    .subpass(0)
+                    .build()
+            })
+            .collect();
+
+        let pipelines = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &infos)?;
 
         rhi.destroy_shader_module(vert_shader_module);
         rhi.destroy_shader_module(frag_shader_module);
 
-        self.m_render_pass.m_render_pipeline[0] = RenderPipelineBase {
-            layout: pipeline_layout,
-            pipeline,
-        };
+        self.m_render_pass.m_render_pipeline = pipelines
+            .into_iter()
+            .map(|pipeline| RenderPipelineBase {
+                layout: pipeline_layout,
+                pipeline,
+            })
+            .collect();
         Ok(())
     }
 
@@ -430,7 +485,12 @@ impl PointLightShadowPass {
         Ok(())
     }
 
-    fn draw_model(&self, rhi: &VulkanRHI) {
+    fn draw_model(
+        &self,
+        rhi: &VulkanRHI,
+        render_scene: &RenderScene,
+        render_resource: &mut GlobalRenderResource,
+    ) {
         let command_buffer = rhi.get_current_command_buffer();
 
         let mut clear_values: [vk::ClearValue; 2] = [Default::default(); 2];
@@ -457,7 +517,134 @@ impl PointLightShadowPass {
         rhi.cmd_begin_render_pass(command_buffer, &begin_info, vk::SubpassContents::INLINE);
         rhi.push_event(command_buffer, "Point Light Shadow\0", [1.0; 4]);
 
+        if self
+            .m_mesh_point_light_shadow_perframe_storage_buffer_object
+            .point_light_num
+            > 0
+        {
+            self.draw_visible_nodes(rhi, command_buffer, render_scene, render_resource);
+        }
+
         rhi.pop_event(command_buffer);
         rhi.cmd_end_render_pass(command_buffer);
     }
+
+    fn draw_visible_nodes(
+        &self,
+        rhi: &VulkanRHI,
+        command_buffer: vk::CommandBuffer,
+        render_scene: &RenderScene,
+        render_resource: &mut GlobalRenderResource,
+    ) {
+        let perframe_dynamic_offset = round_up(
+            render_resource
+                ._storage_buffer
+                ._global_upload_ringbuffers_end[rhi.get_current_frame_index()],
+            render_resource
+                ._storage_buffer
+                ._min_storage_buffer_offset_alignment,
+        );
+
+        render_resource
+            ._storage_buffer
+            ._global_upload_ringbuffers_end[rhi.get_current_frame_index()] = perframe_dynamic_offset
+            + std::mem::size_of::<MeshPointLightShadowPerframeStorageBufferObject>() as u32;
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                &self.m_mesh_point_light_shadow_perframe_storage_buffer_object as *const _
+                    as *const c_void,
+                render_resource
+                    ._storage_buffer
+                    ._global_upload_ringbuffer_pointer
+                    .add(perframe_dynamic_offset as usize),
+                std::mem::size_of::<MeshPointLightShadowPerframeStorageBufferObject>(),
+            );
+        }
+
+        let visiable_nodes = render_scene.get_point_lights_visible_mesh_nodes();
+        let (opaque_nodes, masked_nodes) = split_masked_mesh_nodes(visiable_nodes);
+
+        for (pipeline, nodes) in self
+            .m_render_pass
+            .m_render_pipeline
+            .iter()
+            .zip([opaque_nodes, masked_nodes])
+        {
+            if nodes.is_empty() {
+                continue;
+            }
+            rhi.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline,
+            );
+
+            for batch in mesh_node_batches(nodes) {
+                if batch[0].alpha_mode == MaterialAlphaMode::Mask {
+                    let ref_material = unsafe { &*batch[0].ref_material.as_ptr() };
+                    rhi.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        pipeline.layout,
+                        2,
+                        &[ref_material.material_descriptor_set],
+                        &[],
+                    );
+                }
+
+                let ref_mesh = unsafe { &*batch[0].ref_mesh.as_ptr() };
+
+                rhi.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.layout,
+                    1,
+                    &[ref_mesh.mesh_vertex_blending_descriptor_set],
+                    &[],
+                );
+
+                let buffers = [
+                    ref_mesh.mesh_vertex_position_buffer,
+                    ref_mesh.mesh_vertex_varying_enable_blending_buffer,
+                    ref_mesh.mesh_vertex_varying_buffer,
+                ];
+
+                rhi.cmd_bind_vertex_buffers(command_buffer, 0, &buffers, &[0, 0, 0]);
+                rhi.cmd_bind_index_buffer(
+                    command_buffer,
+                    ref_mesh.mesh_index_buffer,
+                    0,
+                    ref_mesh.mesh_index_type,
+                );
+
+                let perdrawcall_dynamic_offsets = render_resource
+                    ._storage_buffer
+                    .upload_mesh_instances(rhi.get_current_frame_index(), batch);
+
+                for (index, perdrawcall_dynamic_offset) in
+                    perdrawcall_dynamic_offsets.into_iter().enumerate()
+                {
+                    let current_count = MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT
+                        .min(batch.len() - index * MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT);
+
+                    rhi.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        pipeline.layout,
+                        0,
+                        &[self.m_render_pass.m_descriptor_infos[0].descriptor_set],
+                        &[perframe_dynamic_offset, perdrawcall_dynamic_offset, 0],
+                    );
+                    rhi.cmd_draw_indexed(
+                        command_buffer,
+                        ref_mesh.mesh_index_count,
+                        current_count as u32,
+                        0,
+                        0,
+                        0,
+                    );
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,364 @@
+use std::ffi::CString;
+
+use anyhow::Result;
+use linkme::distributed_slice;
+use vulkanalia::{
+    prelude::v1_0::*,
+    vk::{VertexInputAttributeDescription, VertexInputBindingDescription},
+};
+
+use crate::{
+    function::render::{
+        interface::vulkan::vulkan_rhi::{
+            MAX_SHADER_FULLSCREEN_PASS_COUNT, VULKAN_RHI_DESCRIPTOR_COMBINED_IMAGE_SAMPLER,
+            VULKAN_RHI_DESCRIPTOR_INPUT_ATTACHMENT, VULKAN_RHI_DESCRIPTOR_UNIFORM_BUFFER,
+            VulkanRHI,
+        },
+        render_pass::{Descriptor, RenderPass, RenderPipelineBase},
+        render_pipeline::{MAX_SHADER_FULLSCREEN_INPUT_COUNT, ShaderFullscreenPassConfig},
+        render_type::RHISamplerType,
+    },
+    resource::res_type::data::render_pipeline::ShaderFullscreenInput,
+    shader::generated::shader::POST_PROCESS_VERT,
+};
+
+pub struct ShaderFullscreenInitInfo<'a> {
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+    pub rhi: &'a VulkanRHI,
+    pub config: &'a ShaderFullscreenPassConfig,
+    /// One view per `config.inputs`, in the same order.
+    pub input_attachments: &'a [vk::ImageView],
+}
+
+/// A post pass whose fragment shader comes from a render pipeline preset. Unlike the builtin
+/// post passes the descriptor layout depends on the node, so the pass owns it.
+#[derive(Default)]
+pub struct ShaderFullscreenPass {
+    pub m_render_pass: RenderPass,
+    m_subpass: u32,
+    m_config: Option<ShaderFullscreenPassConfig>,
+    m_params_buffer: vk::Buffer,
+    m_params_buffer_memory: vk::DeviceMemory,
+}
+
+impl ShaderFullscreenPass {
+    pub fn initialize(&mut self, info: &ShaderFullscreenInitInfo) -> Result<()> {
+        self.m_render_pass.m_framebuffer.render_pass = info.render_pass;
+        self.m_subpass = info.subpass;
+        self.m_config = Some(info.config.clone());
+        self.setup_descriptor_layout(info.rhi)?;
+        self.setup_params_buffer(info.rhi)?;
+        self.setup_pipelines(info.rhi)?;
+        self.setup_descriptor_set(info.rhi)?;
+        self.update_after_framebuffer_recreate(info.rhi, info.input_attachments)?;
+        Ok(())
+    }
+    pub fn draw(&self, rhi: &VulkanRHI) {
+        let color = [1.0; 4];
+        let command_buffer = rhi.get_current_command_buffer();
+        rhi.push_event(command_buffer, "Shader Fullscreen\0", color);
+        let info = rhi.get_swapchain_info();
+        rhi.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.m_render_pass.m_render_pipeline[0].pipeline,
+        );
+        rhi.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(info.viewport));
+        rhi.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(info.scissor));
+        rhi.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.m_render_pass.m_render_pipeline[0].layout,
+            0,
+            &[self.m_render_pass.m_descriptor_infos[0].descriptor_set],
+            &[],
+        );
+        rhi.cmd_draw(command_buffer, 3, 1, 0, 0);
+        rhi.pop_event(command_buffer);
+    }
+    pub fn update_after_render_pass_recreate(
+        &mut self,
+        rhi: &VulkanRHI,
+        render_pass: vk::RenderPass,
+        subpass: u32,
+    ) -> Result<()> {
+        for pipeline in self.m_render_pass.m_render_pipeline.drain(..) {
+            rhi.destroy_pipeline(pipeline.pipeline);
+            rhi.destroy_pipeline_layout(pipeline.layout);
+        }
+        self.m_render_pass.m_framebuffer.render_pass = render_pass;
+        self.m_subpass = subpass;
+        self.setup_pipelines(rhi)
+    }
+    pub fn update_after_framebuffer_recreate(
+        &mut self,
+        rhi: &VulkanRHI,
+        input_attachments: &[vk::ImageView],
+    ) -> Result<()> {
+        let config = self.config();
+        let sampler = *rhi.get_or_create_default_sampler(RHISamplerType::Nearest)?;
+        let image_infos = input_attachments
+            .iter()
+            .map(|view| {
+                [vk::DescriptorImageInfo::builder()
+                    .sampler(sampler)
+                    .image_view(*view)
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .build()]
+            })
+            .collect::<Vec<_>>();
+
+        let descriptor_writes = config
+            .inputs
+            .iter()
+            .zip(image_infos.iter())
+            .map(|(input, image_info)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(self.m_render_pass.m_descriptor_infos[0].descriptor_set)
+                    .dst_binding(input.binding)
+                    .descriptor_type(input_descriptor_type(input))
+                    .image_info(image_info)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        rhi.update_descriptor_sets(&descriptor_writes)?;
+        Ok(())
+    }
+    pub fn destroy(&self, rhi: &VulkanRHI) -> Result<()> {
+        for pipeline in &self.m_render_pass.m_render_pipeline {
+            rhi.destroy_pipeline(pipeline.pipeline);
+            rhi.destroy_pipeline_layout(pipeline.layout);
+        }
+        for descriptor in &self.m_render_pass.m_descriptor_infos {
+            rhi.free_descriptor_sets(&[descriptor.descriptor_set])?;
+            rhi.destroy_descriptor_set_layout(descriptor.layout);
+        }
+        if !self.m_params_buffer.is_null() {
+            rhi.destroy_buffer(self.m_params_buffer);
+            rhi.free_memory(self.m_params_buffer_memory);
+        }
+        Ok(())
+    }
+}
+
+#[distributed_slice(VULKAN_RHI_DESCRIPTOR_INPUT_ATTACHMENT)]
+static INPUT_ATTACHMENT_COUNT: u32 =
+    MAX_SHADER_FULLSCREEN_PASS_COUNT * MAX_SHADER_FULLSCREEN_INPUT_COUNT as u32;
+
+#[distributed_slice(VULKAN_RHI_DESCRIPTOR_COMBINED_IMAGE_SAMPLER)]
+static COMBINED_IMAGE_SAMPLER_COUNT: u32 =
+    MAX_SHADER_FULLSCREEN_PASS_COUNT * MAX_SHADER_FULLSCREEN_INPUT_COUNT as u32;
+
+#[distributed_slice(VULKAN_RHI_DESCRIPTOR_UNIFORM_BUFFER)]
+static UNIFORM_BUFFER_COUNT: u32 = MAX_SHADER_FULLSCREEN_PASS_COUNT;
+
+fn input_descriptor_type(input: &ShaderFullscreenInput) -> vk::DescriptorType {
+    if input.sampled {
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+    } else {
+        vk::DescriptorType::INPUT_ATTACHMENT
+    }
+}
+
+impl ShaderFullscreenPass {
+    fn config(&self) -> &ShaderFullscreenPassConfig {
+        self.m_config.as_ref().unwrap()
+    }
+
+    fn setup_descriptor_layout(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let config = self.config();
+        let mut bindings = config
+            .inputs
+            .iter()
+            .map(|input| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(input.binding)
+                    .descriptor_type(input_descriptor_type(input))
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        if !config.params.is_empty() {
+            bindings.push(
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(config.params_binding)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+            );
+        }
+        let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+        let layout = rhi.create_descriptor_set_layout(&create_info)?;
+
+        self.m_render_pass.m_descriptor_infos.clear();
+        self.m_render_pass.m_descriptor_infos.push(Descriptor {
+            layout,
+            descriptor_set: Default::default(),
+        });
+        Ok(())
+    }
+
+    /// The params never change once loaded, so the block is written once. Each float is padded
+    /// to 16 bytes, the std140 stride of a `float params[]` array.
+    fn setup_params_buffer(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let params = self
+            .config()
+            .params
+            .iter()
+            .map(|&param| [param, 0.0, 0.0, 0.0])
+            .collect::<Vec<_>>();
+        if params.is_empty() {
+            return Ok(());
+        }
+        let size = (params.len() * std::mem::size_of::<[f32; 4]>()) as vk::DeviceSize;
+        let (buffer, memory) = rhi.create_buffer(
+            size,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        let data = rhi.map_memory(memory, 0, size, vk::MemoryMapFlags::empty())?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(params.as_ptr(), data.cast(), params.len());
+        }
+        rhi.unmap_memory(memory);
+
+        self.m_params_buffer = buffer;
+        self.m_params_buffer_memory = memory;
+        Ok(())
+    }
+
+    fn setup_pipelines(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        self.m_render_pass.m_render_pipeline.clear();
+        let config = self.config();
+        let entry = CString::new(config.entry.as_str())?;
+        let vert_shader_module = rhi.create_shader_module(&POST_PROCESS_VERT)?;
+        // user SPIR-V can be rejected, which must not leak the module created before it
+        let frag_shader_module = match rhi.create_shader_module(&config.spirv) {
+            Ok(module) => module,
+            Err(err) => {
+                rhi.destroy_shader_module(vert_shader_module);
+                return Err(err);
+            }
+        };
+
+        let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vert_shader_module)
+            .name(b"main\0");
+
+        let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(frag_shader_module)
+            .name(entry.as_bytes_with_nul());
+
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&[] as &[VertexInputBindingDescription])
+            .vertex_attribute_descriptions(&[] as &[VertexInputAttributeDescription]);
+
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_STRIP)
+            .primitive_restart_enable(false);
+
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.0)
+            .cull_mode(vk::CullModeFlags::BACK)
+            .front_face(vk::FrontFace::CLOCKWISE)
+            .depth_bias_enable(false);
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+            .sample_shading_enable(false)
+            .rasterization_samples(vk::SampleCountFlags::_1);
+
+        let attachment = vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::all())
+            .blend_enable(false);
+
+        let attachments = &[attachment];
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .logic_op_enable(false)
+            .logic_op(vk::LogicOp::COPY)
+            .attachments(attachments)
+            .blend_constants([0.0, 0.0, 0.0, 0.0]);
+
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+        let set_layouts = &[self.m_render_pass.m_descriptor_infos[0].layout];
+        let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(set_layouts);
+
+        let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
+
+        let stages = &[vert_stage, frag_stage];
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state)
+            .layout(pipeline_layout)
+            .render_pass(self.m_render_pass.m_framebuffer.render_pass)
+            .subpass(self.m_subpass)
+            .build();
+
+        let pipelines = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info]);
+
+        rhi.destroy_shader_module(vert_shader_module);
+        rhi.destroy_shader_module(frag_shader_module);
+        let pipeline = match pipelines {
+            Ok(pipelines) => pipelines[0],
+            Err(err) => {
+                rhi.destroy_pipeline_layout(pipeline_layout);
+                return Err(err);
+            }
+        };
+
+        self.m_render_pass
+            .m_render_pipeline
+            .push(RenderPipelineBase {
+                layout: pipeline_layout,
+                pipeline,
+            });
+
+        Ok(())
+    }
+
+    fn setup_descriptor_set(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let set_layouts = [self.m_render_pass.m_descriptor_infos[0].layout];
+        let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(rhi.get_descriptor_pool())
+            .set_layouts(&set_layouts);
+
+        self.m_render_pass.m_descriptor_infos[0].descriptor_set =
+            rhi.allocate_descriptor_sets(&alloc_info)?[0];
+
+        if self.m_params_buffer.is_null() {
+            return Ok(());
+        }
+        let buffer_info = [vk::DescriptorBufferInfo::builder()
+            .buffer(self.m_params_buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE)
+            .build()];
+        let descriptor_writes = [vk::WriteDescriptorSet::builder()
+            .dst_set(self.m_render_pass.m_descriptor_infos[0].descriptor_set)
+            .dst_binding(self.config().params_binding)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&buffer_info)
+            .build()];
+        rhi.update_descriptor_sets(&descriptor_writes)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,522 @@
+use anyhow::Result;
+use linkme::distributed_slice;
+use vulkanalia::{
+    prelude::v1_0::*,
+    vk::{VertexInputAttributeDescription, VertexInputBindingDescription},
+};
+
+use crate::{
+    core::math::{matrix4::Matrix4x4, vector3::Vector3},
+    function::render::{
+        interface::vulkan::vulkan_rhi::{VULKAN_RHI_DESCRIPTOR_COMBINED_IMAGE_SAMPLER, VulkanRHI},
+        light::ScreenSpaceAmbientOcclusion,
+        render_common::MeshPerframeStorageBufferObject,
+        render_pass::{
+            Descriptor, DescriptorLayout, DescriptorLayoutRegistry, FrameBufferAttachment,
+            RenderPass, RenderPipelineBase,
+        },
+        render_type::RHISamplerType,
+    },
+    shader::generated::shader::{POST_PROCESS_VERT, SSAO_BLUR_FRAG, SSAO_FRAG},
+};
+
+/// The occlusion straight from the samples, then blurred. Step `i` writes attachment `i` with
+/// pipeline `i` and descriptor set `i`.
+const _SSAO_PASS_RAW: usize = 0;
+const _SSAO_PASS_BLURRED: usize = 1;
+const _SSAO_PASS_STEP_COUNT: usize = 2;
+
+const SSAO_PASS_FORMAT: vk::Format = vk::Format::R8_UNORM;
+
+pub struct SSAOPassInitInfo<'a> {
+    pub rhi: &'a VulkanRHI,
+    pub descriptor_layout_manager: &'a DescriptorLayoutRegistry,
+    pub gbuffer_normal_attachment: vk::ImageView,
+}
+
+/// Keep in sync with the push constants of shader/glsl/ssao.h.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct SSAOPushConstantObject {
+    proj_view_matrix: Matrix4x4,
+    camera_position: Vector3,
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+    sample_count: u32,
+    blur_radius: i32,
+}
+
+/// Ambient occlusion from the GBuffer normals and the depth, rendered between the base pass and
+/// the deferred lighting. Each step is a render pass of its own since it samples neighbouring
+/// pixels, which a subpass cannot.
+#[derive(Default)]
+pub struct SSAOPass {
+    pub m_render_pass: RenderPass,
+    m_framebuffers: Vec<vk::Framebuffer>,
+}
+
+impl SSAOPass {
+    pub fn initialize(&mut self, info: &SSAOPassInitInfo) -> Result<()> {
+        let rhi = info.rhi;
+        self.setup_attachments(rhi)?;
+        self.setup_render_pass(rhi)?;
+        self.setup_framebuffer(rhi)?;
+        self.setup_descriptor_layout(rhi, info.descriptor_layout_manager)?;
+        self.setup_pipelines(rhi)?;
+        self.setup_descriptor_set(rhi)?;
+        self.update_descriptor_set(rhi, info.gbuffer_normal_attachment)?;
+        Ok(())
+    }
+
+    /// The blurred occlusion the deferred lighting samples.
+    pub fn get_ambient_occlusion_image_view(&self) -> vk::ImageView {
+        self.m_render_pass.m_framebuffer.attachments[_SSAO_PASS_BLURRED].view
+    }
+
+    /// With no settings the result is only cleared to unoccluded.
+    pub fn draw(
+        &self,
+        rhi: &VulkanRHI,
+        ambient_occlusion: Option<&ScreenSpaceAmbientOcclusion>,
+        perframe_storage_buffer_object: &MeshPerframeStorageBufferObject,
+    ) {
+        let command_buffer = rhi.get_current_command_buffer();
+        rhi.push_event(command_buffer, "SSAO\0", [1.0; 4]);
+        match ambient_occlusion {
+            Some(ambient_occlusion) => {
+                let push_constants = SSAOPushConstantObject {
+                    proj_view_matrix: perframe_storage_buffer_object.proj_view_matrix,
+                    camera_position: perframe_storage_buffer_object.camera_position,
+                    radius: ambient_occlusion.m_radius,
+                    bias: ambient_occlusion.m_bias,
+                    intensity: ambient_occlusion.m_intensity,
+                    sample_count: ambient_occlusion.m_sample_count,
+                    blur_radius: ambient_occlusion.m_blur_radius as i32,
+                };
+                for step in 0.._SSAO_PASS_STEP_COUNT {
+                    self.draw_step(rhi, step, Some(&push_constants));
+                }
+            }
+            None => self.draw_step(rhi, _SSAO_PASS_BLURRED, None),
+        }
+        rhi.pop_event(command_buffer);
+    }
+
+    pub fn update_after_framebuffer_recreate(
+        &mut self,
+        rhi: &VulkanRHI,
+        gbuffer_normal_attachment: vk::ImageView,
+    ) -> Result<()> {
+        for framebuffer in self.m_framebuffers.drain(..) {
+            rhi.destroy_framebuffer(framebuffer);
+        }
+        self.destroy_attachments(rhi);
+        self.setup_attachments(rhi)?;
+        self.setup_framebuffer(rhi)?;
+        self.update_descriptor_set(rhi, gbuffer_normal_attachment)
+    }
+
+    pub fn destroy(&self, rhi: &VulkanRHI) {
+        for framebuffer in &self.m_framebuffers {
+            rhi.destroy_framebuffer(*framebuffer);
+        }
+        self.destroy_attachments(rhi);
+        for pipeline in &self.m_render_pass.m_render_pipeline {
+            rhi.destroy_pipeline(pipeline.pipeline);
+            rhi.destroy_pipeline_layout(pipeline.layout);
+        }
+        rhi.destroy_render_pass(self.m_render_pass.m_framebuffer.render_pass);
+    }
+
+    fn draw_step(
+        &self,
+        rhi: &VulkanRHI,
+        step: usize,
+        push_constants: Option<&SSAOPushConstantObject>,
+    ) {
+        let command_buffer = rhi.get_current_command_buffer();
+        let swapchain_info = rhi.get_swapchain_info();
+
+        let render_area = vk::Rect2D::builder()
+            .offset(vk::Offset2D::default())
+            .extent(swapchain_info.extent);
+        let mut clear_value = vk::ClearValue::default();
+        clear_value.color.float32 = [1.0, 1.0, 1.0, 1.0];
+        let clear_values = [clear_value];
+        let info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.m_render_pass.m_framebuffer.render_pass)
+            .framebuffer(self.m_framebuffers[step])
+            .render_area(render_area)
+            .clear_values(&clear_values);
+
+        rhi.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+        if let Some(push_constants) = push_constants {
+            let pipeline = &self.m_render_pass.m_render_pipeline[step];
+            rhi.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline,
+            );
+            rhi.cmd_set_viewport(
+                command_buffer,
+                0,
+                std::slice::from_ref(swapchain_info.viewport),
+            );
+            rhi.cmd_set_scissor(
+                command_buffer,
+                0,
+                std::slice::from_ref(swapchain_info.scissor),
+            );
+            rhi.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.layout,
+                0,
+                &[self.m_render_pass.m_descriptor_infos[step].descriptor_set],
+                &[],
+            );
+            rhi.cmd_push_constants(
+                command_buffer,
+                pipeline.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                unsafe {
+                    std::slice::from_raw_parts(
+                        push_constants as *const SSAOPushConstantObject as *const u8,
+                        std::mem::size_of::<SSAOPushConstantObject>(),
+                    )
+                },
+            );
+            rhi.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+        rhi.cmd_end_render_pass(command_buffer);
+    }
+}
+
+pub struct SSAODescriptorLayout;
+impl DescriptorLayout for SSAODescriptorLayout {
+    fn new(rhi: &VulkanRHI) -> Result<vk::DescriptorSetLayout> {
+        let layout_bindings = [
+            // depth
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
+            // GBuffer normals, or the occlusion to blur
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
+        ];
+
+        let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&layout_bindings);
+        let layout = rhi.create_descriptor_set_layout(&create_info)?;
+        Ok(layout)
+    }
+}
+
+#[distributed_slice(VULKAN_RHI_DESCRIPTOR_COMBINED_IMAGE_SAMPLER)]
+static COMBINED_IMAGE_SAMPLER_COUNT: u32 = 2 * _SSAO_PASS_STEP_COUNT as u32;
+
+impl SSAOPass {
+    fn setup_attachments(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let swapchain_info = rhi.get_swapchain_info();
+        self.m_render_pass.m_framebuffer.attachments = (0.._SSAO_PASS_STEP_COUNT)
+            .map(|_| {
+                let (image, mem) = rhi.create_image(
+                    swapchain_info.extent.width,
+                    swapchain_info.extent.height,
+                    SSAO_PASS_FORMAT,
+                    vk::ImageTiling::OPTIMAL,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    vk::ImageCreateFlags::empty(),
+                    1,
+                    1,
+                )?;
+                let view = rhi.create_image_view(
+                    image,
+                    SSAO_PASS_FORMAT,
+                    vk::ImageAspectFlags::COLOR,
+                    vk::ImageViewType::_2D,
+                    1,
+                    1,
+                )?;
+                Ok(FrameBufferAttachment {
+                    image,
+                    mem,
+                    view,
+                    format: SSAO_PASS_FORMAT,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(())
+    }
+
+    fn destroy_attachments(&self, rhi: &VulkanRHI) {
+        for attachment in &self.m_render_pass.m_framebuffer.attachments {
+            rhi.destroy_image_view(attachment.view);
+            rhi.destroy_image(attachment.image);
+            rhi.free_memory(attachment.mem);
+        }
+    }
+
+    fn setup_render_pass(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let attachments = [vk::AttachmentDescription::builder()
+            .format(SSAO_PASS_FORMAT)
+            .samples(vk::SampleCountFlags::_1)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .build()];
+        let color_attachment_refs = [vk::AttachmentReference::builder()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
+        let subpasses = [vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_refs)
+            .build()];
+        let dependencies = [
+            // the GBuffer, the depth or the raw occlusion is written before, and the last frame's
+            // lighting may still be reading the target
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                )
+                .dst_stage_mask(
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                )
+                .src_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )
+                .dst_access_mask(
+                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )
+                .build(),
+            vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                .build(),
+        ];
+
+        let create_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies);
+
+        self.m_render_pass.m_framebuffer.render_pass = rhi.create_render_pass(&create_info)?;
+
+        Ok(())
+    }
+
+    fn setup_framebuffer(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let swapchain_info = rhi.get_swapchain_info();
+        self.m_framebuffers = self
+            .m_render_pass
+            .m_framebuffer
+            .attachments
+            .iter()
+            .map(|attachment| {
+                let attachments = [attachment.view];
+                let create_info = vk::FramebufferCreateInfo::builder()
+                    .render_pass(self.m_render_pass.m_framebuffer.render_pass)
+                    .attachments(&attachments)
+                    .width(swapchain_info.extent.width)
+                    .height(swapchain_info.extent.height)
+                    .layers(1);
+                rhi.create_framebuffer(&create_info)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn setup_descriptor_layout(
+        &mut self,
+        rhi: &VulkanRHI,
+        descriptor_layout_manager: &DescriptorLayoutRegistry,
+    ) -> Result<()> {
+        let layout = descriptor_layout_manager.acquire::<SSAODescriptorLayout>(rhi)?;
+        self.m_render_pass.m_descriptor_infos = (0.._SSAO_PASS_STEP_COUNT)
+            .map(|_| Descriptor {
+                layout,
+                descriptor_set: Default::default(),
+            })
+            .collect();
+        Ok(())
+    }
+
+    fn setup_pipelines(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        self.m_render_pass.m_render_pipeline.clear();
+        for frag_shader in [SSAO_FRAG, SSAO_BLUR_FRAG] {
+            let vert_shader_module = rhi.create_shader_module(&POST_PROCESS_VERT)?;
+            let frag_shader_module = rhi.create_shader_module(frag_shader)?;
+
+            let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vert_shader_module)
+                .name(b"main\0");
+
+            let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag_shader_module)
+                .name(b"main\0");
+
+            let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+                .vertex_binding_descriptions(&[] as &[VertexInputBindingDescription])
+                .vertex_attribute_descriptions(&[] as &[VertexInputAttributeDescription]);
+
+            let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+                .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+                .primitive_restart_enable(false);
+
+            let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+                .viewport_count(1)
+                .scissor_count(1);
+
+            let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+                .depth_clamp_enable(false)
+                .rasterizer_discard_enable(false)
+                .polygon_mode(vk::PolygonMode::FILL)
+                .line_width(1.0)
+                .cull_mode(vk::CullModeFlags::BACK)
+                .front_face(vk::FrontFace::CLOCKWISE)
+                .depth_bias_enable(false);
+
+            let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+                .sample_shading_enable(false)
+                .rasterization_samples(vk::SampleCountFlags::_1);
+
+            let attachment = vk::PipelineColorBlendAttachmentState::builder()
+                .color_write_mask(vk::ColorComponentFlags::all())
+                .blend_enable(false);
+
+            let attachments = &[attachment];
+            let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+                .logic_op_enable(false)
+                .logic_op(vk::LogicOp::COPY)
+                .attachments(attachments)
+                .blend_constants([0.0, 0.0, 0.0, 0.0]);
+
+            let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+                .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+            let set_layouts = &[self.m_render_pass.m_descriptor_infos[0].layout];
+            let push_constant_ranges = [vk::PushConstantRange::builder()
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .offset(0)
+                .size(std::mem::size_of::<SSAOPushConstantObject>() as u32)];
+            let layout_info = vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(set_layouts)
+                .push_constant_ranges(&push_constant_ranges);
+
+            let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
+
+            let stages = &[vert_stage, frag_stage];
+            let info = vk::GraphicsPipelineCreateInfo::builder()
+                .stages(stages)
+                .vertex_input_state(&vertex_input_state)
+                .input_assembly_state(&input_assembly_state)
+                .viewport_state(&viewport_state)
+                .rasterization_state(&rasterization_state)
+                .multisample_state(&multisample_state)
+                .color_blend_state(&color_blend_state)
+                .dynamic_state(&dynamic_state)
+                .layout(pipeline_layout)
+                .render_pass(self.m_render_pass.m_framebuffer.render_pass)
+                .subpass(0)
+                .build();
+
+            let pipeline = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
+
+            rhi.destroy_shader_module(vert_shader_module);
+            rhi.destroy_shader_module(frag_shader_module);
+
+            self.m_render_pass
+                .m_render_pipeline
+                .push(RenderPipelineBase {
+                    layout: pipeline_layout,
+                    pipeline,
+                });
+        }
+        Ok(())
+    }
+
+    fn setup_descriptor_set(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        for descriptor_info in &mut self.m_render_pass.m_descriptor_infos {
+            let set_layouts = [descriptor_info.layout];
+            let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(rhi.get_descriptor_pool())
+                .set_layouts(&set_layouts);
+            descriptor_info.descriptor_set = rhi.allocate_descriptor_sets(&alloc_info)?[0];
+        }
+        Ok(())
+    }
+
+    fn update_descriptor_set(
+        &self,
+        rhi: &VulkanRHI,
+        gbuffer_normal_attachment: vk::ImageView,
+    ) -> Result<()> {
+        let sampler = *rhi.get_or_create_default_sampler(RHISamplerType::Nearest)?;
+        let depth_image_info = [vk::DescriptorImageInfo::builder()
+            .sampler(sampler)
+            .image_view(*rhi.get_depth_image_info().image_view)
+            .image_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+            .build()];
+        let step_input_image_infos = [
+            gbuffer_normal_attachment,
+            self.m_render_pass.m_framebuffer.attachments[_SSAO_PASS_RAW].view,
+        ]
+        .map(|image_view| {
+            [vk::DescriptorImageInfo::builder()
+                .sampler(sampler)
+                .image_view(image_view)
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .build()]
+        });
+
+        let descriptor_writes = self
+            .m_render_pass
+            .m_descriptor_infos
+            .iter()
+            .zip(&step_input_image_infos)
+            .flat_map(|(descriptor_info, step_input_image_info)| {
+                [
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(descriptor_info.descriptor_set)
+                        .dst_binding(0)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .image_info(&depth_image_info)
+                        .build(),
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(descriptor_info.descriptor_set)
+                        .dst_binding(1)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .image_info(step_input_image_info)
+                        .build(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        rhi.update_descriptor_sets(&descriptor_writes)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,522 @@
+use anyhow::Result;
+use linkme::distributed_slice;
+use vulkanalia::{
+    prelude::v1_0::*,
+    vk::{VertexInputAttributeDescription, VertexInputBindingDescription},
+};
+
+use crate::{
+    core::math::matrix4::Matrix4x4,
+    function::render::{
+        interface::vulkan::vulkan_rhi::{
+            VULKAN_RHI_DESCRIPTOR_COMBINED_IMAGE_SAMPLER, VULKAN_RHI_DESCRIPTOR_INPUT_ATTACHMENT,
+            VulkanRHI,
+        },
+        light::TemporalAntiAliasing,
+        render_pass::{
+            Descriptor, DescriptorLayout, DescriptorLayoutRegistry, FrameBufferAttachment,
+            RenderPass, RenderPipelineBase,
+        },
+        render_type::RHISamplerType,
+    },
+    shader::generated::shader::{POST_PROCESS_VERT, TAA_FRAG},
+};
+
+const TAA_PASS_HISTORY_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+pub struct TAAPassInitInfo<'a> {
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+    pub rhi: &'a VulkanRHI,
+    pub descriptor_layout_manager: &'a DescriptorLayoutRegistry,
+    pub input_attachment: vk::ImageView,
+    pub velocity_attachment: vk::ImageView,
+}
+
+/// Keep in sync with the push constants of shader/glsl/taa.frag.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct TAAPushConstantObject {
+    reprojection_matrix: Matrix4x4,
+    history_weight: f32,
+    reset_history: u32,
+    _padding_reset_history_1: u32,
+    _padding_reset_history_2: u32,
+}
+
+/// Temporal anti-aliasing. The camera is jittered every frame, and each frame is blended with
+/// the history of the ones before, reprojected by the velocity and clamped to the neighborhood
+/// of the current pixel. The result lands in the resolve attachment of the main camera pass and
+/// is copied into the history once the render pass ends.
+#[derive(Default)]
+pub struct TAAPass {
+    pub m_render_pass: RenderPass,
+    m_subpass: u32,
+    /// Whether the history holds a frame, it is lost whenever the attachments are recreated.
+    m_history_valid: bool,
+    m_reset_history: bool,
+}
+
+impl TAAPass {
+    pub fn initialize(&mut self, info: &TAAPassInitInfo) -> Result<()> {
+        self.m_render_pass.m_framebuffer.render_pass = info.render_pass;
+        self.m_subpass = info.subpass;
+        self.setup_attachments(info.rhi)?;
+        self.setup_descriptor_layout(info.rhi, info.descriptor_layout_manager)?;
+        self.setup_pipelines(info.rhi)?;
+        self.setup_descriptor_set(info.rhi)?;
+        self.update_descriptor_set(info.rhi, info.input_attachment, info.velocity_attachment)?;
+        Ok(())
+    }
+
+    pub fn prepare_pass_data(&mut self) {
+        self.m_reset_history = !self.m_history_valid;
+        self.m_history_valid = true;
+    }
+
+    /// A new history is moved out of its undefined layout before the main camera pass samples
+    /// it.
+    pub fn prepare_history(&self, rhi: &VulkanRHI) {
+        if !self.m_reset_history {
+            return;
+        }
+        self.history_barrier(
+            rhi,
+            (
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+            (vk::AccessFlags::empty(), vk::AccessFlags::SHADER_READ),
+            (
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+            ),
+        );
+    }
+
+    pub fn draw(
+        &self,
+        rhi: &VulkanRHI,
+        temporal_aa: &TemporalAntiAliasing,
+        reprojection_matrix: &Matrix4x4,
+    ) {
+        let command_buffer = rhi.get_current_command_buffer();
+        rhi.push_event(command_buffer, "TAA\0", [1.0; 4]);
+        let info = rhi.get_swapchain_info();
+        let pipeline = &self.m_render_pass.m_render_pipeline[0];
+        rhi.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline.pipeline,
+        );
+        rhi.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(info.viewport));
+        rhi.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(info.scissor));
+        rhi.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline.layout,
+            0,
+            &[self.m_render_pass.m_descriptor_infos[0].descriptor_set],
+            &[],
+        );
+        let push_constants = TAAPushConstantObject {
+            reprojection_matrix: *reprojection_matrix,
+            history_weight: temporal_aa.m_history_weight,
+            reset_history: (self.m_reset_history || temporal_aa.m_reset_history) as u32,
+            ..Default::default()
+        };
+        rhi.cmd_push_constants(
+            command_buffer,
+            pipeline.layout,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            unsafe {
+                std::slice::from_raw_parts(
+                    &push_constants as *const TAAPushConstantObject as *const u8,
+                    std::mem::size_of::<TAAPushConstantObject>(),
+                )
+            },
+        );
+        rhi.cmd_draw(command_buffer, 3, 1, 0, 0);
+        rhi.pop_event(command_buffer);
+    }
+
+    /// Copies the resolved frame into the history the next frame blends with.
+    pub fn store_history(&self, rhi: &VulkanRHI, resolve_image: vk::Image) {
+        let command_buffer = rhi.get_current_command_buffer();
+        self.history_barrier(
+            rhi,
+            (
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            ),
+            (
+                vk::AccessFlags::SHADER_READ,
+                vk::AccessFlags::TRANSFER_WRITE,
+            ),
+            (
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::TRANSFER,
+            ),
+        );
+
+        let extent = rhi.get_swapchain_info().extent;
+        let subresource = vk::ImageSubresourceLayers::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(0)
+            .base_array_layer(0)
+            .layer_count(1)
+            .build();
+        let region = vk::ImageCopy::builder()
+            .src_subresource(subresource)
+            .src_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+            .dst_subresource(subresource)
+            .dst_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .build();
+        rhi.cmd_copy_image(
+            command_buffer,
+            resolve_image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            self.m_render_pass.m_framebuffer.attachments[0].image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[region],
+        );
+
+        // the next frame writes the resolve attachment again only after the copy read it
+        self.history_barrier(
+            rhi,
+            (
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+            (
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::SHADER_READ,
+            ),
+            (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            ),
+        );
+    }
+
+    /// Rebuilds the pipeline for a recreated main camera render pass, which may also have moved
+    /// this pass to another subpass.
+    pub fn update_after_render_pass_recreate(
+        &mut self,
+        rhi: &VulkanRHI,
+        render_pass: vk::RenderPass,
+        subpass: u32,
+    ) -> Result<()> {
+        for pipeline in self.m_render_pass.m_render_pipeline.drain(..) {
+            rhi.destroy_pipeline(pipeline.pipeline);
+            rhi.destroy_pipeline_layout(pipeline.layout);
+        }
+        self.m_render_pass.m_framebuffer.render_pass = render_pass;
+        self.m_subpass = subpass;
+        self.setup_pipelines(rhi)
+    }
+
+    pub fn update_after_framebuffer_recreate(
+        &mut self,
+        rhi: &VulkanRHI,
+        input_attachment: vk::ImageView,
+        velocity_attachment: vk::ImageView,
+    ) -> Result<()> {
+        self.destroy_attachments(rhi);
+        self.setup_attachments(rhi)?;
+        self.update_descriptor_set(rhi, input_attachment, velocity_attachment)
+    }
+
+    pub fn destroy(&self, rhi: &VulkanRHI) {
+        self.destroy_attachments(rhi);
+        for pipeline in &self.m_render_pass.m_render_pipeline {
+            rhi.destroy_pipeline(pipeline.pipeline);
+            rhi.destroy_pipeline_layout(pipeline.layout);
+        }
+    }
+
+    fn history_barrier(
+        &self,
+        rhi: &VulkanRHI,
+        layouts: (vk::ImageLayout, vk::ImageLayout),
+        access_masks: (vk::AccessFlags, vk::AccessFlags),
+        stage_masks: (vk::PipelineStageFlags, vk::PipelineStageFlags),
+    ) {
+        let barrier = vk::ImageMemoryBarrier::builder()
+            .src_access_mask(access_masks.0)
+            .dst_access_mask(access_masks.1)
+            .old_layout(layouts.0)
+            .new_layout(layouts.1)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.m_render_pass.m_framebuffer.attachments[0].image)
+            .subresource_range(
+                vk::ImageSubresourceRange::builder()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .base_mip_level(0)
+                    .level_count(1)
+                    .base_array_layer(0)
+                    .layer_count(1)
+                    .build(),
+            )
+            .build();
+        rhi.cmd_pipeline_barrier(
+            rhi.get_current_command_buffer(),
+            stage_masks.0,
+            stage_masks.1,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier],
+        );
+    }
+}
+
+pub struct TAADescriptorLayout;
+impl DescriptorLayout for TAADescriptorLayout {
+    fn new(rhi: &VulkanRHI) -> Result<vk::DescriptorSetLayout> {
+        // the scene color, the history and the velocity are sampled, the depth is read in place
+        let mut layout_bindings = (0..3)
+            .map(|binding| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(binding)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        layout_bindings.push(
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(3)
+                .descriptor_type(vk::DescriptorType::INPUT_ATTACHMENT)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
+        );
+
+        let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&layout_bindings);
+        let layout = rhi.create_descriptor_set_layout(&create_info)?;
+        Ok(layout)
+    }
+}
+
+#[distributed_slice(VULKAN_RHI_DESCRIPTOR_COMBINED_IMAGE_SAMPLER)]
+static COMBINED_IMAGE_SAMPLER_COUNT: u32 = 3;
+#[distributed_slice(VULKAN_RHI_DESCRIPTOR_INPUT_ATTACHMENT)]
+static INPUT_ATTACHMENT_COUNT: u32 = 1;
+
+impl TAAPass {
+    fn setup_attachments(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let extent = rhi.get_swapchain_info().extent;
+        let (image, mem) = rhi.create_image(
+            extent.width,
+            extent.height,
+            TAA_PASS_HISTORY_FORMAT,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::ImageCreateFlags::empty(),
+            1,
+            1,
+        )?;
+        let view = rhi.create_image_view(
+            image,
+            TAA_PASS_HISTORY_FORMAT,
+            vk::ImageAspectFlags::COLOR,
+            vk::ImageViewType::_2D,
+            1,
+            1,
+        )?;
+        self.m_render_pass.m_framebuffer.attachments = vec![FrameBufferAttachment {
+            image,
+            mem,
+            view,
+            format: TAA_PASS_HISTORY_FORMAT,
+        }];
+        self.m_history_valid = false;
+        Ok(())
+    }
+
+    fn destroy_attachments(&self, rhi: &VulkanRHI) {
+        for attachment in &self.m_render_pass.m_framebuffer.attachments {
+            rhi.destroy_image_view(attachment.view);
+            rhi.destroy_image(attachment.image);
+            rhi.free_memory(attachment.mem);
+        }
+    }
+
+    fn setup_descriptor_layout(
+        &mut self,
+        rhi: &VulkanRHI,
+        descriptor_layout_manager: &DescriptorLayoutRegistry,
+    ) -> Result<()> {
+        self.m_render_pass.m_descriptor_infos.clear();
+        let layout = descriptor_layout_manager.acquire::<TAADescriptorLayout>(rhi)?;
+        self.m_render_pass.m_descriptor_infos.push(Descriptor {
+            layout,
+            descriptor_set: Default::default(),
+        });
+        Ok(())
+    }
+
+    fn setup_pipelines(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        self.m_render_pass.m_render_pipeline.clear();
+        let vert_shader_module = rhi.create_shader_module(&POST_PROCESS_VERT)?;
+        let frag_shader_module = rhi.create_shader_module(&TAA_FRAG)?;
+
+        let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vert_shader_module)
+            .name(b"main\0");
+
+        let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(frag_shader_module)
+            .name(b"main\0");
+
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&[] as &[VertexInputBindingDescription])
+            .vertex_attribute_descriptions(&[] as &[VertexInputAttributeDescription]);
+
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+            .primitive_restart_enable(false);
+
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.0)
+            .cull_mode(vk::CullModeFlags::BACK)
+            .front_face(vk::FrontFace::CLOCKWISE)
+            .depth_bias_enable(false);
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+            .sample_shading_enable(false)
+            .rasterization_samples(vk::SampleCountFlags::_1);
+
+        let attachment = vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::all())
+            .blend_enable(false);
+
+        let attachments = &[attachment];
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .logic_op_enable(false)
+            .logic_op(vk::LogicOp::COPY)
+            .attachments(attachments)
+            .blend_constants([0.0, 0.0, 0.0, 0.0]);
+
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+
+        let set_layouts = &[self.m_render_pass.m_descriptor_infos[0].layout];
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(std::mem::size_of::<TAAPushConstantObject>() as u32)];
+        let layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+
+        let pipeline_layout = rhi.create_pipeline_layout(&layout_info)?;
+
+        let stages = &[vert_stage, frag_stage];
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state)
+            .layout(pipeline_layout)
+            .render_pass(self.m_render_pass.m_framebuffer.render_pass)
+            .subpass(self.m_subpass)
+            .build();
+
+        let pipeline = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
+
+        rhi.destroy_shader_module(vert_shader_module);
+        rhi.destroy_shader_module(frag_shader_module);
+
+        self.m_render_pass
+            .m_render_pipeline
+            .push(RenderPipelineBase {
+                layout: pipeline_layout,
+                pipeline,
+            });
+
+        Ok(())
+    }
+
+    fn setup_descriptor_set(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        let set_layouts = [self.m_render_pass.m_descriptor_infos[0].layout];
+        let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(rhi.get_descriptor_pool())
+            .set_layouts(&set_layouts);
+
+        self.m_render_pass.m_descriptor_infos[0].descriptor_set =
+            rhi.allocate_descriptor_sets(&alloc_info)?[0];
+        Ok(())
+    }
+
+    fn update_descriptor_set(
+        &self,
+        rhi: &VulkanRHI,
+        input_attachment: vk::ImageView,
+        velocity_attachment: vk::ImageView,
+    ) -> Result<()> {
+        let nearest_sampler = *rhi.get_or_create_default_sampler(RHISamplerType::Nearest)?;
+        let linear_sampler = *rhi.get_or_create_default_sampler(RHISamplerType::Linear)?;
+        // the depth belongs to the rhi, and always feeds TAA as its second input attachment
+        let image_infos = [
+            (nearest_sampler, input_attachment),
+            (
+                linear_sampler,
+                self.m_render_pass.m_framebuffer.attachments[0].view,
+            ),
+            (nearest_sampler, velocity_attachment),
+            (nearest_sampler, *rhi.get_depth_image_info().image_view),
+        ]
+        .map(|(sampler, image_view)| {
+            [vk::DescriptorImageInfo::builder()
+                .sampler(sampler)
+                .image_view(image_view)
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .build()]
+        });
+
+        let descriptor_writes = image_infos
+            .iter()
+            .enumerate()
+            .map(|(binding, image_info)| {
+                let descriptor_type = if binding == 3 {
+                    vk::DescriptorType::INPUT_ATTACHMENT
+                } else {
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+                };
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(self.m_render_pass.m_descriptor_infos[0].descriptor_set)
+                    .dst_binding(binding as u32)
+                    .descriptor_type(descriptor_type)
+                    .image_info(image_info)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        rhi.update_descriptor_sets(&descriptor_writes)?;
+        Ok(())
+    }
+}
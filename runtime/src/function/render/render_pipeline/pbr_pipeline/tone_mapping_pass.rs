@@ -9,13 +9,14 @@ use crate::{
     function::render::{
         interface::vulkan::vulkan_rhi::{VULKAN_RHI_DESCRIPTOR_INPUT_ATTACHMENT, VulkanRHI}, render_pass::{
             Descriptor, DescriptorLayout, DescriptorLayoutRegistry, RenderPass, RenderPipelineBase,
-        }, render_pipeline::pbr_pipeline::main_camera_pass::MainCameraSubPass, render_resource::GlobalRenderResource, render_type::RHISamplerType
+        }, render_resource::GlobalRenderResource, render_type::RHISamplerType
     },
     shader::generated::shader::{POST_PROCESS_VERT, TONE_MAPPING_FRAG},
 };
 
 pub struct ToneMappingInitInfo<'a> {
     pub render_pass: vk::RenderPass,
+    pub subpass: u32,
     pub descriptor_layout_manager: &'a DescriptorLayoutRegistry,
     pub rhi: &'a VulkanRHI,
     pub input_attachment: vk::ImageView,
@@ -25,11 +26,13 @@ pub struct ToneMappingInitInfo<'a> {
 #[derive(Default)]
 pub struct ToneMappingPass {
     pub m_render_pass: RenderPass,
+    m_subpass: u32,
 }
 
 impl ToneMappingPass {
     pub fn initialize(&mut self, info: &ToneMappingInitInfo) -> Result<()> {
         self.m_render_pass.m_framebuffer.render_pass = info.render_pass;
+        self.m_subpass = info.subpass;
         self.setup_descriptor_layout(info.rhi, &info.descriptor_layout_manager)?;
         self.setup_pipelines(info.rhi)?;
         self.setup_descriptor_set(info.rhi)?;
@@ -59,6 +62,22 @@ impl ToneMappingPass {
         rhi.cmd_draw(command_buffer, 3, 1, 0, 0);
         rhi.pop_event(command_buffer);
     }
+    /// Rebuilds the pipeline for a recreated main camera render pass, which may also have moved
+    /// this pass to another subpass.
+    pub fn update_after_render_pass_recreate(
+        &mut self,
+        rhi: &VulkanRHI,
+        render_pass: vk::RenderPass,
+        subpass: u32,
+    ) -> Result<()> {
+        for pipeline in self.m_render_pass.m_render_pipeline.drain(..) {
+            rhi.destroy_pipeline(pipeline.pipeline);
+            rhi.destroy_pipeline_layout(pipeline.layout);
+        }
+        self.m_render_pass.m_framebuffer.render_pass = render_pass;
+        self.m_subpass = subpass;
+        self.setup_pipelines(rhi)
+    }
     pub fn update_after_framebuffer_recreate(
         &mut self,
         rhi: &VulkanRHI,
@@ -186,7 +205,7 @@ impl ToneMappingPass {
             .dynamic_state(&dynamic_state)
             .layout(pipeline_layout)
             .render_pass(self.m_render_pass.m_framebuffer.render_pass)
-            .subpass(MainCameraSubPass::ToneMapping as u32)
+            .subpass(self.m_subpass)
             .build();
 
         let pipeline = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
@@ -11,7 +11,7 @@ use crate::{
             }, render_pass::{
                 Descriptor, DescriptorLayout, DescriptorLayoutRegistry, RenderPass,
                 RenderPipelineBase,
-            }, render_pipeline::pbr_pipeline::main_camera_pass::MainCameraSubPass, render_resource::GlobalRenderResource, render_type::RHISamplerType
+            }, render_resource::GlobalRenderResource, render_type::RHISamplerType
         },
         ui::ui2::{UiDrawCmd, UiRuntime, UiVertex},
     },
@@ -20,6 +20,7 @@ use crate::{
 
 pub struct UIPassInitInfo<'a> {
     pub render_pass: vk::RenderPass,
+    pub subpass: u32,
     pub rhi: &'a VulkanRHI,
     pub descriptor_layout_manager: &'a DescriptorLayoutRegistry,
     pub global_render_resource: &'a GlobalRenderResource,
@@ -31,6 +32,7 @@ pub struct UIPass {
     renderer_data: [RefCell<RendererData>; K_MAX_FRAMES_IN_FLIGHT],
     texture_resources: RefCell<Vec<Option<UiTextureGpuResource>>>,
     synced_texture_version: RefCell<u64>,
+    m_subpass: u32,
 }
 
 #[derive(Copy, Clone, Default)]
@@ -45,6 +47,7 @@ impl UIPass {
     pub fn initialize(&mut self, info: &UIPassInitInfo) -> Result<()> {
 
         self.m_render_pass.m_framebuffer.render_pass = info.render_pass;
+        self.m_subpass = info.subpass;
         self.setup_descriptor_layout(info.rhi, &info.descriptor_layout_manager)?;
         self.setup_pipelines(info.rhi)?;
         self.update_after_framebuffer_recreate(info.rhi)?;
@@ -61,6 +64,22 @@ impl UIPass {
         rhi.pop_event(command_buffer);
     }
 
+    /// Rebuilds the pipeline for a recreated main camera render pass, which moves this pass
+    /// whenever the number of post passes changes.
+    pub fn update_after_render_pass_recreate(
+        &mut self,
+        rhi: &VulkanRHI,
+        render_pass: vk::RenderPass,
+        subpass: u32,
+    ) -> Result<()> {
+        for pipeline in self.m_render_pass.m_render_pipeline.drain(..) {
+            rhi.destroy_pipeline(pipeline.pipeline);
+            rhi.destroy_pipeline_layout(pipeline.layout);
+        }
+        self.m_render_pass.m_framebuffer.render_pass = render_pass;
+        self.m_subpass = subpass;
+        self.setup_pipelines(rhi)
+    }
     pub fn update_after_framebuffer_recreate(&mut self, _rhi: &VulkanRHI) -> Result<()> {
         Ok(())
     }
@@ -316,7 +335,7 @@ impl UIPass {
             .dynamic_state(&dynamic_state)
             .layout(pipeline_layout)
             .render_pass(self.m_render_pass.m_framebuffer.render_pass)
-            .subpass(MainCameraSubPass::UI as u32)
+            .subpass(self.m_subpass)
             .build();
 
         let pipeline = rhi.create_graphics_pipelines(vk::PipelineCache::null(), &[info])?[0];
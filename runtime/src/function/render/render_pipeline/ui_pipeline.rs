@@ -10,8 +10,8 @@ use crate::{
                 K_MAX_FRAMES_IN_FLIGHT, VULKAN_RHI_DESCRIPTOR_COMBINED_IMAGE_SAMPLER, VulkanRHI,
             },
             render_pass::{
-                Descriptor, DescriptorLayout, DescriptorLayoutRegistry, RenderPass,
-                RenderPipelineBase,
+                Descriptor, DescriptorLayout, DescriptorLayoutRegistry, FrameBufferAttachment,
+                RenderPass, RenderPipelineBase,
             },
             render_pipeline::RenderPipelineTrait,
             render_resource::{GlobalRenderResource, RenderResource},
@@ -26,6 +26,8 @@ use crate::{
 pub struct UIRenderPipelineCreateInfo<'a> {
     pub rhi: &'a VulkanRHI,
     pub descriptor_layout_registry: &'a DescriptorLayoutRegistry,
+    /// Above one sample the UI renders to a multisampled target resolved into the swapchain.
+    pub msaa_samples: vk::SampleCountFlags,
 }
 
 pub struct UIRenderPipeline {
@@ -34,7 +36,10 @@ pub struct UIRenderPipeline {
 
 impl UIRenderPipeline {
     pub fn create(create_info: &UIRenderPipelineCreateInfo) -> Result<Self> {
-        let mut pass = UIOnlyPass::default();
+        let mut pass = UIOnlyPass {
+            msaa_samples: create_info.msaa_samples,
+            ..Default::default()
+        };
         pass.initialize(create_info.rhi, create_info.descriptor_layout_registry)?;
         Ok(Self { pass })
     }
@@ -64,8 +69,8 @@ impl RenderPipelineTrait for UIRenderPipeline {
         &mut self,
         rhi: &VulkanRHI,
         _render_resource: &GlobalRenderResource,
-    ) {
-        self.pass.recreate_after_swapchain(rhi).unwrap();
+    ) -> Result<()> {
+        self.pass.recreate_after_swapchain(rhi)
     }
 }
 
@@ -73,6 +78,9 @@ impl RenderPipelineTrait for UIRenderPipeline {
 struct UIOnlyPass {
     render_pass: RenderPass,
     swapchain_framebuffers: Vec<vk::Framebuffer>,
+    msaa_samples: vk::SampleCountFlags,
+    /// Only created with MSAA on, sized like the swapchain.
+    msaa_attachment: FrameBufferAttachment,
     renderer_data: [RefCell<RendererData>; K_MAX_FRAMES_IN_FLIGHT],
     texture_resources: RefCell<Vec<Option<UiTextureGpuResource>>>,
     synced_texture_version: RefCell<u64>,
@@ -91,12 +99,15 @@ impl UIOnlyPass {
         self.setup_render_pass(rhi)?;
         self.setup_descriptor_layout(rhi, registry)?;
         self.setup_pipeline(rhi)?;
+        self.setup_msaa_attachment(rhi)?;
         self.setup_framebuffers(rhi)?;
         Ok(())
     }
 
     fn recreate_after_swapchain(&mut self, rhi: &VulkanRHI) -> Result<()> {
         self.destroy_framebuffers(rhi);
+        self.destroy_msaa_attachment(rhi);
+        self.setup_msaa_attachment(rhi)?;
         self.setup_framebuffers(rhi)?;
         Ok(())
     }
@@ -105,6 +116,7 @@ impl UIOnlyPass {
         for framebuffer in &self.swapchain_framebuffers {
             rhi.destroy_framebuffer(*framebuffer);
         }
+        self.destroy_msaa_attachment(rhi);
         self.destroy_texture_resources(rhi);
         if let Some(pipe) = self.render_pass.m_render_pipeline.first() {
             rhi.destroy_pipeline(pipe.pipeline);
@@ -122,11 +134,12 @@ impl UIOnlyPass {
         let render_area = vk::Rect2D::builder()
             .offset(vk::Offset2D::default())
             .extent(swapchain_info.extent);
+        // the resolve attachment is not cleared, so its clear value goes unused
         let clear_values = [vk::ClearValue {
             color: vk::ClearColorValue {
                 float32: [0.0, 0.0, 0.0, 0.0],
             },
-        }];
+        }; 2];
         let begin_info = vk::RenderPassBeginInfo::builder()
             .render_pass(self.render_pass.m_framebuffer.render_pass)
             .framebuffer(self.swapchain_framebuffers[current_swapchain_image_index])
@@ -137,26 +150,54 @@ impl UIOnlyPass {
         rhi.cmd_end_render_pass(command_buffer);
     }
 
+    fn is_multisampled(&self) -> bool {
+        self.msaa_samples != vk::SampleCountFlags::_1
+    }
+
     fn setup_render_pass(&mut self, rhi: &VulkanRHI) -> Result<()> {
         let swapchain_info = rhi.get_swapchain_info();
-        let attachments = [vk::AttachmentDescription::builder()
-            .format(swapchain_info.image_format)
-            .samples(vk::SampleCountFlags::_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
-            .build()];
+        let mut attachments = vec![
+            vk::AttachmentDescription::builder()
+                .format(swapchain_info.image_format)
+                .samples(self.msaa_samples)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+                .build(),
+        ];
         let color_attachment_ref = [vk::AttachmentReference::builder()
             .attachment(0)
             .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
             .build()];
-        let subpasses = [vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_ref)
+        let resolve_attachment_ref = [vk::AttachmentReference::builder()
+            .attachment(1)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
             .build()];
+        let mut subpass = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_ref);
+        // the samples are resolved into the swapchain image, which is all that is kept
+        if self.is_multisampled() {
+            attachments[0].store_op = vk::AttachmentStoreOp::DONT_CARE;
+            attachments[0].final_layout = vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
+            attachments.push(
+                vk::AttachmentDescription::builder()
+                    .format(swapchain_info.image_format)
+                    .samples(vk::SampleCountFlags::_1)
+                    .load_op(vk::AttachmentLoadOp::DONT_CARE)
+                    .store_op(vk::AttachmentStoreOp::STORE)
+                    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                    .initial_layout(vk::ImageLayout::UNDEFINED)
+                    .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+                    .build(),
+            );
+            subpass = subpass.resolve_attachments(&resolve_attachment_ref);
+        }
+        let subpasses = [subpass.build()];
         let create_info = vk::RenderPassCreateInfo::builder()
             .attachments(&attachments)
             .subpasses(&subpasses);
@@ -170,7 +211,11 @@ impl UIOnlyPass {
             .image_views
             .iter()
             .map(|image_view| {
-                let attachments = [*image_view];
+                let attachments = if self.is_multisampled() {
+                    vec![self.msaa_attachment.view, *image_view]
+                } else {
+                    vec![*image_view]
+                };
                 let create_info = vk::FramebufferCreateInfo::builder()
                     .render_pass(self.render_pass.m_framebuffer.render_pass)
                     .attachments(&attachments)
@@ -190,6 +235,40 @@ impl UIOnlyPass {
         }
     }
 
+    fn setup_msaa_attachment(&mut self, rhi: &VulkanRHI) -> Result<()> {
+        if !self.is_multisampled() {
+            return Ok(());
+        }
+        let swapchain_info = rhi.get_swapchain_info();
+        let attachment = &mut self.msaa_attachment;
+        attachment.format = swapchain_info.image_format;
+        (attachment.image, attachment.mem) = rhi.create_multisampled_image(
+            swapchain_info.extent.width,
+            swapchain_info.extent.height,
+            attachment.format,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            self.msaa_samples,
+        )?;
+        attachment.view = rhi.create_image_view(
+            attachment.image,
+            attachment.format,
+            vk::ImageAspectFlags::COLOR,
+            vk::ImageViewType::_2D,
+            1,
+            1,
+        )?;
+        Ok(())
+    }
+
+    fn destroy_msaa_attachment(&self, rhi: &VulkanRHI) {
+        if self.msaa_attachment.image == vk::Image::null() {
+            return;
+        }
+        rhi.destroy_image_view(self.msaa_attachment.view);
+        rhi.destroy_image(self.msaa_attachment.image);
+        rhi.free_memory(self.msaa_attachment.mem);
+    }
+
     fn setup_descriptor_layout(
         &mut self,
         rhi: &VulkanRHI,
@@ -239,7 +318,7 @@ impl UIOnlyPass {
             .depth_bias_enable(false);
         let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
             .sample_shading_enable(false)
-            .rasterization_samples(vk::SampleCountFlags::_1);
+            .rasterization_samples(self.msaa_samples);
         let attachment = vk::PipelineColorBlendAttachmentState::builder()
             .color_write_mask(vk::ColorComponentFlags::all())
             .blend_enable(true)
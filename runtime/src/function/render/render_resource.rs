@@ -1,37 +1,48 @@
 use anyhow::Result;
 use itertools::Itertools;
 use linkme::distributed_slice;
+use log::{error, warn};
 use std::{
-    collections::HashMap, f32::consts::PI, os::raw::c_void, ptr::copy_nonoverlapping, rc::Rc,
+    borrow::Cow, collections::HashMap, f32::consts::PI, os::raw::c_void, ptr::copy_nonoverlapping,
+    rc::Rc,
 };
 use vulkanalia::prelude::v1_0::*;
 
 use crate::{
     core::math::{vector2::Vector2, vector3::Vector3, vector4::Vector4},
     function::render::{
+        ibl_precompute,
         interface::vulkan::vulkan_rhi::{self, K_MAX_FRAMES_IN_FLIGHT, VulkanRHI},
         render_camera::RenderCamera,
         render_common::{
+            MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT,
             MeshDirectionalLightShadowPerframeStorageBufferObject,
             MeshInefficientPickPerframeStorageBufferObject, MeshPerMaterialUniformBufferObject,
-            MeshPerframeStorageBufferObject, MeshPointLightShadowPerframeStorageBufferObject,
-            TextureDataToUpdate, VulkanMesh, VulkanPBRMaterial,
+            MeshPerdrawcallStorageBufferObject, MeshPerframeStorageBufferObject,
+            MeshPointLightShadowPerframeStorageBufferObject, RenderMeshNode,
+            S_CLUSTERED_LIGHT_TYPE_POINT, S_CLUSTERED_LIGHT_TYPE_SPOT, S_MAX_CLUSTERED_LIGHT_COUNT,
+            S_MAX_DIRECTIONAL_LIGHT_CASCADE_COUNT, S_MAX_POINT_LIGHT_COUNT,
+            S_MAX_SPOT_LIGHT_SHADOW_COUNT, S_NORMAL_ENCODING_RG_SNORM, S_NORMAL_ENCODING_RG_UNORM,
+            S_NORMAL_ENCODING_RGB, SharedMeshLightClusterStorageBufferObject, TextureDataToUpdate,
+            VulkanClusteredLight, VulkanMesh, VulkanMeshInstance, VulkanPBRMaterial,
         },
         render_entity::RenderEntity,
-        render_helper::calculate_directional_light_camera,
+        render_helper::{calculate_light_clusters, round_up},
         render_mesh::{
             VulkanMeshVertexPosition, VulkanMeshVertexVarying,
             VulkanMeshVertexVaryingEnableBlending,
         },
         render_resource_base::RenderResourceBase,
         render_scene::RenderScene,
-        render_swap_context::LevelResourceDesc,
+        render_swap_context::{LevelIBLResourceDesc, LevelResourceDesc},
         render_type::{
             MeshVertexDataDefinition, RHISamplerType, RenderMaterialData, RenderMeshData,
             TextureData,
         },
+        sky_atmosphere::SkyAtmosphere,
+        texture_decoder::{self, is_block_compressed},
     },
-    resource::asset_manager::AssetManager,
+    resource::{asset_manager::AssetManager, res_type::data::material::MaterialAlphaMode},
 };
 
 #[distributed_slice(vulkan_rhi::VULKAN_RHI_DESCRIPTOR_STORAGE_BUFFER)]
@@ -80,6 +91,54 @@ pub struct StorageBuffer {
     pub _global_null_descriptor_storage_buffer_memory: vk::DeviceMemory,
 }
 
+impl StorageBuffer {
+    /// Writes the model matrices of one batch into consecutive per-drawcall blocks of
+    /// `MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT` instances with a single ring buffer allocation,
+    /// returning the dynamic offset of each block. Every pass's per-drawcall object has the
+    /// layout of `MeshPerdrawcallStorageBufferObject`.
+    pub fn upload_mesh_instances(
+        &mut self,
+        frame_index: usize,
+        nodes: &[RenderMeshNode],
+    ) -> Vec<u32> {
+        let block_size = round_up(
+            size_of::<MeshPerdrawcallStorageBufferObject>() as u32,
+            self._min_storage_buffer_offset_alignment,
+        );
+        let block_count = nodes.len().div_ceil(MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT) as u32;
+
+        let first_block_offset = round_up(
+            self._global_upload_ringbuffers_end[frame_index],
+            self._min_storage_buffer_offset_alignment,
+        );
+        self._global_upload_ringbuffers_end[frame_index] =
+            first_block_offset + block_size * block_count;
+
+        for (i, node) in nodes.iter().enumerate() {
+            let mut instance = VulkanMeshInstance::default();
+            instance.model_matrix = *node.model_matrix;
+            instance.previous_model_matrix = *node.previous_model_matrix;
+
+            let block_offset =
+                first_block_offset + block_size * (i / MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT) as u32;
+            let instance_offset =
+                (i % MESH_PER_DRAWCALL_MAX_INSTANCE_COUNT) * size_of::<VulkanMeshInstance>();
+            unsafe {
+                copy_nonoverlapping(
+                    &instance as *const _ as *const c_void,
+                    self._global_upload_ringbuffer_pointer
+                        .add(block_offset as usize + instance_offset),
+                    size_of::<VulkanMeshInstance>(),
+                );
+            }
+        }
+
+        (0..block_count)
+            .map(|block| first_block_offset + block_size * block)
+            .collect()
+    }
+}
+
 #[derive(Default)]
 pub struct GlobalRenderResource {
     pub _ibl_resource: IBLResource,
@@ -101,10 +160,14 @@ pub struct RenderResource {
     pub m_mesh_perframe_storage_buffer_object: MeshPerframeStorageBufferObject,
     pub m_mesh_point_light_shadow_perframe_storage_buffer_object:
         MeshPointLightShadowPerframeStorageBufferObject,
-    pub m_mesh_directional_light_shadow_perframe_storage_buffer_object:
-        MeshDirectionalLightShadowPerframeStorageBufferObject,
+    pub m_mesh_directional_light_shadow_perframe_storage_buffer_objects:
+        [MeshDirectionalLightShadowPerframeStorageBufferObject;
+            S_MAX_DIRECTIONAL_LIGHT_CASCADE_COUNT],
+    pub m_mesh_spot_light_shadow_perframe_storage_buffer_objects:
+        [MeshDirectionalLightShadowPerframeStorageBufferObject; S_MAX_SPOT_LIGHT_SHADOW_COUNT],
     pub m_mesh_inefficient_pick_perframe_storage_buffer_object:
         MeshInefficientPickPerframeStorageBufferObject,
+    pub m_mesh_light_cluster_storage_buffer_object: SharedMeshLightClusterStorageBufferObject,
 
     pub m_vulkan_meshes: HashMap<usize, Rc<VulkanMesh>>,
     pub m_vulkan_pbr_materials: HashMap<usize, Rc<VulkanPBRMaterial>>,
@@ -174,113 +237,11 @@ impl RenderResource {
     ) {
         self.create_and_map_storage_buffer(rhi);
 
-        let skybox_irradiance_map = &level_resource_desc
-            .m_ibl_resource_desc
-            .m_skybox_irradiance_map;
-        let irradiace_pos_x_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_irradiance_map.positive_x_map,
-            4,
-        )
-        .unwrap();
-        let irradiace_neg_x_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_irradiance_map.negative_x_map,
-            4,
-        )
-        .unwrap();
-        let irradiace_pos_y_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_irradiance_map.positive_y_map,
-            4,
-        )
-        .unwrap();
-        let irradiace_neg_y_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_irradiance_map.negative_y_map,
-            4,
-        )
-        .unwrap();
-        let irradiace_pos_z_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_irradiance_map.positive_z_map,
-            4,
-        )
-        .unwrap();
-        let irradiace_neg_z_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_irradiance_map.negative_z_map,
-            4,
-        )
-        .unwrap();
-
-        let skybox_specular_map = &level_resource_desc
-            .m_ibl_resource_desc
-            .m_skybox_specular_map;
-        let specular_pos_x_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_specular_map.positive_x_map,
-            4,
-        )
-        .unwrap();
-        let specular_neg_x_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_specular_map.negative_x_map,
-            4,
-        )
-        .unwrap();
-        let specular_pos_y_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_specular_map.positive_y_map,
-            4,
-        )
-        .unwrap();
-        let specular_neg_y_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_specular_map.negative_y_map,
-            4,
-        )
-        .unwrap();
-        let specular_pos_z_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_specular_map.positive_z_map,
-            4,
-        )
-        .unwrap();
-        let specular_neg_z_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &skybox_specular_map.negative_z_map,
-            4,
-        )
-        .unwrap();
-
-        let brdf_map = RenderResourceBase::load_texture_hdr(
-            asset_manager,
-            &level_resource_desc.m_ibl_resource_desc.m_brdf_map,
-            4,
-        )
-        .unwrap();
+        let (irradiance_map, specular_map, brdf_map) =
+            Self::load_ibl_maps(asset_manager, &level_resource_desc.m_ibl_resource_desc);
 
         self.create_ibl_samplers(rhi);
-
-        let irradiance_maps = [
-            irradiace_pos_x_map,
-            irradiace_neg_x_map,
-            irradiace_pos_z_map,
-            irradiace_neg_z_map,
-            irradiace_pos_y_map,
-            irradiace_neg_y_map,
-        ];
-        let specular_maps = [
-            specular_pos_x_map,
-            specular_neg_x_map,
-            specular_pos_z_map,
-            specular_neg_z_map,
-            specular_pos_y_map,
-            specular_neg_y_map,
-        ];
-
-        self.create_ibl_textures(rhi, &irradiance_maps, &specular_maps);
+        self.create_ibl_textures(rhi, &irradiance_map, &specular_map);
 
         (
             self.m_global_render_resource
@@ -292,15 +253,7 @@ impl RenderResource {
             self.m_global_render_resource
                 ._ibl_resource
                 ._brdf_lut_texture_image_view,
-        ) = rhi
-            .create_texture_image(
-                brdf_map.m_width,
-                brdf_map.m_height,
-                &brdf_map.m_pixels,
-                brdf_map.m_format,
-                0,
-            )
-            .unwrap();
+        ) = Self::create_texture_image(rhi, &brdf_map).unwrap();
 
         let color_grading_map = RenderResourceBase::load_texture(
             asset_manager,
@@ -321,15 +274,7 @@ impl RenderResource {
             self.m_global_render_resource
                 ._color_grading_resource
                 ._color_grading_lut_texture_image_view,
-        ) = rhi
-            .create_texture_image(
-                color_grading_map.m_width,
-                color_grading_map.m_height,
-                &color_grading_map.m_pixels,
-                color_grading_map.m_format,
-                0,
-            )
-            .unwrap();
+        ) = Self::create_texture_image(rhi, &color_grading_map).unwrap();
     }
 
     pub fn update_per_frame_buffer(&mut self, render_scene: &RenderScene, camera: &RenderCamera) {
@@ -339,9 +284,20 @@ impl RenderResource {
         let proj_view_matrix = proj_matrix * view_matrix;
 
         let ambient_light = render_scene.m_ambient_light.m_irradiance;
-        let point_light_num = render_scene.m_point_light_list.m_lights.len();
+        // only the nearest point lights have shadow maps; the rest are shaded through the clusters
+        let point_light_num = render_scene
+            .m_point_light_list
+            .m_lights
+            .len()
+            .min(S_MAX_POINT_LIGHT_COUNT);
 
         self.m_mesh_perframe_storage_buffer_object.proj_view_matrix = proj_view_matrix;
+        self.m_mesh_perframe_storage_buffer_object
+            .previous_unjittered_proj_view_matrix = self
+            .m_mesh_perframe_storage_buffer_object
+            .unjittered_proj_view_matrix;
+        self.m_mesh_perframe_storage_buffer_object
+            .unjittered_proj_view_matrix = camera.get_unjittered_perspective_matrix() * view_matrix;
         self.m_mesh_perframe_storage_buffer_object.camera_position = *camera_position;
         self.m_mesh_perframe_storage_buffer_object.ambient_light = ambient_light;
         self.m_mesh_perframe_storage_buffer_object.point_light_num = point_light_num as u32;
@@ -352,6 +308,7 @@ impl RenderResource {
             .m_point_light_list
             .m_lights
             .iter()
+            .take(point_light_num)
             .enumerate()
             .for_each(|(i, light)| {
                 let radius = light.calculate_radius();
@@ -374,11 +331,26 @@ impl RenderResource {
                 );
             });
 
-        let directional_light_proj_view = calculate_directional_light_camera(render_scene, camera);
+        let cascade_splits = render_scene.get_directional_light_cascade_splits();
+        render_scene
+            .get_directional_light_cascade_proj_views()
+            .iter()
+            .enumerate()
+            .for_each(|(i, proj_view)| {
+                self.m_mesh_perframe_storage_buffer_object
+                    .directional_light_cascade_proj_view[i] = *proj_view;
+                self.m_mesh_directional_light_shadow_perframe_storage_buffer_objects[i]
+                    .light_proj_view = *proj_view;
+            });
+        let split = |i: usize| cascade_splits.get(i).copied().unwrap_or(f32::MAX);
         self.m_mesh_perframe_storage_buffer_object
-            .directional_light_proj_view = directional_light_proj_view;
-        self.m_mesh_directional_light_shadow_perframe_storage_buffer_object
-            .light_proj_view = directional_light_proj_view;
+            .directional_light_cascade_splits =
+            Vector4::new(split(0), split(1), split(2), split(3));
+        self.m_mesh_perframe_storage_buffer_object
+            .directional_light_cascade_count = cascade_splits.len() as u32;
+        self.m_mesh_perframe_storage_buffer_object
+            .directional_light_cascade_blend_ratio =
+            render_scene.m_directional_light.m_cascade_blend_ratio;
 
         self.m_mesh_perframe_storage_buffer_object
             .scene_directional_light
@@ -387,8 +359,103 @@ impl RenderResource {
             .scene_directional_light
             .color = render_scene.m_directional_light.m_color;
 
+        let sky_atmosphere = render_scene.m_sky_atmosphere.as_ref();
+        self.m_mesh_perframe_storage_buffer_object.sky_enable = sky_atmosphere.is_some() as u32;
+        self.m_mesh_perframe_storage_buffer_object
+            .sky_sun_illuminance =
+            sky_atmosphere.map_or(Vector3::ZERO, SkyAtmosphere::sky_sun_illuminance);
+
+        let perframe = &mut self.m_mesh_perframe_storage_buffer_object;
+        perframe.fog_enable = render_scene.m_fog.is_some() as u32;
+        if let Some(fog) = &render_scene.m_fog {
+            perframe.fog_sky_color =
+                sky_atmosphere.map_or(Vector3::ZERO, SkyAtmosphere::horizon_color);
+            perframe.fog_density = fog.m_density;
+            perframe.fog_base_height = fog.m_base_height;
+            perframe.fog_height_falloff = fog.m_height_falloff;
+            perframe.fog_start_distance = fog.m_start_distance;
+            perframe.fog_max_opacity = fog.m_max_opacity;
+        }
+
         self.m_mesh_inefficient_pick_perframe_storage_buffer_object
             .proj_view_matrix = proj_view_matrix;
+
+        self.update_light_clusters(render_scene, camera, point_light_num);
+    }
+
+    fn update_light_clusters(
+        &mut self,
+        render_scene: &RenderScene,
+        camera: &RenderCamera,
+        point_light_shadow_count: usize,
+    ) {
+        let spot_light_list = &render_scene.m_spot_light_list;
+        let mut light_cluster = self.m_mesh_light_cluster_storage_buffer_object.borrow_mut();
+        spot_light_list
+            .m_shadow_proj_views
+            .iter()
+            .enumerate()
+            .for_each(|(i, proj_view)| {
+                self.m_mesh_spot_light_shadow_perframe_storage_buffer_objects[i].light_proj_view =
+                    *proj_view;
+                light_cluster.spot_light_shadow_proj_view[i] = *proj_view;
+            });
+
+        let point_lights = render_scene
+            .m_point_light_list
+            .m_lights
+            .iter()
+            .enumerate()
+            .map(|(i, light)| VulkanClusteredLight {
+                position: light.m_position,
+                radius: light.calculate_radius(),
+                intensity: light.m_flux / (4.0 * PI),
+                light_type: S_CLUSTERED_LIGHT_TYPE_POINT,
+                shadow_index: if i < point_light_shadow_count {
+                    i as i32
+                } else {
+                    -1
+                },
+                ..Default::default()
+            });
+        let spot_lights = spot_light_list
+            .m_lights
+            .iter()
+            .enumerate()
+            .map(|(i, light)| VulkanClusteredLight {
+                position: light.m_position,
+                radius: light.calculate_radius(),
+                intensity: light.m_flux / (4.0 * PI),
+                light_type: S_CLUSTERED_LIGHT_TYPE_SPOT,
+                direction: light.m_direction.normalize(),
+                cos_outer_cone: light.m_outer_cone_angle.cos(),
+                cos_inner_cone: light.m_inner_cone_angle.min(light.m_outer_cone_angle).cos(),
+                shadow_index: spot_light_list
+                    .m_shadow_light_indices
+                    .iter()
+                    .position(|&shadow_light_index| shadow_light_index == i)
+                    .map_or(-1, |shadow_index| shadow_index as i32),
+                ..Default::default()
+            });
+
+        let light_cluster = &mut **light_cluster;
+        let mut light_count = 0;
+        for (slot, light) in light_cluster.lights.iter_mut().zip(
+            point_lights
+                .chain(spot_lights)
+                .take(S_MAX_CLUSTERED_LIGHT_COUNT),
+        ) {
+            *slot = light;
+            light_count += 1;
+        }
+        light_cluster.light_count = light_count as u32;
+        light_cluster.cluster_z_near = camera.get_znear();
+        light_cluster.cluster_z_scale = calculate_light_clusters(
+            camera,
+            &light_cluster.lights[..light_count],
+            &mut light_cluster.cluster_light_grids,
+            &mut light_cluster.light_indices,
+        );
     }
 
     pub fn upload_game_object_render_resource(
@@ -563,60 +630,95 @@ impl RenderResource {
             let mut now_material = VulkanPBRMaterial::default();
             let empty_image = [255, 255, 255, 255];
             let empty_image_data = empty_image.as_slice();
+            // without an emissive texture nothing should glow
+            let black_image = [0, 0, 0, 255];
+            let empty_mip_pixels: &[Vec<u8>] = &[];
+            let base_color_texture =
+                Self::sampleable_material_texture(rhi, material_data.m_base_color_texture.as_ref());
+            let metallic_roughness_texture = Self::sampleable_material_texture(
+                rhi,
+                material_data.m_metallic_roughness_texture.as_ref(),
+            );
+            let normal_texture =
+                Self::sampleable_material_texture(rhi, material_data.m_normal_texture.as_ref());
+            let occlusion_texture =
+                Self::sampleable_material_texture(rhi, material_data.m_occlusion_texture.as_ref());
+            let emissive_texture =
+                Self::sampleable_material_texture(rhi, material_data.m_emissive_texture.as_ref());
 
             let mut base_color_image_pixels = empty_image_data;
             let mut base_color_image_width = 1;
             let mut base_color_image_height = 1;
+            let mut base_color_image_mip_levels = 1;
+            let mut base_color_image_mip_pixels = empty_mip_pixels;
             let mut base_color_image_format = vk::Format::R8G8B8A8_SRGB;
-            if let Some(texture) = &material_data.m_base_color_texture {
+            if let Some(texture) = &base_color_texture {
                 base_color_image_pixels = &texture.m_pixels;
                 base_color_image_width = texture.m_width;
                 base_color_image_height = texture.m_height;
                 base_color_image_format = texture.m_format;
+                base_color_image_mip_levels = texture.m_mip_levels;
+                base_color_image_mip_pixels = &texture.m_mip_pixels;
             }
 
             let mut metallic_roughness_image_pixels = empty_image_data;
             let mut metallic_roughness_image_width = 1;
             let mut metallic_roughness_image_height = 1;
+            let mut metallic_roughness_image_mip_levels = 1;
+            let mut metallic_roughness_image_mip_pixels = empty_mip_pixels;
             let mut metallic_roughness_image_format = vk::Format::R8G8B8A8_UNORM;
-            if let Some(texture) = &material_data.m_metallic_roughness_texture {
+            if let Some(texture) = &metallic_roughness_texture {
                 metallic_roughness_image_pixels = &texture.m_pixels;
                 metallic_roughness_image_width = texture.m_width;
                 metallic_roughness_image_height = texture.m_height;
                 metallic_roughness_image_format = texture.m_format;
+                metallic_roughness_image_mip_levels = texture.m_mip_levels;
+                metallic_roughness_image_mip_pixels = &texture.m_mip_pixels;
             }
 
             let mut normal_roughness_image_pixels = empty_image_data;
             let mut normal_roughness_image_width = 1;
             let mut normal_roughness_image_height = 1;
+            let mut normal_roughness_image_mip_levels = 1;
+            let mut normal_roughness_image_mip_pixels = empty_mip_pixels;
             let mut normal_roughness_image_format = vk::Format::R8G8B8A8_UNORM;
-            if let Some(texture) = &material_data.m_normal_texture {
+            if let Some(texture) = &normal_texture {
                 normal_roughness_image_pixels = &texture.m_pixels;
                 normal_roughness_image_width = texture.m_width;
                 normal_roughness_image_height = texture.m_height;
                 normal_roughness_image_format = texture.m_format;
+                normal_roughness_image_mip_levels = texture.m_mip_levels;
+                normal_roughness_image_mip_pixels = &texture.m_mip_pixels;
             }
 
             let mut occlusion_image_pixels = empty_image_data;
             let mut occlusion_image_width = 1;
             let mut occlusion_image_height = 1;
+            let mut occlusion_image_mip_levels = 1;
+            let mut occlusion_image_mip_pixels = empty_mip_pixels;
             let mut occlusion_image_format = vk::Format::R8G8B8A8_UNORM;
-            if let Some(texture) = &material_data.m_occlusion_texture {
+            if let Some(texture) = &occlusion_texture {
                 occlusion_image_pixels = &texture.m_pixels;
                 occlusion_image_width = texture.m_width;
                 occlusion_image_height = texture.m_height;
                 occlusion_image_format = texture.m_format;
+                occlusion_image_mip_levels = texture.m_mip_levels;
+                occlusion_image_mip_pixels = &texture.m_mip_pixels;
             }
 
-            let mut emissive_image_pixels = empty_image_data;
+            let mut emissive_image_pixels = black_image.as_slice();
             let mut emissive_image_width = 1;
             let mut emissive_image_height = 1;
+            let mut emissive_image_mip_levels = 1;
+            let mut emissive_image_mip_pixels = empty_mip_pixels;
             let mut emissive_image_format = vk::Format::R8G8B8A8_UNORM;
-            if let Some(texture) = &material_data.m_emissive_texture {
+            if let Some(texture) = &emissive_texture {
                 emissive_image_pixels = &texture.m_pixels;
                 emissive_image_width = texture.m_width;
                 emissive_image_height = texture.m_height;
                 emissive_image_format = texture.m_format;
+                emissive_image_mip_levels = texture.m_mip_levels;
+                emissive_image_mip_pixels = &texture.m_mip_pixels;
             }
 
             {
@@ -643,14 +745,27 @@ impl RenderResource {
                 let material_uniform_buffer_info = unsafe {
                     &mut *(staging_buffer_data as *mut MeshPerMaterialUniformBufferObject)
                 };
-                material_uniform_buffer_info.is_blend = entity.m_blend as u32;
+                material_uniform_buffer_info.is_blend =
+                    (entity.m_alpha_mode == MaterialAlphaMode::Blend) as u32;
                 material_uniform_buffer_info.id_double_sided = entity.m_double_sided as u32;
+                material_uniform_buffer_info.alpha_cutoff =
+                    if entity.m_alpha_mode == MaterialAlphaMode::Mask {
+                        entity.m_alpha_cutoff
+                    } else {
+                        0.0
+                    };
                 material_uniform_buffer_info.base_color_factor = entity.m_base_color_factor;
                 material_uniform_buffer_info.metallic_factor = entity.m_metallic_factor;
                 material_uniform_buffer_info.roughness_factor = entity.m_roughness_factor;
                 material_uniform_buffer_info.normal_scale = entity.m_normal_scale;
                 material_uniform_buffer_info.occlusion_strength = entity.m_occlusion_strength;
                 material_uniform_buffer_info.emissive_factor = entity.m_emissive_factor;
+                material_uniform_buffer_info.normal_encoding = normal_texture
+                    .as_ref()
+                    .and(material_data.m_normal_texture.as_ref())
+                    .map_or(S_NORMAL_ENCODING_RGB, |texture| {
+                        Self::normal_encoding(texture.m_format)
+                    });
 
                 rhi.unmap_memory(staging_memory);
 
@@ -683,26 +798,36 @@ impl RenderResource {
                 base_color_image_width,
                 base_color_image_height,
                 base_color_image_format,
+                base_color_image_mip_levels,
+                base_color_image_mip_pixels,
 
                 metallic_roughness_image_pixels,
                 metallic_roughness_image_width,
                 metallic_roughness_image_height,
                 metallic_roughness_image_format,
+                metallic_roughness_image_mip_levels,
+                metallic_roughness_image_mip_pixels,
 
                 normal_roughness_image_pixels,
                 normal_roughness_image_width,
                 normal_roughness_image_height,
                 normal_roughness_image_format,
+                normal_roughness_image_mip_levels,
+                normal_roughness_image_mip_pixels,
 
                 occlusion_image_pixels,
                 occlusion_image_width,
                 occlusion_image_height,
                 occlusion_image_format,
+                occlusion_image_mip_levels,
+                occlusion_image_mip_pixels,
 
                 emissive_image_pixels,
                 emissive_image_width,
                 emissive_image_height,
                 emissive_image_format,
+                emissive_image_mip_levels,
+                emissive_image_mip_pixels,
 
                 now_material: &mut now_material,
             };
@@ -1072,19 +1197,37 @@ impl RenderResource {
     }
 
     fn update_texture_image_data(rhi: &VulkanRHI, texture_data: &mut TextureDataToUpdate) {
+        // atlases and containers come with their mip levels, the other textures are blitted on
+        // upload
+        let create_texture_image = |width: u32,
+                                    height: u32,
+                                    pixels: &[u8],
+                                    mip_pixels: &[Vec<u8>],
+                                    format: vk::Format,
+                                    mip_levels: u32| {
+            if mip_pixels.is_empty() && !is_block_compressed(format) {
+                rhi.create_texture_image(width, height, pixels, format, mip_levels)
+            } else {
+                let levels = std::iter::once(pixels)
+                    .chain(mip_pixels.iter().map(Vec::as_slice))
+                    .collect::<Vec<_>>();
+                rhi.create_texture_image_from_mips(width, height, &levels, format)
+            }
+        };
+
         (
             texture_data.now_material.base_color_texture_image,
             texture_data.now_material.base_color_image_allocation,
             texture_data.now_material.base_color_image_view,
-        ) = rhi
-            .create_texture_image(
-                texture_data.base_color_image_width,
-                texture_data.base_color_image_height,
-                texture_data.base_color_image_pixels,
-                texture_data.base_color_image_format,
-                0,
-            )
-            .unwrap();
+        ) = create_texture_image(
+            texture_data.base_color_image_width,
+            texture_data.base_color_image_height,
+            texture_data.base_color_image_pixels,
+            texture_data.base_color_image_mip_pixels,
+            texture_data.base_color_image_format,
+            texture_data.base_color_image_mip_levels,
+        )
+        .unwrap();
 
         (
             texture_data.now_material.metallic_roughness_texture_image,
@@ -1092,57 +1235,57 @@ impl RenderResource {
                 .now_material
                 .metallic_roughness_image_allocation,
             texture_data.now_material.metallic_roughness_image_view,
-        ) = rhi
-            .create_texture_image(
-                texture_data.metallic_roughness_image_width,
-                texture_data.metallic_roughness_image_height,
-                texture_data.metallic_roughness_image_pixels,
-                texture_data.metallic_roughness_image_format,
-                0,
-            )
-            .unwrap();
+        ) = create_texture_image(
+            texture_data.metallic_roughness_image_width,
+            texture_data.metallic_roughness_image_height,
+            texture_data.metallic_roughness_image_pixels,
+            texture_data.metallic_roughness_image_mip_pixels,
+            texture_data.metallic_roughness_image_format,
+            texture_data.metallic_roughness_image_mip_levels,
+        )
+        .unwrap();
 
         (
             texture_data.now_material.normal_texture_image,
             texture_data.now_material.normal_image_allocation,
             texture_data.now_material.normal_image_view,
-        ) = rhi
-            .create_texture_image(
-                texture_data.normal_roughness_image_width,
-                texture_data.normal_roughness_image_height,
-                texture_data.normal_roughness_image_pixels,
-                texture_data.normal_roughness_image_format,
-                0,
-            )
-            .unwrap();
+        ) = create_texture_image(
+            texture_data.normal_roughness_image_width,
+            texture_data.normal_roughness_image_height,
+            texture_data.normal_roughness_image_pixels,
+            texture_data.normal_roughness_image_mip_pixels,
+            texture_data.normal_roughness_image_format,
+            texture_data.normal_roughness_image_mip_levels,
+        )
+        .unwrap();
 
         (
             texture_data.now_material.occlusion_texture_image,
             texture_data.now_material.occlusion_image_allocation,
             texture_data.now_material.occlusion_image_view,
-        ) = rhi
-            .create_texture_image(
-                texture_data.occlusion_image_width,
-                texture_data.occlusion_image_height,
-                texture_data.occlusion_image_pixels,
-                texture_data.occlusion_image_format,
-                0,
-            )
-            .unwrap();
+        ) = create_texture_image(
+            texture_data.occlusion_image_width,
+            texture_data.occlusion_image_height,
+            texture_data.occlusion_image_pixels,
+            texture_data.occlusion_image_mip_pixels,
+            texture_data.occlusion_image_format,
+            texture_data.occlusion_image_mip_levels,
+        )
+        .unwrap();
 
         (
             texture_data.now_material.emissive_texture_image,
             texture_data.now_material.emissive_image_allocation,
             texture_data.now_material.emissive_image_view,
-        ) = rhi
-            .create_texture_image(
-                texture_data.emissive_image_width,
-                texture_data.emissive_image_height,
-                texture_data.emissive_image_pixels,
-                texture_data.emissive_image_format,
-                0,
-            )
-            .unwrap();
+        ) = create_texture_image(
+            texture_data.emissive_image_width,
+            texture_data.emissive_image_height,
+            texture_data.emissive_image_pixels,
+            texture_data.emissive_image_mip_pixels,
+            texture_data.emissive_image_format,
+            texture_data.emissive_image_mip_levels,
+        )
+        .unwrap();
     }
 
     fn create_and_map_storage_buffer(&mut self, rhi: &VulkanRHI) {
@@ -1209,6 +1352,57 @@ impl RenderResource {
             .unwrap();
     }
 
+    /// The maps generated from the environment map when one is set, otherwise the authored ones.
+    fn load_ibl_maps(
+        asset_manager: &AssetManager,
+        ibl_resource_desc: &LevelIBLResourceDesc,
+    ) -> (TextureData, TextureData, TextureData) {
+        if !ibl_resource_desc.m_environment_map.is_empty() {
+            let ibl = ibl_precompute::load_or_precompute_ibl(
+                asset_manager,
+                &ibl_resource_desc.m_environment_map,
+            )
+            .unwrap();
+            return (ibl.m_irradiance_map, ibl.m_specular_map, ibl.m_brdf_map);
+        }
+
+        let skybox_irradiance_map = &ibl_resource_desc.m_skybox_irradiance_map;
+        let irradiance_map = RenderResourceBase::load_cube_map_hdr(
+            asset_manager,
+            &skybox_irradiance_map.cube_map,
+            [
+                &skybox_irradiance_map.positive_x_map,
+                &skybox_irradiance_map.negative_x_map,
+                &skybox_irradiance_map.positive_z_map,
+                &skybox_irradiance_map.negative_z_map,
+                &skybox_irradiance_map.positive_y_map,
+                &skybox_irradiance_map.negative_y_map,
+            ],
+        )
+        .unwrap();
+
+        let skybox_specular_map = &ibl_resource_desc.m_skybox_specular_map;
+        let specular_map = RenderResourceBase::load_cube_map_hdr(
+            asset_manager,
+            &skybox_specular_map.cube_map,
+            [
+                &skybox_specular_map.positive_x_map,
+                &skybox_specular_map.negative_x_map,
+                &skybox_specular_map.positive_z_map,
+                &skybox_specular_map.negative_z_map,
+                &skybox_specular_map.positive_y_map,
+                &skybox_specular_map.negative_y_map,
+            ],
+        )
+        .unwrap();
+
+        let brdf_map =
+            RenderResourceBase::load_texture_hdr(asset_manager, &ibl_resource_desc.m_brdf_map, 4)
+                .unwrap();
+
+        (irradiance_map, specular_map, brdf_map)
+    }
+
     fn create_ibl_samplers(&mut self, rhi: &VulkanRHI) {
         let physical_device_properties = rhi.get_physical_device_properties();
         let mut sampler_info = vk::SamplerCreateInfo::builder()
@@ -1288,15 +1482,9 @@ impl RenderResource {
     fn create_ibl_textures(
         &mut self,
         rhi: &VulkanRHI,
-        irradiance_maps: &[TextureData; 6],
-        specular_maps: &[TextureData; 6],
+        irradiance_map: &TextureData,
+        specular_map: &TextureData,
     ) {
-        let irradiance_cubemap_miplevels =
-            (irradiance_maps[0].m_width.max(irradiance_maps[0].m_height) as f32)
-                .log2()
-                .floor() as u32
-                + 1;
-
         (
             self.m_global_render_resource
                 ._ibl_resource
@@ -1307,25 +1495,7 @@ impl RenderResource {
             self.m_global_render_resource
                 ._ibl_resource
                 ._irradiance_texture_image_view,
-        ) = rhi
-            .create_cube_map(
-                irradiance_maps[0].m_width,
-                irradiance_maps[0].m_height,
-                &irradiance_maps
-                    .iter()
-                    .map(|texture| texture.m_pixels.as_slice())
-                    .collect_array()
-                    .unwrap(),
-                irradiance_maps[0].m_format,
-                irradiance_cubemap_miplevels,
-            )
-            .unwrap();
-
-        let specular_cubemap_miplevels = (specular_maps[0].m_width.max(specular_maps[0].m_height)
-            as f32)
-            .log2()
-            .floor() as u32
-            + 1;
+        ) = Self::create_texture_image(rhi, irradiance_map).unwrap();
 
         (
             self.m_global_render_resource
@@ -1337,19 +1507,93 @@ impl RenderResource {
             self.m_global_render_resource
                 ._ibl_resource
                 ._specular_texture_image_view,
-        ) = rhi
-            .create_cube_map(
-                specular_maps[0].m_width,
-                specular_maps[0].m_height,
-                &specular_maps
-                    .iter()
-                    .map(|texture| texture.m_pixels.as_slice())
-                    .collect_array()
-                    .unwrap(),
-                specular_maps[0].m_format,
-                specular_cubemap_miplevels,
-            )
-            .unwrap();
+        ) = Self::create_texture_image(rhi, specular_map).unwrap();
+    }
+
+    /// Uploads a 2D texture or a six layer cube map. Levels that come with the texture are
+    /// copied as they are, the others are blitted.
+    fn create_texture_image(
+        rhi: &VulkanRHI,
+        texture: &TextureData,
+    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView)> {
+        let texture = Self::sampleable_texture(rhi, texture)?;
+        let is_cube_map = texture.m_array_layers == 6;
+        if texture.m_mip_pixels.is_empty() && !is_block_compressed(texture.m_format) {
+            if is_cube_map {
+                let face_size = texture.m_pixels.len() / 6;
+                rhi.create_cube_map(
+                    texture.m_width,
+                    texture.m_height,
+                    &texture.m_pixels.chunks(face_size).collect_array().unwrap(),
+                    texture.m_format,
+                    texture.m_mip_levels,
+                )
+            } else {
+                rhi.create_texture_image(
+                    texture.m_width,
+                    texture.m_height,
+                    &texture.m_pixels,
+                    texture.m_format,
+                    texture.m_mip_levels,
+                )
+            }
+        } else {
+            let levels = std::iter::once(texture.m_pixels.as_slice())
+                .chain(texture.m_mip_pixels.iter().map(Vec::as_slice))
+                .collect::<Vec<_>>();
+            if is_cube_map {
+                rhi.create_cube_map_from_mips(
+                    texture.m_width,
+                    texture.m_height,
+                    &levels,
+                    texture.m_format,
+                )
+            } else {
+                rhi.create_texture_image_from_mips(
+                    texture.m_width,
+                    texture.m_height,
+                    &levels,
+                    texture.m_format,
+                )
+            }
+        }
+    }
+
+    /// Block compressed textures the device cannot sample are decoded on the CPU.
+    fn sampleable_texture<'a>(
+        rhi: &VulkanRHI,
+        texture: &'a TextureData,
+    ) -> Result<Cow<'a, TextureData>> {
+        if !is_block_compressed(texture.m_format)
+            || rhi.is_texture_format_supported(texture.m_format)
+        {
+            return Ok(Cow::Borrowed(texture));
+        }
+        warn!(
+            "{:?} textures are not supported by the device, decoding them on the CPU",
+            texture.m_format
+        );
+        Ok(Cow::Owned(texture_decoder::decode_texture(texture)?))
+    }
+
+    /// A material texture that cannot be made sampleable falls back to the default of its slot.
+    fn sampleable_material_texture<'a>(
+        rhi: &VulkanRHI,
+        texture: Option<&'a TextureData>,
+    ) -> Option<Cow<'a, TextureData>> {
+        Self::sampleable_texture(rhi, texture?)
+            .inspect_err(|err| error!("{err}, using the default texture instead"))
+            .ok()
+    }
+
+    /// Decoding turns BC5 into plain RGBA8, so only the source format tells two channel normal
+    /// maps apart.
+    fn normal_encoding(format: vk::Format) -> u32 {
+        match format {
+            vk::Format::BC5_UNORM_BLOCK | vk::Format::R8G8_UNORM => S_NORMAL_ENCODING_RG_UNORM,
+            vk::Format::BC5_SNORM_BLOCK | vk::Format::R8G8_SNORM => S_NORMAL_ENCODING_RG_SNORM,
+            _ => S_NORMAL_ENCODING_RGB,
+        }
     }
 }
 
@@ -1,11 +1,11 @@
 use std::{collections::HashMap, fs::File, io::{BufReader, Read}, path::PathBuf};
 
 use image::EncodableLayout;
-use log::error;
+use log::{error, warn};
 use itertools::Itertools;
 use vulkanalia::prelude::v1_0::*;
 
-use crate::{core::math::{axis_aligned::AxisAlignedBox, vector2::Vector2, vector3::Vector3}, function::render::render_type::{ImageType, MaterialSourceDesc, MeshSourceDesc, MeshVertexDataDefinition, RenderMaterialData, RenderMeshData, StaticMeshData, TextureData}, resource::{asset_manager::AssetManager, res_type::data::mesh_data::MeshData}};
+use crate::{core::math::{axis_aligned::AxisAlignedBox, vector2::Vector2, vector3::Vector3}, function::render::{render_helper::mip_level_count, render_type::{ImageType, MaterialSourceDesc, MeshSourceDesc, MeshVertexDataDefinition, RenderMaterialData, RenderMeshData, StaticMeshData, TextureData}, texture_container::{self, is_texture_container}}, resource::{asset_manager::AssetManager, res_type::data::mesh_data::MeshData}};
 
 
 #[derive(Clone, Default)]
@@ -15,11 +15,15 @@ pub struct RenderResourceBase{
 
 impl RenderResourceBase {
 
+    /// KTX2 and DDS files keep the format and mip levels they were stored with.
     pub fn load_texture_hdr(
         asset_manager: &AssetManager,
         file: &str, 
         desired_channels: u32
     ) -> Option<TextureData> {
+        if is_texture_container(file) {
+            return Self::load_texture_container(asset_manager, file, 1);
+        }
         let image = image::open(asset_manager.get_full_path(file)).ok()?;
         let mut texture = TextureData::default();
         match desired_channels {
@@ -37,17 +41,22 @@ impl RenderResourceBase {
         }
         texture.m_depth = 1;
         texture.m_array_layers = 1;
-        texture.m_mip_levels = 1;
+        texture.m_mip_levels = mip_level_count(texture.m_width, texture.m_height);
         texture.m_type = ImageType::_2D;
 
         Some(texture)
     }
 
+    /// KTX2 and DDS files keep the format and mip levels they were stored with, `is_srgb` only
+    /// applies to images.
     pub fn load_texture(
         asset_manager: &AssetManager,
         file: &str, 
         is_srgb: bool
     ) -> Option<TextureData> {
+        if is_texture_container(file) {
+            return Self::load_texture_container(asset_manager, file, 1);
+        }
         let image = image::open(asset_manager.get_full_path(file)).ok()?;
         let image = image.to_rgba8();
 
@@ -62,12 +71,143 @@ impl RenderResourceBase {
         };
         texture.m_depth = 1;
         texture.m_array_layers = 1;
-        texture.m_mip_levels = 1;
+        texture.m_mip_levels = mip_level_count(texture.m_width, texture.m_height);
         texture.m_type = ImageType::_2D;
 
         Some(texture)
     }
 
+    /// Loads a cube map from a KTX2 or DDS container when `cube_map` is set, otherwise from six
+    /// HDR faces given in layer order.
+    pub fn load_cube_map_hdr(
+        asset_manager: &AssetManager,
+        cube_map: &str,
+        faces: [&str; 6]
+    ) -> Option<TextureData> {
+        if !cube_map.is_empty() {
+            return Self::load_texture_container(asset_manager, cube_map, 6);
+        }
+        let faces = faces
+            .iter()
+            .map(|face| Self::load_texture_hdr(asset_manager, face, 4))
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut texture = TextureData::default();
+        texture.m_pixels = faces.iter().map(|face| face.m_pixels.as_slice()).collect_vec().concat();
+        texture.m_width = faces[0].m_width;
+        texture.m_height = faces[0].m_height;
+        texture.m_format = faces[0].m_format;
+        texture.m_depth = 1;
+        texture.m_array_layers = 6;
+        texture.m_mip_levels = faces[0].m_mip_levels;
+        texture.m_type = ImageType::Cube;
+
+        Some(texture)
+    }
+
+    fn load_texture_container(
+        asset_manager: &AssetManager,
+        file: &str,
+        array_layers: u32
+    ) -> Option<TextureData> {
+        let bytes = asset_manager.load_binary(file).ok()?;
+        let texture = texture_container::load_texture_container(&bytes)
+            .map_err(|e| error!("Failed to load texture {}: {}", file, e))
+            .ok()?;
+        if texture.m_array_layers != array_layers {
+            error!("{} has {} faces, expected {}", file, texture.m_array_layers, array_layers);
+            return None;
+        }
+        Some(texture)
+    }
+
+    /// Loads a texture atlas of `tile_count` x `tile_count` tiles. A full chain would blend
+    /// neighbouring tiles once a tile shrinks below a texel, so the levels stop at 2x2 texel
+    /// tiles and are built on the CPU, where the filter stays inside each tile.
+    pub fn load_texture_atlas(
+        asset_manager: &AssetManager,
+        file: &str,
+        is_srgb: bool,
+        tile_count: u32
+    ) -> Option<TextureData> {
+        let mut texture = Self::load_texture(asset_manager, file, is_srgb)?;
+        // containers already carry the levels they were authored with
+        if is_texture_container(file) {
+            return Some(texture);
+        }
+        let tile_width = texture.m_width / tile_count;
+        let tile_height = texture.m_height / tile_count;
+        if texture.m_width % tile_count != 0
+            || texture.m_height % tile_count != 0
+            || tile_width != tile_height
+            || !tile_width.is_power_of_two()
+        {
+            warn!(
+                "{} is not an atlas of {} square power of two tiles, its mips may bleed",
+                file, tile_count
+            );
+            return Some(texture);
+        }
+
+        texture.m_mip_levels = tile_width.trailing_zeros().max(1);
+        let mut width = texture.m_width;
+        let mut height = texture.m_height;
+        for _ in 1..texture.m_mip_levels {
+            let src = texture.m_mip_pixels.last().unwrap_or(&texture.m_pixels);
+            let level = Self::downsample_rgba8(src, width, height, is_srgb);
+            texture.m_mip_pixels.push(level);
+            width /= 2;
+            height /= 2;
+        }
+
+        Some(texture)
+    }
+
+    /// 2x2 box filter. Tiles are a power of two and aligned, so no 2x2 block spans two tiles.
+    fn downsample_rgba8(src: &[u8], width: u32, height: u32, is_srgb: bool) -> Vec<u8> {
+        let to_linear = |c: u8| {
+            let c = c as f32 / 255.0;
+            if !is_srgb {
+                c
+            } else if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        let from_linear = |c: f32| {
+            let c = if !is_srgb {
+                c
+            } else if c <= 0.0031308 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            };
+            (c * 255.0).round().clamp(0.0, 255.0) as u8
+        };
+
+        let (dst_width, dst_height) = (width / 2, height / 2);
+        let mut dst = vec![0_u8; (dst_width * dst_height * 4) as usize];
+        for y in 0..dst_height {
+            for x in 0..dst_width {
+                let texels = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .map(|(dx, dy)| (((y * 2 + dy) * width + x * 2 + dx) * 4) as usize);
+                let dst_texel = ((y * dst_width + x) * 4) as usize;
+                for channel in 0..4 {
+                    // alpha is always stored linearly
+                    if channel == 3 {
+                        let sum: u32 = texels.iter().map(|t| src[t + 3] as u32).sum();
+                        dst[dst_texel + 3] = ((sum + 2) / 4) as u8;
+                    } else {
+                        let sum: f32 = texels.iter().map(|t| to_linear(src[t + channel])).sum();
+                        dst[dst_texel + channel] = from_linear(sum / 4.0);
+                    }
+                }
+            }
+        }
+        dst
+    }
+
     pub fn load_mesh_data(
         &mut self, 
         asset_manager: &AssetManager,
@@ -135,12 +275,19 @@ impl RenderResourceBase {
         asset_manager: &AssetManager,
         source: &MaterialSourceDesc
     ) -> RenderMaterialData {
+        let load_texture = |file: &str, is_srgb: bool| {
+            if source.m_atlas_tile_count > 0 {
+                Self::load_texture_atlas(asset_manager, file, is_srgb, source.m_atlas_tile_count)
+            } else {
+                Self::load_texture(asset_manager, file, is_srgb)
+            }
+        };
         let mut ret = RenderMaterialData::default();
-        ret.m_base_color_texture = Self::load_texture(asset_manager, &source.m_base_color_file, true);
-        ret.m_metallic_roughness_texture = Self::load_texture(asset_manager, &source.m_metallic_roughness_file, false);
-        ret.m_normal_texture = Self::load_texture(asset_manager, &source.m_normal_file, false);
-        ret.m_occlusion_texture = Self::load_texture(asset_manager, &source.m_occlusion_file, false);
-        ret.m_emissive_texture = Self::load_texture(asset_manager, &source.m_emissive_file, false);
+        ret.m_base_color_texture = load_texture(&source.m_base_color_file, true);
+        ret.m_metallic_roughness_texture = load_texture(&source.m_metallic_roughness_file, false);
+        ret.m_normal_texture = load_texture(&source.m_normal_file, false);
+        ret.m_occlusion_texture = load_texture(&source.m_occlusion_file, false);
+        ret.m_emissive_texture = load_texture(&source.m_emissive_file, false);
         ret
     }
     
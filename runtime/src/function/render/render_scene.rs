@@ -1,20 +1,38 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use crate::{
-    core::math::bounding_box::{BoundingBox, bounding_box_transform},
+    core::math::{
+        bounding_box::{BoundingBox, bounding_box_transform},
+        matrix4::Matrix4x4,
+    },
     function::{
         framework::object::object_id_allocator::GObjectID,
         render::{
-            light::{AmbientLight, DirectionalLight, PointLightList},
+            light::{
+                AmbientLight, DirectionalLight, HDRBloom, HeightFog, PointLight, PointLightList,
+                ScreenSpaceAmbientOcclusion, SpotLight, SpotLightList, TemporalAntiAliasing,
+            },
             render_camera::RenderCamera,
-            render_common::RenderMeshNode,
+            render_common::{
+                RenderMeshNode, RenderStatistics, S_MAX_POINT_LIGHT_COUNT,
+                S_MAX_SPOT_LIGHT_SHADOW_COUNT,
+            },
             render_entity::RenderEntity,
             render_guid_allocator::GuidAllocator,
+            render_helper::{
+                box_intersect_sphere, calculate_directional_light_camera,
+                calculate_directional_light_cascade_splits, calculate_spot_light_camera,
+                create_cluster_frustum_from_matrix, mesh_node_draw_call_count,
+                sort_mesh_nodes_for_batching, tiled_frustum_intersect_box,
+                tiled_frustum_intersect_sphere,
+            },
             render_object::GameObjectPartId,
             render_resource::RenderResource,
             render_type::{MaterialSourceDesc, MeshSourceDesc},
+            sky_atmosphere::SkyAtmosphere,
         },
     },
+    resource::res_type::data::material::MaterialAlphaMode,
 };
 
 #[derive(Default)]
@@ -22,27 +40,170 @@ pub struct RenderScene {
     pub m_ambient_light: AmbientLight,
     pub m_directional_light: DirectionalLight,
     pub m_point_light_list: PointLightList,
+    pub m_spot_light_list: SpotLightList,
+    /// Procedural sky lighting the scene, `None` when the sky is a skybox.
+    pub m_sky_atmosphere: Option<SkyAtmosphere>,
+    /// `None` when the scene has no fog.
+    pub m_fog: Option<HeightFog>,
+    /// `None` when ambient occlusion is off, the deferred lighting then sees none.
+    pub m_ambient_occlusion: Option<ScreenSpaceAmbientOcclusion>,
+    /// `None` when bloom is off.
+    pub m_bloom: Option<HDRBloom>,
+    pub m_temporal_aa: TemporalAntiAliasing,
 
     m_render_entities: RefCell<HashMap<u32, Box<RenderEntity>>>,
+    /// Every point light in the scene; `m_point_light_list` holds the ones shaded this frame.
+    m_point_lights: HashMap<GObjectID, PointLight>,
+    /// Every spot light in the scene; `m_spot_light_list` holds the ones shaded this frame.
+    m_spot_lights: HashMap<GObjectID, SpotLight>,
 
     m_main_camera_visible_mesh_nodes: Vec<RenderMeshNode>,
-    m_directional_light_visible_mesh_nodes: Vec<RenderMeshNode>,
+    /// Blended meshes, sorted back to front instead of for batching.
+    m_main_camera_visible_transparent_mesh_nodes: Vec<RenderMeshNode>,
+    m_directional_light_visible_mesh_nodes: Vec<Vec<RenderMeshNode>>,
+    m_directional_light_cascade_proj_views: Vec<Matrix4x4>,
+    m_directional_light_cascade_splits: Vec<f32>,
+    m_point_lights_visible_mesh_nodes: Vec<RenderMeshNode>,
+    m_spot_light_visible_mesh_nodes: Vec<Vec<RenderMeshNode>>,
 
     m_instance_id_allocator: RefCell<GuidAllocator<GameObjectPartId>>,
     m_mesh_asset_id_allocator: RefCell<GuidAllocator<MeshSourceDesc>>,
     m_material_asset_id_allocator: RefCell<GuidAllocator<MaterialSourceDesc>>,
 
     m_mesh_object_id_map: RefCell<HashMap<u32, GObjectID>>,
+
+    m_statistics: RenderStatistics,
 }
 
 impl RenderScene {
+    /// Advances the procedural sky and points the directional light and the ambient light at
+    /// its sun and sky.
+    pub fn tick_sky_atmosphere(&mut self, delta_time: f32) {
+        let Some(sky_atmosphere) = &mut self.m_sky_atmosphere else {
+            return;
+        };
+        sky_atmosphere.tick(delta_time);
+        self.m_directional_light.m_direction = sky_atmosphere.sun_direction();
+        self.m_directional_light.m_color = sky_atmosphere.sun_color();
+        self.m_ambient_light.m_irradiance = sky_atmosphere.ambient_light();
+    }
+
     pub fn update_visible_objects(
         &mut self,
         render_resource: &RenderResource,
         camera: &RenderCamera,
     ) {
+        self.update_visible_lights(camera);
         self.update_visible_objects_main_camera(render_resource, camera);
         self.update_visible_objects_directional_light(render_resource, camera);
+        self.update_visible_objects_point_light(render_resource);
+        self.update_visible_objects_spot_light(render_resource);
+
+        self.m_statistics.main_camera_draw_call_count =
+            mesh_node_draw_call_count(&self.m_main_camera_visible_mesh_nodes)
+                + mesh_node_draw_call_count(&self.m_main_camera_visible_transparent_mesh_nodes);
+        self.m_statistics.shadow_draw_call_count = self
+            .m_directional_light_visible_mesh_nodes
+            .iter()
+            .chain(self.m_spot_light_visible_mesh_nodes.iter())
+            .chain(std::iter::once(&self.m_point_lights_visible_mesh_nodes))
+            .map(|nodes| mesh_node_draw_call_count(nodes))
+            .sum();
+    }
+
+    /// Fills `m_point_light_list` and `m_spot_light_list` with the lights whose range reaches
+    /// into the camera frustum, nearest first. The first `S_MAX_POINT_LIGHT_COUNT` point lights
+    /// and the first `S_MAX_SPOT_LIGHT_SHADOW_COUNT` shadowed spot lights get shadow maps.
+    pub fn update_visible_lights(&mut self, camera: &RenderCamera) {
+        let proj_view_matrix = camera.get_perspective_matrix() * camera.get_view_matrix();
+        let frustum =
+            create_cluster_frustum_from_matrix(&proj_view_matrix, -1.0, 1.0, -1.0, 1.0, 0.0, 1.0);
+        let camera_position = *camera.position();
+
+        let mut visible_lights: Vec<_> = self
+            .m_point_lights
+            .iter()
+            .filter(|(_, light)| {
+                tiled_frustum_intersect_sphere(
+                    &frustum,
+                    &light.m_position,
+                    light.calculate_radius(),
+                )
+            })
+            .map(|(go_id, light)| ((light.m_position - camera_position).length(), *go_id, light))
+            .collect();
+        visible_lights.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        self.m_point_light_list.m_lights = visible_lights
+            .into_iter()
+            .map(|(_, _, light)| light.clone())
+            .collect();
+
+        self.m_statistics.visible_point_light_count = self.m_point_light_list.m_lights.len();
+        self.m_statistics.culled_point_light_count =
+            self.m_point_lights.len() - self.m_point_light_list.m_lights.len();
+
+        let mut visible_spot_lights: Vec<_> = self
+            .m_spot_lights
+            .iter()
+            .filter(|(_, light)| {
+                tiled_frustum_intersect_sphere(
+                    &frustum,
+                    &light.m_position,
+                    light.calculate_radius(),
+                )
+            })
+            .map(|(go_id, light)| ((light.m_position - camera_position).length(), *go_id, light))
+            .collect();
+        visible_spot_lights.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        self.m_spot_light_list.m_lights = visible_spot_lights
+            .into_iter()
+            .map(|(_, _, light)| light.clone())
+            .collect();
+        self.m_spot_light_list.m_shadow_light_indices = self
+            .m_spot_light_list
+            .m_lights
+            .iter()
+            .enumerate()
+            .filter(|(_, light)| light.m_cast_shadow)
+            .map(|(i, _)| i)
+            .take(S_MAX_SPOT_LIGHT_SHADOW_COUNT)
+            .collect();
+        self.m_spot_light_list.m_shadow_proj_views = self
+            .m_spot_light_list
+            .m_shadow_light_indices
+            .iter()
+            .map(|&i| calculate_spot_light_camera(&self.m_spot_light_list.m_lights[i]))
+            .collect();
+
+        self.m_statistics.visible_spot_light_count = self.m_spot_light_list.m_lights.len();
+        self.m_statistics.culled_spot_light_count =
+            self.m_spot_lights.len() - self.m_spot_light_list.m_lights.len();
+    }
+
+    pub fn insert_or_update_point_light(&mut self, go_id: GObjectID, light: PointLight) {
+        self.m_point_lights.insert(go_id, light);
+    }
+
+    pub fn delete_point_light(&mut self, go_id: GObjectID) {
+        self.m_point_lights.remove(&go_id);
+    }
+
+    pub fn get_point_light_count(&self) -> usize {
+        self.m_point_lights.len()
+    }
+
+    pub fn insert_or_update_spot_light(&mut self, go_id: GObjectID, light: SpotLight) {
+        self.m_spot_lights.insert(go_id, light);
+    }
+
+    pub fn delete_spot_light(&mut self, go_id: GObjectID) {
+        self.m_spot_lights.remove(&go_id);
+    }
+
+    pub fn get_spot_light_count(&self) -> usize {
+        self.m_spot_lights.len()
     }
 
     pub fn alloc_instance_id(&self, part_id: &GameObjectPartId) -> usize {
@@ -70,19 +231,31 @@ impl RenderScene {
             .borrow_mut()
             .remove(&(go_id as u32));
 
-        let part_id = GameObjectPartId {
-            m_go_id: go_id,
-            m_part_id: 0,
-        };
-        if let Some(instance_id) = self
+        // every part got an instance id when the object was added, even the ones skipped since
+        for part_index in 0.. {
+            let part_id = GameObjectPartId {
+                m_go_id: go_id,
+                m_part_id: part_index,
+            };
+            if !self.delete_entity_by_part_id(&part_id) {
+                break;
+            }
+        }
+    }
+
+    /// Returns false if the part never got an instance id.
+    pub fn delete_entity_by_part_id(&self, part_id: &GameObjectPartId) -> bool {
+        let Some(instance_id) = self
             .m_instance_id_allocator
             .borrow()
-            .get_element_guid(&part_id)
-        {
-            self.m_render_entities
-                .borrow_mut()
-                .remove(&(instance_id as u32));
-        }
+            .get_element_guid(part_id)
+        else {
+            return false;
+        };
+        self.m_render_entities
+            .borrow_mut()
+            .remove(&(instance_id as u32));
+        true
     }
 
     pub fn calc_scene_bounding_box(&self) -> BoundingBox {
@@ -92,19 +265,31 @@ impl RenderScene {
             .borrow()
             .iter()
             .for_each(|(_id, entity)| {
-                let mesh_asset_bounding_box = BoundingBox {
-                    min_bound: *entity.m_bounding_box.get_min_corner(),
-                    max_bound: *entity.m_bounding_box.get_max_corner(),
-                };
-                let mesh_bounding_box_world =
-                    bounding_box_transform(&mesh_asset_bounding_box, &entity.m_model_matrix);
-                scene_bounding_box.merge_box(&mesh_bounding_box_world);
+                if let Some(mesh_bounding_box_world) = Self::entity_world_bounding_box(entity) {
+                    scene_bounding_box.merge_box(&mesh_bounding_box_world);
+                }
             });
         scene_bounding_box
     }
 
-    pub fn insert_or_update_render_entity(&self, render_entity: Box<RenderEntity>) {
+    /// Runs once a frame before the entities are updated, so an entity that does not move has
+    /// no velocity.
+    pub fn store_previous_model_matrices(&self) {
+        for entity in self.m_render_entities.borrow_mut().values_mut() {
+            entity.m_previous_model_matrix = entity.m_model_matrix.clone();
+        }
+    }
+
+    pub fn insert_or_update_render_entity(&self, mut render_entity: Box<RenderEntity>) {
         let instance_id = render_entity.m_instance_id;
+        render_entity.m_previous_model_matrix = self
+            .m_render_entities
+            .borrow()
+            .get(&instance_id)
+            .map_or(&render_entity.m_model_matrix, |entity| {
+                &entity.m_model_matrix
+            })
+            .clone();
         if self.m_render_entities.borrow().contains_key(&instance_id) {
             *self
                 .m_render_entities
@@ -122,45 +307,301 @@ impl RenderScene {
         &self.m_main_camera_visible_mesh_nodes
     }
 
-    pub fn get_directional_light_visible_mesh_nodes(&self) -> &[RenderMeshNode] {
-        &self.m_directional_light_visible_mesh_nodes
+    /// Blended meshes in the camera frustum, farthest first.
+    pub fn get_main_camera_visible_transparent_mesh_nodes(&self) -> &[RenderMeshNode] {
+        &self.m_main_camera_visible_transparent_mesh_nodes
+    }
+
+    pub fn get_directional_light_visible_mesh_nodes(
+        &self,
+        cascade_index: usize,
+    ) -> &[RenderMeshNode] {
+        self.m_directional_light_visible_mesh_nodes
+            .get(cascade_index)
+            .map_or(&[], |nodes| nodes.as_slice())
+    }
+
+    /// Meshes within range of a visible point light, drawn into the point light shadow maps.
+    pub fn get_point_lights_visible_mesh_nodes(&self) -> &[RenderMeshNode] {
+        &self.m_point_lights_visible_mesh_nodes
+    }
+
+    /// Meshes inside the frustum of the spot light drawn into shadow map layer `shadow_index`.
+    pub fn get_spot_light_visible_mesh_nodes(&self, shadow_index: usize) -> &[RenderMeshNode] {
+        self.m_spot_light_visible_mesh_nodes
+            .get(shadow_index)
+            .map_or(&[], |nodes| nodes.as_slice())
+    }
+
+    /// Light-space projection of each shadow cascade, fitted in `update_visible_objects`.
+    pub fn get_directional_light_cascade_proj_views(&self) -> &[Matrix4x4] {
+        &self.m_directional_light_cascade_proj_views
+    }
+
+    /// View-space depth where each shadow cascade ends.
+    pub fn get_directional_light_cascade_splits(&self) -> &[f32] {
+        &self.m_directional_light_cascade_splits
+    }
+
+    pub fn get_statistics(&self) -> &RenderStatistics {
+        &self.m_statistics
     }
 }
 
 impl RenderScene {
+    /// World-space bounds of the entity's cached mesh box, or `None` for an empty mesh.
+    fn entity_world_bounding_box(entity: &RenderEntity) -> Option<BoundingBox> {
+        let min_bound = *entity.m_bounding_box.get_min_corner();
+        let max_bound = *entity.m_bounding_box.get_max_corner();
+        if min_bound.x > max_bound.x || min_bound.y > max_bound.y || min_bound.z > max_bound.z {
+            return None;
+        }
+        let mesh_asset_bounding_box = BoundingBox {
+            min_bound,
+            max_bound,
+        };
+        Some(bounding_box_transform(
+            &mesh_asset_bounding_box,
+            &entity.m_model_matrix,
+        ))
+    }
+
     fn update_visible_objects_main_camera(
         &mut self,
         render_resource: &RenderResource,
-        _camera: &RenderCamera,
+        camera: &RenderCamera,
     ) {
         let main_camera_visible_mesh_nodes = &mut self.m_main_camera_visible_mesh_nodes;
         main_camera_visible_mesh_nodes.clear();
 
+        let proj_view_matrix = camera.get_perspective_matrix() * camera.get_view_matrix();
+        let frustum =
+            create_cluster_frustum_from_matrix(&proj_view_matrix, -1.0, 1.0, -1.0, 1.0, 0.0, 1.0);
+        let camera_position = *camera.position();
+        let mut culled_mesh_count = 0;
+        let mut transparent_mesh_nodes = Vec::new();
+
         for (_instance_id, entity) in self.m_render_entities.borrow().iter() {
+            let world_bounding_box = Self::entity_world_bounding_box(entity);
+            // Entities without bounds are empty meshes and cost nothing to keep.
+            if let Some(world_bounding_box) = &world_bounding_box {
+                if !tiled_frustum_intersect_box(&frustum, world_bounding_box) {
+                    culled_mesh_count += 1;
+                    continue;
+                }
+            }
+
             let mut temp_node = RenderMeshNode::default();
             temp_node.model_matrix = entity.m_model_matrix.clone();
+            temp_node.previous_model_matrix = entity.m_previous_model_matrix.clone();
             temp_node.node_id = entity.m_instance_id;
 
             let mesh_asset = render_resource.get_entity_mesh(entity);
             temp_node.ref_mesh = Rc::downgrade(mesh_asset);
             temp_node.enable_vertex_blending = entity.m_enable_vertex_blending;
+            temp_node.alpha_mode = entity.m_alpha_mode;
 
             let material_asset = render_resource.get_entity_material(entity);
             temp_node.ref_material = Rc::downgrade(material_asset);
 
-            main_camera_visible_mesh_nodes.push(temp_node);
+            if entity.m_alpha_mode == MaterialAlphaMode::Blend {
+                let distance = world_bounding_box.map_or(0.0, |bounding_box| {
+                    let center = (bounding_box.min_bound + bounding_box.max_bound) * 0.5;
+                    (center - camera_position).length()
+                });
+                transparent_mesh_nodes.push((distance, temp_node));
+            } else {
+                main_camera_visible_mesh_nodes.push(temp_node);
+            }
         }
+        sort_mesh_nodes_for_batching(main_camera_visible_mesh_nodes);
+        // Farthest first, so each blended mesh lands over what is behind it.
+        transparent_mesh_nodes.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        self.m_statistics.main_camera_visible_mesh_count =
+            main_camera_visible_mesh_nodes.len() + transparent_mesh_nodes.len();
+        self.m_main_camera_visible_transparent_mesh_nodes = transparent_mesh_nodes
+            .into_iter()
+            .map(|(_, node)| node)
+            .collect();
+        self.m_statistics.main_camera_culled_mesh_count = culled_mesh_count;
     }
 
     fn update_visible_objects_directional_light(
         &mut self,
         render_resource: &RenderResource,
-        _camera: &RenderCamera,
+        camera: &RenderCamera,
     ) {
+        let cascade_count = self.m_directional_light.cascade_count();
+        let shadow_distance = self
+            .m_directional_light
+            .m_shadow_distance
+            .clamp(camera.get_znear(), camera.get_zfar());
+        self.m_directional_light_cascade_splits = calculate_directional_light_cascade_splits(
+            camera.get_znear(),
+            shadow_distance,
+            cascade_count,
+            self.m_directional_light.m_cascade_split_lambda,
+        );
+
+        let mut split_near = camera.get_znear();
+        let mut cascade_proj_views = Vec::with_capacity(cascade_count);
+        for &split_far in &self.m_directional_light_cascade_splits {
+            cascade_proj_views.push(calculate_directional_light_camera(
+                self, camera, split_near, split_far,
+            ));
+            split_near = split_far;
+        }
+        self.m_directional_light_cascade_proj_views = cascade_proj_views;
+
+        let frustums: Vec<_> = self
+            .m_directional_light_cascade_proj_views
+            .iter()
+            .map(|proj_view| {
+                create_cluster_frustum_from_matrix(proj_view, -1.0, 1.0, -1.0, 1.0, 0.0, 1.0)
+            })
+            .collect();
+        let mut visible_mesh_count = 0;
+        let mut culled_mesh_count = 0;
+
         let directional_light_visible_mesh_nodes = &mut self.m_directional_light_visible_mesh_nodes;
-        directional_light_visible_mesh_nodes.clear();
+        directional_light_visible_mesh_nodes.resize_with(cascade_count, Vec::new);
+        directional_light_visible_mesh_nodes
+            .iter_mut()
+            .for_each(|nodes| nodes.clear());
+
+        for (_instance_id, entity) in self.m_render_entities.borrow().iter() {
+            // Blended meshes cast no shadow.
+            if entity.m_alpha_mode == MaterialAlphaMode::Blend {
+                continue;
+            }
+            let world_bounding_box = Self::entity_world_bounding_box(entity);
+            let cascade_visibility: Vec<bool> = frustums
+                .iter()
+                .map(|frustum| {
+                    world_bounding_box.as_ref().is_none_or(|bounding_box| {
+                        tiled_frustum_intersect_box(frustum, bounding_box)
+                    })
+                })
+                .collect();
+            if !cascade_visibility.contains(&true) {
+                culled_mesh_count += 1;
+                continue;
+            }
+            visible_mesh_count += 1;
+
+            let mut temp_node = RenderMeshNode::default();
+            temp_node.model_matrix = entity.m_model_matrix.clone();
+            temp_node.node_id = entity.m_instance_id;
+
+            let mesh_asset = render_resource.get_entity_mesh(entity);
+            temp_node.ref_mesh = Rc::downgrade(mesh_asset);
+            temp_node.enable_vertex_blending = entity.m_enable_vertex_blending;
+            temp_node.alpha_mode = entity.m_alpha_mode;
+
+            let material_asset = render_resource.get_entity_material(entity);
+            temp_node.ref_material = Rc::downgrade(material_asset);
+
+            for (nodes, visible) in directional_light_visible_mesh_nodes
+                .iter_mut()
+                .zip(cascade_visibility)
+            {
+                if visible {
+                    nodes.push(temp_node.clone());
+                }
+            }
+        }
+        directional_light_visible_mesh_nodes
+            .iter_mut()
+            .for_each(|nodes| sort_mesh_nodes_for_batching(nodes));
+
+        self.m_statistics.directional_light_visible_mesh_count = visible_mesh_count;
+        self.m_statistics.directional_light_culled_mesh_count = culled_mesh_count;
+    }
+
+    fn update_visible_objects_point_light(&mut self, render_resource: &RenderResource) {
+        let point_lights_visible_mesh_nodes = &mut self.m_point_lights_visible_mesh_nodes;
+        point_lights_visible_mesh_nodes.clear();
+
+        let light_spheres: Vec<_> = self
+            .m_point_light_list
+            .m_lights
+            .iter()
+            .take(S_MAX_POINT_LIGHT_COUNT)
+            .map(|light| (light.m_position, light.calculate_radius()))
+            .collect();
+        if light_spheres.is_empty() {
+            return;
+        }
+
+        for (_instance_id, entity) in self.m_render_entities.borrow().iter() {
+            // Blended meshes cast no shadow.
+            if entity.m_alpha_mode == MaterialAlphaMode::Blend {
+                continue;
+            }
+            if let Some(world_bounding_box) = Self::entity_world_bounding_box(entity) {
+                let in_range = light_spheres.iter().any(|(center, radius)| {
+                    box_intersect_sphere(&world_bounding_box, center, *radius)
+                });
+                if !in_range {
+                    continue;
+                }
+            }
+
+            let mut temp_node = RenderMeshNode::default();
+            temp_node.model_matrix = entity.m_model_matrix.clone();
+            temp_node.node_id = entity.m_instance_id;
+
+            let mesh_asset = render_resource.get_entity_mesh(entity);
+            temp_node.ref_mesh = Rc::downgrade(mesh_asset);
+            temp_node.enable_vertex_blending = entity.m_enable_vertex_blending;
+            temp_node.alpha_mode = entity.m_alpha_mode;
+
+            let material_asset = render_resource.get_entity_material(entity);
+            temp_node.ref_material = Rc::downgrade(material_asset);
+
+            point_lights_visible_mesh_nodes.push(temp_node);
+        }
+        sort_mesh_nodes_for_batching(point_lights_visible_mesh_nodes);
+    }
+
+    fn update_visible_objects_spot_light(&mut self, render_resource: &RenderResource) {
+        let frustums: Vec<_> = self
+            .m_spot_light_list
+            .m_shadow_proj_views
+            .iter()
+            .map(|proj_view| {
+                create_cluster_frustum_from_matrix(proj_view, -1.0, 1.0, -1.0, 1.0, 0.0, 1.0)
+            })
+            .collect();
+
+        let spot_light_visible_mesh_nodes = &mut self.m_spot_light_visible_mesh_nodes;
+        spot_light_visible_mesh_nodes.resize_with(frustums.len(), Vec::new);
+        spot_light_visible_mesh_nodes
+            .iter_mut()
+            .for_each(|nodes| nodes.clear());
+        if frustums.is_empty() {
+            return;
+        }
 
         for (_instance_id, entity) in self.m_render_entities.borrow().iter() {
+            // Blended meshes cast no shadow.
+            if entity.m_alpha_mode == MaterialAlphaMode::Blend {
+                continue;
+            }
+            let world_bounding_box = Self::entity_world_bounding_box(entity);
+            let shadow_visibility: Vec<bool> = frustums
+                .iter()
+                .map(|frustum| {
+                    world_bounding_box.as_ref().is_none_or(|bounding_box| {
+                        tiled_frustum_intersect_box(frustum, bounding_box)
+                    })
+                })
+                .collect();
+            if !shadow_visibility.contains(&true) {
+                continue;
+            }
+
             let mut temp_node = RenderMeshNode::default();
             temp_node.model_matrix = entity.m_model_matrix.clone();
             temp_node.node_id = entity.m_instance_id;
@@ -168,11 +609,22 @@ impl RenderScene {
             let mesh_asset = render_resource.get_entity_mesh(entity);
             temp_node.ref_mesh = Rc::downgrade(mesh_asset);
             temp_node.enable_vertex_blending = entity.m_enable_vertex_blending;
+            temp_node.alpha_mode = entity.m_alpha_mode;
 
             let material_asset = render_resource.get_entity_material(entity);
             temp_node.ref_material = Rc::downgrade(material_asset);
 
-            directional_light_visible_mesh_nodes.push(temp_node);
+            for (nodes, visible) in spot_light_visible_mesh_nodes
+                .iter_mut()
+                .zip(shadow_visibility)
+            {
+                if visible {
+                    nodes.push(temp_node.clone());
+                }
+            }
         }
+        spot_light_visible_mesh_nodes
+            .iter_mut()
+            .for_each(|nodes| sort_mesh_nodes_for_batching(nodes));
     }
 }
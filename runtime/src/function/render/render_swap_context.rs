@@ -1,8 +1,9 @@
 use std::{cell::RefCell, collections::VecDeque};
 
-use crate::{core::math::matrix4::Matrix4x4, function::render::{render_camera::RenderCameraType, render_object::GameObjectDesc}, resource::res_type::global::global_rendering::{SkyBoxIrradianceMap, SkyBoxSpecularMap}};
+use crate::{core::math::{matrix4::Matrix4x4, vector3::Vector3}, function::{framework::object::object_id_allocator::GObjectID, render::{render_camera::RenderCameraType, render_object::GameObjectDesc}}, resource::res_type::global::global_rendering::{SkyBoxIrradianceMap, SkyBoxSpecularMap}};
 
 pub struct LevelIBLResourceDesc{
+    pub m_environment_map: String,
     pub m_skybox_irradiance_map: SkyBoxIrradianceMap,
     pub m_skybox_specular_map: SkyBoxSpecularMap,
     pub m_brdf_map: String,
@@ -24,6 +25,36 @@ pub struct CameraSwapData{
     pub m_view_matrix: Option<Matrix4x4>,
 }
 
+#[derive(Clone, Copy)]
+pub struct PointLightDesc{
+    pub m_position: Vector3,
+    pub m_flux: Vector3,
+}
+
+/// Point lights changed or removed since the last swap, keyed by their owning game object.
+#[derive(Default, Clone)]
+pub struct PointLightSwapData{
+    pub m_dirty_lights: Vec<(GObjectID, PointLightDesc)>,
+    pub m_lights_to_delete: Vec<GObjectID>,
+}
+
+#[derive(Clone, Copy)]
+pub struct SpotLightDesc{
+    pub m_position: Vector3,
+    pub m_direction: Vector3,
+    pub m_flux: Vector3,
+    pub m_inner_cone_angle: f32,
+    pub m_outer_cone_angle: f32,
+    pub m_cast_shadow: bool,
+}
+
+/// Spot lights changed or removed since the last swap, keyed by their owning game object.
+#[derive(Default, Clone)]
+pub struct SpotLightSwapData{
+    pub m_dirty_lights: Vec<(GObjectID, SpotLightDesc)>,
+    pub m_lights_to_delete: Vec<GObjectID>,
+}
+
 #[derive(Default, Clone)]
 pub struct GameObjectResourceDesc{
     m_game_object_descs: VecDeque<GameObjectDesc>,
@@ -52,6 +83,10 @@ pub struct RenderSwapData{
     pub m_camera_swap_data: Option<CameraSwapData>,
     pub m_game_object_resource_descs: Option<GameObjectResourceDesc>,
     pub m_game_object_to_delete: Option<GameObjectResourceDesc>,
+    pub m_point_light_swap_data: Option<PointLightSwapData>,
+    pub m_spot_light_swap_data: Option<SpotLightSwapData>,
+    /// Render pipeline preset to switch to, such as the one a newly loaded world names.
+    pub m_render_pipeline_url: Option<String>,
 }
 
 impl RenderSwapData{
@@ -81,6 +116,32 @@ impl RenderSwapData{
         }
     }
 
+    pub fn add_dirty_point_light(&mut self, go_id: GObjectID, desc: PointLightDesc){
+        let point_light_swap_data = self.m_point_light_swap_data.get_or_insert_with(Default::default);
+        point_light_swap_data.m_lights_to_delete.retain(|id| *id != go_id);
+        point_light_swap_data.m_dirty_lights.retain(|(id, _)| *id != go_id);
+        point_light_swap_data.m_dirty_lights.push((go_id, desc));
+    }
+
+    pub fn add_delete_point_light(&mut self, go_id: GObjectID){
+        let point_light_swap_data = self.m_point_light_swap_data.get_or_insert_with(Default::default);
+        point_light_swap_data.m_dirty_lights.retain(|(id, _)| *id != go_id);
+        point_light_swap_data.m_lights_to_delete.push(go_id);
+    }
+
+    pub fn add_dirty_spot_light(&mut self, go_id: GObjectID, desc: SpotLightDesc){
+        let spot_light_swap_data = self.m_spot_light_swap_data.get_or_insert_with(Default::default);
+        spot_light_swap_data.m_lights_to_delete.retain(|id| *id != go_id);
+        spot_light_swap_data.m_dirty_lights.retain(|(id, _)| *id != go_id);
+        spot_light_swap_data.m_dirty_lights.push((go_id, desc));
+    }
+
+    pub fn add_delete_spot_light(&mut self, go_id: GObjectID){
+        let spot_light_swap_data = self.m_spot_light_swap_data.get_or_insert_with(Default::default);
+        spot_light_swap_data.m_dirty_lights.retain(|(id, _)| *id != go_id);
+        spot_light_swap_data.m_lights_to_delete.push(go_id);
+    }
+
 }
 
 pub enum SwapDataType{
@@ -125,7 +186,10 @@ impl RenderSwapContext{
     fn is_ready_to_swap(&self) -> bool {
         self.m_swap_data[self.m_render_swap_data_index].borrow().m_game_object_resource_descs.is_none() &&
         self.m_swap_data[self.m_render_swap_data_index].borrow().m_game_object_to_delete.is_none() &&
-        self.m_swap_data[self.m_render_swap_data_index].borrow().m_camera_swap_data.is_none() 
+        self.m_swap_data[self.m_render_swap_data_index].borrow().m_camera_swap_data.is_none() &&
+        self.m_swap_data[self.m_render_swap_data_index].borrow().m_point_light_swap_data.is_none() &&
+        self.m_swap_data[self.m_render_swap_data_index].borrow().m_spot_light_swap_data.is_none() &&
+        self.m_swap_data[self.m_render_swap_data_index].borrow().m_render_pipeline_url.is_none()
     }
 
     pub fn reset_game_object_resource_swap_data(&self){
@@ -140,6 +204,18 @@ impl RenderSwapContext{
         self.m_swap_data[self.m_render_swap_data_index].borrow_mut().m_camera_swap_data = None;
     }
 
+    pub fn reset_point_light_swap_data(&self){
+        self.m_swap_data[self.m_render_swap_data_index].borrow_mut().m_point_light_swap_data = None;
+    }
+
+    pub fn reset_spot_light_swap_data(&self){
+        self.m_swap_data[self.m_render_swap_data_index].borrow_mut().m_spot_light_swap_data = None;
+    }
+
+    pub fn reset_render_pipeline_swap_data(&self){
+        self.m_swap_data[self.m_render_swap_data_index].borrow_mut().m_render_pipeline_url = None;
+    }
+
     fn swap(&mut self) {
         self.reset_game_object_resource_swap_data();
         self.reset_camera_swap_data();
@@ -1,40 +1,51 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, path::Path, rc::Rc};
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use image::RgbaImage;
+use vulkanalia::prelude::v1_0::*;
 
 use crate::{
-    core::math::vector2::Vector2,
+    core::math::{vector2::Vector2, vector3::Vector3},
     function::{
         render::{
             debugdraw::debug_draw_manager::{DebugDrawManager, DebugDrawManagerCreateInfo},
             interface::{rhi::RHICreateInfo, vulkan::vulkan_rhi::VulkanRHI},
-            light::{AmbientLight, DirectionalLight},
+            light::{
+                AmbientLight, DirectionalLight, HDRBloom, HeightFog, PointLight,
+                ScreenSpaceAmbientOcclusion, SpotLight, TemporalAntiAliasing,
+            },
             render_camera::RenderCamera,
+            render_common::RenderStatistics,
             render_entity::RenderEntity,
             render_object::{GameObjectMeshDesc, GameObjectPartId},
             render_pass::DescriptorLayoutRegistry,
             render_pipeline::{
-                RenderPipelineTrait,
+                RenderPipelineConfig, RenderPipelineTrait,
                 pbr_pipeline::{
                     PBRRenderPipeline, PBRRenderPipelineCreateInfo,
                     main_camera_pass::{MeshPerMaterialDescriptorLayout, PerMeshDescriptorLayout},
-                }, ui_pipeline::{UIRenderPipeline, UIRenderPipelineCreateInfo},
+                },
+                ui_pipeline::{UIRenderPipeline, UIRenderPipelineCreateInfo},
             },
             render_resource::RenderResource,
             render_resource_base::RenderResourceBase,
             render_scene::RenderScene,
             render_swap_context::{
                 LevelColorGradingResourceDesc, LevelIBLResourceDesc, LevelResourceDesc,
-                RenderSwapContext, RenderSwapData,
+                PointLightSwapData, RenderSwapContext, RenderSwapData, SpotLightSwapData,
             },
             render_type::{MaterialSourceDesc, MeshSourceDesc},
+            sky_atmosphere::SkyAtmosphere,
             window_system::WindowSystem,
         },
         ui::ui2::UiRuntime,
     },
     resource::{
-        asset_manager::AssetManager, config_manager::ConfigManager,
-        res_type::global::global_rendering::GlobalRenderingRes,
+        asset_manager::AssetManager,
+        config_manager::ConfigManager,
+        res_type::{
+            data::render_pipeline::RenderPipelineRes, global::global_rendering::GlobalRenderingRes,
+        },
     },
 };
 
@@ -51,11 +62,15 @@ pub struct RenderSystemCreateInfo<'a> {
     pub config_manager: &'a ConfigManager,
 }
 
-pub struct RenderSystem {
+pub struct HeadlessRenderSystemCreateInfo<'a> {
+    pub viewport_size: (u32, u32),
+    pub asset_manager: &'a AssetManager,
+    pub config_manager: &'a ConfigManager,
+}
+
+/// Everything that needs a Vulkan device. Absent when the engine runs headless.
+struct RenderBackend {
     m_rhi: VulkanRHI,
-    m_swap_context: RenderSwapContext,
-    m_render_camera: Rc<RefCell<RenderCamera>>,
-    m_render_scene: RenderScene,
     m_render_resource: RenderResource,
     m_render_pipeline: Box<dyn RenderPipelineTrait>,
     m_descriptor_layout_registry: DescriptorLayoutRegistry,
@@ -63,6 +78,27 @@ pub struct RenderSystem {
     m_debugdraw_manager: RefCell<DebugDrawManager>,
 }
 
+pub struct RenderSystem {
+    m_swap_context: RenderSwapContext,
+    m_render_camera: Rc<RefCell<RenderCamera>>,
+    m_render_scene: RenderScene,
+    m_backend: Option<RenderBackend>,
+
+    m_headless_viewport_size: (u32, u32),
+    m_last_consumed_swap_data: RenderSwapData,
+
+    /// Index into the jitter sequence of TAA.
+    m_taa_frame_index: u32,
+    /// Where the camera was last frame, to tell a camera cut from movement.
+    m_last_camera_position: Option<Vector3>,
+}
+
+/// Set to `1` to overwrite reference images in `compare_frame_with_reference`.
+const UPDATE_REFERENCE_IMAGES_ENV: &str = "PICCOLO_UPDATE_REFERENCE_IMAGES";
+
+/// Jitter positions per cycle; the Halton sequence covers the pixel evenly well before this.
+const TAA_JITTER_SAMPLE_COUNT: u32 = 8;
+
 impl RenderSystem {
     pub fn create(create_info: &RenderSystemCreateInfo) -> Self {
         let rhi_create_info = RHICreateInfo {
@@ -72,33 +108,12 @@ impl RenderSystem {
 
         let asset_manager = create_info.asset_manager;
         let config_manager = create_info.config_manager;
-        let global_rendering_res_url = config_manager.get_global_rendering_res_url();
-        let global_rendering_res: GlobalRenderingRes =
-            asset_manager.load_asset(&global_rendering_res_url).unwrap();
-
-        let swap_context = RenderSwapContext::default();
-
-        let mut render_camera = RenderCamera::default();
-        let camera_pose = &global_rendering_res.camera_config.pose;
-        render_camera.look_at(camera_pose.position, &camera_pose.target, &camera_pose.up);
-        render_camera.set_znear(global_rendering_res.camera_config.z_near);
-        render_camera.set_zfar(global_rendering_res.camera_config.z_far);
-        render_camera.set_aspect(
-            global_rendering_res.camera_config.aspect.x
-                / global_rendering_res.camera_config.aspect.y,
-        );
-
-        let mut render_scene = RenderScene::default();
-        render_scene.m_ambient_light = AmbientLight {
-            m_irradiance: global_rendering_res.ambient_light,
-        };
-        render_scene.m_directional_light = DirectionalLight {
-            m_direction: global_rendering_res.directional_light.direction,
-            m_color: global_rendering_res.directional_light.color,
-        };
+        let global_rendering_res = Self::load_global_rendering_res(asset_manager, config_manager);
+        let mut render_system = Self::create_without_backend(&global_rendering_res, (0, 0));
 
         let level_resource_desc = LevelResourceDesc {
             m_ibl_resource_desc: LevelIBLResourceDesc {
+                m_environment_map: global_rendering_res.environment_map,
                 m_skybox_irradiance_map: global_rendering_res.skybox_irradiance_map,
                 m_skybox_specular_map: global_rendering_res.skybox_specular_map,
                 m_brdf_map: global_rendering_res.brdf_map,
@@ -117,21 +132,41 @@ impl RenderSystem {
 
         let descriptor_layout_registry = DescriptorLayoutRegistry::default();
 
+        let msaa_samples = vulkan_rhi
+            .get_msaa_sample_count(global_rendering_res.multisample_anti_aliasing.sample_count)
+            .unwrap_or_else(|err| {
+                log::warn!("{err}, rendering without MSAA");
+                vk::SampleCountFlags::_1
+            });
+
         let render_pipeline: Box<dyn RenderPipelineTrait> = match create_info.render_pipeline_type {
             RenderPipelineType::PBR => {
-                Box::new(PBRRenderPipeline::create(&PBRRenderPipelineCreateInfo {
-                    rhi: &vulkan_rhi,
-                    render_resource: &render_resource,
-                    descriptor_layout_registry: &descriptor_layout_registry,
-                    enable_fxaa: global_rendering_res.enable_fxaa,
-                }).unwrap())
+                let config =
+                    Self::load_render_pipeline_config(asset_manager, &global_rendering_res);
+                render_system.m_render_scene.m_temporal_aa.m_enable = config.uses_taa();
+                Box::new(
+                    PBRRenderPipeline::create(&PBRRenderPipelineCreateInfo {
+                        rhi: &vulkan_rhi,
+                        render_resource: &render_resource,
+                        descriptor_layout_registry: &descriptor_layout_registry,
+                        config,
+                        directional_light_cascade_count: render_system
+                            .m_render_scene
+                            .m_directional_light
+                            .cascade_count(),
+                        msaa_samples,
+                    })
+                    .unwrap(),
+                )
             }
-            RenderPipelineType::UI => {
-                Box::new(UIRenderPipeline::create(&UIRenderPipelineCreateInfo {
+            RenderPipelineType::UI => Box::new(
+                UIRenderPipeline::create(&UIRenderPipelineCreateInfo {
                     rhi: &vulkan_rhi,
                     descriptor_layout_registry: &descriptor_layout_registry,
-                }).unwrap())
-            }
+                    msaa_samples,
+                })
+                .unwrap(),
+            ),
         };
 
         render_resource.m_mesh_descriptor_set_layout = descriptor_layout_registry
@@ -148,16 +183,33 @@ impl RenderSystem {
         })
         .unwrap();
 
-        Self {
+        render_system.m_backend = Some(RenderBackend {
             m_rhi: vulkan_rhi,
-            m_swap_context: swap_context,
-            m_render_camera: Rc::new(RefCell::new(render_camera)),
-            m_render_scene: render_scene,
             m_render_resource: render_resource,
             m_render_pipeline: render_pipeline,
             m_debugdraw_manager: RefCell::new(debugdraw_manager),
             m_descriptor_layout_registry: descriptor_layout_registry,
+        });
+        render_system
+    }
+
+    /// Builds a render system that keeps the camera and swap data but never touches Vulkan.
+    pub fn create_headless(create_info: &HeadlessRenderSystemCreateInfo) -> Self {
+        let global_rendering_res =
+            Self::load_global_rendering_res(create_info.asset_manager, create_info.config_manager);
+        let (width, height) = create_info.viewport_size;
+        let render_system = Self::create_without_backend(&global_rendering_res, (width, height));
+        if width > 0 && height > 0 {
+            render_system
+                .m_render_camera
+                .borrow_mut()
+                .set_aspect(width as f32 / height as f32);
         }
+        render_system
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.m_backend.is_none()
     }
 
     pub fn tick(
@@ -166,32 +218,53 @@ impl RenderSystem {
         asset_manager: &AssetManager,
         delta_time: f32,
     ) -> Result<()> {
+        self.m_render_scene.tick_sky_atmosphere(delta_time);
+        if self.is_headless() {
+            self.consume_headless_swap_data();
+            return Ok(());
+        }
+        self.m_render_scene.store_previous_model_matrices();
         self.process_swap_data(asset_manager);
-        self.m_rhi.prepare_context();
-        self.m_render_resource
-            .update_per_frame_buffer(&self.m_render_scene, &self.m_render_camera.borrow());
+        self.update_temporal_aa();
+        let backend = self.m_backend.as_mut().unwrap();
+        backend.m_rhi.prepare_context();
         self.m_render_scene
-            .update_visible_objects(&self.m_render_resource, &self.m_render_camera.borrow());
-        self.m_render_pipeline
-            .prepare_pass_data(&self.m_rhi, &self.m_render_resource);
-        if self.m_render_pipeline.supports_debugdraw() {
-            self.m_debugdraw_manager
+            .update_visible_objects(&backend.m_render_resource, &self.m_render_camera.borrow());
+        backend
+            .m_render_resource
+            .update_per_frame_buffer(&self.m_render_scene, &self.m_render_camera.borrow());
+        backend
+            .m_render_pipeline
+            .prepare_pass_data(&backend.m_rhi, &backend.m_render_resource);
+        if backend.m_render_pipeline.supports_debugdraw() {
+            backend
+                .m_debugdraw_manager
                 .borrow_mut()
-                .prepare_pass_data(&self.m_render_resource);
-            self.m_debugdraw_manager
+                .prepare_pass_data(&backend.m_render_resource);
+            backend
+                .m_debugdraw_manager
                 .borrow_mut()
-                .tick(&self.m_rhi, delta_time);
+                .tick(&backend.m_rhi, delta_time);
         }
         self.render(ui_runtime)?;
+        self.m_render_scene.m_temporal_aa.m_reset_history = false;
         Ok(())
     }
 
     pub fn destroy(&mut self) -> Result<()> {
-        self.m_debugdraw_manager.borrow_mut().destroy(&self.m_rhi);
-        self.m_render_pipeline.destroy(&self.m_rhi);
-        self.m_render_resource
-            .flush_deferred_mesh_destroys(&self.m_rhi);
-        self.m_rhi.destroy();
+        let Some(backend) = self.m_backend.as_mut() else {
+            return Ok(());
+        };
+        backend.m_rhi.wait_idle()?;
+        backend
+            .m_debugdraw_manager
+            .borrow_mut()
+            .destroy(&backend.m_rhi);
+        backend.m_render_pipeline.destroy(&backend.m_rhi);
+        backend
+            .m_render_resource
+            .flush_deferred_mesh_destroys(&backend.m_rhi);
+        backend.m_rhi.destroy();
         Ok(())
     }
 
@@ -211,10 +284,20 @@ impl RenderSystem {
         &self.m_swap_context.get_logic_swap_data()
     }
 
+    /// The swap data the headless render tick consumed most recently, for inspection in tests.
+    pub fn get_last_consumed_swap_data(&self) -> &RenderSwapData {
+        &self.m_last_consumed_swap_data
+    }
+
     pub fn get_render_camera(&self) -> &Rc<RefCell<RenderCamera>> {
         &self.m_render_camera
     }
 
+    /// Counters from the most recent frame, such as how many meshes frustum culling removed.
+    pub fn get_render_statistics(&self) -> &RenderStatistics {
+        self.m_render_scene.get_statistics()
+    }
+
     pub fn update_engine_content_viewport(
         &mut self,
         offset_x: f32,
@@ -222,17 +305,182 @@ impl RenderSystem {
         width: f32,
         height: f32,
     ) {
-        let rhi = &mut self.m_rhi;
-        rhi.m_data.m_viewport.x = offset_x;
-        rhi.m_data.m_viewport.y = offset_y;
-        rhi.m_data.m_viewport.width = width;
-        rhi.m_data.m_viewport.height = height;
+        match self.m_backend.as_mut() {
+            Some(backend) => {
+                let rhi = &mut backend.m_rhi;
+                rhi.m_data.m_viewport.x = offset_x;
+                rhi.m_data.m_viewport.y = offset_y;
+                rhi.m_data.m_viewport.width = width;
+                rhi.m_data.m_viewport.height = height;
+            }
+            None => self.m_headless_viewport_size = (width as u32, height as u32),
+        }
 
         self.m_render_camera.borrow_mut().set_aspect(width / height);
     }
 
+    /// Size of the image the frame is rendered into: the swapchain extent, or the
+    /// configured size when headless.
+    pub fn get_output_extent(&self) -> (u32, u32) {
+        match self.m_backend.as_ref() {
+            Some(backend) => {
+                let extent = backend.m_rhi.get_swapchain_info().extent;
+                (extent.width, extent.height)
+            }
+            None => self.m_headless_viewport_size,
+        }
+    }
+
     pub fn get_rhi(&self) -> &VulkanRHI {
-        &self.m_rhi
+        &self
+            .m_backend
+            .as_ref()
+            .expect("headless render system has no rhi")
+            .m_rhi
+    }
+
+    /// Reads back the last frame rendered offscreen.
+    pub fn capture_frame(&self) -> Result<RgbaImage> {
+        let rhi = &self
+            .m_backend
+            .as_ref()
+            .ok_or_else(|| anyhow!("headless render system has no frame to capture"))?
+            .m_rhi;
+        let (width, height) = self.get_output_extent();
+        let pixels = rhi.read_back_offscreen_image()?;
+        RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| anyhow!("offscreen image size mismatch"))
+    }
+
+    pub fn save_frame(&self, path: &Path) -> Result<()> {
+        let frame = self.capture_frame()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        frame.save(path)?;
+        Ok(())
+    }
+
+    /// Compares the last offscreen frame with a reference image and returns the fraction of
+    /// pixels whose channels differ by more than `channel_tolerance`. A missing reference is
+    /// an error; with `PICCOLO_UPDATE_REFERENCE_IMAGES=1` the frame is written as the new
+    /// reference instead.
+    pub fn compare_frame_with_reference(
+        &self,
+        reference_path: &Path,
+        channel_tolerance: u8,
+    ) -> Result<f32> {
+        if std::env::var(UPDATE_REFERENCE_IMAGES_ENV).is_ok_and(|value| value == "1") {
+            log::warn!(
+                "recording reference image path='{}'",
+                reference_path.display()
+            );
+            self.save_frame(reference_path)?;
+            return Ok(0.0);
+        }
+        if !reference_path.exists() {
+            return Err(anyhow!(
+                "reference image '{}' is missing, rerun with {}=1 to record it",
+                reference_path.display(),
+                UPDATE_REFERENCE_IMAGES_ENV
+            ));
+        }
+        let frame = self.capture_frame()?;
+        let reference = image::open(reference_path)?.to_rgba8();
+        if frame.dimensions() != reference.dimensions() {
+            return Err(anyhow!(
+                "frame is {:?} but reference is {:?}",
+                frame.dimensions(),
+                reference.dimensions()
+            ));
+        }
+        let mismatched_pixels = frame
+            .pixels()
+            .zip(reference.pixels())
+            .filter(|(a, b)| {
+                a.0.iter()
+                    .zip(b.0.iter())
+                    .any(|(a, b)| a.abs_diff(*b) > channel_tolerance)
+            })
+            .count();
+        Ok(mismatched_pixels as f32 / (frame.width() * frame.height()).max(1) as f32)
+    }
+
+    /// Switches the lighting path and post pass order. The main camera pass and its
+    /// framebuffers are rebuilt before the next frame is recorded.
+    pub fn set_render_pipeline_config(&mut self, config: &RenderPipelineConfig) -> Result<()> {
+        config.validate()?;
+        if let Some(backend) = self.m_backend.as_mut() {
+            backend.m_rhi.wait_idle()?;
+            let previous_config = backend.m_render_pipeline.config().cloned();
+            backend.m_render_pipeline.set_config(config);
+            if let Err(err) = Self::pass_update_after_recreate_swapchain(
+                &backend.m_rhi,
+                backend.m_render_pipeline.as_mut(),
+                &backend.m_render_resource,
+                &backend.m_debugdraw_manager,
+            ) {
+                // a preset can still fail to build, e.g. a shader pass whose SPIR-V the driver
+                // rejects, so the configuration that built before is restored
+                if let Some(previous_config) = previous_config {
+                    backend.m_render_pipeline.set_config(&previous_config);
+                    Self::pass_update_after_recreate_swapchain(
+                        &backend.m_rhi,
+                        backend.m_render_pipeline.as_mut(),
+                        &backend.m_render_resource,
+                        &backend.m_debugdraw_manager,
+                    )
+                    .map_err(|restore_err| {
+                        anyhow!(
+                            "{err}, restoring the previous render pipeline failed: {restore_err}"
+                        )
+                    })?;
+                }
+                return Err(err);
+            }
+        }
+        self.m_render_scene.m_temporal_aa.m_enable = config.uses_taa();
+        Ok(())
+    }
+
+    /// Loads a preset from `asset/render_pipeline/` and switches to it.
+    pub fn load_render_pipeline_preset(
+        &mut self,
+        asset_manager: &AssetManager,
+        url: &str,
+    ) -> Result<()> {
+        let preset: RenderPipelineRes = asset_manager.load_asset(url)?;
+        self.set_render_pipeline_config(&RenderPipelineConfig::from_preset(&preset, asset_manager)?)
+    }
+
+    /// Hour of the day of the procedural sky, `None` when the sky is a skybox.
+    pub fn get_time_of_day(&self) -> Option<f32> {
+        self.m_render_scene
+            .m_sky_atmosphere
+            .as_ref()
+            .map(SkyAtmosphere::get_time_of_day)
+    }
+
+    /// Moves the procedural sky to `hours`, wrapped into [0, 24), and relights the scene.
+    /// Ignored when the sky is a skybox.
+    pub fn set_time_of_day(&mut self, hours: f32) {
+        if let Some(sky_atmosphere) = &mut self.m_render_scene.m_sky_atmosphere {
+            sky_atmosphere.set_time_of_day(hours);
+        }
+        self.m_render_scene.tick_sky_atmosphere(0.0);
+    }
+
+    /// In-game hours per real second of the procedural sky, 0 stops the clock. Ignored when
+    /// the sky is a skybox.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        if let Some(sky_atmosphere) = &mut self.m_render_scene.m_sky_atmosphere {
+            sky_atmosphere.set_time_scale(time_scale);
+        }
+    }
+
+    /// Drops the TAA history next frame, for cuts the camera movement alone does not reveal.
+    pub fn reset_temporal_history(&mut self) {
+        self.m_render_scene.m_temporal_aa.m_reset_history = true;
     }
 
     pub fn get_guid_of_picked_mesh(&self, picked_uv: &Vector2) -> u32 {
@@ -242,7 +490,184 @@ impl RenderSystem {
 }
 
 impl RenderSystem {
+    fn load_global_rendering_res(
+        asset_manager: &AssetManager,
+        config_manager: &ConfigManager,
+    ) -> GlobalRenderingRes {
+        let global_rendering_res_url = config_manager.get_global_rendering_res_url();
+        asset_manager.load_asset(&global_rendering_res_url).unwrap()
+    }
+
+    /// Falls back to the built-in pipeline when the configured preset fails to load or validate.
+    fn load_render_pipeline_config(
+        asset_manager: &AssetManager,
+        global_rendering_res: &GlobalRenderingRes,
+    ) -> RenderPipelineConfig {
+        let fallback = || RenderPipelineConfig::with_fxaa(global_rendering_res.enable_fxaa);
+        let url = &global_rendering_res.render_pipeline;
+        if url.is_empty() {
+            return fallback();
+        }
+        asset_manager
+            .load_asset::<RenderPipelineRes>(url)
+            .and_then(|preset| RenderPipelineConfig::from_preset(&preset, asset_manager))
+            .and_then(|config| config.validate().map(|()| config))
+            .unwrap_or_else(|err| {
+                log::error!("failed to load render pipeline '{url}': {err}, using the default one");
+                fallback()
+            })
+    }
+
+    fn create_without_backend(
+        global_rendering_res: &GlobalRenderingRes,
+        headless_viewport_size: (u32, u32),
+    ) -> Self {
+        let mut render_camera = RenderCamera::default();
+        let camera_pose = &global_rendering_res.camera_config.pose;
+        render_camera.look_at(camera_pose.position, &camera_pose.target, &camera_pose.up);
+        render_camera.set_znear(global_rendering_res.camera_config.z_near);
+        render_camera.set_zfar(global_rendering_res.camera_config.z_far);
+        render_camera.set_aspect(
+            global_rendering_res.camera_config.aspect.x
+                / global_rendering_res.camera_config.aspect.y,
+        );
+
+        let mut render_scene = RenderScene::default();
+        render_scene.m_ambient_light = AmbientLight {
+            m_irradiance: global_rendering_res.ambient_light,
+        };
+        render_scene.m_directional_light = DirectionalLight {
+            m_direction: global_rendering_res.directional_light.direction,
+            m_color: global_rendering_res.directional_light.color,
+            m_cascade_count: global_rendering_res.directional_light_shadow.cascade_count,
+            m_cascade_split_lambda: global_rendering_res.directional_light_shadow.split_lambda,
+            m_shadow_distance: global_rendering_res.directional_light_shadow.max_distance,
+            m_cascade_blend_ratio: global_rendering_res.directional_light_shadow.blend_ratio,
+        };
+        let procedural_sky = &global_rendering_res.procedural_sky;
+        render_scene.m_sky_atmosphere = procedural_sky
+            .enable
+            .then(|| SkyAtmosphere::new(procedural_sky));
+        render_scene.tick_sky_atmosphere(0.0);
+        let fog = &global_rendering_res.fog;
+        render_scene.m_fog = fog.enable.then(|| HeightFog {
+            m_density: fog.density,
+            m_base_height: fog.base_height,
+            m_height_falloff: fog.height_falloff,
+            m_start_distance: fog.start_distance,
+            m_max_opacity: fog.max_opacity,
+        });
+        let ambient_occlusion = &global_rendering_res.ambient_occlusion;
+        render_scene.m_ambient_occlusion =
+            ambient_occlusion
+                .enable
+                .then(|| ScreenSpaceAmbientOcclusion {
+                    m_sample_count: ambient_occlusion.sample_count,
+                    m_radius: ambient_occlusion.radius,
+                    m_bias: ambient_occlusion.bias,
+                    m_intensity: ambient_occlusion.intensity,
+                    m_blur_radius: ambient_occlusion.blur_radius,
+                });
+        let bloom = &global_rendering_res.bloom;
+        render_scene.m_bloom = bloom.enable.then(|| HDRBloom {
+            m_threshold: bloom.threshold,
+            m_knee: bloom.knee,
+            m_intensity: bloom.intensity,
+        });
+        let temporal_anti_aliasing = &global_rendering_res.temporal_anti_aliasing;
+        render_scene.m_temporal_aa = TemporalAntiAliasing {
+            m_history_weight: temporal_anti_aliasing.history_weight,
+            m_camera_cut_distance: temporal_anti_aliasing.camera_cut_distance,
+            ..Default::default()
+        };
+
+        Self {
+            m_swap_context: RenderSwapContext::default(),
+            m_render_camera: Rc::new(RefCell::new(render_camera)),
+            m_render_scene: render_scene,
+            m_backend: None,
+            m_headless_viewport_size: headless_viewport_size,
+            m_last_consumed_swap_data: RenderSwapData::default(),
+            m_taa_frame_index: 0,
+            m_last_camera_position: None,
+        }
+    }
+
+    /// Moves the camera jitter along the Halton (2, 3) sequence while TAA runs, and drops the
+    /// history when the camera jumped further than the cut distance.
+    fn update_temporal_aa(&mut self) {
+        let mut render_camera = self.m_render_camera.borrow_mut();
+        let temporal_aa = &mut self.m_render_scene.m_temporal_aa;
+        if !temporal_aa.m_enable {
+            render_camera.set_jitter(Vector2::new(0.0, 0.0));
+            self.m_last_camera_position = None;
+            return;
+        }
+
+        let camera_position = *render_camera.position();
+        let camera_cut = self
+            .m_last_camera_position
+            .is_some_and(|last_camera_position| {
+                (camera_position - last_camera_position).length()
+                    > temporal_aa.m_camera_cut_distance
+            });
+        temporal_aa.m_reset_history |= camera_cut;
+        self.m_last_camera_position = Some(camera_position);
+
+        let halton = |mut index: u32, base: u32| {
+            let mut fraction = 1.0;
+            let mut result = 0.0;
+            while index > 0 {
+                fraction /= base as f32;
+                result += fraction * (index % base) as f32;
+                index /= base;
+            }
+            result
+        };
+        self.m_taa_frame_index = self.m_taa_frame_index % TAA_JITTER_SAMPLE_COUNT + 1;
+        // a sub-pixel offset, in NDC where the viewport spans 2
+        let viewport = self
+            .m_backend
+            .as_ref()
+            .unwrap()
+            .m_rhi
+            .get_swapchain_info()
+            .viewport;
+        render_camera.set_jitter(Vector2::new(
+            (halton(self.m_taa_frame_index, 2) - 0.5) * 2.0 / viewport.width,
+            (halton(self.m_taa_frame_index, 3) - 0.5) * 2.0 / viewport.height,
+        ));
+    }
+
+    /// Applies the camera update and releases the render-side swap data so the logic side
+    /// keeps swapping, without uploading anything.
+    fn consume_headless_swap_data(&mut self) {
+        let swap_data = self.m_swap_context.get_render_swap_data().take();
+        if let Some(camera_swap_data) = &swap_data.m_camera_swap_data {
+            let mut render_camera = self.m_render_camera.borrow_mut();
+            if let Some(m_fov_x) = camera_swap_data.m_fov_x {
+                render_camera.set_fov_x(m_fov_x);
+            }
+            if let Some(m_view_matrix) = &camera_swap_data.m_view_matrix {
+                render_camera.set_main_view_matrix(m_view_matrix.clone());
+            }
+            if let Some(m_camera_type) = camera_swap_data.m_camera_type {
+                render_camera.set_current_camera_type(m_camera_type);
+            }
+        }
+        if let Some(point_light_swap_data) = &swap_data.m_point_light_swap_data {
+            Self::apply_point_light_swap_data(&mut self.m_render_scene, point_light_swap_data);
+        }
+        if let Some(spot_light_swap_data) = &swap_data.m_spot_light_swap_data {
+            Self::apply_spot_light_swap_data(&mut self.m_render_scene, spot_light_swap_data);
+        }
+        self.m_render_scene
+            .update_visible_lights(&self.m_render_camera.borrow());
+        self.m_last_consumed_swap_data = swap_data;
+    }
+
     fn process_swap_data(&mut self, asset_manager: &AssetManager) {
+        let backend = self.m_backend.as_mut().unwrap();
         let swap_data = self.m_swap_context.get_render_swap_data();
         if swap_data.borrow().m_game_object_resource_descs.is_some() {
             {
@@ -265,6 +690,9 @@ impl RenderSystem {
                         render_entity.m_model_matrix =
                             Rc::new(game_object_part.m_transform_desc.m_transform_matrix);
                         render_entity.m_base_color_factor = game_object_part.m_base_color_factor;
+                        render_entity.m_alpha_mode = game_object_part.m_material_desc.m_alpha_mode;
+                        render_entity.m_alpha_cutoff =
+                            game_object_part.m_material_desc.m_alpha_cutoff;
 
                         self.m_render_scene
                             .add_instance_id_to_map(render_entity.m_instance_id, gobject.get_id());
@@ -285,19 +713,20 @@ impl RenderSystem {
                                     .borrow_mut()
                                     .alloc_guid(&mesh_source);
                                 if !is_mesh_loaded {
-                                    let (mesh_data, bounding_box) = self
+                                    let (mesh_data, bounding_box) = backend
                                         .m_render_resource
                                         .load_mesh_data(asset_manager, &mesh_source);
                                     render_entity.m_bounding_box = bounding_box;
-                                    self.m_render_resource
+                                    backend
+                                        .m_render_resource
                                         .upload_game_object_render_resource_mesh(
-                                            &self.m_rhi,
+                                            &backend.m_rhi,
                                             &render_entity,
                                             &mesh_data,
                                         );
                                     println!("load mesh data");
                                 } else {
-                                    render_entity.m_bounding_box = self
+                                    render_entity.m_bounding_box = backend
                                         .m_render_resource
                                         .get_cached_bounding_box(&mesh_source)
                                         .unwrap()
@@ -320,20 +749,21 @@ impl RenderSystem {
                                     .alloc_guid(&mesh_source);
                                 if !is_mesh_loaded {
                                     let (mesh_data, bounding_box) =
-                                        self.m_render_resource.load_mesh_data_from_raw(
+                                        backend.m_render_resource.load_mesh_data_from_raw(
                                             &mesh_source,
                                             &mesh_desc.borrow().m_vertices,
                                             &mesh_desc.borrow().m_indices,
                                         );
                                     render_entity.m_bounding_box = bounding_box;
-                                    self.m_render_resource
+                                    backend
+                                        .m_render_resource
                                         .upload_game_object_render_resource_mesh(
-                                            &self.m_rhi,
+                                            &backend.m_rhi,
                                             &render_entity,
                                             &mesh_data,
                                         );
                                 } else {
-                                    render_entity.m_bounding_box = self
+                                    render_entity.m_bounding_box = backend
                                         .m_render_resource
                                         .get_cached_bounding_box(&mesh_source)
                                         .unwrap()
@@ -349,23 +779,35 @@ impl RenderSystem {
                                     .get_mesh_asset_id_allocator()
                                     .borrow_mut()
                                     .alloc_guid(&mesh_source);
+                                // an empty part has nothing to upload, drop whatever it drew before
+                                if mesh_desc.borrow().m_indices.is_empty() {
+                                    backend
+                                        .m_render_resource
+                                        .destroy_game_object_render_resource(
+                                            render_entity.m_mesh_asset_id,
+                                        );
+                                    self.m_render_scene.delete_entity_by_part_id(&part_id);
+                                    mesh_desc.borrow_mut().m_is_dirty = false;
+                                    continue;
+                                }
                                 if mesh_desc.borrow().m_is_dirty {
                                     let (mesh_data, bounding_box) =
-                                        self.m_render_resource.load_mesh_data_from_raw(
+                                        backend.m_render_resource.load_mesh_data_from_raw(
                                             &mesh_source,
                                             &mesh_desc.borrow().m_vertices,
                                             &mesh_desc.borrow().m_indices,
                                         );
                                     render_entity.m_bounding_box = bounding_box;
-                                    self.m_render_resource
+                                    backend
+                                        .m_render_resource
                                         .upload_game_object_render_resource_mesh(
-                                            &self.m_rhi,
+                                            &backend.m_rhi,
                                             &render_entity,
                                             &mesh_data,
                                         );
                                     mesh_desc.borrow_mut().m_is_dirty = false;
                                 } else {
-                                    render_entity.m_bounding_box = self
+                                    render_entity.m_bounding_box = backend
                                         .m_render_resource
                                         .get_cached_bounding_box(&mesh_source)
                                         .unwrap()
@@ -419,6 +861,10 @@ impl RenderSystem {
                                     .m_material_desc
                                     .m_occlusion_texture_file
                                     .clone(),
+                                m_atlas_tile_count: game_object_part
+                                    .m_material_desc
+                                    .m_atlas_tile_count,
+                                ..Default::default()
                             }
                         } else {
                             material_source.m_base_color_file =
@@ -428,6 +874,8 @@ impl RenderSystem {
                             material_source.m_normal_file =
                                 "asset/texture/default/normal.jpg".to_string();
                         }
+                        material_source.m_alpha_mode =
+                            game_object_part.m_material_desc.m_alpha_mode;
                         let is_material_loaded = self
                             .m_render_scene
                             .get_material_asset_id_allocator()
@@ -444,9 +892,10 @@ impl RenderSystem {
                                 asset_manager,
                                 &material_source,
                             );
-                            self.m_render_resource
+                            backend
+                                .m_render_resource
                                 .upload_game_object_render_resource_material(
-                                    &self.m_rhi,
+                                    &backend.m_rhi,
                                     &render_entity,
                                     &material_data,
                                 );
@@ -480,7 +929,8 @@ impl RenderSystem {
                                     .get_mesh_asset_id_allocator()
                                     .borrow_mut()
                                     .alloc_guid(&mesh_source);
-                                self.m_render_resource
+                                backend
+                                    .m_render_resource
                                     .destroy_game_object_render_resource(asset_id);
                             }
                             _ => {}
@@ -514,22 +964,76 @@ impl RenderSystem {
             }
             self.m_swap_context.reset_camera_swap_data();
         }
+
+        if let Some(point_light_swap_data) = swap_data.borrow_mut().m_point_light_swap_data.take() {
+            Self::apply_point_light_swap_data(&mut self.m_render_scene, &point_light_swap_data);
+        }
+
+        if let Some(spot_light_swap_data) = swap_data.borrow_mut().m_spot_light_swap_data.take() {
+            Self::apply_spot_light_swap_data(&mut self.m_render_scene, &spot_light_swap_data);
+        }
+
+        let render_pipeline_url = swap_data.borrow_mut().m_render_pipeline_url.take();
+        if let Some(render_pipeline_url) = render_pipeline_url {
+            if let Err(err) = self.load_render_pipeline_preset(asset_manager, &render_pipeline_url)
+            {
+                log::error!("failed to switch render pipeline to '{render_pipeline_url}': {err}");
+            }
+        }
+    }
+
+    fn apply_point_light_swap_data(
+        render_scene: &mut RenderScene,
+        point_light_swap_data: &PointLightSwapData,
+    ) {
+        for (go_id, desc) in &point_light_swap_data.m_dirty_lights {
+            render_scene.insert_or_update_point_light(
+                *go_id,
+                PointLight::new(desc.m_position, desc.m_flux),
+            );
+        }
+        for go_id in &point_light_swap_data.m_lights_to_delete {
+            render_scene.delete_point_light(*go_id);
+        }
+    }
+
+    fn apply_spot_light_swap_data(
+        render_scene: &mut RenderScene,
+        spot_light_swap_data: &SpotLightSwapData,
+    ) {
+        for (go_id, desc) in &spot_light_swap_data.m_dirty_lights {
+            render_scene.insert_or_update_spot_light(
+                *go_id,
+                SpotLight {
+                    m_position: desc.m_position,
+                    m_direction: desc.m_direction,
+                    m_flux: desc.m_flux,
+                    m_inner_cone_angle: desc.m_inner_cone_angle,
+                    m_outer_cone_angle: desc.m_outer_cone_angle,
+                    m_cast_shadow: desc.m_cast_shadow,
+                },
+            );
+        }
+        for go_id in &spot_light_swap_data.m_lights_to_delete {
+            render_scene.delete_spot_light(*go_id);
+        }
     }
 
     fn render(&mut self, ui_runtime: &UiRuntime) -> Result<()> {
-        let rhi = &self.m_rhi;
-        self.m_render_resource
-            .reset_ring_buffer_offset(rhi.get_current_frame_index());
+        let backend = self.m_backend.as_mut().unwrap();
+        backend
+            .m_render_resource
+            .reset_ring_buffer_offset(backend.m_rhi.get_current_frame_index());
         {
-            let rhi = &mut self.m_rhi;
+            let rhi = &mut backend.m_rhi;
             rhi.wait_for_fence()?;
             rhi.reset_command_pool()?;
             let mut pass_update_after_recreate_swapchain = |rhi: &VulkanRHI| {
                 Self::pass_update_after_recreate_swapchain(
                     rhi,
-                    self.m_render_pipeline.as_mut(),
-                    &self.m_render_resource,
-                    &self.m_debugdraw_manager,
+                    backend.m_render_pipeline.as_mut(),
+                    &backend.m_render_resource,
+                    &backend.m_debugdraw_manager,
                 )
             };
             if rhi.prepare_before_pass(&mut pass_update_after_recreate_swapchain)? {
@@ -537,33 +1041,35 @@ impl RenderSystem {
             }
         }
         {
-            let rhi = &self.m_rhi;
+            let rhi = &backend.m_rhi;
 
-            self.m_render_pipeline.draw(
+            backend.m_render_pipeline.draw(
                 &rhi,
                 &self.m_render_scene,
-                &mut self.m_render_resource.m_global_render_resource,
+                &mut backend.m_render_resource.m_global_render_resource,
                 ui_runtime,
             );
 
-            if self.m_render_pipeline.supports_debugdraw() {
-                self.m_debugdraw_manager.borrow_mut().draw(&rhi)?;
+            if backend.m_render_pipeline.supports_debugdraw() {
+                backend.m_debugdraw_manager.borrow_mut().draw(&rhi)?;
             }
         }
         {
             let mut pass_update_after_recreate_swapchain = |rhi: &VulkanRHI| {
                 Self::pass_update_after_recreate_swapchain(
                     rhi,
-                    self.m_render_pipeline.as_mut(),
-                    &self.m_render_resource,
-                    &self.m_debugdraw_manager,
+                    backend.m_render_pipeline.as_mut(),
+                    &backend.m_render_resource,
+                    &backend.m_debugdraw_manager,
                 )
             };
-            self.m_rhi
+            backend
+                .m_rhi
                 .submit_rendering(&mut pass_update_after_recreate_swapchain)?;
         }
-        self.m_render_resource
-            .on_main_frame_submit_complete(&self.m_rhi);
+        backend
+            .m_render_resource
+            .on_main_frame_submit_complete(&backend.m_rhi);
         Ok(())
     }
 
@@ -572,10 +1078,11 @@ impl RenderSystem {
         render_pipeline: &mut dyn RenderPipelineTrait,
         render_resource: &RenderResource,
         debugdraw_manager: &RefCell<DebugDrawManager>,
-    ) {
-        render_pipeline.recreate_after_swapchain(rhi, &render_resource.m_global_render_resource);
+    ) -> Result<()> {
+        render_pipeline.recreate_after_swapchain(rhi, &render_resource.m_global_render_resource)?;
         debugdraw_manager
             .borrow_mut()
             .update_after_recreate_swap_chain(rhi);
+        Ok(())
     }
 }
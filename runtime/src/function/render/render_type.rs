@@ -2,6 +2,8 @@ use vulkanalia::prelude::v1_0::*;
 use bitflags::bitflags;
 use vulkanalia::vk::Flags;
 
+use crate::resource::res_type::data::material::MaterialAlphaMode;
+
 pub const RHI_SUBPASS_EXTERNAL: u32 = !0;
 
 #[repr(transparent)]
@@ -1204,16 +1206,12 @@ pub enum RHISamplerType{
     Nearest,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub enum ImageType{
     #[default]
     UNKNOWN = 0,
-    _2D
-}
-
-pub enum RenderPipelineType{
-    ForwardPipeline,
-    DeferredPipeline,
+    _2D,
+    Cube
 }
 
 #[derive(Default)]
@@ -1221,7 +1219,7 @@ pub struct BufferData {
     pub m_data: Vec<u8>,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct TextureData {
     pub m_width: u32,
     pub m_height: u32,
@@ -1229,6 +1227,9 @@ pub struct TextureData {
     pub m_mip_levels: u32,
     pub m_array_layers: u32,
     pub m_pixels: Vec<u8>,
+    /// Levels 1.. when they are built on the CPU or stored in a container, largest first, each
+    /// with all of its layers. Empty means the levels are blitted on upload.
+    pub m_mip_pixels: Vec<Vec<u8>>,
     pub m_format: vk::Format,
     pub m_type: ImageType
 }
@@ -1254,6 +1255,10 @@ pub struct MaterialSourceDesc{
     pub m_normal_file: String,
     pub m_occlusion_file: String,
     pub m_emissive_file: String,
+    /// Tiles along each side when the textures are atlases, 0 otherwise.
+    pub m_atlas_tile_count: u32,
+    /// Part of the key, so meshes sharing textures but not the alpha mode get their own material.
+    pub m_alpha_mode: MaterialAlphaMode,
 }
 
 #[derive(Default)]
@@ -0,0 +1,234 @@
+use std::f32::consts::PI;
+
+use crate::{
+    core::math::vector3::Vector3, resource::res_type::global::global_rendering::ProceduralSky,
+};
+
+// the atmosphere of shader/glsl/sky_atmosphere.h, Z up and lengths in meters
+const PLANET_RADIUS: f32 = 6360e3;
+const ATMOSPHERE_RADIUS: f32 = 6420e3;
+const VIEW_HEIGHT: f32 = 1.0;
+const RAYLEIGH_SCATTERING: Vector3 = Vector3::new(5.8e-6, 13.5e-6, 33.1e-6);
+const RAYLEIGH_SCALE_HEIGHT: f32 = 8e3;
+const MIE_SCATTERING: f32 = 21e-6;
+const MIE_EXTINCTION: f32 = MIE_SCATTERING / 0.9;
+const MIE_SCALE_HEIGHT: f32 = 1.2e3;
+const MIE_G: f32 = 0.76;
+const VIEW_STEP_COUNT: u32 = 16;
+const LIGHT_STEP_COUNT: u32 = 8;
+
+const AMBIENT_SAMPLE_COUNT: u32 = 32;
+const HORIZON_COLOR_SAMPLE_COUNT: u32 = 8;
+/// Elevation in radians the horizon color is taken at.
+const HORIZON_COLOR_ELEVATION: f32 = 0.05;
+/// Sine of the sun elevation over which sunlight fades in and out around the horizon.
+const HORIZON_FADE: f32 = 0.02;
+
+/// Time of day of the procedural sky and the sunlight and skylight it gives.
+pub struct SkyAtmosphere {
+    m_time_of_day: f32,
+    m_time_scale: f32,
+    m_sun_azimuth: f32,
+    m_sun_path_tilt: f32,
+    m_sun_intensity: f32,
+    m_sky_intensity: f32,
+    m_night_ambient_light: Vector3,
+}
+
+impl SkyAtmosphere {
+    pub fn new(procedural_sky: &ProceduralSky) -> Self {
+        Self {
+            m_time_of_day: procedural_sky.time_of_day.rem_euclid(24.0),
+            m_time_scale: procedural_sky.time_scale,
+            m_sun_azimuth: procedural_sky.sun_azimuth.to_radians(),
+            m_sun_path_tilt: procedural_sky.sun_path_tilt.to_radians(),
+            m_sun_intensity: procedural_sky.sun_intensity,
+            m_sky_intensity: procedural_sky.sky_intensity,
+            m_night_ambient_light: procedural_sky.night_ambient_light,
+        }
+    }
+
+    pub fn tick(&mut self, delta_time: f32) {
+        self.set_time_of_day(self.m_time_of_day + delta_time * self.m_time_scale);
+    }
+
+    pub fn get_time_of_day(&self) -> f32 {
+        self.m_time_of_day
+    }
+
+    /// Wraps `hours` into [0, 24).
+    pub fn set_time_of_day(&mut self, hours: f32) {
+        self.m_time_of_day = hours.rem_euclid(24.0);
+    }
+
+    pub fn get_time_scale(&self) -> f32 {
+        self.m_time_scale
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.m_time_scale = time_scale;
+    }
+
+    /// Unit vector towards the sun, which circles once a day and crosses the horizon at 6 and 18.
+    pub fn sun_direction(&self) -> Vector3 {
+        let angle = (self.m_time_of_day - 6.0) / 24.0 * 2.0 * PI;
+        let (sin_azimuth, cos_azimuth) = self.m_sun_azimuth.sin_cos();
+        let (sin_tilt, cos_tilt) = self.m_sun_path_tilt.sin_cos();
+        let sunrise = Vector3::new(cos_azimuth, sin_azimuth, 0.0);
+        let noon = Vector3::new(sin_azimuth * sin_tilt, -cos_azimuth * sin_tilt, cos_tilt);
+        (sunrise * angle.cos() + noon * angle.sin()).normalize()
+    }
+
+    /// Sunlight after the atmosphere, black once the sun has set.
+    pub fn sun_color(&self) -> Vector3 {
+        let direction = self.sun_direction();
+        let fade = smoothstep(-HORIZON_FADE, HORIZON_FADE, direction.z);
+        if fade == 0.0 {
+            return Vector3::ZERO;
+        }
+        let above_horizon =
+            Vector3::new(direction.x, direction.y, direction.z.max(0.0)).normalize();
+        let origin = Vector3::new(0.0, 0.0, PLANET_RADIUS + VIEW_HEIGHT);
+        transmittance(optical_depth_to_space(&origin, &above_horizon))
+            * (self.m_sun_intensity * fade)
+    }
+
+    /// Sunlight above the atmosphere the sky is lit with.
+    pub fn sky_sun_illuminance(&self) -> Vector3 {
+        Vector3::ONES * (self.m_sun_intensity * self.m_sky_intensity)
+    }
+
+    /// Mean sky radiance just above the horizon, which distant fog fades to.
+    pub fn horizon_color(&self) -> Vector3 {
+        let sun_direction = self.sun_direction();
+        let sun_illuminance = self.sky_sun_illuminance();
+        let (sin_elevation, cos_elevation) = HORIZON_COLOR_ELEVATION.sin_cos();
+        (0..HORIZON_COLOR_SAMPLE_COUNT)
+            .map(|i| {
+                let azimuth = 2.0 * PI * i as f32 / HORIZON_COLOR_SAMPLE_COUNT as f32;
+                let direction = Vector3::new(
+                    cos_elevation * azimuth.cos(),
+                    cos_elevation * azimuth.sin(),
+                    sin_elevation,
+                );
+                sky_radiance(&direction, &sun_direction, &sun_illuminance)
+            })
+            .fold(Vector3::ZERO, |sum, radiance| sum + radiance)
+            / HORIZON_COLOR_SAMPLE_COUNT as f32
+    }
+
+    /// Skylight on an upward facing surface, as the shaders' ambient light, plus the night floor.
+    pub fn ambient_light(&self) -> Vector3 {
+        let sun_direction = self.sun_direction();
+        let sun_illuminance = self.sky_sun_illuminance();
+        let skylight = (0..AMBIENT_SAMPLE_COUNT)
+            .map(|i| {
+                // cosine distributed, so the mean radiance is the irradiance over PI
+                let u = (i as f32 + 0.5) / AMBIENT_SAMPLE_COUNT as f32;
+                let phi = 2.0 * PI * (i.reverse_bits() as f32 / (u32::MAX as f32 + 1.0));
+                let radius = u.sqrt();
+                let direction =
+                    Vector3::new(radius * phi.cos(), radius * phi.sin(), (1.0 - u).sqrt());
+                sky_radiance(&direction, &sun_direction, &sun_illuminance)
+            })
+            .fold(Vector3::ZERO, |sum, radiance| sum + radiance);
+        skylight / AMBIENT_SAMPLE_COUNT as f32 + self.m_night_ambient_light
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn ray_sphere_exit(origin: &Vector3, direction: &Vector3, radius: f32) -> f32 {
+    let b = origin.dot(direction);
+    let c = origin.squared_length() - radius * radius;
+    -b + (b * b - c).max(0.0).sqrt()
+}
+
+/// Distance to the planet, negative when the ray misses it.
+fn ray_planet_hit(origin: &Vector3, direction: &Vector3) -> f32 {
+    let b = origin.dot(direction);
+    let c = origin.squared_length() - PLANET_RADIUS * PLANET_RADIUS;
+    let d = b * b - c;
+    if d < 0.0 || b > 0.0 {
+        -1.0
+    } else {
+        -b - d.sqrt()
+    }
+}
+
+/// Rayleigh and Mie densities at a point, relative to sea level.
+fn density(position: &Vector3) -> (f32, f32) {
+    let height = (position.length() - PLANET_RADIUS).max(0.0);
+    (
+        (-height / RAYLEIGH_SCALE_HEIGHT).exp(),
+        (-height / MIE_SCALE_HEIGHT).exp(),
+    )
+}
+
+fn optical_depth_to_space(origin: &Vector3, direction: &Vector3) -> (f32, f32) {
+    let step_length =
+        ray_sphere_exit(origin, direction, ATMOSPHERE_RADIUS) / LIGHT_STEP_COUNT as f32;
+    (0..LIGHT_STEP_COUNT).fold((0.0, 0.0), |(rayleigh, mie), i| {
+        let (rayleigh_density, mie_density) =
+            density(&(*origin + *direction * ((i as f32 + 0.5) * step_length)));
+        (
+            rayleigh + rayleigh_density * step_length,
+            mie + mie_density * step_length,
+        )
+    })
+}
+
+fn transmittance((rayleigh, mie): (f32, f32)) -> Vector3 {
+    let extinction = RAYLEIGH_SCATTERING * rayleigh + Vector3::ONES * (MIE_EXTINCTION * mie);
+    Vector3::new(
+        (-extinction.x).exp(),
+        (-extinction.y).exp(),
+        (-extinction.z).exp(),
+    )
+}
+
+fn sky_radiance(
+    view_direction: &Vector3,
+    sun_direction: &Vector3,
+    sun_illuminance: &Vector3,
+) -> Vector3 {
+    let origin = Vector3::new(0.0, 0.0, PLANET_RADIUS + VIEW_HEIGHT);
+    let planet_hit = ray_planet_hit(&origin, view_direction);
+    let ray_length = if planet_hit > 0.0 {
+        planet_hit
+    } else {
+        ray_sphere_exit(&origin, view_direction, ATMOSPHERE_RADIUS)
+    };
+    let step_length = ray_length / VIEW_STEP_COUNT as f32;
+
+    let mut view_depth = (0.0, 0.0);
+    let mut rayleigh = Vector3::ZERO;
+    let mut mie = Vector3::ZERO;
+    for i in 0..VIEW_STEP_COUNT {
+        let position = origin + *view_direction * ((i as f32 + 0.5) * step_length);
+        let (rayleigh_density, mie_density) = density(&position);
+        view_depth.0 += rayleigh_density * step_length;
+        view_depth.1 += mie_density * step_length;
+
+        // points in the planet's shadow receive no sunlight
+        if ray_planet_hit(&position, sun_direction) < 0.0 {
+            let light_depth = optical_depth_to_space(&position, sun_direction);
+            let transmittance =
+                transmittance((view_depth.0 + light_depth.0, view_depth.1 + light_depth.1));
+            rayleigh += transmittance * (rayleigh_density * step_length);
+            mie += transmittance * (mie_density * step_length);
+        }
+    }
+
+    let mu = view_direction.dot(sun_direction);
+    let rayleigh_phase = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+    let g2 = MIE_G * MIE_G;
+    let mie_phase = 3.0 / (8.0 * PI) * ((1.0 - g2) * (1.0 + mu * mu))
+        / ((2.0 + g2) * (1.0 + g2 - 2.0 * MIE_G * mu).powf(1.5));
+
+    *sun_illuminance
+        * (rayleigh * RAYLEIGH_SCATTERING * rayleigh_phase + mie * (MIE_SCATTERING * mie_phase))
+}
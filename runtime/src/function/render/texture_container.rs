@@ -0,0 +1,378 @@
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use vulkanalia::prelude::v1_0::*;
+
+use crate::function::render::{
+    render_type::{ImageType, TextureData},
+    texture_decoder::block_byte_size,
+};
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+const KTX2_HEADER_SIZE: usize = 80;
+const KTX2_LEVEL_INDEX_SIZE: usize = 24;
+const KTX2_LEVEL_ALIGNMENT: usize = 16;
+
+const DDS_MAGIC: [u8; 4] = *b"DDS ";
+const DDS_HEADER_SIZE: usize = 128;
+const DDS_DX10_HEADER_SIZE: usize = 20;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xfc00;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const DDS_DIMENSION_TEXTURE2D: u32 = 3;
+
+/// Whether `file` is a KTX2 or DDS container rather than an image the `image` crate decodes.
+pub fn is_texture_container(file: &str) -> bool {
+    Path::new(file)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("ktx2") || extension.eq_ignore_ascii_case("dds")
+        })
+}
+
+/// Parses a KTX2 or DDS container. The payload keeps the stored format, so block compressed
+/// levels upload as they are. `m_pixels` holds the first level and `m_mip_pixels` the others,
+/// each level with all of its faces back to back.
+pub fn load_texture_container(bytes: &[u8]) -> Result<TextureData> {
+    if bytes.starts_with(&KTX2_IDENTIFIER) {
+        load_ktx2(bytes)
+    } else if bytes.starts_with(&DDS_MAGIC) {
+        load_dds(bytes)
+    } else {
+        Err(anyhow!("Unknown texture container"))
+    }
+}
+
+/// Bytes of one face of a mip level, `None` when the format is not supported or the size
+/// overflows.
+pub fn level_byte_size(format: vk::Format, width: u32, height: u32) -> Option<usize> {
+    let (width, height) = (width.max(1) as usize, height.max(1) as usize);
+    if let Some(block_size) = block_byte_size(format) {
+        return width
+            .div_ceil(4)
+            .checked_mul(height.div_ceil(4))?
+            .checked_mul(block_size);
+    }
+    let texel_size = match format {
+        vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB => 4,
+        vk::Format::R16G16_SFLOAT => 4,
+        vk::Format::R16G16B16A16_SFLOAT => 8,
+        vk::Format::R32G32B32A32_SFLOAT => 16,
+        _ => return None,
+    };
+    width.checked_mul(height)?.checked_mul(texel_size)
+}
+
+/// Writes a float texture or cube map as KTX2, levels as `load_texture_container` returns them.
+pub fn save_ktx2(texture: &TextureData) -> Result<Vec<u8>> {
+    let (channel_count, channel_bits) = match texture.m_format {
+        vk::Format::R16G16_SFLOAT => (2, 16),
+        vk::Format::R16G16B16A16_SFLOAT => (4, 16),
+        vk::Format::R32G32B32A32_SFLOAT => (4, 32),
+        _ => {
+            return Err(anyhow!(
+                "Saving {:?} as KTX2 is not supported",
+                texture.m_format
+            ));
+        }
+    };
+    let faces = if texture.m_array_layers == 6 { 6 } else { 1 };
+    let levels = std::iter::once(&texture.m_pixels)
+        .chain(&texture.m_mip_pixels)
+        .collect::<Vec<_>>();
+    let data_format_descriptor = float_data_format_descriptor(channel_count, channel_bits);
+    let data_format_descriptor_offset = KTX2_HEADER_SIZE + levels.len() * KTX2_LEVEL_INDEX_SIZE;
+
+    // the level data is stored smallest level first
+    let mut level_offsets = vec![0; levels.len()];
+    let mut offset = data_format_descriptor_offset + data_format_descriptor.len();
+    for (level, pixels) in levels.iter().enumerate().rev() {
+        offset = offset.next_multiple_of(KTX2_LEVEL_ALIGNMENT);
+        level_offsets[level] = offset;
+        offset += pixels.len();
+    }
+
+    let mut bytes = Vec::with_capacity(offset);
+    bytes.extend_from_slice(&KTX2_IDENTIFIER);
+    for field in [
+        texture.m_format.as_raw() as u32,
+        4,
+        texture.m_width,
+        texture.m_height,
+        0,
+        0,
+        faces,
+        levels.len() as u32,
+        0,
+        data_format_descriptor_offset as u32,
+        data_format_descriptor.len() as u32,
+        0,
+        0,
+    ] {
+        bytes.extend_from_slice(&field.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[0; 16]);
+    for (pixels, offset) in levels.iter().zip(&level_offsets) {
+        for field in [*offset, pixels.len(), pixels.len()] {
+            bytes.extend_from_slice(&(field as u64).to_le_bytes());
+        }
+    }
+    bytes.extend_from_slice(&data_format_descriptor);
+    for (level, pixels) in levels.iter().enumerate().rev() {
+        bytes.resize(level_offsets[level], 0);
+        bytes.extend_from_slice(pixels);
+    }
+    Ok(bytes)
+}
+
+/// Basic data format descriptor of linear RG or RGBA with a 16 or 32 bit float per channel.
+fn float_data_format_descriptor(channel_count: usize, channel_bits: u16) -> Vec<u8> {
+    const KHR_DF_SAMPLE_DATATYPE_FLOAT_SIGNED: u8 = 0xc0;
+
+    let block_size = 24 + 16 * channel_count as u16;
+    // -1 and 1 in the float type of the channels
+    let (lower, upper) = match channel_bits {
+        16 => (0xbc00, 0x3c00),
+        _ => ((-1.0f32).to_bits(), 1.0f32.to_bits()),
+    };
+    let mut descriptor = Vec::with_capacity(4 + block_size as usize);
+    descriptor.extend_from_slice(&(4 + block_size as u32).to_le_bytes());
+    descriptor.extend_from_slice(&0u32.to_le_bytes());
+    descriptor.extend_from_slice(&2u16.to_le_bytes());
+    descriptor.extend_from_slice(&block_size.to_le_bytes());
+    // RGBSDA color model, BT.709 primaries, linear transfer, all texel bytes in the only plane
+    let texel_size = (channel_count as u16 * channel_bits / 8) as u8;
+    descriptor.extend_from_slice(&[1, 1, 1, 0, 0, 0, 0, 0, texel_size, 0, 0, 0, 0, 0, 0, 0]);
+    for (sample, channel) in [0u8, 1, 2, 15][..channel_count].iter().enumerate() {
+        descriptor.extend_from_slice(&(sample as u16 * channel_bits).to_le_bytes());
+        descriptor.extend_from_slice(&[
+            (channel_bits - 1) as u8,
+            channel | KHR_DF_SAMPLE_DATATYPE_FLOAT_SIGNED,
+        ]);
+        descriptor.extend_from_slice(&[0; 4]);
+        descriptor.extend_from_slice(&lower.to_le_bytes());
+        descriptor.extend_from_slice(&upper.to_le_bytes());
+    }
+    descriptor
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|field| u32::from_le_bytes(field.try_into().unwrap()))
+        .ok_or_else(|| anyhow!("Texture container is truncated"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|field| u64::from_le_bytes(field.try_into().unwrap()))
+        .ok_or_else(|| anyhow!("Texture container is truncated"))
+}
+
+/// Bytes of one face of each level. A chain longer than the size allows would shift the size of
+/// its last levels out, so it is rejected along with sizes that overflow.
+fn level_sizes(
+    format: vk::Format,
+    width: u32,
+    height: u32,
+    level_count: u32,
+) -> Result<Vec<usize>> {
+    let max_level_count = u32::BITS - width.max(height).max(1).leading_zeros();
+    if level_count > max_level_count {
+        return Err(anyhow!(
+            "{} mip levels are more than a {}x{} texture has",
+            level_count,
+            width,
+            height
+        ));
+    }
+    (0..level_count)
+        .map(|level| {
+            level_byte_size(format, width >> level, height >> level)
+                .ok_or_else(|| anyhow!("{}x{} {:?} texture is too large", width, height, format))
+        })
+        .collect()
+}
+
+fn texture_from_levels(
+    width: u32,
+    height: u32,
+    faces: u32,
+    format: vk::Format,
+    mut levels: Vec<Vec<u8>>,
+) -> TextureData {
+    let mut texture = TextureData::default();
+    texture.m_width = width;
+    texture.m_height = height;
+    texture.m_depth = 1;
+    texture.m_mip_levels = levels.len() as u32;
+    texture.m_array_layers = faces;
+    texture.m_format = format;
+    texture.m_type = if faces == 6 {
+        ImageType::Cube
+    } else {
+        ImageType::_2D
+    };
+    texture.m_mip_pixels = levels.split_off(1);
+    texture.m_pixels = levels.pop().unwrap();
+    texture
+}
+
+fn load_ktx2(bytes: &[u8]) -> Result<TextureData> {
+    let format = vk::Format::from_raw(read_u32(bytes, 12)? as i32);
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    let depth = read_u32(bytes, 28)?;
+    let layers = read_u32(bytes, 32)?;
+    let faces = read_u32(bytes, 36)?;
+    let level_count = read_u32(bytes, 40)?.max(1);
+    let supercompression = read_u32(bytes, 44)?;
+
+    if supercompression != 0 {
+        return Err(anyhow!("Supercompressed KTX2 textures are not supported"));
+    }
+    if depth > 1 || layers > 1 || (faces != 1 && faces != 6) {
+        return Err(anyhow!("Only 2D textures and cube maps are supported"));
+    }
+    level_byte_size(format, 1, 1).ok_or_else(|| anyhow!("Unsupported KTX2 format {:?}", format))?;
+
+    let levels = level_sizes(format, width, height, level_count)?
+        .into_iter()
+        .enumerate()
+        .map(|(level, face_size)| {
+            let index = KTX2_HEADER_SIZE + level * KTX2_LEVEL_INDEX_SIZE;
+            let offset = read_u64(bytes, index)? as usize;
+            let truncated = || anyhow!("KTX2 mip level {} is truncated", level);
+            let end = face_size
+                .checked_mul(faces as usize)
+                .and_then(|size| offset.checked_add(size))
+                .ok_or_else(truncated)?;
+            bytes
+                .get(offset..end)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(truncated)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(texture_from_levels(width, height, faces, format, levels))
+}
+
+fn dxgi_format(dxgi_format: u32) -> Option<vk::Format> {
+    match dxgi_format {
+        2 => Some(vk::Format::R32G32B32A32_SFLOAT),
+        10 => Some(vk::Format::R16G16B16A16_SFLOAT),
+        28 => Some(vk::Format::R8G8B8A8_UNORM),
+        29 => Some(vk::Format::R8G8B8A8_SRGB),
+        71 => Some(vk::Format::BC1_RGBA_UNORM_BLOCK),
+        72 => Some(vk::Format::BC1_RGBA_SRGB_BLOCK),
+        77 => Some(vk::Format::BC3_UNORM_BLOCK),
+        78 => Some(vk::Format::BC3_SRGB_BLOCK),
+        83 => Some(vk::Format::BC5_UNORM_BLOCK),
+        84 => Some(vk::Format::BC5_SNORM_BLOCK),
+        95 => Some(vk::Format::BC6H_UFLOAT_BLOCK),
+        96 => Some(vk::Format::BC6H_SFLOAT_BLOCK),
+        98 => Some(vk::Format::BC7_UNORM_BLOCK),
+        99 => Some(vk::Format::BC7_SRGB_BLOCK),
+        _ => None,
+    }
+}
+
+fn fourcc_format(fourcc: u32) -> Option<vk::Format> {
+    match &fourcc.to_le_bytes() {
+        b"DXT1" => Some(vk::Format::BC1_RGBA_UNORM_BLOCK),
+        b"DXT5" => Some(vk::Format::BC3_UNORM_BLOCK),
+        b"ATI2" | b"BC5U" => Some(vk::Format::BC5_UNORM_BLOCK),
+        b"BC5S" => Some(vk::Format::BC5_SNORM_BLOCK),
+        // D3DFMT_A16B16G16R16F and D3DFMT_A32B32G32R32F
+        [113, 0, 0, 0] => Some(vk::Format::R16G16B16A16_SFLOAT),
+        [116, 0, 0, 0] => Some(vk::Format::R32G32B32A32_SFLOAT),
+        _ => None,
+    }
+}
+
+fn load_dds(bytes: &[u8]) -> Result<TextureData> {
+    let flags = read_u32(bytes, 8)?;
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    let mip_map_count = read_u32(bytes, 28)?;
+    let pixel_format_flags = read_u32(bytes, 80)?;
+    let fourcc = read_u32(bytes, 84)?;
+    let caps2 = read_u32(bytes, 112)?;
+
+    let level_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        mip_map_count.max(1)
+    } else {
+        1
+    };
+    let (format, faces, data_offset) =
+        if pixel_format_flags & DDPF_FOURCC != 0 && &fourcc.to_le_bytes() == b"DX10" {
+            let dxgi = read_u32(bytes, DDS_HEADER_SIZE)?;
+            let dimension = read_u32(bytes, DDS_HEADER_SIZE + 4)?;
+            let misc_flags = read_u32(bytes, DDS_HEADER_SIZE + 8)?;
+            let array_size = read_u32(bytes, DDS_HEADER_SIZE + 12)?;
+            if dimension != DDS_DIMENSION_TEXTURE2D || array_size > 1 {
+                return Err(anyhow!("Only 2D textures and cube maps are supported"));
+            }
+            let format =
+                dxgi_format(dxgi).ok_or_else(|| anyhow!("Unsupported DXGI format {}", dxgi))?;
+            let faces = if misc_flags & DDS_RESOURCE_MISC_TEXTURECUBE != 0 {
+                6
+            } else {
+                1
+            };
+            (format, faces, DDS_HEADER_SIZE + DDS_DX10_HEADER_SIZE)
+        } else {
+            let format = if pixel_format_flags & DDPF_FOURCC != 0 {
+                fourcc_format(fourcc)
+            } else if pixel_format_flags & DDPF_RGB != 0
+                && read_u32(bytes, 88)? == 32
+                && read_u32(bytes, 92)? == 0x000000ff
+                && read_u32(bytes, 96)? == 0x0000ff00
+                && read_u32(bytes, 100)? == 0x00ff0000
+            {
+                Some(vk::Format::R8G8B8A8_UNORM)
+            } else {
+                None
+            }
+            .ok_or_else(|| anyhow!("Unsupported DDS pixel format"))?;
+            let faces = if caps2 & DDSCAPS2_CUBEMAP == 0 {
+                1
+            } else if caps2 & DDSCAPS2_CUBEMAP_ALLFACES == DDSCAPS2_CUBEMAP_ALLFACES {
+                6
+            } else {
+                return Err(anyhow!("Cube maps without all six faces are not supported"));
+            };
+            (format, faces, DDS_HEADER_SIZE)
+        };
+
+    // DDS stores every mip chain of a face before the next face, the levels keep faces together
+    let level_sizes = level_sizes(format, width, height, level_count)?;
+    let data_end = level_sizes
+        .iter()
+        .try_fold(0usize, |face_size, size| face_size.checked_add(*size))
+        .and_then(|face_size| face_size.checked_mul(faces as usize))
+        .and_then(|size| size.checked_add(data_offset));
+    if data_end.is_none_or(|data_end| bytes.len() < data_end) {
+        return Err(anyhow!("DDS texture is truncated"));
+    }
+    let mut levels = level_sizes
+        .iter()
+        .map(|size| Vec::with_capacity(size * faces as usize))
+        .collect::<Vec<_>>();
+    let mut offset = data_offset;
+    for _ in 0..faces {
+        for (level, size) in levels.iter_mut().zip(&level_sizes) {
+            level.extend_from_slice(&bytes[offset..offset + size]);
+            offset += size;
+        }
+    }
+
+    Ok(texture_from_levels(width, height, faces, format, levels))
+}
@@ -0,0 +1,910 @@
+use anyhow::{Result, anyhow};
+use vulkanalia::prelude::v1_0::*;
+
+use crate::function::render::render_type::TextureData;
+
+/// Subset of every texel in the 64 two subset partitions, one bit per texel.
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Subset of every texel in the 64 three subset partitions, two bits per texel.
+const PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+/// Anchor texel of the second subset of the two subset partitions.
+const ANCHORS_2: [usize; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor texels of the second and third subsets of the three subset partitions.
+const ANCHORS_3: [[usize; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6,
+        8, 5, 15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8,
+        5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3,
+        15, 6, 10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15,
+        15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+    ],
+];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+struct BC7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+const BC7_MODES: [BC7Mode; 8] = [
+    BC7Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    BC7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: true,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    BC7Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    BC7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    BC7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 3,
+    },
+    BC7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 2,
+    },
+    BC7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 4,
+        secondary_index_bits: 0,
+    },
+    BC7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+];
+
+/// Reads a 128 bit block from its least significant bit up.
+struct BitReader {
+    m_bits: u128,
+    m_position: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> Self {
+        Self {
+            m_bits: u128::from_le_bytes(block.try_into().unwrap()),
+            m_position: 0,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        if count == 0 {
+            return 0;
+        }
+        let value = (self.m_bits >> self.m_position) as u32 & ((1_u64 << count) - 1) as u32;
+        self.m_position += count;
+        value
+    }
+
+    fn read_i32(&mut self, count: u32) -> i32 {
+        self.read(count) as i32
+    }
+
+    /// Reads a field stored most significant bit first.
+    fn read_reversed(&mut self, count: u32) -> u32 {
+        self.read(count).reverse_bits() >> (32 - count)
+    }
+}
+
+pub fn is_block_compressed(format: vk::Format) -> bool {
+    block_byte_size(format).is_some()
+}
+
+/// Bytes of a 4x4 block, `None` when the format is not block compressed.
+pub fn block_byte_size(format: vk::Format) -> Option<usize> {
+    match format {
+        vk::Format::BC1_RGB_UNORM_BLOCK
+        | vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK => Some(8),
+        vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC5_SNORM_BLOCK
+        | vk::Format::BC6H_UFLOAT_BLOCK
+        | vk::Format::BC6H_SFLOAT_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK => Some(16),
+        _ => None,
+    }
+}
+
+/// Format the CPU decoder writes for `format`. BC6H decodes to RGBA32F like `load_texture_hdr`,
+/// the other formats to RGBA8 with the same color space.
+pub fn decoded_format(format: vk::Format) -> Option<vk::Format> {
+    match format {
+        vk::Format::BC1_RGB_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK => Some(vk::Format::R8G8B8A8_UNORM),
+        vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK => Some(vk::Format::R8G8B8A8_SRGB),
+        vk::Format::BC5_SNORM_BLOCK => Some(vk::Format::R8G8B8A8_SNORM),
+        vk::Format::BC6H_UFLOAT_BLOCK | vk::Format::BC6H_SFLOAT_BLOCK => {
+            Some(vk::Format::R32G32B32A32_SFLOAT)
+        }
+        _ => None,
+    }
+}
+
+/// Decodes every level and layer of a block compressed texture into `decoded_format`.
+pub fn decode_texture(texture: &TextureData) -> Result<TextureData> {
+    let format = texture.m_format;
+    let (Some(block_size), Some(decoded_format)) =
+        (block_byte_size(format), decoded_format(format))
+    else {
+        return Err(anyhow!("{:?} is not a block compressed format", format));
+    };
+    let texel_size = if decoded_format == vk::Format::R32G32B32A32_SFLOAT {
+        16
+    } else {
+        4
+    };
+    let layers = texture.m_array_layers.max(1) as usize;
+
+    let decode_level = |level: &[u8], mip_level: usize| -> Result<Vec<u8>> {
+        let width = (texture.m_width as usize >> mip_level).max(1);
+        let height = (texture.m_height as usize >> mip_level).max(1);
+        let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+        let layer_size = blocks_x * blocks_y * block_size;
+        if level.len() < layer_size * layers {
+            return Err(anyhow!("Mip level {} is truncated", mip_level));
+        }
+
+        let mut pixels = vec![0_u8; width * height * texel_size * layers];
+        let mut texels = [0_u8; 16 * 16];
+        for layer in 0..layers {
+            let layer_pixels = &mut pixels[layer * width * height * texel_size..];
+            for block_y in 0..blocks_y {
+                for block_x in 0..blocks_x {
+                    let offset = layer * layer_size + (block_y * blocks_x + block_x) * block_size;
+                    decode_block(format, &level[offset..offset + block_size], &mut texels);
+                    for y in 0..4.min(height - block_y * 4) {
+                        for x in 0..4.min(width - block_x * 4) {
+                            let src = (y * 4 + x) * texel_size;
+                            let dst = ((block_y * 4 + y) * width + block_x * 4 + x) * texel_size;
+                            layer_pixels[dst..dst + texel_size]
+                                .copy_from_slice(&texels[src..src + texel_size]);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(pixels)
+    };
+
+    let mut decoded = TextureData::default();
+    decoded.m_pixels = decode_level(&texture.m_pixels, 0)?;
+    for (i, level) in texture.m_mip_pixels.iter().enumerate() {
+        decoded.m_mip_pixels.push(decode_level(level, i + 1)?);
+    }
+    decoded.m_width = texture.m_width;
+    decoded.m_height = texture.m_height;
+    decoded.m_depth = texture.m_depth;
+    decoded.m_mip_levels = texture.m_mip_levels;
+    decoded.m_array_layers = texture.m_array_layers;
+    decoded.m_format = decoded_format;
+    decoded.m_type = texture.m_type;
+    Ok(decoded)
+}
+
+/// Writes the 16 texels of `block` row by row into `texels`.
+fn decode_block(format: vk::Format, block: &[u8], texels: &mut [u8]) {
+    let write_rgba8 = |texels: &mut [u8], colors: &[[u8; 4]; 16]| {
+        texels[..64].copy_from_slice(colors.as_flattened());
+    };
+    match format {
+        vk::Format::BC1_RGB_UNORM_BLOCK | vk::Format::BC1_RGB_SRGB_BLOCK => {
+            write_rgba8(texels, &decode_bc1(block, false, false));
+        }
+        vk::Format::BC1_RGBA_UNORM_BLOCK | vk::Format::BC1_RGBA_SRGB_BLOCK => {
+            write_rgba8(texels, &decode_bc1(block, true, false));
+        }
+        vk::Format::BC3_UNORM_BLOCK | vk::Format::BC3_SRGB_BLOCK => {
+            let mut colors = decode_bc1(&block[8..], false, true);
+            let alpha = decode_bc4(&block[..8], false);
+            colors
+                .iter_mut()
+                .zip(alpha)
+                .for_each(|(color, alpha)| color[3] = alpha);
+            write_rgba8(texels, &colors);
+        }
+        vk::Format::BC5_UNORM_BLOCK | vk::Format::BC5_SNORM_BLOCK => {
+            let signed = format == vk::Format::BC5_SNORM_BLOCK;
+            let red = decode_bc4(&block[..8], signed);
+            let green = decode_bc4(&block[8..], signed);
+            let one = if signed { 127 } else { 255 };
+            let colors = std::array::from_fn(|i| [red[i], green[i], 0, one]);
+            write_rgba8(texels, &colors);
+        }
+        vk::Format::BC6H_UFLOAT_BLOCK | vk::Format::BC6H_SFLOAT_BLOCK => {
+            let colors = decode_bc6h(block, format == vk::Format::BC6H_SFLOAT_BLOCK);
+            texels.copy_from_slice(bytemuck::cast_slice(colors.as_flattened()));
+        }
+        vk::Format::BC7_UNORM_BLOCK | vk::Format::BC7_SRGB_BLOCK => {
+            write_rgba8(texels, &decode_bc7(block));
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// `with_alpha` keeps the punch-through texel of the three color mode transparent, BC3 always
+/// uses the four color mode.
+fn decode_bc1(block: &[u8], with_alpha: bool, four_color: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let expand = |c: u16| {
+        let (r, g, b) = (
+            (c >> 11) as u32,
+            ((c >> 5) & 0x3f) as u32,
+            (c & 0x1f) as u32,
+        );
+        [
+            (r << 3) | (r >> 2),
+            (g << 2) | (g >> 4),
+            (b << 3) | (b >> 2),
+        ]
+    };
+    let (e0, e1) = (expand(c0), expand(c1));
+    let blend = |w0: u32, w1: u32| -> [u8; 4] {
+        let channel = |i: usize| ((e0[i] * w0 + e1[i] * w1) / (w0 + w1)) as u8;
+        [channel(0), channel(1), channel(2), 255]
+    };
+
+    let palette = if four_color || c0 > c1 {
+        [blend(1, 0), blend(0, 1), blend(2, 1), blend(1, 2)]
+    } else {
+        let black = [0, 0, 0, if with_alpha { 0 } else { 255 }];
+        [blend(1, 0), blend(0, 1), blend(1, 1), black]
+    };
+    std::array::from_fn(|i| palette[((indices >> (i * 2)) & 0x3) as usize])
+}
+
+/// Single channel block, also the alpha of BC3 and both channels of BC5. Signed blocks return
+/// the two's complement bytes.
+fn decode_bc4(block: &[u8], signed: bool) -> [u8; 16] {
+    let (e0, e1) = if signed {
+        (
+            (block[0] as i8).max(-127) as i32,
+            (block[1] as i8).max(-127) as i32,
+        )
+    } else {
+        (block[0] as i32, block[1] as i32)
+    };
+    let indices = u64::from_le_bytes([
+        block[2], block[3], block[4], block[5], block[6], block[7], 0, 0,
+    ]);
+
+    let mut palette = [e0, e1, 0, 0, 0, 0, 0, 0];
+    if e0 > e1 {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i as i32) * e0 + i as i32 * e1) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i as i32) * e0 + i as i32 * e1) / 5;
+        }
+        palette[6] = if signed { -127 } else { 0 };
+        palette[7] = if signed { 127 } else { 255 };
+    }
+    std::array::from_fn(|i| palette[((indices >> (i * 3)) & 0x7) as usize] as u8)
+}
+
+fn interpolate(e0: u32, e1: u32, weight: u32) -> u32 {
+    ((64 - weight) * e0 + weight * e1 + 32) >> 6
+}
+
+fn weights(index_bits: u32) -> &'static [u32] {
+    match index_bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+fn subset_of(subsets: usize, partition: usize, texel: usize) -> usize {
+    match subsets {
+        2 => ((PARTITIONS_2[partition] >> texel) & 0x1) as usize,
+        3 => ((PARTITIONS_3[partition] >> (texel * 2)) & 0x3) as usize,
+        _ => 0,
+    }
+}
+
+fn is_anchor(subsets: usize, partition: usize, texel: usize) -> bool {
+    texel == 0
+        || match subsets {
+            2 => texel == ANCHORS_2[partition],
+            3 => texel == ANCHORS_3[0][partition] || texel == ANCHORS_3[1][partition],
+            _ => false,
+        }
+}
+
+fn decode_bc7(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut reader = BitReader::new(block);
+    // the mode is the position of the lowest set bit, blocks without one are reserved
+    let Some(mode) = (0..8).find(|_| reader.read(1) == 1) else {
+        return [[0; 4]; 16];
+    };
+    let info = &BC7_MODES[mode];
+    let partition = reader.read(info.partition_bits) as usize;
+    let rotation = reader.read(info.rotation_bits);
+    let index_selection = reader.read(info.index_selection_bits);
+
+    let endpoint_count = info.subsets * 2;
+    let mut endpoints = [[0_u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = reader.read(info.color_bits);
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        endpoint[3] = reader.read(info.alpha_bits);
+    }
+
+    let mut color_bits = info.color_bits;
+    let mut alpha_bits = info.alpha_bits;
+    if info.endpoint_pbits || info.shared_pbits {
+        let mut pbits = [0_u32; 6];
+        if info.endpoint_pbits {
+            pbits
+                .iter_mut()
+                .take(endpoint_count)
+                .for_each(|p| *p = reader.read(1));
+        } else {
+            for subset in 0..info.subsets {
+                let p = reader.read(1);
+                pbits[subset * 2] = p;
+                pbits[subset * 2 + 1] = p;
+            }
+        }
+        for (endpoint, p) in endpoints.iter_mut().zip(pbits).take(endpoint_count) {
+            for channel in endpoint.iter_mut() {
+                *channel = (*channel << 1) | p;
+            }
+        }
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    let expand = |value: u32, bits: u32| {
+        let value = value << (8 - bits);
+        value | (value >> bits)
+    };
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for channel in 0..3 {
+            endpoint[channel] = expand(endpoint[channel], color_bits);
+        }
+        endpoint[3] = if alpha_bits > 0 {
+            expand(endpoint[3], alpha_bits)
+        } else {
+            255
+        };
+    }
+
+    let mut indices = [0_u32; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(info.subsets, partition, texel) as u32;
+        *index = reader.read(info.index_bits - anchor);
+    }
+    let mut secondary_indices = [0_u32; 16];
+    if info.secondary_index_bits > 0 {
+        for (texel, index) in secondary_indices.iter_mut().enumerate() {
+            *index = reader.read(info.secondary_index_bits - (texel == 0) as u32);
+        }
+    }
+
+    std::array::from_fn(|texel| {
+        let subset = subset_of(info.subsets, partition, texel);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        let (color_weight, alpha_weight) = if info.secondary_index_bits == 0 {
+            let weight = weights(info.index_bits)[indices[texel] as usize];
+            (weight, weight)
+        } else if index_selection == 0 {
+            (
+                weights(info.index_bits)[indices[texel] as usize],
+                weights(info.secondary_index_bits)[secondary_indices[texel] as usize],
+            )
+        } else {
+            (
+                weights(info.secondary_index_bits)[secondary_indices[texel] as usize],
+                weights(info.index_bits)[indices[texel] as usize],
+            )
+        };
+        let mut color = [0_u8; 4];
+        for channel in 0..3 {
+            color[channel] = interpolate(e0[channel], e1[channel], color_weight) as u8;
+        }
+        color[3] = interpolate(e0[3], e1[3], alpha_weight) as u8;
+        if rotation > 0 {
+            color.swap(3, rotation as usize - 1);
+        }
+        color
+    })
+}
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * (-24.0_f32).exp2(),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * ((exponent - 15) as f32).exp2(),
+    }
+}
+
+fn decode_bc6h(block: &[u8], signed: bool) -> [[f32; 4]; 16] {
+    let mut reader = BitReader::new(block);
+    // endpoints w, x, y, z of each channel
+    let mut r = [0_i32; 4];
+    let mut g = [0_i32; 4];
+    let mut b = [0_i32; 4];
+
+    let mut mode = reader.read_i32(2);
+    if mode > 1 {
+        mode |= reader.read_i32(3) << 2;
+    }
+    // (endpoint bits, red, green and blue delta bits, transformed endpoints, two regions)
+    let (endpoint_bits, delta_bits, transformed, two_regions) = match mode {
+        0b00 => {
+            g[2] |= reader.read_i32(1) << 4;
+            b[2] |= reader.read_i32(1) << 4;
+            b[3] |= reader.read_i32(1) << 4;
+            r[0] |= reader.read_i32(10);
+            g[0] |= reader.read_i32(10);
+            b[0] |= reader.read_i32(10);
+            r[1] |= reader.read_i32(5);
+            g[3] |= reader.read_i32(1) << 4;
+            g[2] |= reader.read_i32(4);
+            g[1] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1);
+            g[3] |= reader.read_i32(4);
+            b[1] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 1;
+            b[2] |= reader.read_i32(4);
+            r[2] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 2;
+            r[3] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 3;
+            (10, [5, 5, 5], true, true)
+        }
+        0b01 => {
+            g[2] |= reader.read_i32(1) << 5;
+            g[3] |= reader.read_i32(1) << 4;
+            g[3] |= reader.read_i32(1) << 5;
+            r[0] |= reader.read_i32(7);
+            b[3] |= reader.read_i32(1);
+            b[3] |= reader.read_i32(1) << 1;
+            b[2] |= reader.read_i32(1) << 4;
+            g[0] |= reader.read_i32(7);
+            b[2] |= reader.read_i32(1) << 5;
+            b[3] |= reader.read_i32(1) << 2;
+            g[2] |= reader.read_i32(1) << 4;
+            b[0] |= reader.read_i32(7);
+            b[3] |= reader.read_i32(1) << 3;
+            b[3] |= reader.read_i32(1) << 5;
+            b[3] |= reader.read_i32(1) << 4;
+            r[1] |= reader.read_i32(6);
+            g[2] |= reader.read_i32(4);
+            g[1] |= reader.read_i32(6);
+            g[3] |= reader.read_i32(4);
+            b[1] |= reader.read_i32(6);
+            b[2] |= reader.read_i32(4);
+            r[2] |= reader.read_i32(6);
+            r[3] |= reader.read_i32(6);
+            (7, [6, 6, 6], true, true)
+        }
+        0b00010 => {
+            r[0] |= reader.read_i32(10);
+            g[0] |= reader.read_i32(10);
+            b[0] |= reader.read_i32(10);
+            r[1] |= reader.read_i32(5);
+            r[0] |= reader.read_i32(1) << 10;
+            g[2] |= reader.read_i32(4);
+            g[1] |= reader.read_i32(4);
+            g[0] |= reader.read_i32(1) << 10;
+            b[3] |= reader.read_i32(1);
+            g[3] |= reader.read_i32(4);
+            b[1] |= reader.read_i32(4);
+            b[0] |= reader.read_i32(1) << 10;
+            b[3] |= reader.read_i32(1) << 1;
+            b[2] |= reader.read_i32(4);
+            r[2] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 2;
+            r[3] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 3;
+            (11, [5, 4, 4], true, true)
+        }
+        0b00110 => {
+            r[0] |= reader.read_i32(10);
+            g[0] |= reader.read_i32(10);
+            b[0] |= reader.read_i32(10);
+            r[1] |= reader.read_i32(4);
+            r[0] |= reader.read_i32(1) << 10;
+            g[3] |= reader.read_i32(1) << 4;
+            g[2] |= reader.read_i32(4);
+            g[1] |= reader.read_i32(5);
+            g[0] |= reader.read_i32(1) << 10;
+            g[3] |= reader.read_i32(4);
+            b[1] |= reader.read_i32(4);
+            b[0] |= reader.read_i32(1) << 10;
+            b[3] |= reader.read_i32(1) << 1;
+            b[2] |= reader.read_i32(4);
+            r[2] |= reader.read_i32(4);
+            b[3] |= reader.read_i32(1);
+            b[3] |= reader.read_i32(1) << 2;
+            r[3] |= reader.read_i32(4);
+            g[2] |= reader.read_i32(1) << 4;
+            b[3] |= reader.read_i32(1) << 3;
+            (11, [4, 5, 4], true, true)
+        }
+        0b01010 => {
+            r[0] |= reader.read_i32(10);
+            g[0] |= reader.read_i32(10);
+            b[0] |= reader.read_i32(10);
+            r[1] |= reader.read_i32(4);
+            r[0] |= reader.read_i32(1) << 10;
+            b[2] |= reader.read_i32(1) << 4;
+            g[2] |= reader.read_i32(4);
+            g[1] |= reader.read_i32(4);
+            g[0] |= reader.read_i32(1) << 10;
+            b[3] |= reader.read_i32(1);
+            g[3] |= reader.read_i32(4);
+            b[1] |= reader.read_i32(5);
+            b[0] |= reader.read_i32(1) << 10;
+            b[2] |= reader.read_i32(4);
+            r[2] |= reader.read_i32(4);
+            b[3] |= reader.read_i32(1) << 1;
+            b[3] |= reader.read_i32(1) << 2;
+            r[3] |= reader.read_i32(4);
+            b[3] |= reader.read_i32(1) << 4;
+            b[3] |= reader.read_i32(1) << 3;
+            (11, [4, 4, 5], true, true)
+        }
+        0b01110 => {
+            r[0] |= reader.read_i32(9);
+            b[2] |= reader.read_i32(1) << 4;
+            g[0] |= reader.read_i32(9);
+            g[2] |= reader.read_i32(1) << 4;
+            b[0] |= reader.read_i32(9);
+            b[3] |= reader.read_i32(1) << 4;
+            r[1] |= reader.read_i32(5);
+            g[3] |= reader.read_i32(1) << 4;
+            g[2] |= reader.read_i32(4);
+            g[1] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1);
+            g[3] |= reader.read_i32(4);
+            b[1] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 1;
+            b[2] |= reader.read_i32(4);
+            r[2] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 2;
+            r[3] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 3;
+            (9, [5, 5, 5], true, true)
+        }
+        0b10010 => {
+            r[0] |= reader.read_i32(8);
+            g[3] |= reader.read_i32(1) << 4;
+            b[2] |= reader.read_i32(1) << 4;
+            g[0] |= reader.read_i32(8);
+            b[3] |= reader.read_i32(1) << 2;
+            g[2] |= reader.read_i32(1) << 4;
+            b[0] |= reader.read_i32(8);
+            b[3] |= reader.read_i32(1) << 3;
+            b[3] |= reader.read_i32(1) << 4;
+            r[1] |= reader.read_i32(6);
+            g[2] |= reader.read_i32(4);
+            g[1] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1);
+            g[3] |= reader.read_i32(4);
+            b[1] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 1;
+            b[2] |= reader.read_i32(4);
+            r[2] |= reader.read_i32(6);
+            r[3] |= reader.read_i32(6);
+            (8, [6, 5, 5], true, true)
+        }
+        0b10110 => {
+            r[0] |= reader.read_i32(8);
+            b[3] |= reader.read_i32(1);
+            b[2] |= reader.read_i32(1) << 4;
+            g[0] |= reader.read_i32(8);
+            g[2] |= reader.read_i32(1) << 5;
+            g[2] |= reader.read_i32(1) << 4;
+            b[0] |= reader.read_i32(8);
+            g[3] |= reader.read_i32(1) << 5;
+            b[3] |= reader.read_i32(1) << 4;
+            r[1] |= reader.read_i32(5);
+            g[3] |= reader.read_i32(1) << 4;
+            g[2] |= reader.read_i32(4);
+            g[1] |= reader.read_i32(6);
+            g[3] |= reader.read_i32(4);
+            b[1] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 1;
+            b[2] |= reader.read_i32(4);
+            r[2] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 2;
+            r[3] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 3;
+            (8, [5, 6, 5], true, true)
+        }
+        0b11010 => {
+            r[0] |= reader.read_i32(8);
+            b[3] |= reader.read_i32(1) << 1;
+            b[2] |= reader.read_i32(1) << 4;
+            g[0] |= reader.read_i32(8);
+            b[2] |= reader.read_i32(1) << 5;
+            g[2] |= reader.read_i32(1) << 4;
+            b[0] |= reader.read_i32(8);
+            b[3] |= reader.read_i32(1) << 5;
+            b[3] |= reader.read_i32(1) << 4;
+            r[1] |= reader.read_i32(5);
+            g[3] |= reader.read_i32(1) << 4;
+            g[2] |= reader.read_i32(4);
+            g[1] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1);
+            g[3] |= reader.read_i32(4);
+            b[1] |= reader.read_i32(6);
+            b[2] |= reader.read_i32(4);
+            r[2] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 2;
+            r[3] |= reader.read_i32(5);
+            b[3] |= reader.read_i32(1) << 3;
+            (8, [5, 5, 6], true, true)
+        }
+        0b11110 => {
+            r[0] |= reader.read_i32(6);
+            g[3] |= reader.read_i32(1) << 4;
+            b[3] |= reader.read_i32(1);
+            b[3] |= reader.read_i32(1) << 1;
+            b[2] |= reader.read_i32(1) << 4;
+            g[0] |= reader.read_i32(6);
+            g[2] |= reader.read_i32(1) << 5;
+            b[2] |= reader.read_i32(1) << 5;
+            b[3] |= reader.read_i32(1) << 2;
+            g[2] |= reader.read_i32(1) << 4;
+            b[0] |= reader.read_i32(6);
+            g[3] |= reader.read_i32(1) << 5;
+            b[3] |= reader.read_i32(1) << 3;
+            b[3] |= reader.read_i32(1) << 5;
+            b[3] |= reader.read_i32(1) << 4;
+            r[1] |= reader.read_i32(6);
+            g[2] |= reader.read_i32(4);
+            g[1] |= reader.read_i32(6);
+            g[3] |= reader.read_i32(4);
+            b[1] |= reader.read_i32(6);
+            b[2] |= reader.read_i32(4);
+            r[2] |= reader.read_i32(6);
+            r[3] |= reader.read_i32(6);
+            (6, [6, 6, 6], false, true)
+        }
+        0b00011 => {
+            r[0] |= reader.read_i32(10);
+            g[0] |= reader.read_i32(10);
+            b[0] |= reader.read_i32(10);
+            r[1] |= reader.read_i32(10);
+            g[1] |= reader.read_i32(10);
+            b[1] |= reader.read_i32(10);
+            (10, [10, 10, 10], false, false)
+        }
+        0b00111 => {
+            r[0] |= reader.read_i32(10);
+            g[0] |= reader.read_i32(10);
+            b[0] |= reader.read_i32(10);
+            r[1] |= reader.read_i32(9);
+            r[0] |= reader.read_i32(1) << 10;
+            g[1] |= reader.read_i32(9);
+            g[0] |= reader.read_i32(1) << 10;
+            b[1] |= reader.read_i32(9);
+            b[0] |= reader.read_i32(1) << 10;
+            (11, [9, 9, 9], true, false)
+        }
+        0b01011 | 0b01111 => {
+            let (high_bits, delta) = if mode == 0b01011 { (2, 8) } else { (6, 4) };
+            r[0] |= reader.read_i32(10);
+            g[0] |= reader.read_i32(10);
+            b[0] |= reader.read_i32(10);
+            // the high bits of the base endpoint are stored most significant bit first
+            r[1] |= reader.read_i32(delta);
+            r[0] |= (reader.read_reversed(high_bits) << 10) as i32;
+            g[1] |= reader.read_i32(delta);
+            g[0] |= (reader.read_reversed(high_bits) << 10) as i32;
+            b[1] |= reader.read_i32(delta);
+            b[0] |= (reader.read_reversed(high_bits) << 10) as i32;
+            (10 + high_bits, [delta, delta, delta], true, false)
+        }
+        // reserved modes decode to black
+        _ => return [[0.0, 0.0, 0.0, 1.0]; 16],
+    };
+    let partition = if two_regions {
+        reader.read(5) as usize
+    } else {
+        0
+    };
+
+    let endpoint_count = if two_regions { 4 } else { 2 };
+    let mask = (1 << endpoint_bits) - 1;
+    for (endpoints, delta_bits) in [&mut r, &mut g, &mut b].into_iter().zip(delta_bits) {
+        if signed {
+            endpoints[0] = sign_extend(endpoints[0], endpoint_bits);
+        }
+        let base = endpoints[0];
+        for endpoint in endpoints.iter_mut().take(endpoint_count).skip(1) {
+            if transformed {
+                *endpoint = (base + sign_extend(*endpoint, delta_bits)) & mask;
+            }
+            if signed {
+                *endpoint = sign_extend(*endpoint, endpoint_bits);
+            }
+        }
+    }
+
+    let unquantize = |value: i32| -> i32 {
+        if !signed {
+            if endpoint_bits >= 15 || value == 0 {
+                value
+            } else if value == mask {
+                0xffff
+            } else {
+                ((value << 16) + 0x8000) >> endpoint_bits
+            }
+        } else {
+            let magnitude = value.abs();
+            let unquantized = if endpoint_bits >= 16 || magnitude == 0 {
+                magnitude
+            } else if magnitude >= (1 << (endpoint_bits - 1)) - 1 {
+                0x7fff
+            } else {
+                ((magnitude << 15) + 0x4000) >> (endpoint_bits - 1)
+            };
+            if value < 0 { -unquantized } else { unquantized }
+        }
+    };
+    let to_half = |value: i32| -> u16 {
+        if !signed {
+            ((value * 31) >> 6) as u16
+        } else if value < 0 {
+            0x8000 | (((-value) * 31) >> 5) as u16
+        } else {
+            ((value * 31) >> 5) as u16
+        }
+    };
+
+    let index_bits = if two_regions { 3 } else { 4 };
+    let subsets = if two_regions { 2 } else { 1 };
+    std::array::from_fn(|texel| {
+        let anchor = is_anchor(subsets, partition, texel) as u32;
+        let weight = weights(index_bits)[reader.read(index_bits - anchor) as usize] as i32;
+        let subset = subset_of(subsets, partition, texel);
+        let channel = |endpoints: &[i32; 4]| {
+            let (e0, e1) = (
+                unquantize(endpoints[subset * 2]),
+                unquantize(endpoints[subset * 2 + 1]),
+            );
+            half_to_f32(to_half(((64 - weight) * e0 + weight * e1 + 32) >> 6))
+        };
+        [channel(&r), channel(&g), channel(&b), 1.0]
+    })
+}
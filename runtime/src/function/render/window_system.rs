@@ -96,8 +96,22 @@ impl WindowSystem {
         Ok(())
     }
 
+    /// Sets up the window state without creating an OS window. Window operations become
+    /// no-ops and the size stays at the requested one.
+    pub fn initialize_headless(&mut self, window_create_info: WindowCreateInfo) {
+        self.m_width = window_create_info.width;
+        self.m_height = window_create_info.height;
+        self.m_window = None;
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.m_window.is_none()
+    }
+
     pub fn set_title(&self, title: &str) {
-        self.m_window.as_ref().unwrap().set_title(title);
+        if let Some(window) = self.m_window.as_ref() {
+            window.set_title(title);
+        }
     }
 
     pub fn get_window(&self) -> &Rc<Window> {
@@ -109,12 +123,19 @@ impl WindowSystem {
     }
 
     pub fn get_window_size(&self) -> (u32, u32) {
-        let physical_size = self.m_window.as_ref().unwrap().inner_size();
-        (physical_size.width, physical_size.height)
+        match self.m_window.as_ref() {
+            Some(window) => {
+                let physical_size = window.inner_size();
+                (physical_size.width, physical_size.height)
+            }
+            None => (self.m_width, self.m_height),
+        }
     }
 
     pub fn request_redraw(&self) {
-        self.m_window.as_ref().unwrap().request_redraw();
+        if let Some(window) = self.m_window.as_ref() {
+            window.request_redraw();
+        }
     }
 
     pub fn request_close(&self) {
@@ -249,25 +270,22 @@ impl WindowSystem {
 
     pub fn set_focus_mode(&self, mode: bool) {
         self.m_is_focus_mode.set(mode);
+        let Some(window) = self.m_window.as_ref() else {
+            return;
+        };
         if mode {
-            self.m_window
-                .as_ref()
-                .unwrap()
-                .set_cursor_grab(CursorGrabMode::Locked)
-                .unwrap();
-            self.m_window.as_ref().unwrap().set_cursor_visible(false);
+            window.set_cursor_grab(CursorGrabMode::Locked).unwrap();
+            window.set_cursor_visible(false);
         } else {
-            self.m_window
-                .as_ref()
-                .unwrap()
-                .set_cursor_grab(CursorGrabMode::None)
-                .unwrap();
-            self.m_window.as_ref().unwrap().set_cursor_visible(true);
+            window.set_cursor_grab(CursorGrabMode::None).unwrap();
+            window.set_cursor_visible(true);
         }
     }
 
     pub fn drag_window(&self) -> Result<()> {
-        self.m_window.as_ref().unwrap().drag_window()?;
+        if let Some(window) = self.m_window.as_ref() {
+            window.drag_window()?;
+        }
         Ok(())
     }
 }
@@ -18,6 +18,9 @@ pub struct UiInputSnapshot {
     pub ime_preedit: String,
     pub key_backspace_pressed: bool,
     pub key_enter_pressed: bool,
+    /// Focus navigation step requested this frame: -1 previous, 1 next.
+    pub nav_move: i32,
+    pub nav_activate: bool,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -73,6 +76,9 @@ pub struct UiRuntime {
     hover_id: Option<u64>,
     prev_hover_id: Option<u64>,
     focused_text_input_id: Option<u64>,
+    focused_text_input_alive: bool,
+    nav_focus_index: Option<usize>,
+    nav_item_count: usize,
     menu_bar_active: bool,
     menu_cursor_x: f32,
     menu_popup_open: Option<String>,
@@ -230,6 +236,50 @@ impl UiRuntime {
         self.draw_list = UiDrawList::default();
         self.prev_hover_id = self.hover_id;
         self.hover_id = None;
+        // Drop focus of a text input that was not submitted last frame.
+        if !self.focused_text_input_alive {
+            self.focused_text_input_id = None;
+        }
+        self.focused_text_input_alive = false;
+        self.update_nav_focus();
+    }
+
+    /// Whether a focused text input should receive keyboard input instead of the game.
+    pub fn wants_keyboard_input(&self) -> bool {
+        self.focused_text_input_id.is_some() && self.focused_text_input_alive
+    }
+
+    pub fn clear_keyboard_focus(&mut self) {
+        self.focused_text_input_id = None;
+    }
+
+    fn update_nav_focus(&mut self) {
+        // Widgets register in submission order, so last frame's count bounds the focus.
+        let item_count = self.nav_item_count;
+        self.nav_item_count = 0;
+        if self.mouse_pressed(0) {
+            self.nav_focus_index = None;
+        }
+        if item_count == 0 {
+            self.nav_focus_index = None;
+            return;
+        }
+        let step = self.current_input.nav_move;
+        if step == 0 {
+            return;
+        }
+        let next = match self.nav_focus_index {
+            Some(index) => (index as i32 + step).rem_euclid(item_count as i32) as usize,
+            None if step > 0 => 0,
+            None => item_count - 1,
+        };
+        self.nav_focus_index = Some(next);
+    }
+
+    fn register_nav_item(&mut self) -> bool {
+        let index = self.nav_item_count;
+        self.nav_item_count += 1;
+        self.nav_focus_index == Some(index)
     }
 
     pub fn get_viewport(&self) -> [f32; 2] {
@@ -554,14 +604,18 @@ impl UiRuntime {
         let mouse = self.current_input.mouse_pos;
         let hovered = point_in_rect(mouse, pos, size);
         let left_pressed = self.mouse_pressed(0);
+        let nav_focused = self.register_nav_item();
 
-        if hovered && left_pressed {
+        if (hovered && left_pressed) || (nav_focused && self.current_input.nav_activate) {
             self.focused_text_input_id = Some(widget_id);
         } else if left_pressed && !hovered && self.focused_text_input_id == Some(widget_id) {
             self.focused_text_input_id = None;
         }
 
         let focused = self.focused_text_input_id == Some(widget_id);
+        if focused {
+            self.focused_text_input_alive = true;
+        }
         let mut changed = false;
         let mut submitted = false;
         if focused {
@@ -595,7 +649,7 @@ impl UiRuntime {
             pos,
             size,
             1.0,
-            if focused {
+            if focused || nav_focused {
                 [120, 170, 255, 255]
             } else {
                 [85, 100, 122, 220]
@@ -743,8 +797,10 @@ impl UiRuntime {
         if hovered && just_pressed {
             self.active_id = Some(widget_id);
         }
+        let nav_focused = self.register_nav_item();
         let pressed = self.active_id == Some(widget_id) && is_down;
-        let clicked = self.active_id == Some(widget_id) && hovered && just_released;
+        let clicked = (self.active_id == Some(widget_id) && hovered && just_released)
+            || (nav_focused && self.current_input.nav_activate);
         if self.active_id == Some(widget_id) && just_released {
             self.active_id = None;
         }
@@ -753,12 +809,22 @@ impl UiRuntime {
         let hover_for_render = hovered && self.prev_hover_id == Some(widget_id);
         let bg = if pressed {
             [70, 130, 240, 255]
-        } else if hover_for_render {
+        } else if hover_for_render || nav_focused {
             [85, 145, 255, 255]
         } else {
             [50, 100, 200, 255]
         };
         push_colored_rect(&mut self.draw_list, pos, size, bg, clip);
+        if nav_focused {
+            push_rect_border(
+                &mut self.draw_list,
+                pos,
+                size,
+                2.0,
+                [255, 220, 128, 255],
+                clip,
+            );
+        }
         // Keep button label inside bounds and vertically centered.
         let glyph = [8.0, 14.0];
         let text_x = pos[0] + 8.0;
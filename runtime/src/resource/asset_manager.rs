@@ -37,6 +37,14 @@ impl AssetManager {
         Ok(asset_json.unwrap())
     }
 
+    /// Reads a file as is, such as compiled SPIR-V.
+    pub fn load_binary(&self, asset_url: &str) -> Result<Vec<u8>> {
+        std::fs::read(self.get_full_path(asset_url)).map_err(|e| {
+            error!("Failed to read asset file {}: {}", asset_url, e);
+            anyhow!("Failed to read asset file {}: {}", asset_url, e)
+        })
+    }
+
     pub fn save_asset<AssetType: serde::Serialize>(
         &self,
         asset_url: &str,
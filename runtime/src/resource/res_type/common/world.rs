@@ -8,4 +8,7 @@ pub struct WorldRes {
     pub m_level_urls: Vec<String>,
     #[serde(rename = "default_level_url")]
     pub m_default_level_url: String,
+    /// Render pipeline preset the world is rendered with. Empty keeps the global one.
+    #[serde(rename = "render_pipeline", default)]
+    pub m_render_pipeline_url: String,
 }
@@ -1,3 +1,4 @@
 pub mod camera_config;
 pub mod material;
 pub mod mesh_data;
+pub mod render_pipeline;
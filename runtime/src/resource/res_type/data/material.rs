@@ -1,3 +1,20 @@
+/// How the alpha of the base color is used, as in glTF.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum MaterialAlphaMode {
+    #[default]
+    Opaque,
+    /// Texels below the cutoff are discarded, the rest are opaque.
+    Mask,
+    /// Drawn after the opaque meshes, back to front, blended over them.
+    Blend,
+}
+
+fn default_alpha_cutoff() -> f32 {
+    0.5
+}
+
 #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct MaterialRes {
     #[serde(rename = "base_colour_texture_file")]
@@ -10,4 +27,14 @@ pub struct MaterialRes {
     pub m_occlusion_texture_file: String,
     #[serde(rename = "emissive_texture_file")]
     pub m_emissive_texture_file: String,
+    /// Tiles along each side when the textures are atlases such as the block atlas, so their
+    /// mips stay inside each tile. Every texture of the material shares the grid, as they share
+    /// texture coordinates. 0 for ordinary textures.
+    #[serde(rename = "atlas_tile_count", default)]
+    pub m_atlas_tile_count: u32,
+    #[serde(rename = "alpha_mode", default)]
+    pub m_alpha_mode: MaterialAlphaMode,
+    /// Only used by `MaterialAlphaMode::Mask`.
+    #[serde(rename = "alpha_cutoff", default = "default_alpha_cutoff")]
+    pub m_alpha_cutoff: f32,
 }
@@ -0,0 +1,114 @@
+/// Which subpasses of the main camera pass shade the scene.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum RenderLightingPath {
+    Forward,
+    #[default]
+    Deferred,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum RenderPipelineNodeKind {
+    BasePass,
+    DeferredLighting,
+    ForwardLighting,
+    ToneMapping,
+    ColorGrading,
+    FXAA,
+    /// Temporal anti-aliasing, blending the jittered frames over time.
+    TAA,
+    /// A post pass defined entirely by the node's `shader`.
+    ShaderFullscreen,
+    UI,
+    CombineUI,
+}
+
+impl RenderPipelineNodeKind {
+    pub fn is_post_process(&self) -> bool {
+        matches!(
+            self,
+            Self::ToneMapping
+                | Self::ColorGrading
+                | Self::FXAA
+                | Self::TAA
+                | Self::ShaderFullscreen
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ShaderFullscreenInputSource {
+    SceneColor,
+    Depth,
+}
+
+/// An image a fullscreen shader reads as an input attachment. Its `input_attachment_index` is
+/// its position in `inputs`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ShaderFullscreenInput {
+    pub source: ShaderFullscreenInputSource,
+    pub binding: u32,
+    /// Bound as a `sampler2D` rather than a `subpassInput`, for shaders that read texels
+    /// around the one being shaded.
+    #[serde(default)]
+    pub sampled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ShaderFullscreenParam {
+    pub name: String,
+    pub value: f32,
+}
+
+fn default_shader_entry() -> String {
+    "main".to_string()
+}
+
+fn default_params_binding() -> u32 {
+    1
+}
+
+/// The fragment shader of a `ShaderFullscreen` node. It runs after `post_process.vert` and
+/// writes the next scene color.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ShaderFullscreenDesc {
+    pub spirv_path: String,
+    #[serde(default = "default_shader_entry")]
+    pub entry: String,
+    pub inputs: Vec<ShaderFullscreenInput>,
+    /// Uploaded in order as a std140 uniform block `float params[]`, one 16-byte element each.
+    #[serde(default)]
+    pub params: Vec<ShaderFullscreenParam>,
+    #[serde(default = "default_params_binding")]
+    pub params_binding: u32,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct RenderPipelineNode {
+    pub id: u32,
+    pub kind: RenderPipelineNodeKind,
+    pub name: String,
+    #[serde(default)]
+    pub position: [f32; 2],
+    /// Only set on `ShaderFullscreen` nodes.
+    #[serde(default)]
+    pub shader: Option<ShaderFullscreenDesc>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct RenderPipelineEdge {
+    pub from_node: u32,
+    pub from_port: String,
+    pub to_node: u32,
+    pub to_port: String,
+    #[serde(default)]
+    pub framebuffer: String,
+}
+
+/// A main camera pass preset from `asset/render_pipeline/`.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct RenderPipelineRes {
+    #[serde(default)]
+    pub lighting_path: RenderLightingPath,
+    pub nodes: Vec<RenderPipelineNode>,
+    pub edges: Vec<RenderPipelineEdge>,
+}
@@ -2,6 +2,10 @@ use crate::{core::math::vector3::Vector3, resource::res_type::data::camera_confi
 
 #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct SkyBoxIrradianceMap {
+    /// KTX2 or DDS cube map used instead of the six face maps when set. Its faces are in layer
+    /// order +X, -X, +Z, -Z, +Y, -Y, the order the face maps are uploaded in.
+    #[serde(default)]
+    pub cube_map: String,
     pub negative_x_map: String,
     pub positive_x_map: String,
     pub negative_y_map: String,
@@ -12,6 +16,10 @@ pub struct SkyBoxIrradianceMap {
 
 #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct SkyBoxSpecularMap {
+    /// KTX2 or DDS cube map used instead of the six face maps when set. Its faces are in layer
+    /// order +X, -X, +Z, -Z, +Y, -Y, the order the face maps are uploaded in.
+    #[serde(default)]
+    pub cube_map: String,
     pub negative_x_map: String,
     pub positive_x_map: String,
     pub negative_y_map: String,
@@ -26,11 +34,202 @@ pub struct DirectionalLight {
     pub color: Vector3,
 }
 
+/// Cascaded shadow map settings of the directional light.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct DirectionalLightShadow {
+    pub cascade_count: u32,
+    /// Blend between logarithmic (1.0) and uniform (0.0) cascade splits.
+    pub split_lambda: f32,
+    /// View distance covered by the last cascade, clamped to the camera far plane.
+    pub max_distance: f32,
+    /// Fraction of each cascade faded into the next one.
+    pub blend_ratio: f32,
+}
+
+impl Default for DirectionalLightShadow {
+    fn default() -> Self {
+        Self {
+            cascade_count: 3,
+            split_lambda: 0.75,
+            max_distance: 200.0,
+            blend_ratio: 0.1,
+        }
+    }
+}
+
+/// Analytic atmosphere with a day/night cycle. When enabled it replaces the skybox as the
+/// background and drives the directional light and the ambient light.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ProceduralSky {
+    pub enable: bool,
+    /// Hour in [0, 24) the level starts at. The sun rises at 6 and sets at 18.
+    pub time_of_day: f32,
+    /// In-game hours per real second, 0 stops the clock.
+    pub time_scale: f32,
+    /// Direction of sunrise in degrees counterclockwise from +X.
+    pub sun_azimuth: f32,
+    /// Tilt of the sun path away from the zenith in degrees, lowering the noon sun.
+    pub sun_path_tilt: f32,
+    /// Sunlight above the atmosphere, as a directional light intensity.
+    pub sun_intensity: f32,
+    /// Brightness of the sky and the ambient light it casts relative to the sun.
+    pub sky_intensity: f32,
+    /// Ambient light added to the sky's, what is left at night.
+    pub night_ambient_light: Vector3,
+}
+
+impl Default for ProceduralSky {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            time_of_day: 10.0,
+            time_scale: 0.0,
+            sun_azimuth: 0.0,
+            sun_path_tilt: 30.0,
+            sun_intensity: 1.5,
+            sky_intensity: 10.0,
+            night_ambient_light: Vector3::new(0.01, 0.012, 0.02),
+        }
+    }
+}
+
+/// Exponential distance and height fog over the lit scene, colored by the sky behind it.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Fog {
+    pub enable: bool,
+    /// Extinction per meter at `base_height`.
+    pub density: f32,
+    /// Height with `density`, the fog thins out above it and thickens below.
+    pub base_height: f32,
+    /// Falloff per meter of the density going up, 0 gives the same density at every height.
+    pub height_falloff: f32,
+    /// Distance from the camera the fog starts at.
+    pub start_distance: f32,
+    /// Most of the scene color the fog replaces.
+    pub max_opacity: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            density: 0.01,
+            base_height: 0.0,
+            height_falloff: 0.05,
+            start_distance: 0.0,
+            max_opacity: 1.0,
+        }
+    }
+}
+
+/// Screen-space ambient occlusion of the deferred path, darkening the ambient and image based
+/// lighting in creases and contact areas.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AmbientOcclusion {
+    pub enable: bool,
+    /// Hemisphere samples per pixel, at most 64.
+    pub sample_count: u32,
+    /// World-space radius in meters occluders are searched within.
+    pub radius: f32,
+    /// Depth difference in meters below which a sample does not count as occluded.
+    pub bias: f32,
+    /// Exponent applied to the result, above 1 darkens it.
+    pub intensity: f32,
+    /// Half size in pixels of the depth-aware blur, at most 4. 0 leaves the noise in.
+    pub blur_radius: u32,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            sample_count: 16,
+            radius: 0.5,
+            bias: 0.025,
+            intensity: 1.0,
+            blur_radius: 2,
+        }
+    }
+}
+
+/// Glow around the parts of the HDR scene brighter than a threshold, added before tone mapping.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Bloom {
+    pub enable: bool,
+    /// Scene luminance above which pixels start to glow.
+    pub threshold: f32,
+    /// Width of the soft transition around the threshold, relative to it. 0 cuts off hard.
+    pub knee: f32,
+    /// Scale of the glow added to the scene.
+    pub intensity: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.5,
+        }
+    }
+}
+
+/// Settings of the TAA pass, which runs when the render pipeline preset has a `TAA` node.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TemporalAntiAliasing {
+    /// Share of the history in each resolved frame. Higher is smoother but ghosts longer.
+    pub history_weight: f32,
+    /// Distance the camera may move in one frame before it counts as a cut, which drops the
+    /// history.
+    pub camera_cut_distance: f32,
+}
+
+impl Default for TemporalAntiAliasing {
+    fn default() -> Self {
+        Self {
+            history_weight: 0.9,
+            camera_cut_distance: 2.0,
+        }
+    }
+}
+
+/// Hardware MSAA of the forward lighting path and the UI pipeline. The deferred path ignores it.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MultisampleAntiAliasing {
+    /// Samples per pixel, a power of two the device supports. 1 turns MSAA off.
+    pub sample_count: u32,
+}
+
+impl Default for MultisampleAntiAliasing {
+    fn default() -> Self {
+        Self { sample_count: 1 }
+    }
+}
+
 #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct GlobalRenderingRes {
     pub enable_fxaa: bool,
+    /// Render pipeline preset to start with. When empty, the deferred pipeline runs tone
+    /// mapping and color grading, followed by FXAA if `enable_fxaa` is set.
+    #[serde(default)]
+    pub render_pipeline: String,
+    /// Equirectangular HDR image with +Z up. When set, the irradiance map, the specular map and
+    /// the BRDF LUT are generated from it and cached next to it, and the three maps below are
+    /// not needed.
+    #[serde(default)]
+    pub environment_map: String,
+    #[serde(default)]
     pub skybox_irradiance_map: SkyBoxIrradianceMap,
+    #[serde(default)]
     pub skybox_specular_map: SkyBoxSpecularMap,
+    #[serde(default)]
     pub brdf_map: String,
     pub color_grading_map: String,
 
@@ -38,4 +237,18 @@ pub struct GlobalRenderingRes {
     pub ambient_light: Vector3,
     pub camera_config: CameraConfig,
     pub directional_light: DirectionalLight,
+    #[serde(default)]
+    pub directional_light_shadow: DirectionalLightShadow,
+    #[serde(default)]
+    pub procedural_sky: ProceduralSky,
+    #[serde(default)]
+    pub fog: Fog,
+    #[serde(default)]
+    pub ambient_occlusion: AmbientOcclusion,
+    #[serde(default)]
+    pub bloom: Bloom,
+    #[serde(default)]
+    pub temporal_anti_aliasing: TemporalAntiAliasing,
+    #[serde(default)]
+    pub multisample_anti_aliasing: MultisampleAntiAliasing,
 }
@@ -1,13 +1,17 @@
+pub static BLOOM_DOWNSAMPLE_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\bloom_downsample.frag.spv");
+pub static BLOOM_UPSAMPLE_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\bloom_upsample.frag.spv");
 pub static COLOR_GRADING_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\color_grading.frag.spv");
 pub static COMBINE_UI_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\combine_ui.frag.spv");
 pub static DEBUGDRAW_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\debugdraw.frag.spv");
 pub static DEBUGDRAW_VERT: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\debugdraw.vert.spv");
 pub static DEFERRED_LIGHTING_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\deferred_lighting.frag.spv");
 pub static DEFERRED_LIGHTING_VERT: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\deferred_lighting.vert.spv");
+pub static DEPTH_RESOLVE_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\depth_resolve.frag.spv");
 pub static FXAA_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\fxaa.frag.spv");
 pub static FXAA_VERT: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\fxaa.vert.spv");
 pub static MESH_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\mesh.frag.spv");
 pub static MESH_VERT: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\mesh.vert.spv");
+pub static MESH_DIRECTIONAL_LIGHT_SHADOW_ALPHA_TEST_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\mesh_directional_light_shadow_alpha_test.frag.spv");
 pub static MESH_DIRECTIONAL_LIGHT_SHADOW_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\mesh_directional_light_shadow.frag.spv");
 pub static MESH_DIRECTIONAL_LIGHT_SHADOW_VERT: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\mesh_directional_light_shadow.vert.spv");
 pub static MESH_GBUFFER_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\mesh_gbuffer.frag.spv");
@@ -19,6 +23,9 @@ pub static MESH_POINT_LIGHT_SHADOW_VERT: &[u8] = include_bytes!(r"E:\program\rus
 pub static POST_PROCESS_VERT: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\post_process.vert.spv");
 pub static SKYBOX_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\skybox.frag.spv");
 pub static SKYBOX_VERT: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\skybox.vert.spv");
+pub static SSAO_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\ssao.frag.spv");
+pub static SSAO_BLUR_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\ssao_blur.frag.spv");
+pub static TAA_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\taa.frag.spv");
 pub static TONE_MAPPING_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\tone_mapping.frag.spv");
 pub static UI_FRAG: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\ui.frag.spv");
 pub static UI_VERT: &[u8] = include_bytes!(r"E:\program\rust\MuSilk-Rust-Vulkan-Minecraft\runtime\src\shader\generated\spv\ui.vert.spv");
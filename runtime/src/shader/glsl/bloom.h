@@ -0,0 +1,12 @@
+// parameters of the bloom passes, keep in sync with BloomPushConstantObject in bloom_pass.rs
+
+layout(push_constant) uniform _bloom_params
+{
+    highp vec2  target_size;
+    highp float threshold;
+    highp float knee;
+    highp float intensity;
+    highp uint  prefilter;
+};
+
+layout(set = 0, binding = 0) uniform highp sampler2D in_source;
@@ -0,0 +1,54 @@
+#version 310 es
+
+#extension GL_GOOGLE_include_directive : enable
+
+#include "constants.h"
+#include "bloom.h"
+
+layout(location = 0) out highp vec4 out_color;
+
+// keeps what is above the threshold, easing in over the knee instead of cutting off hard
+highp vec3 bloom_prefilter(highp vec3 color)
+{
+    highp float brightness = max(color.r, max(color.g, color.b));
+    highp float soft_knee  = threshold * knee + 0.0001;
+    highp float soft       = clamp(brightness - threshold + soft_knee, 0.0, 2.0 * soft_knee);
+    soft                   = soft * soft / (4.0 * soft_knee);
+    return color * max(soft, brightness - threshold) / max(brightness, 0.0001);
+}
+
+void main()
+{
+    highp vec2 uv         = gl_FragCoord.xy / target_size;
+    highp vec2 texel_size = 1.0 / vec2(textureSize(in_source, 0));
+
+    // 13 taps as four overlapping boxes around the center and one in the middle, which keeps
+    // small bright spots from flickering as they move
+    highp vec3 a = textureLod(in_source, uv + texel_size * vec2(-2.0, -2.0), 0.0).rgb;
+    highp vec3 b = textureLod(in_source, uv + texel_size * vec2(0.0, -2.0), 0.0).rgb;
+    highp vec3 c = textureLod(in_source, uv + texel_size * vec2(2.0, -2.0), 0.0).rgb;
+    highp vec3 d = textureLod(in_source, uv + texel_size * vec2(-1.0, -1.0), 0.0).rgb;
+    highp vec3 e = textureLod(in_source, uv + texel_size * vec2(1.0, -1.0), 0.0).rgb;
+    highp vec3 f = textureLod(in_source, uv + texel_size * vec2(-2.0, 0.0), 0.0).rgb;
+    highp vec3 g = textureLod(in_source, uv, 0.0).rgb;
+    highp vec3 h = textureLod(in_source, uv + texel_size * vec2(2.0, 0.0), 0.0).rgb;
+    highp vec3 i = textureLod(in_source, uv + texel_size * vec2(-1.0, 1.0), 0.0).rgb;
+    highp vec3 j = textureLod(in_source, uv + texel_size * vec2(1.0, 1.0), 0.0).rgb;
+    highp vec3 k = textureLod(in_source, uv + texel_size * vec2(-2.0, 2.0), 0.0).rgb;
+    highp vec3 l = textureLod(in_source, uv + texel_size * vec2(0.0, 2.0), 0.0).rgb;
+    highp vec3 m = textureLod(in_source, uv + texel_size * vec2(2.0, 2.0), 0.0).rgb;
+
+    highp vec3 color = (d + e + i + j) * 0.125;
+    color += (a + b + f + g) * 0.03125;
+    color += (b + c + g + h) * 0.03125;
+    color += (f + g + k + l) * 0.03125;
+    color += (g + h + l + m) * 0.03125;
+
+    if (prefilter != 0U)
+    {
+        // the half float scene may hold values far beyond what the chain can average out
+        color = bloom_prefilter(min(color, vec3(65000.0)));
+    }
+
+    out_color = vec4(color, 1.0);
+}
@@ -0,0 +1,27 @@
+#version 310 es
+
+#extension GL_GOOGLE_include_directive : enable
+
+#include "constants.h"
+#include "bloom.h"
+
+layout(location = 0) out highp vec4 out_color;
+
+void main()
+{
+    highp vec2 uv         = gl_FragCoord.xy / target_size;
+    highp vec2 texel_size = 1.0 / vec2(textureSize(in_source, 0));
+
+    // 3x3 tent filter over the smaller level, added onto the target by blending
+    highp vec3 color = textureLod(in_source, uv, 0.0).rgb * 4.0;
+    color += textureLod(in_source, uv + texel_size * vec2(-1.0, 0.0), 0.0).rgb * 2.0;
+    color += textureLod(in_source, uv + texel_size * vec2(1.0, 0.0), 0.0).rgb * 2.0;
+    color += textureLod(in_source, uv + texel_size * vec2(0.0, -1.0), 0.0).rgb * 2.0;
+    color += textureLod(in_source, uv + texel_size * vec2(0.0, 1.0), 0.0).rgb * 2.0;
+    color += textureLod(in_source, uv + texel_size * vec2(-1.0, -1.0), 0.0).rgb;
+    color += textureLod(in_source, uv + texel_size * vec2(1.0, -1.0), 0.0).rgb;
+    color += textureLod(in_source, uv + texel_size * vec2(-1.0, 1.0), 0.0).rgb;
+    color += textureLod(in_source, uv + texel_size * vec2(1.0, 1.0), 0.0).rgb;
+
+    out_color = vec4(color * (intensity / 16.0), 0.0);
+}
@@ -1,5 +1,16 @@
 #define m_max_point_light_count 15
 #define m_max_point_light_geom_vertices 90 // 90 = 2 * 3 * m_max_point_light_count
+#define m_max_directional_light_cascade_count 4
+#define m_max_spot_light_shadow_count 4
+#define m_light_cluster_x 16
+#define m_light_cluster_y 9
+#define m_light_cluster_z 24
+#define m_max_clustered_light_count 1024
+#define m_clustered_light_type_point 0U
+#define m_clustered_light_type_spot 1U
+#define m_normal_encoding_rgb 0U
+#define m_normal_encoding_rg_unorm 1U
+#define m_normal_encoding_rg_snorm 2U
 #define m_mesh_per_drawcall_max_instance_count 64
 #define m_mesh_vertex_blending_max_joint_count 1024
 #define CHAOS_LAYOUT_MAJOR column_major
@@ -34,19 +34,40 @@ layout(set = 0, binding = 0) readonly buffer _mesh_per_frame
     uint             _padding_point_light_num_3;
     PointLight       scene_point_lights[m_max_point_light_count];
     DirectionalLight scene_directional_light;
-    highp mat4       directional_light_proj_view;
+    highp mat4       directional_light_cascade_proj_view[m_max_directional_light_cascade_count];
+    highp vec4       directional_light_cascade_splits;
+    highp uint       directional_light_cascade_count;
+    highp float      directional_light_cascade_blend_ratio;
+    uint             _padding_directional_light_cascade_1;
+    uint             _padding_directional_light_cascade_2;
+    highp vec3       sky_sun_illuminance;
+    highp uint       sky_enable;
+    highp vec3       fog_sky_color;
+    highp uint       fog_enable;
+    highp float      fog_density;
+    highp float      fog_base_height;
+    highp float      fog_height_falloff;
+    highp float      fog_start_distance;
+    highp float      fog_max_opacity;
+    uint             _padding_fog_1;
+    uint             _padding_fog_2;
+    uint             _padding_fog_3;
 };
 
 layout(set = 0, binding = 3) uniform sampler2D brdfLUT_sampler;
 layout(set = 0, binding = 4) uniform samplerCube irradiance_sampler;
 layout(set = 0, binding = 5) uniform samplerCube specular_sampler;
 layout(set = 0, binding = 6) uniform highp sampler2DArray point_lights_shadow;
-layout(set = 0, binding = 7) uniform highp sampler2D directional_light_shadow;
+layout(set = 0, binding = 7) uniform highp sampler2DArray directional_light_shadow;
+
+#include "light_cluster.h"
 
 layout(input_attachment_index = 0, set = 1, binding = 0) uniform highp subpassInput in_gbuffer_a;
 layout(input_attachment_index = 1, set = 1, binding = 1) uniform highp subpassInput in_gbuffer_b;
 layout(input_attachment_index = 2, set = 1, binding = 2) uniform highp subpassInput in_gbuffer_c;
 layout(input_attachment_index = 3, set = 1, binding = 3) uniform highp subpassInput in_scene_depth;
+// blurred SSAO, white when it is off
+layout(set = 1, binding = 4) uniform highp sampler2D in_ambient_occlusion;
 
 layout(set = 2, binding = 1) uniform samplerCube skybox_sampler;
 
@@ -54,6 +75,8 @@ layout(location = 0) in highp vec2 in_texcoord;
 layout(location = 0) out highp vec4 out_color;
 
 #include "mesh_lighting.h"
+#include "sky_atmosphere.h"
+#include "fog.h"
 
 void main()
 {
@@ -68,6 +91,7 @@ void main()
     highp float metallic            = gbuffer.metallic;
     highp float dielectric_specular = 0.08 * gbuffer.specular;
     highp float roughness           = gbuffer.roughness;
+    highp float ambient_occlusion   = textureLod(in_ambient_occlusion, in_texcoord, 0.0).r;
 
     highp vec3 in_world_position;
     {
@@ -78,20 +102,38 @@ void main()
         in_world_position                    = in_world_position_with_w.xyz / in_world_position_with_w.www;
     }
 
-    highp vec3 result_color = vec3(0.0, 0.0, 0.0);
+    highp vec3  result_color = vec3(0.0, 0.0, 0.0);
+    highp float fog_amount   = 0.0;
 
     if (SHADINGMODELID_UNLIT == gbuffer.shadingModelID)
     {
         // skybox
-        highp vec3 in_UVW            = normalize(in_world_position - camera_position);
-        highp vec3 origin_sample_UVW = vec3(in_UVW.x, in_UVW.z, in_UVW.y);
-
-        result_color = textureLod(skybox_sampler, origin_sample_UVW, 0.0).rgb;
+        highp vec3 in_UVW = normalize(in_world_position - camera_position);
+        if (sky_enable != 0U)
+        {
+            result_color = sky_color(in_UVW,
+                                     normalize(scene_directional_light.direction),
+                                     sky_sun_illuminance,
+                                     scene_directional_light.color);
+        }
+        else
+        {
+            highp vec3 origin_sample_UVW = vec3(in_UVW.x, in_UVW.z, in_UVW.y);
+
+            result_color = textureLod(skybox_sampler, origin_sample_UVW, 0.0).rgb;
+        }
     }
     else if (SHADINGMODELID_DEFAULT_LIT == gbuffer.shadingModelID)
     {
 #include "mesh_lighting.inl"
+
+        if (fog_enable != 0U)
+        {
+            fog_amount   = fog_opacity(in_world_position);
+            result_color = mix(result_color, fog_color(in_world_position), fog_amount);
+        }
     }
 
-    out_color = vec4(result_color, 1.0f);
+    // the blend scales the emissive light of the base pass by alpha, so the fog fades it as well
+    out_color = vec4(result_color, 1.0 - fog_amount);
 }
@@ -0,0 +1,25 @@
+#version 310 es
+
+#extension GL_GOOGLE_include_directive : enable
+
+#include "constants.h"
+precision highp float;
+precision highp int;
+
+layout(input_attachment_index = 0, set = 0, binding = 0) uniform highp subpassInputMS in_msaa_depth;
+
+layout(push_constant) uniform _unused_name_depth_resolve
+{
+    int sample_count;
+};
+
+void main()
+{
+    // the nearest sample, so what is drawn anywhere in the pixel covers what is behind it
+    float depth = subpassLoad(in_msaa_depth, 0).r;
+    for (int i = 1; i < sample_count; ++i)
+    {
+        depth = min(depth, subpassLoad(in_msaa_depth, i).r);
+    }
+    gl_FragDepth = depth;
+}
@@ -0,0 +1,38 @@
+// exponential distance and height fog, Z up
+
+#define FOG_SKY_LOD 6.0
+
+// fraction of the scene color at world_position the fog replaces
+highp float fog_opacity(highp vec3 world_position)
+{
+    highp vec3  view_vector   = world_position - camera_position;
+    highp float view_distance = length(view_vector);
+    highp float fog_distance  = max(view_distance - fog_start_distance, 0.0);
+    highp float direction_z   = view_vector.z / max(view_distance, 0.0001);
+
+    // the density falls off exponentially with height, integrated along the ray from where the fog starts
+    highp float start_height  = camera_position.z + direction_z * fog_start_distance;
+    highp float start_density = fog_density * exp(-fog_height_falloff * (start_height - fog_base_height));
+    highp float climb         = fog_height_falloff * direction_z * fog_distance;
+    highp float height_factor = (abs(climb) > 0.0001) ? (1.0 - exp(-climb)) / climb : 1.0;
+
+    highp float optical_depth = start_density * fog_distance * height_factor;
+    return min(1.0 - exp(-optical_depth), fog_max_opacity);
+}
+
+// the sky behind world_position
+highp vec3 fog_color(highp vec3 world_position)
+{
+    if (sky_enable != 0U)
+    {
+        return fog_sky_color;
+    }
+    highp vec3 view_direction = normalize(world_position - camera_position);
+    return textureLod(specular_sampler, vec3(view_direction.x, view_direction.z, view_direction.y), FOG_SKY_LOD).rgb;
+}
+
+// fades color towards the sky behind world_position
+highp vec3 apply_fog(highp vec3 color, highp vec3 world_position)
+{
+    return mix(color, fog_color(world_position), fog_opacity(world_position));
+}
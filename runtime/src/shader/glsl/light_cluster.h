@@ -0,0 +1,38 @@
+struct ClusteredLight
+{
+    highp vec3  position;
+    highp float radius;
+    highp vec3  intensity;
+    highp uint  light_type;
+    highp vec3  direction;
+    highp float cos_outer_cone;
+    highp float cos_inner_cone;
+    highp int   shadow_index;
+    uint        _padding_shadow_index_1;
+    uint        _padding_shadow_index_2;
+};
+
+layout(set = 0, binding = 8) readonly buffer _mesh_light_cluster
+{
+    highp uint     clustered_light_count;
+    highp float    light_cluster_z_scale;
+    highp float    light_cluster_z_near;
+    uint           _padding_light_cluster_z_near;
+    highp mat4     spot_light_shadow_proj_view[m_max_spot_light_shadow_count];
+    ClusteredLight clustered_lights[m_max_clustered_light_count];
+    highp uvec2    cluster_light_grids[m_light_cluster_x * m_light_cluster_y * m_light_cluster_z];
+    highp uint     light_indices[];
+};
+
+layout(set = 0, binding = 9) uniform highp sampler2DArray spot_lights_shadow;
+
+// froxel of a main camera clip position: screen tile by exponential view-depth slice
+highp int light_cluster_index(highp vec4 position_clip)
+{
+    highp vec2 uv = clamp((position_clip.xy / position_clip.w) * 0.5 + 0.5, 0.0, 0.999999);
+    highp int  x  = int(uv.x * float(m_light_cluster_x));
+    highp int  y  = int(uv.y * float(m_light_cluster_y));
+    highp int  z  = int(log(max(position_clip.w, light_cluster_z_near) / light_cluster_z_near) * light_cluster_z_scale);
+    z             = clamp(z, 0, m_light_cluster_z - 1);
+    return x + m_light_cluster_x * (y + m_light_cluster_y * z);
+}
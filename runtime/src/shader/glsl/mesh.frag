@@ -33,14 +33,33 @@ layout(set = 0, binding = 0) readonly buffer _unused_name_perframe
     uint             _padding_point_light_num_3;
     PointLight       scene_point_lights[m_max_point_light_count];
     DirectionalLight scene_directional_light;
-    highp mat4       directional_light_proj_view;
+    highp mat4       directional_light_cascade_proj_view[m_max_directional_light_cascade_count];
+    highp vec4       directional_light_cascade_splits;
+    highp uint       directional_light_cascade_count;
+    highp float      directional_light_cascade_blend_ratio;
+    uint             _padding_directional_light_cascade_1;
+    uint             _padding_directional_light_cascade_2;
+    highp vec3       sky_sun_illuminance;
+    highp uint       sky_enable;
+    highp vec3       fog_sky_color;
+    highp uint       fog_enable;
+    highp float      fog_density;
+    highp float      fog_base_height;
+    highp float      fog_height_falloff;
+    highp float      fog_start_distance;
+    highp float      fog_max_opacity;
+    uint             _padding_fog_1;
+    uint             _padding_fog_2;
+    uint             _padding_fog_3;
 };
 
 layout(set = 0, binding = 3) uniform sampler2D brdfLUT_sampler;
 layout(set = 0, binding = 4) uniform samplerCube irradiance_sampler;
 layout(set = 0, binding = 5) uniform samplerCube specular_sampler;
 layout(set = 0, binding = 6) uniform highp sampler2DArray point_lights_shadow;
-layout(set = 0, binding = 7) uniform highp sampler2D directional_light_shadow;
+layout(set = 0, binding = 7) uniform highp sampler2DArray directional_light_shadow;
+
+#include "light_cluster.h"
 
 layout(set = 2, binding = 0) uniform _unused_name_permaterial
 {
@@ -52,8 +71,13 @@ layout(set = 2, binding = 0) uniform _unused_name_permaterial
     highp vec3  emissiveFactor;
     uint        is_blend;
     uint        is_double_sided;
+    highp float alpha_cutoff;
+    uint        normal_encoding;
 };
 
+// set by the pipelines drawing masked materials
+layout(constant_id = 0) const bool alpha_test = false;
+
 layout(set = 2, binding = 1) uniform sampler2D base_color_texture_sampler;
 layout(set = 2, binding = 2) uniform sampler2D metallic_roughness_texture_sampler;
 layout(set = 2, binding = 3) uniform sampler2D normal_texture_sampler;
@@ -65,8 +89,11 @@ layout(location = 0) in highp vec3 in_world_position;
 layout(location = 1) in highp vec3 in_normal;
 layout(location = 2) in highp vec3 in_tangent;
 layout(location = 3) in highp vec2 in_texcoord;
+layout(location = 4) in highp vec4 in_clip_position;
+layout(location = 5) in highp vec4 in_previous_clip_position;
 
 layout(location = 0) out highp vec4 out_scene_color;
+layout(location = 1) out highp vec2 out_velocity;
 
 highp vec3 getBasecolor()
 {
@@ -76,7 +103,17 @@ highp vec3 getBasecolor()
 
 highp vec3 calculateNormal()
 {
-    highp vec3 tangent_normal = texture(normal_texture_sampler, in_texcoord).xyz * 2.0 - 1.0;
+    highp vec3 normal_sample  = texture(normal_texture_sampler, in_texcoord).xyz;
+    highp vec3 tangent_normal = normal_sample * 2.0 - 1.0;
+    // two channel (BC5) normal maps only store xy, signed ones are sampled in [-1, 1] already
+    if (normal_encoding != m_normal_encoding_rgb)
+    {
+        if (normal_encoding == m_normal_encoding_rg_snorm)
+        {
+            tangent_normal.xy = normal_sample.xy;
+        }
+        tangent_normal.z = sqrt(max(1.0 - dot(tangent_normal.xy, tangent_normal.xy), 0.0));
+    }
 
     highp vec3 N = normalize(in_normal);
     highp vec3 T = normalize(in_tangent.xyz);
@@ -86,19 +123,44 @@ highp vec3 calculateNormal()
     return normalize(TBN * tangent_normal);
 }
 
+highp vec2 calculateVelocity()
+{
+    // screen uv moved since last frame, the TAA pass steps back along it to find the history
+    highp vec2 current_ndc  = in_clip_position.xy / in_clip_position.w;
+    highp vec2 previous_ndc = in_previous_clip_position.xy / in_previous_clip_position.w;
+    return (current_ndc - previous_ndc) * 0.5;
+}
+
 #include "mesh_lighting.h"
+#include "fog.h"
 
 void main()
 {
+    highp float alpha = texture(base_color_texture_sampler, in_texcoord).a * baseColorFactor.a;
+    if (alpha_test && alpha < alpha_cutoff)
+    {
+        discard;
+    }
+
     highp vec3  N                   = calculateNormal();
     highp vec3  basecolor           = getBasecolor();
     highp float metallic            = texture(metallic_roughness_texture_sampler, in_texcoord).z * metallicFactor;
     highp float dielectric_specular = 0.04;
     highp float roughness           = texture(metallic_roughness_texture_sampler, in_texcoord).y * roughnessFactor;
+    highp float ambient_occlusion   = 1.0;
 
     highp vec3 result_color;
 
 #include "mesh_lighting.inl"
 
-    out_scene_color = vec4(result_color, 1.0);
+    result_color += texture(emissive_color_texture_sampler, in_texcoord).xyz * emissiveFactor;
+
+    // after the emissive light, so glowing surfaces fade into the fog as well
+    if (fog_enable != 0U)
+    {
+        result_color = apply_fog(result_color, in_world_position);
+    }
+
+    out_scene_color = vec4(result_color, is_blend != 0u ? alpha : 1.0);
+    out_velocity    = calculateVelocity();
 }
@@ -7,34 +7,53 @@
 
 struct DirectionalLight
 {
-    vec3  direction;
-    float _padding_direction;
-    vec3  color;
-    float _padding_color;
+    highp vec3 direction;
+    lowp float _padding_direction;
+    highp vec3 color;
+    lowp float _padding_color;
 };
 
 struct PointLight
 {
-    vec3  position;
-    float radius;
-    vec3  intensity;
-    float _padding_intensity;
+    highp vec3  position;
+    highp float radius;
+    highp vec3  intensity;
+    lowp float  _padding_intensity;
 };
 
 layout(set = 0, binding = 0) readonly buffer _unused_name_perframe
 {
-    mat4             proj_view_matrix;
-    vec3             camera_position;
-    float            _padding_camera_position;
-    vec3             ambient_light;
-    float            _padding_ambient_light;
-    uint             point_light_num;
+    highp mat4       proj_view_matrix;
+    highp vec3       camera_position;
+    lowp float       _padding_camera_position;
+    highp vec3       ambient_light;
+    lowp float       _padding_ambient_light;
+    highp uint       point_light_num;
     uint             _padding_point_light_num_1;
     uint             _padding_point_light_num_2;
     uint             _padding_point_light_num_3;
     PointLight       scene_point_lights[m_max_point_light_count];
     DirectionalLight scene_directional_light;
-    highp mat4       directional_light_proj_view;
+    highp mat4       directional_light_cascade_proj_view[m_max_directional_light_cascade_count];
+    highp vec4       directional_light_cascade_splits;
+    highp uint       directional_light_cascade_count;
+    highp float      directional_light_cascade_blend_ratio;
+    uint             _padding_directional_light_cascade_1;
+    uint             _padding_directional_light_cascade_2;
+    highp vec3       sky_sun_illuminance;
+    highp uint       sky_enable;
+    highp vec3       fog_sky_color;
+    highp uint       fog_enable;
+    highp float      fog_density;
+    highp float      fog_base_height;
+    highp float      fog_height_falloff;
+    highp float      fog_start_distance;
+    highp float      fog_max_opacity;
+    uint             _padding_fog_1;
+    uint             _padding_fog_2;
+    uint             _padding_fog_3;
+    highp mat4       unjittered_proj_view_matrix;
+    highp mat4       previous_unjittered_proj_view_matrix;
 };
 
 layout(set = 0, binding = 1) readonly buffer _unused_name_per_drawcall
@@ -60,10 +79,14 @@ layout(location = 0) out vec3 out_world_position; // output in framebuffer 0 for
 layout(location = 1) out vec3 out_normal;
 layout(location = 2) out vec3 out_tangent;
 layout(location = 3) out vec2 out_texcoord;
+// clip positions without the jitter, the fragment shader takes the velocity from them
+layout(location = 4) out vec4 out_clip_position;
+layout(location = 5) out vec4 out_previous_clip_position;
 
 void main()
 {
     highp mat4  model_matrix           = mesh_instances[gl_InstanceIndex].model_matrix;
+    highp mat4  previous_model_matrix  = mesh_instances[gl_InstanceIndex].previous_model_matrix;
     highp float enable_vertex_blending = mesh_instances[gl_InstanceIndex].enable_vertex_blending;
 
     highp vec3 model_position;
@@ -120,6 +143,10 @@ void main()
 
     gl_Position = proj_view_matrix * vec4(out_world_position, 1.0f);
 
+    // skinned meshes reuse this frame's joints, so only their rigid motion gets velocity
+    out_clip_position          = unjittered_proj_view_matrix * vec4(out_world_position, 1.0f);
+    out_previous_clip_position = previous_unjittered_proj_view_matrix * previous_model_matrix * vec4(model_position, 1.0f);
+
     // TODO: normal matrix
     mat3x3 tangent_matrix = mat3x3(model_matrix[0].xyz, model_matrix[1].xyz, model_matrix[2].xyz);
     out_normal            = normalize(tangent_matrix * model_normal);
@@ -26,6 +26,10 @@ layout(set = 1, binding = 0) readonly buffer _unused_name_per_mesh_joint_binding
 };
 
 layout(location = 0) in highp vec3 in_position;
+layout(location = 3) in highp vec2 in_texcoord;
+
+// only read by the alpha-tested fragment shader
+layout(location = 0) out highp vec2 out_texcoord;
 
 void main()
 {
@@ -74,4 +78,5 @@ void main()
     highp vec3 position_world_space = (model_matrix * vec4(model_position, 1.0)).xyz;
 
     gl_Position = light_proj_view * vec4(position_world_space, 1.0f);
+    out_texcoord = in_texcoord;
 }
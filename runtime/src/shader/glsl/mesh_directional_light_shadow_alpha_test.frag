@@ -0,0 +1,32 @@
+#version 310 es
+
+layout(set = 2, binding = 0) uniform _unused_name_permaterial
+{
+    highp vec4  baseColorFactor;
+    highp float metallicFactor;
+    highp float roughnessFactor;
+    highp float normalScale;
+    highp float occlusionStrength;
+    highp vec3  emissiveFactor;
+    uint        is_blend;
+    uint        is_double_sided;
+    highp float alpha_cutoff;
+    uint        normal_encoding;
+};
+
+layout(set = 2, binding = 1) uniform sampler2D base_color_texture_sampler;
+
+layout(location = 0) in highp vec2 in_texcoord;
+
+layout(location = 0) out highp float out_depth;
+
+// no early fragment tests here, the depth of a discarded texel must not be written
+void main()
+{
+    if (texture(base_color_texture_sampler, in_texcoord).a * baseColorFactor.a < alpha_cutoff)
+    {
+        discard;
+    }
+
+    out_depth = gl_FragCoord.z;
+}
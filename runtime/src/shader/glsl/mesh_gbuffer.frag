@@ -15,8 +15,13 @@ layout(set = 2, binding = 0) uniform _unused_name_permaterial
     highp vec3  emissiveFactor;
     uint        is_blend;
     uint        is_double_sided;
+    highp float alpha_cutoff;
+    uint        normal_encoding;
 };
 
+// set by the pipelines drawing masked materials
+layout(constant_id = 0) const bool alpha_test = false;
+
 layout(set = 2, binding = 1) uniform sampler2D base_color_texture_sampler;
 layout(set = 2, binding = 2) uniform sampler2D metallic_roughness_texture_sampler;
 layout(set = 2, binding = 3) uniform sampler2D normal_texture_sampler;
@@ -28,12 +33,16 @@ layout(location = 0) in highp vec3 in_world_position;
 layout(location = 1) in highp vec3 in_normal;
 layout(location = 2) in highp vec3 in_tangent;
 layout(location = 3) in highp vec2 in_texcoord;
+layout(location = 4) in highp vec4 in_clip_position;
+layout(location = 5) in highp vec4 in_previous_clip_position;
 
 // output screen color to location 0
 layout(location = 0) out highp vec4 out_gbuffer_a;
 layout(location = 1) out highp vec4 out_gbuffer_b;
 layout(location = 2) out highp vec4 out_gbuffer_c;
-// layout(location = 3) out highp vec4 out_scene_color;
+// emissive light, the deferred lighting adds onto it
+layout(location = 3) out highp vec4 out_scene_color;
+layout(location = 4) out highp vec2 out_velocity;
 
 highp vec3 getBasecolor()
 {
@@ -41,9 +50,27 @@ highp vec3 getBasecolor()
     return basecolor;
 }
 
+highp vec2 calculateVelocity()
+{
+    // screen uv moved since last frame, the TAA pass steps back along it to find the history
+    highp vec2 current_ndc  = in_clip_position.xy / in_clip_position.w;
+    highp vec2 previous_ndc = in_previous_clip_position.xy / in_previous_clip_position.w;
+    return (current_ndc - previous_ndc) * 0.5;
+}
+
 highp vec3 calculateNormal()
 {
-    highp vec3 tangent_normal = texture(normal_texture_sampler, in_texcoord).xyz * 2.0 - 1.0;
+    highp vec3 normal_sample  = texture(normal_texture_sampler, in_texcoord).xyz;
+    highp vec3 tangent_normal = normal_sample * 2.0 - 1.0;
+    // two channel (BC5) normal maps only store xy, signed ones are sampled in [-1, 1] already
+    if (normal_encoding != m_normal_encoding_rgb)
+    {
+        if (normal_encoding == m_normal_encoding_rg_snorm)
+        {
+            tangent_normal.xy = normal_sample.xy;
+        }
+        tangent_normal.z = sqrt(max(1.0 - dot(tangent_normal.xy, tangent_normal.xy), 0.0));
+    }
 
     highp vec3 N = normalize(in_normal);
     highp vec3 T = normalize(in_tangent.xyz);
@@ -55,6 +82,11 @@ highp vec3 calculateNormal()
 
 void main()
 {
+    if (alpha_test && texture(base_color_texture_sampler, in_texcoord).a * baseColorFactor.a < alpha_cutoff)
+    {
+        discard;
+    }
+
     PGBufferData gbuffer;
     gbuffer.worldNormal    = calculateNormal();
     gbuffer.baseColor      = getBasecolor();
@@ -67,5 +99,6 @@ void main()
 
     EncodeGBufferData(gbuffer, out_gbuffer_a, out_gbuffer_b, out_gbuffer_c);
 
-    // out_scene_color.rgba = vec4(Le, 1.0);
+    out_scene_color = vec4(Le, 1.0);
+    out_velocity    = calculateVelocity();
 }
\ No newline at end of file
@@ -79,4 +79,73 @@ highp vec3 BRDF(highp vec3  L,
 
 highp vec2 ndcxy_to_uv(highp vec2 ndcxy) { return ndcxy * vec2(0.5, 0.5) + vec2(0.5, 0.5); }
 
-highp vec2 uv_to_ndcxy(highp vec2 uv) { return uv * vec2(2.0, 2.0) + vec2(-1.0, -1.0); }
\ No newline at end of file
+highp vec2 uv_to_ndcxy(highp vec2 uv) { return uv * vec2(2.0, 2.0) + vec2(-1.0, -1.0); }
+
+// 1.0 if the position is lit in the given shadow cascade, 0.0 if it is occluded
+highp float directional_light_cascade_shadow(highp int cascade_index, highp vec3 world_position)
+{
+    highp vec4 position_clip = directional_light_cascade_proj_view[cascade_index] * vec4(world_position, 1.0);
+    highp vec3 position_ndc  = position_clip.xyz / position_clip.w;
+
+    highp vec2 uv = ndcxy_to_uv(position_ndc.xy);
+
+    highp float closest_depth = texture(directional_light_shadow, vec3(uv, float(cascade_index))).r + 0.000075;
+    highp float current_depth = position_ndc.z;
+
+    return (closest_depth >= current_depth) ? 1.0 : 0.0;
+}
+
+// 1.0 if the position is lit by the point light with the given shadow map, 0.0 if it is occluded
+highp float point_light_shadow(highp int   shadow_index,
+                               highp vec3  light_position,
+                               highp float light_radius,
+                               highp vec3  world_position)
+{
+    // world space to light view space
+    // identity rotation
+    // Z - Up
+    // Y - Forward
+    // X - Right
+    highp vec3 position_view_space = world_position - light_position;
+
+    highp vec3 position_spherical_function_domain = normalize(position_view_space);
+
+    // use abs to avoid divergence
+    // z > 0
+    // (x_2d, y_2d, 0) + (0, 0, 1) = λ ((x_sph, y_sph, z_sph) + (0, 0, 1))
+    // (x_2d, y_2d) = (x_sph, y_sph) / (z_sph + 1)
+    // z < 0
+    // (x_2d, y_2d, 0) + (0, 0, -1) = λ ((x_sph, y_sph, z_sph) + (0, 0, -1))
+    // (x_2d, y_2d) = (x_sph, y_sph) / (-z_sph + 1)
+    highp vec2 position_ndcxy =
+        position_spherical_function_domain.xy / (abs(position_spherical_function_domain.z) + 1.0);
+
+    // use sign to avoid divergence
+    // -1.0 to 0
+    // 1.0 to 1
+    highp vec2  uv = ndcxy_to_uv(position_ndcxy);
+    highp float layer_index =
+        (0.5 + 0.5 * sign(position_spherical_function_domain.z)) + 2.0 * float(shadow_index);
+
+    highp float depth          = texture(point_lights_shadow, vec3(uv, layer_index)).r + 0.000075;
+    highp float closest_length = (depth)*light_radius;
+
+    highp float current_length = length(position_view_space);
+
+    return (closest_length >= current_length) ? 1.0 : 0.0;
+}
+
+// 1.0 if the position is lit by the spot light with the given shadow map, 0.0 if it is occluded
+highp float spot_light_shadow(highp int shadow_index, highp vec3 world_position)
+{
+    highp vec4 position_clip = spot_light_shadow_proj_view[shadow_index] * vec4(world_position, 1.0);
+    highp vec3 position_ndc  = position_clip.xyz / position_clip.w;
+
+    highp vec2 uv = ndcxy_to_uv(position_ndc.xy);
+
+    // perspective depth is denser near the light, so the bias is smaller than the directional one
+    highp float closest_depth = texture(spot_lights_shadow, vec3(uv, float(shadow_index))).r + 0.00001;
+    highp float current_depth = position_ndc.z;
+
+    return (closest_depth >= current_depth) ? 1.0 : 0.0;
+}
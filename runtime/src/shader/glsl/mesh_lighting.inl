@@ -6,64 +6,48 @@ highp vec3 origin_samplecube_R = vec3(R.x, R.z, R.y);
 
 highp vec3 F0 = mix(vec3(dielectric_specular, dielectric_specular, dielectric_specular), basecolor, metallic);
 
+// w of the main camera clip position is the view-space depth
+highp vec4  position_clip_main_camera = proj_view_matrix * vec4(in_world_position, 1.0);
+highp float view_depth                = position_clip_main_camera.w;
+
 // direct light specular and diffuse BRDF contribution
-highp vec3 Lo = vec3(0.0, 0.0, 0.0);
-for (highp int light_index = 0; light_index < int(point_light_num) && light_index < m_max_point_light_count;
-     ++light_index)
+// only the point and spot lights binned into this fragment's cluster can reach it
+highp vec3  Lo           = vec3(0.0, 0.0, 0.0);
+highp uvec2 cluster_grid = cluster_light_grids[light_cluster_index(position_clip_main_camera)];
+for (highp uint cluster_light_index = 0U; cluster_light_index < cluster_grid.y; ++cluster_light_index)
 {
-    highp vec3  point_light_position = scene_point_lights[light_index].position;
-    highp float point_light_radius   = scene_point_lights[light_index].radius;
+    ClusteredLight light = clustered_lights[light_indices[cluster_grid.x + cluster_light_index]];
 
-    highp vec3  L   = normalize(point_light_position - in_world_position);
+    highp vec3  L   = normalize(light.position - in_world_position);
     highp float NoL = min(dot(N, L), 1.0);
 
     // point light
-    highp float distance             = length(point_light_position - in_world_position);
+    highp float distance             = length(light.position - in_world_position);
     highp float distance_attenuation = 1.0 / (distance * distance + 1.0);
-    highp float radius_attenuation   = 1.0 - ((distance * distance) / (point_light_radius * point_light_radius));
+    highp float radius_attenuation   = 1.0 - ((distance * distance) / (light.radius * light.radius));
+
+    // spot light cone
+    highp float cone_attenuation = 1.0;
+    if (light.light_type == m_clustered_light_type_spot)
+    {
+        highp float cos_angle = dot(-L, light.direction);
+        cone_attenuation      = smoothstep(light.cos_outer_cone, max(light.cos_inner_cone, light.cos_outer_cone + 0.0001), cos_angle);
+    }
 
-    highp float light_attenuation = radius_attenuation * distance_attenuation * NoL;
+    highp float light_attenuation = radius_attenuation * distance_attenuation * cone_attenuation * NoL;
     if (light_attenuation > 0.0)
     {
-        highp float shadow;
+        highp float shadow = 1.0;
+        if (light.shadow_index >= 0)
         {
-            // world space to light view space
-            // identity rotation
-            // Z - Up
-            // Y - Forward
-            // X - Right
-            highp vec3 position_view_space = in_world_position - point_light_position;
-
-            highp vec3 position_spherical_function_domain = normalize(position_view_space);
-
-            // use abs to avoid divergence
-            // z > 0
-            // (x_2d, y_2d, 0) + (0, 0, 1) = λ ((x_sph, y_sph, z_sph) + (0, 0, 1))
-            // (x_2d, y_2d) = (x_sph, y_sph) / (z_sph + 1)
-            // z < 0
-            // (x_2d, y_2d, 0) + (0, 0, -1) = λ ((x_sph, y_sph, z_sph) + (0, 0, -1))
-            // (x_2d, y_2d) = (x_sph, y_sph) / (-z_sph + 1)
-            highp vec2 position_ndcxy =
-                position_spherical_function_domain.xy / (abs(position_spherical_function_domain.z) + 1.0);
-
-            // use sign to avoid divergence
-            // -1.0 to 0
-            // 1.0 to 1
-            highp vec2  uv = ndcxy_to_uv(position_ndcxy);
-            highp float layer_index =
-                (0.5 + 0.5 * sign(position_spherical_function_domain.z)) + 2.0 * float(light_index);
-
-            highp float depth          = texture(point_lights_shadow, vec3(uv, layer_index)).r + 0.000075;
-            highp float closest_length = (depth)*point_light_radius;
-
-            highp float current_length = length(position_view_space);
-
-            shadow = (closest_length >= current_length) ? 1.0f : -1.0f;
+            shadow = (light.light_type == m_clustered_light_type_spot) ?
+                         spot_light_shadow(light.shadow_index, in_world_position) :
+                         point_light_shadow(light.shadow_index, light.position, light.radius, in_world_position);
         }
 
         if (shadow > 0.0f)
         {
-            highp vec3 En = scene_point_lights[light_index].intensity * light_attenuation;
+            highp vec3 En = light.intensity * light_attenuation;
             Lo += BRDF(L, V, N, F0, basecolor, metallic, roughness) * En;
         }
     }
@@ -95,26 +79,46 @@ highp vec3 Libl = (kD * diffuse + specular);
 
     if (NoL > 0.0)
     {
-        highp float shadow;
+        highp float shadow = 1.0;
         {
-            highp vec4 position_clip = directional_light_proj_view * vec4(in_world_position, 1.0);
-            highp vec3 position_ndc  = position_clip.xyz / position_clip.w;
-
-            highp vec2 uv = ndcxy_to_uv(position_ndc.xy);
-
-            highp float closest_depth = texture(directional_light_shadow, uv).r + 0.000075;
-            highp float current_depth = position_ndc.z;
-
-            shadow = (closest_depth >= current_depth) ? 1.0f : -1.0f;
+            highp int cascade_count =
+                min(int(directional_light_cascade_count), m_max_directional_light_cascade_count);
+            highp int cascade_index = cascade_count;
+            for (highp int i = cascade_count - 1; i >= 0; --i)
+            {
+                if (view_depth <= directional_light_cascade_splits[i])
+                {
+                    cascade_index = i;
+                }
+            }
+
+            // beyond the last cascade everything is lit
+            if (cascade_index < cascade_count)
+            {
+                shadow = directional_light_cascade_shadow(cascade_index, in_world_position);
+
+                // fade into the next cascade (or into no shadow) over the end of this one
+                highp float cascade_near = (cascade_index > 0) ? directional_light_cascade_splits[cascade_index - 1] : 0.0;
+                highp float cascade_far  = directional_light_cascade_splits[cascade_index];
+                highp float blend_depth  = (cascade_far - cascade_near) * directional_light_cascade_blend_ratio;
+                highp float blend        = (cascade_far - view_depth) / max(blend_depth, 0.0001);
+                if (blend < 1.0)
+                {
+                    highp float next_shadow = (cascade_index + 1 < cascade_count) ?
+                                                  directional_light_cascade_shadow(cascade_index + 1, in_world_position) :
+                                                  1.0;
+                    shadow = mix(next_shadow, shadow, blend);
+                }
+            }
         }
 
         if (shadow > 0.0f)
         {
-            highp vec3 En = scene_directional_light.color * NoL;
+            highp vec3 En = scene_directional_light.color * NoL * shadow;
             Lo += BRDF(L, V, N, F0, basecolor, metallic, roughness) * En;
         }
     }
 }
 
-// result
-result_color = Lo + La + Libl;
+// result, ambient occlusion darkens only the ambient and image based light
+result_color = Lo + (La + Libl) * ambient_occlusion;
@@ -16,14 +16,39 @@ layout(set = 0, binding = 0) readonly buffer _unused_name_global_set_per_frame_b
     highp vec4 point_lights_position_and_radius[m_max_point_light_count];
 };
 
+layout(set = 2, binding = 0) uniform _unused_name_permaterial
+{
+    highp vec4  baseColorFactor;
+    highp float metallicFactor;
+    highp float roughnessFactor;
+    highp float normalScale;
+    highp float occlusionStrength;
+    highp vec3  emissiveFactor;
+    uint        is_blend;
+    uint        is_double_sided;
+    highp float alpha_cutoff;
+    uint        normal_encoding;
+};
+
+layout(set = 2, binding = 1) uniform sampler2D base_color_texture_sampler;
+
+// set by the pipeline drawing masked materials
+layout(constant_id = 0) const bool alpha_test = false;
+
 layout(location = 0) in highp float in_inv_length;
 // NOTE: we can't interpolate the length of "position_view_space" directly, otherwise the result is incorrect
 layout(location = 1) in highp vec3 in_inv_length_position_view_space;
+layout(location = 2) in highp vec2 in_texcoord;
 
 layout(location = 0) out highp float out_depth;
 
 void main()
 {
+    if (alpha_test && texture(base_color_texture_sampler, in_texcoord).a * baseColorFactor.a < alpha_cutoff)
+    {
+        discard;
+    }
+
     // perspective correct interpolation_
     highp vec3 position_view_space = in_inv_length_position_view_space / in_inv_length;
 
@@ -21,8 +21,10 @@ layout(set = 1, binding = 0) readonly buffer _unused_name_per_mesh_joint_binding
 };
 
 layout(location = 0) in highp vec3 in_position;
+layout(location = 3) in highp vec2 in_texcoord;
 
 layout(location = 0) out highp vec3 out_position_world_space;
+layout(location = 1) out highp vec2 out_texcoord;
 
 void main()
 {
@@ -69,4 +71,5 @@ void main()
     }
 
     out_position_world_space = (model_matrix * vec4(model_position, 1.0)).xyz;
+    out_texcoord             = in_texcoord;
 }
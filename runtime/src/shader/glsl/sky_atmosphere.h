@@ -0,0 +1,100 @@
+// single scattering Rayleigh and Mie atmosphere, Z up and lengths in meters
+// keep in sync with sky_atmosphere.rs, which lights the scene with the same model
+
+#define SKY_PLANET_RADIUS 6360e3
+#define SKY_ATMOSPHERE_RADIUS 6420e3
+#define SKY_VIEW_HEIGHT 1.0
+#define SKY_RAYLEIGH_SCATTERING vec3(5.8e-6, 13.5e-6, 33.1e-6)
+#define SKY_RAYLEIGH_SCALE_HEIGHT 8e3
+#define SKY_MIE_SCATTERING 21e-6
+#define SKY_MIE_EXTINCTION (SKY_MIE_SCATTERING / 0.9)
+#define SKY_MIE_SCALE_HEIGHT 1.2e3
+#define SKY_MIE_G 0.76
+#define SKY_VIEW_STEP_COUNT 16
+#define SKY_LIGHT_STEP_COUNT 8
+#define SKY_SUN_DISC_COS_RADIUS 0.99996
+#define SKY_SUN_DISC_SCALE 20.0
+
+// distance along the ray to where it leaves the sphere, the ray starts inside
+highp float sky_ray_sphere_exit(highp vec3 origin, highp vec3 direction, highp float radius)
+{
+    highp float b = dot(origin, direction);
+    highp float c = dot(origin, origin) - radius * radius;
+    return -b + sqrt(max(b * b - c, 0.0));
+}
+
+// distance along the ray to the planet, negative when the ray misses it
+highp float sky_ray_planet_hit(highp vec3 origin, highp vec3 direction)
+{
+    highp float b = dot(origin, direction);
+    highp float c = dot(origin, origin) - SKY_PLANET_RADIUS * SKY_PLANET_RADIUS;
+    highp float d = b * b - c;
+    return (d < 0.0 || b > 0.0) ? -1.0 : -b - sqrt(d);
+}
+
+// Rayleigh and Mie optical depth from a point to the top of the atmosphere
+highp vec2 sky_optical_depth_to_space(highp vec3 origin, highp vec3 direction)
+{
+    highp float step_length = sky_ray_sphere_exit(origin, direction, SKY_ATMOSPHERE_RADIUS) / float(SKY_LIGHT_STEP_COUNT);
+    highp vec2  depth       = vec2(0.0);
+    for (int i = 0; i < SKY_LIGHT_STEP_COUNT; ++i)
+    {
+        highp vec3  position = origin + direction * ((float(i) + 0.5) * step_length);
+        highp float height   = max(length(position) - SKY_PLANET_RADIUS, 0.0);
+        depth += exp(-height / vec2(SKY_RAYLEIGH_SCALE_HEIGHT, SKY_MIE_SCALE_HEIGHT)) * step_length;
+    }
+    return depth;
+}
+
+highp vec3 sky_transmittance(highp vec2 optical_depth)
+{
+    return exp(-(SKY_RAYLEIGH_SCATTERING * optical_depth.x + SKY_MIE_EXTINCTION * optical_depth.y));
+}
+
+// radiance reaching the viewer from view_direction, sun_illuminance is the sunlight above the atmosphere
+highp vec3 sky_radiance(highp vec3 view_direction, highp vec3 sun_direction, highp vec3 sun_illuminance)
+{
+    highp vec3  origin       = vec3(0.0, 0.0, SKY_PLANET_RADIUS + SKY_VIEW_HEIGHT);
+    highp float ray_length   = sky_ray_sphere_exit(origin, view_direction, SKY_ATMOSPHERE_RADIUS);
+    highp float planet_hit   = sky_ray_planet_hit(origin, view_direction);
+    if (planet_hit > 0.0)
+    {
+        ray_length = planet_hit;
+    }
+    highp float step_length  = ray_length / float(SKY_VIEW_STEP_COUNT);
+
+    highp vec2 view_depth = vec2(0.0);
+    highp vec3 rayleigh   = vec3(0.0);
+    highp vec3 mie        = vec3(0.0);
+    for (int i = 0; i < SKY_VIEW_STEP_COUNT; ++i)
+    {
+        highp vec3  position = origin + view_direction * ((float(i) + 0.5) * step_length);
+        highp float height   = max(length(position) - SKY_PLANET_RADIUS, 0.0);
+        highp vec2  density  = exp(-height / vec2(SKY_RAYLEIGH_SCALE_HEIGHT, SKY_MIE_SCALE_HEIGHT)) * step_length;
+        view_depth += density;
+
+        // points in the planet's shadow receive no sunlight
+        if (sky_ray_planet_hit(position, sun_direction) < 0.0)
+        {
+            highp vec3 transmittance = sky_transmittance(view_depth + sky_optical_depth_to_space(position, sun_direction));
+            rayleigh += density.x * transmittance;
+            mie += density.y * transmittance;
+        }
+    }
+
+    highp float mu             = dot(view_direction, sun_direction);
+    highp float rayleigh_phase = 3.0 / (16.0 * 3.14159265) * (1.0 + mu * mu);
+    highp float g2             = SKY_MIE_G * SKY_MIE_G;
+    highp float mie_phase      = 3.0 / (8.0 * 3.14159265) * ((1.0 - g2) * (1.0 + mu * mu)) /
+                            ((2.0 + g2) * pow(1.0 + g2 - 2.0 * SKY_MIE_G * mu, 1.5));
+
+    return sun_illuminance * (rayleigh * SKY_RAYLEIGH_SCATTERING * rayleigh_phase + mie * SKY_MIE_SCATTERING * mie_phase);
+}
+
+// the sky with the sun disc, sun_color is the sunlight after the atmosphere
+highp vec3 sky_color(highp vec3 view_direction, highp vec3 sun_direction, highp vec3 sun_illuminance, highp vec3 sun_color)
+{
+    highp vec3  color    = sky_radiance(view_direction, sun_direction, sun_illuminance);
+    highp float sun_disc = smoothstep(SKY_SUN_DISC_COS_RADIUS, 1.0, dot(view_direction, sun_direction));
+    return color + sun_color * (sun_disc * SKY_SUN_DISC_SCALE);
+}
@@ -4,6 +4,56 @@
 
 #include "constants.h"
 #include "gbuffer.h"
+#include "sky_atmosphere.h"
+
+struct DirectionalLight
+{
+    highp vec3 direction;
+    lowp float _padding_direction;
+    highp vec3 color;
+    lowp float _padding_color;
+};
+
+struct PointLight
+{
+    highp vec3  position;
+    highp float radius;
+    highp vec3  intensity;
+    lowp float  _padding_intensity;
+};
+
+layout(set = 0, binding = 0) readonly buffer _skybox_per_frame
+{
+    highp mat4       proj_view_matrix;
+    highp vec3       camera_position;
+    lowp float       _padding_camera_position;
+    highp vec3       ambient_light;
+    lowp float       _padding_ambient_light;
+    highp uint       point_light_num;
+    uint             _padding_point_light_num_1;
+    uint             _padding_point_light_num_2;
+    uint             _padding_point_light_num_3;
+    PointLight       scene_point_lights[m_max_point_light_count];
+    DirectionalLight scene_directional_light;
+    highp mat4       directional_light_cascade_proj_view[m_max_directional_light_cascade_count];
+    highp vec4       directional_light_cascade_splits;
+    highp uint       directional_light_cascade_count;
+    highp float      directional_light_cascade_blend_ratio;
+    uint             _padding_directional_light_cascade_1;
+    uint             _padding_directional_light_cascade_2;
+    highp vec3       sky_sun_illuminance;
+    highp uint       sky_enable;
+    highp vec3       fog_sky_color;
+    highp uint       fog_enable;
+    highp float      fog_density;
+    highp float      fog_base_height;
+    highp float      fog_height_falloff;
+    highp float      fog_start_distance;
+    highp float      fog_max_opacity;
+    uint             _padding_fog_1;
+    uint             _padding_fog_2;
+    uint             _padding_fog_3;
+};
 
 layout(set = 0, binding = 1) uniform samplerCube specular_sampler;
 
@@ -13,8 +63,19 @@ layout(location = 0) out highp vec4 out_scene_color;
 
 void main()
 {
-    highp vec3 origin_sample_UVW = vec3(in_UVW.x, in_UVW.z, in_UVW.y);
-    highp vec3 color             = textureLod(specular_sampler, origin_sample_UVW, 0.0).rgb;
+    highp vec3 color;
+    if (sky_enable != 0U)
+    {
+        color = sky_color(normalize(in_UVW),
+                          normalize(scene_directional_light.direction),
+                          sky_sun_illuminance,
+                          scene_directional_light.color);
+    }
+    else
+    {
+        highp vec3 origin_sample_UVW = vec3(in_UVW.x, in_UVW.z, in_UVW.y);
+        color                        = textureLod(specular_sampler, origin_sample_UVW, 0.0).rgb;
+    }
 
     out_scene_color = vec4(color, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,72 @@
+#version 310 es
+
+#extension GL_GOOGLE_include_directive : enable
+
+#include "constants.h"
+#include "gbuffer.h"
+#include "ssao.h"
+
+layout(set = 0, binding = 1) uniform highp sampler2D in_gbuffer_a;
+
+layout(location = 0) out highp float out_ambient_occlusion;
+
+// interleaved gradient noise, turns the kernel from pixel to pixel for the blur to average out
+highp float ssao_noise(highp vec2 pixel)
+{
+    return fract(52.9829189 * fract(dot(pixel, vec2(0.06711056, 0.00583715))));
+}
+
+void main()
+{
+    highp vec2 uv = gl_FragCoord.xy / vec2(textureSize(in_scene_depth, 0));
+
+    // the sky is never occluded
+    if (textureLod(in_scene_depth, uv, 0.0).r >= 1.0)
+    {
+        out_ambient_occlusion = 1.0;
+        return;
+    }
+
+    highp mat4  inverse_proj_view_matrix = inverse(proj_view_matrix);
+    highp vec3  P                        = ssao_world_position(inverse_proj_view_matrix, uv);
+    highp vec3  N                        = normalize(DecodeNormal(textureLod(in_gbuffer_a, uv, 0.0).xyz));
+    highp float view_distance            = length(P - camera_position);
+
+    // a tangent frame around N turned by the noise
+    highp vec3  up    = abs(N.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    highp vec3  T     = normalize(cross(up, N));
+    highp vec3  B     = cross(N, T);
+    highp float angle = 6.28318530 * ssao_noise(gl_FragCoord.xy);
+    highp mat3  TBN   = mat3(T * cos(angle) + B * sin(angle), B * cos(angle) - T * sin(angle), N);
+
+    highp uint  count     = clamp(sample_count, 1U, SSAO_MAX_SAMPLE_COUNT);
+    highp float occlusion = 0.0;
+    for (highp uint i = 0U; i < count; ++i)
+    {
+        // cosine distributed directions spiralling by the golden angle, more samples close to P
+        highp float t         = (float(i) + 0.5) / float(count);
+        highp float phi       = float(i) * 2.39996323;
+        highp float sin_theta = sqrt(t);
+        highp vec3  direction = TBN * vec3(sin_theta * cos(phi), sin_theta * sin(phi), sqrt(1.0 - t));
+        highp float scale     = fract(float(i) * 0.61803399 + 0.5);
+        highp vec3  sample_position = P + direction * (radius * mix(0.1, 1.0, scale * scale));
+
+        highp vec4 sample_clip = proj_view_matrix * vec4(sample_position, 1.0);
+        highp vec2 sample_uv   = sample_clip.xy / sample_clip.w * 0.5 + 0.5;
+        if (sample_clip.w <= 0.0 || any(lessThan(sample_uv, vec2(0.0))) || any(greaterThan(sample_uv, vec2(1.0))))
+        {
+            continue;
+        }
+
+        // the sample and the scene at its pixel lie on the same view ray, so comparing their
+        // distances to the camera tells whether the sample is buried
+        highp float sample_distance = length(sample_position - camera_position);
+        highp float scene_distance  = length(ssao_world_position(inverse_proj_view_matrix, sample_uv) - camera_position);
+
+        // geometry far in front of P does not occlude it
+        highp float range = smoothstep(0.0, 1.0, radius / max(abs(view_distance - scene_distance), 0.0001));
+        occlusion += (scene_distance <= sample_distance - bias ? 1.0 : 0.0) * range;
+    }
+
+    out_ambient_occlusion = pow(clamp(1.0 - occlusion / float(count), 0.0, 1.0), intensity);
+}
@@ -0,0 +1,25 @@
+// parameters of the SSAO and blur passes, keep in sync with SSAOPushConstantObject in ssao_pass.rs
+
+#define SSAO_MAX_SAMPLE_COUNT 64U
+#define SSAO_MAX_BLUR_RADIUS 4
+
+layout(push_constant) uniform _ssao_params
+{
+    highp mat4  proj_view_matrix;
+    highp vec3  camera_position;
+    highp float radius;
+    highp float bias;
+    highp float intensity;
+    highp uint  sample_count;
+    highp int   blur_radius;
+};
+
+layout(set = 0, binding = 0) uniform highp sampler2D in_scene_depth;
+
+// world position of the scene at a screen uv
+highp vec3 ssao_world_position(highp mat4 inverse_proj_view_matrix, highp vec2 uv)
+{
+    highp float depth    = textureLod(in_scene_depth, uv, 0.0).r;
+    highp vec4  position = inverse_proj_view_matrix * vec4(uv * 2.0 - 1.0, depth, 1.0);
+    return position.xyz / position.w;
+}
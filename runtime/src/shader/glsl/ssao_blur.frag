@@ -0,0 +1,43 @@
+#version 310 es
+
+#extension GL_GOOGLE_include_directive : enable
+
+#include "constants.h"
+#include "ssao.h"
+
+layout(set = 0, binding = 1) uniform highp sampler2D in_ambient_occlusion;
+
+layout(location = 0) out highp float out_ambient_occlusion;
+
+void main()
+{
+    highp vec2 texel_size = 1.0 / vec2(textureSize(in_scene_depth, 0));
+    highp vec2 uv         = gl_FragCoord.xy * texel_size;
+
+    if (textureLod(in_scene_depth, uv, 0.0).r >= 1.0)
+    {
+        out_ambient_occlusion = 1.0;
+        return;
+    }
+
+    highp mat4  inverse_proj_view_matrix = inverse(proj_view_matrix);
+    highp float center_distance          = length(ssao_world_position(inverse_proj_view_matrix, uv) - camera_position);
+
+    // a box blur that leaves out the taps on other surfaces, so occlusion does not bleed over edges
+    highp int   tap_radius = clamp(blur_radius, 0, SSAO_MAX_BLUR_RADIUS);
+    highp float sum        = 0.0;
+    highp float weight_sum = 0.0;
+    for (highp int y = -tap_radius; y <= tap_radius; ++y)
+    {
+        for (highp int x = -tap_radius; x <= tap_radius; ++x)
+        {
+            highp vec2  tap_uv       = clamp(uv + vec2(float(x), float(y)) * texel_size, vec2(0.0), vec2(1.0));
+            highp float tap_distance = length(ssao_world_position(inverse_proj_view_matrix, tap_uv) - camera_position);
+            highp float weight       = max(1.0 - abs(tap_distance - center_distance) / radius, 0.0);
+            sum += textureLod(in_ambient_occlusion, tap_uv, 0.0).r * weight;
+            weight_sum += weight;
+        }
+    }
+
+    out_ambient_occlusion = sum / max(weight_sum, 0.0001);
+}
@@ -5,6 +5,7 @@ struct VulkanMeshInstance
     highp float _padding_enable_vertex_blending_2;
     highp float _padding_enable_vertex_blending_3;
     highp mat4  model_matrix;
+    highp mat4  previous_model_matrix;
 };
 
 struct VulkanMeshVertexJointBinding
@@ -0,0 +1,71 @@
+#version 310 es
+
+#extension GL_GOOGLE_include_directive : enable
+
+#include "constants.h"
+precision highp float;
+precision highp int;
+
+layout(set = 0, binding = 0) uniform sampler2D in_scene_color;
+layout(set = 0, binding = 1) uniform sampler2D in_history;
+layout(set = 0, binding = 2) uniform sampler2D in_velocity;
+layout(input_attachment_index = 1, set = 0, binding = 3) uniform highp subpassInput in_scene_depth;
+
+layout(push_constant) uniform _unused_name_taa
+{
+    mat4  reprojection_matrix;
+    float history_weight;
+    uint  reset_history;
+    uint  _padding_reset_history_1;
+    uint  _padding_reset_history_2;
+};
+
+layout(location = 0) out vec4 out_color;
+
+void main()
+{
+    ivec2 pixel     = ivec2(gl_FragCoord.xy);
+    ivec2 max_pixel = textureSize(in_scene_color, 0) - 1;
+    vec2  uv        = gl_FragCoord.xy / vec2(textureSize(in_scene_color, 0));
+    vec3  current   = texelFetch(in_scene_color, pixel, 0).rgb;
+    if (reset_history != 0u)
+    {
+        out_color = vec4(current, 1.0);
+        return;
+    }
+
+    // the history is clamped to the colors around the pixel, which rejects what was disoccluded
+    vec3 neighborhood_min = current;
+    vec3 neighborhood_max = current;
+    for (int y = -1; y <= 1; ++y)
+    {
+        for (int x = -1; x <= 1; ++x)
+        {
+            vec3 neighbor    = texelFetch(in_scene_color, clamp(pixel + ivec2(x, y), ivec2(0), max_pixel), 0).rgb;
+            neighborhood_min = min(neighborhood_min, neighbor);
+            neighborhood_max = max(neighborhood_max, neighbor);
+        }
+    }
+
+    vec2  history_uv;
+    float depth = subpassLoad(in_scene_depth).r;
+    if (depth >= 1.0)
+    {
+        // the sky writes no velocity, it only moves with the camera
+        vec4 previous_position = reprojection_matrix * vec4(uv * 2.0 - 1.0, depth, 1.0);
+        history_uv             = previous_position.xy / previous_position.w * 0.5 + 0.5;
+    }
+    else
+    {
+        history_uv = uv - texelFetch(in_velocity, pixel, 0).xy;
+    }
+
+    if (any(lessThan(history_uv, vec2(0.0))) || any(greaterThan(history_uv, vec2(1.0))))
+    {
+        out_color = vec4(current, 1.0);
+        return;
+    }
+
+    vec3 history = clamp(textureLod(in_history, history_uv, 0.0).rgb, neighborhood_min, neighborhood_max);
+    out_color    = vec4(mix(current, history, history_weight), 1.0);
+}
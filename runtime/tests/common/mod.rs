@@ -0,0 +1,84 @@
+use std::{cell::RefCell, path::Path};
+
+use runtime::{
+    app::App,
+    core::math::{quaternion::Quaternion, transform::Transform, vector3::Vector3},
+    engine::Engine,
+    function::framework::{
+        component::{
+            camera_component::CameraComponent, component::ComponentTrait,
+            mesh::mesh_component::MeshComponent, transform_component::TransformComponent,
+        },
+        scene::scene::{Scene, SceneTrait},
+    },
+};
+
+pub const DELTA_TIME: f32 = 1.0 / 60.0;
+
+/// A free camera looking at one static mesh.
+struct StaticScene {
+    scene: Scene,
+}
+
+impl StaticScene {
+    fn new() -> Self {
+        let mut scene = Scene::new();
+        scene.set_url("headless_static");
+        Self { scene }
+    }
+}
+
+impl SceneTrait for StaticScene {
+    fn load(&mut self, engine: &Engine) {
+        let mut camera = Box::new(CameraComponent::new_free_camera());
+        camera.look_at(
+            Vector3::new(0.0, -5.0, 2.0),
+            &Vector3::ZERO,
+            &Vector3::UNIT_Z,
+        );
+        self.scene
+            .create_object(vec![RefCell::new(camera as Box<dyn ComponentTrait>)]);
+
+        let asset_manager = engine.asset_manager();
+        let mesh_res = asset_manager
+            .load_asset("asset/greedy_snake/head.json")
+            .unwrap();
+        let mut mesh = Box::new(MeshComponent::default());
+        mesh.post_load_resource(&asset_manager, &mesh_res);
+        let mut transform = Box::new(TransformComponent::default());
+        transform.post_load_resource(Transform::new(
+            Vector3::ZERO,
+            Quaternion::identity(),
+            Vector3::ONES,
+        ));
+        self.scene.create_object(vec![
+            RefCell::new(transform as Box<dyn ComponentTrait>),
+            RefCell::new(mesh as Box<dyn ComponentTrait>),
+        ]);
+        self.scene.set_loaded(true);
+    }
+
+    fn save(&self) {}
+
+    fn tick(&mut self, engine: &Engine, delta_time: f32) {
+        self.scene.tick(engine, delta_time);
+    }
+
+    fn get_url(&self) -> String {
+        self.scene.get_url().clone()
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.scene.is_loaded()
+    }
+}
+
+/// An app showing `StaticScene`, still to be initialized headless or offscreen.
+pub fn static_scene_app() -> App {
+    let config_file_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../configs/PiccoloTest.ini");
+    let mut app = App::with_config(&config_file_path);
+    app.add_scene(StaticScene::new());
+    app.set_default_scene("headless_static");
+    app.set_fixed_delta_time(Some(DELTA_TIME));
+    app
+}
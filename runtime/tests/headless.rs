@@ -0,0 +1,27 @@
+mod common;
+
+use common::{DELTA_TIME, static_scene_app};
+
+#[test]
+fn headless_steps_scene_and_consumes_swap_data() {
+    let mut app = static_scene_app();
+    app.initialize_headless();
+    assert!(app.engine().is_headless());
+
+    // the render side consumes what the logic side wrote one frame earlier
+    assert_eq!(app.step_frames(2, DELTA_TIME).unwrap(), 2);
+    {
+        let render_system = app.engine().render_system().borrow();
+        let swap_data = render_system.get_last_consumed_swap_data();
+        assert!(swap_data.m_camera_swap_data.is_some());
+        let dirty_objects = swap_data.m_game_object_resource_descs.as_ref().unwrap();
+        assert!(!dirty_objects.is_empty());
+    }
+
+    // nothing moves, so later frames only carry the camera
+    assert_eq!(app.step_frames(10, DELTA_TIME).unwrap(), 10);
+    let render_system = app.engine().render_system().borrow();
+    let swap_data = render_system.get_last_consumed_swap_data();
+    assert!(swap_data.m_camera_swap_data.is_some());
+    assert!(swap_data.m_game_object_resource_descs.is_none());
+}
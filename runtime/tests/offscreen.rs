@@ -0,0 +1,41 @@
+mod common;
+
+use std::path::Path;
+
+use common::{DELTA_TIME, static_scene_app};
+use runtime::function::render::window_system::WindowCreateInfo;
+
+/// Channels may drift this much between drivers before a pixel counts as changed.
+const CHANNEL_TOLERANCE: u8 = 8;
+
+#[test]
+#[cfg_attr(
+    not(feature = "vulkan-tests"),
+    ignore = "needs a Vulkan driver, run with --features vulkan-tests"
+)]
+fn static_scene_matches_reference_image() {
+    let mut app = static_scene_app();
+    app.set_window_create_info(WindowCreateInfo {
+        width: 320,
+        height: 240,
+        ..Default::default()
+    });
+    app.initialize_offscreen();
+    // enough frames for the meshes to upload and the TAA history to settle
+    assert_eq!(app.step_frames(16, DELTA_TIME).unwrap(), 16);
+
+    let reference_path =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/reference/static_scene.png");
+    let mismatch = app
+        .engine()
+        .render_system()
+        .borrow()
+        .compare_frame_with_reference(&reference_path, CHANNEL_TOLERANCE)
+        .unwrap();
+    assert!(
+        mismatch < 0.01,
+        "{:.2}% of pixels changed",
+        mismatch * 100.0
+    );
+    app.engine().shutdown_engine();
+}
@@ -0,0 +1,101 @@
+use runtime::function::render::{
+    render_type::TextureData,
+    texture_container::{load_texture_container, save_ktx2},
+};
+use vulkanalia::prelude::v1_0::*;
+
+/// A 4x4 RGBA32F texture with its full chain of three levels, saved as KTX2.
+fn ktx2_with_mips() -> Vec<u8> {
+    let mut texture = TextureData::default();
+    texture.m_width = 4;
+    texture.m_height = 4;
+    texture.m_mip_levels = 3;
+    texture.m_array_layers = 1;
+    texture.m_format = vk::Format::R32G32B32A32_SFLOAT;
+    texture.m_pixels = vec![1; 4 * 4 * 16];
+    texture.m_mip_pixels = vec![vec![2; 2 * 2 * 16], vec![3; 16]];
+    save_ktx2(&texture).unwrap()
+}
+
+/// A DDS header for a 4x4 RGBA8 texture claiming `mip_map_count` levels, without any data.
+fn dds_header(mip_map_count: u32) -> Vec<u8> {
+    let mut bytes = vec![0; 128];
+    let mut write = |offset: usize, value: u32| {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+    write(4, 124);
+    // DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_MIPMAPCOUNT
+    write(8, 0x1 | 0x2 | 0x4 | 0x1000 | 0x20000);
+    write(12, 4);
+    write(16, 4);
+    write(28, mip_map_count);
+    write(76, 32);
+    // DDPF_RGB | DDPF_ALPHAPIXELS, 32 bits in RGBA order
+    write(80, 0x40 | 0x1);
+    write(88, 32);
+    write(92, 0x000000ff);
+    write(96, 0x0000ff00);
+    write(100, 0x00ff0000);
+    write(104, 0xff000000);
+    bytes[..4].copy_from_slice(b"DDS ");
+    bytes
+}
+
+fn load_error(bytes: &[u8]) -> String {
+    match load_texture_container(bytes) {
+        Ok(_) => panic!("the texture container loaded"),
+        Err(err) => err.to_string(),
+    }
+}
+
+#[test]
+fn ktx2_levels_round_trip() {
+    let texture = load_texture_container(&ktx2_with_mips()).unwrap();
+    assert_eq!(texture.m_mip_levels, 3);
+    assert_eq!(texture.m_pixels, vec![1; 4 * 4 * 16]);
+    assert_eq!(texture.m_mip_pixels, [vec![2; 2 * 2 * 16], vec![3; 16]]);
+}
+
+#[test]
+fn ktx2_half_float_round_trip() {
+    let mut texture = TextureData::default();
+    texture.m_width = 2;
+    texture.m_height = 2;
+    texture.m_mip_levels = 1;
+    texture.m_array_layers = 1;
+    texture.m_format = vk::Format::R16G16_SFLOAT;
+    texture.m_pixels = (0..2 * 2 * 4).collect();
+    let loaded = load_texture_container(&save_ktx2(&texture).unwrap()).unwrap();
+    assert_eq!(loaded.m_format, vk::Format::R16G16_SFLOAT);
+    assert_eq!(loaded.m_pixels, texture.m_pixels);
+}
+
+#[test]
+fn ktx2_with_more_levels_than_its_size_is_rejected() {
+    let mut bytes = ktx2_with_mips();
+    bytes[40..44].copy_from_slice(&40u32.to_le_bytes());
+    assert_eq!(
+        load_error(&bytes),
+        "40 mip levels are more than a 4x4 texture has"
+    );
+}
+
+#[test]
+fn ktx2_level_past_the_address_space_is_truncated() {
+    let mut bytes = ktx2_with_mips();
+    bytes[80..88].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert_eq!(load_error(&bytes), "KTX2 mip level 0 is truncated");
+}
+
+#[test]
+fn dds_mip_map_count_is_bounded_by_its_size() {
+    assert_eq!(
+        load_error(&dds_header(1000)),
+        "1000 mip levels are more than a 4x4 texture has"
+    );
+
+    let mut bytes = dds_header(3);
+    assert_eq!(load_error(&bytes), "DDS texture is truncated");
+    bytes.resize(128 + (16 + 4 + 1) * 4, 0);
+    assert_eq!(load_texture_container(&bytes).unwrap().m_mip_levels, 3);
+}
@@ -1,6 +1,6 @@
 use std::cell::RefCell;
 
-use rand::Rng;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use runtime::ComponentTrait;
 use runtime::engine::Engine;
 use runtime::function::framework::object::object_id_allocator::GObjectID;
@@ -24,6 +24,8 @@ const SNAKE_Z: f32 = 0.0;
 const MOVE_SPEED: f32 = 6.0;
 const MAX_LOGIC_STEPS_PER_TICK: usize = 8;
 const HIDDEN_Z: f32 = -1000.0;
+/// 录制/回放输入时使用固定种子，保证食物位置可复现
+const REPLAY_RNG_SEED: u64 = 0x5EED_5A4E;
 
 #[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Hash)]
 struct GridPos {
@@ -83,10 +85,11 @@ struct SnakeState {
     body_ids: Vec<GObjectID>,
     food_id: GObjectID,
     food_cell: GridPos,
+    rng: StdRng,
 }
 
 impl SnakeState {
-    fn new(head_id: GObjectID, body_ids: Vec<GObjectID>, food_id: GObjectID) -> Self {
+    fn new(head_id: GObjectID, body_ids: Vec<GObjectID>, food_id: GObjectID, rng: StdRng) -> Self {
         let mut state = Self {
             accumulator: 0.0,
             direction: Direction::Right,
@@ -96,6 +99,7 @@ impl SnakeState {
             body_ids,
             food_id,
             food_cell: GridPos::new(0, 0),
+            rng,
         };
         state.reset_snake();
         state
@@ -169,8 +173,16 @@ fn setup(scene: &mut Scene, engine: &Engine) {
         spawn_segment_entity(&mut scene.scene, engine, 1),
     ];
     let food_id = spawn_food_entity(&mut scene.scene, engine);
-    let mut state = SnakeState::new(head_id, body_ids, food_id);
-    state.food_cell = random_free_cell(&state.cells);
+    let rng = {
+        let input_record_system = engine.input_record_system().borrow();
+        if input_record_system.is_recording() || input_record_system.is_replaying() {
+            StdRng::seed_from_u64(REPLAY_RNG_SEED)
+        } else {
+            StdRng::from_os_rng()
+        }
+    };
+    let mut state = SnakeState::new(head_id, body_ids, food_id, rng);
+    state.food_cell = random_free_cell(&state.cells, &mut state.rng);
 
     scene.scene.add_resource(state);
     sync_entity_transforms(scene);
@@ -270,7 +282,7 @@ fn snake_step(scene: &mut Scene, engine: &Engine) {
             state.cells.insert(0, next);
             if next == state.food_cell {
                 ate_food = true;
-                state.food_cell = random_free_cell(&state.cells);
+                state.food_cell = random_free_cell(&state.cells, &mut state.rng);
             } else {
                 state.cells.pop();
             }
@@ -280,7 +292,7 @@ fn snake_step(scene: &mut Scene, engine: &Engine) {
     if need_reset {
         let state = scene.scene.get_mut_resource::<SnakeState>().unwrap();
         state.reset_snake();
-        state.food_cell = random_free_cell(&state.cells);
+        state.food_cell = random_free_cell(&state.cells, &mut state.rng);
         return;
     }
 
@@ -343,8 +355,7 @@ fn sync_entity_transforms(scene: &mut Scene) {
     }
 }
 
-fn random_free_cell(occupied: &[GridPos]) -> GridPos {
-    let mut rng = rand::rng();
+fn random_free_cell(occupied: &[GridPos], rng: &mut StdRng) -> GridPos {
     for _ in 0..128 {
         let x = rng.random_range(0..GRID_SIZE);
         let y = rng.random_range(0..GRID_SIZE);
@@ -500,5 +511,41 @@ fn main() {
     let mut app = App::new();
     app.add_scene(Scene::new());
     app.set_default_scene("greedy_snake");
+    app.apply_command_line_args().unwrap();
     app.run();
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn snake_steps_headless() {
+        let config_file_path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/PiccoloTest.ini");
+        let mut app = App::with_config(&config_file_path);
+        app.add_scene(Scene::new());
+        app.set_default_scene("greedy_snake");
+        app.initialize_headless();
+
+        // the spawned entities reach the render side one frame after the scene loads
+        assert_eq!(app.step_frames(2, 1.0 / 60.0).unwrap(), 2);
+        {
+            let render_system = app.engine().render_system().borrow();
+            let swap_data = render_system.get_last_consumed_swap_data();
+            assert!(swap_data.m_camera_swap_data.is_some());
+            assert!(swap_data.m_game_object_resource_descs.is_some());
+        }
+
+        assert_eq!(app.step_frames(120, 1.0 / 60.0).unwrap(), 120);
+        let render_system = app.engine().render_system().borrow();
+        assert!(
+            render_system
+                .get_last_consumed_swap_data()
+                .m_camera_swap_data
+                .is_some()
+        );
+    }
+}
@@ -48,6 +48,7 @@ fn slot_to_block(slot: u8) -> VoxelKind {
         5 => VoxelKind::Brick,
         6 => VoxelKind::Log,
         7 => VoxelKind::Leaves,
+        8 => VoxelKind::Glass,
         _ => VoxelKind::Dirt,
     }
 }
@@ -100,6 +101,7 @@ impl GameScene {
             VoxelKind::Brick => (6, 0),
             VoxelKind::Log => (7, 0),
             VoxelKind::Leaves => (9, 0),
+            VoxelKind::Glass => (10, 0),
             _ => (2, 0),
         };
         const ATLAS: f32 = 16.0;
@@ -113,9 +115,9 @@ impl GameScene {
     fn draw_hotbar_ui(&mut self, engine: &Engine) {
         let selected_slot = {
             let input = engine.input_system().borrow();
-            input.get_selected_block_slot().clamp(1, 7)
+            input.get_selected_block_slot().clamp(1, 8)
         };
-        let slots: [VoxelKind; 7] = [
+        let slots: [VoxelKind; 8] = [
             VoxelKind::Dirt,
             VoxelKind::Stone,
             VoxelKind::Sand,
@@ -123,6 +125,7 @@ impl GameScene {
             VoxelKind::Brick,
             VoxelKind::Log,
             VoxelKind::Leaves,
+            VoxelKind::Glass,
         ];
 
         let mut ui = engine.ui_runtime().borrow_mut();
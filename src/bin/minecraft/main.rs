@@ -9,5 +9,42 @@ fn main() {
     let mut app = App::new();
     app.add_scene(game_scene::GameScene::new());
     app.set_default_scene("MinecraftAI");
+    app.apply_command_line_args().unwrap();
     app.run();
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn voxel_world_steps_headless() {
+        let config_file_path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/PiccoloTest.ini");
+        let mut app = App::with_config(&config_file_path);
+        app.add_scene(game_scene::GameScene::new());
+        app.set_default_scene("MinecraftAI");
+        app.initialize_headless();
+
+        // the chunks around the spawn reach the render side one frame after the scene loads
+        assert_eq!(app.step_frames(2, 1.0 / 60.0).unwrap(), 2);
+        {
+            let render_system = app.engine().render_system().borrow();
+            let swap_data = render_system.get_last_consumed_swap_data();
+            assert!(swap_data.m_camera_swap_data.is_some());
+            let chunks = swap_data.m_game_object_resource_descs.as_ref().unwrap();
+            assert!(!chunks.is_empty());
+        }
+
+        assert_eq!(app.step_frames(60, 1.0 / 60.0).unwrap(), 60);
+        let render_system = app.engine().render_system().borrow();
+        assert!(
+            render_system
+                .get_last_consumed_swap_data()
+                .m_camera_swap_data
+                .is_some()
+        );
+    }
+}
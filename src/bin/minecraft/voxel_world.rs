@@ -61,8 +61,31 @@ pub enum VoxelKind {
     Log = 7,
     #[serde(alias = "Glow")]
     Leaves = 8,
+    Glass = 9,
 }
 
+impl VoxelKind {
+    /// 是否完全遮住相邻面；树叶镂空、玻璃半透明，后面的面仍需生成。
+    fn is_opaque(self) -> bool {
+        !matches!(self, VoxelKind::Air | VoxelKind::Leaves | VoxelKind::Glass)
+    }
+
+    /// 该方块的面归入哪个区块部件。
+    fn chunk_part(self) -> usize {
+        match self {
+            VoxelKind::Leaves => CUTOUT_PART,
+            VoxelKind::Glass => BLEND_PART,
+            _ => OPAQUE_PART,
+        }
+    }
+}
+
+/// 区块网格的部件，顺序与 `block.json` 的子网格一致：不透明方块、走 alpha 测试的树叶，以及混合绘制的玻璃。
+const OPAQUE_PART: usize = 0;
+const CUTOUT_PART: usize = 1;
+const BLEND_PART: usize = 2;
+const CHUNK_PART_COUNT: usize = 3;
+
 fn world_chunk_coords(wx: i32, wy: i32) -> (i32, i32) {
     (wx.div_euclid(CHUNK_SIZE), wy.div_euclid(CHUNK_SIZE))
 }
@@ -298,8 +321,10 @@ fn atlas_cell(kind: VoxelKind, face: usize) -> (u32, u32) {
             4 | 5 => (8, 0),
             _ => (7, 0),
         },
-        // 树叶先按不透明处理；材质仍走当前 block.material.json。
+        // 树叶走 block_cutout.material.json 的 alpha 测试，镂空取决于图集该格的 alpha 通道。
         VoxelKind::Leaves => (9, 0),
+        // 玻璃走 block_blend.material.json，按图集该格的 alpha 与后面的画面混合。
+        VoxelKind::Glass => (10, 0),
         VoxelKind::Air => (0, 0),
     }
 }
@@ -349,7 +374,8 @@ fn emit_faces_for_cell(
     ];
     for (fi, &(dx, dy, dz)) in neighbors.iter().enumerate() {
         let nk = get(wx + dx, wy + dy, wz + dz);
-        if nk != VoxelKind::Air {
+        // 连成一片的玻璃不生成内部的面，否则混合时会叠出一层层接缝。
+        if nk.is_opaque() || (nk == kind && kind == VoxelKind::Glass) {
             continue;
         }
         let cell = atlas_cell(kind, fi);
@@ -709,13 +735,14 @@ fn emit_faces_for_cell(
     }
 }
 
+/// 按 [`OPAQUE_PART`]、[`CUTOUT_PART`]、[`BLEND_PART`] 的顺序返回区块的各部件网格，没有面的部件网格为空。
 fn build_chunk_mesh(
     chunk_cx: i32,
     chunk_cy: i32,
     overrides: &HashMap<(i32, i32, i32), VoxelKind>,
-) -> GameObjectDynamicMeshDesc {
-    let mut verts = Vec::new();
-    let mut indices = Vec::new();
+) -> [GameObjectDynamicMeshDesc; CHUNK_PART_COUNT] {
+    let mut verts: [Vec<MeshVertexDataDefinition>; CHUNK_PART_COUNT] = Default::default();
+    let mut indices: [Vec<u32>; CHUNK_PART_COUNT] = Default::default();
     let get = |x: i32, y: i32, z: i32| {
         if let Some(&k) = overrides.get(&(x, y, z)) {
             k
@@ -734,6 +761,7 @@ fn build_chunk_mesh(
                 if k == VoxelKind::Air {
                     continue;
                 }
+                let part = k.chunk_part();
                 emit_faces_for_cell(
                     Vector3::new(lx as f32, ly as f32, lz as f32),
                     wx,
@@ -741,23 +769,32 @@ fn build_chunk_mesh(
                     lz,
                     k,
                     &get,
-                    &mut verts,
-                    &mut indices,
+                    &mut verts[part],
+                    &mut indices[part],
                 );
             }
         }
     }
-    let mut mesh = GameObjectDynamicMeshDesc::default();
-    mesh.m_is_dirty = true;
-    mesh.m_mesh_file = format!("minecraft_chunk_{}_{}.mesh", chunk_cx, chunk_cy);
-    mesh.m_vertices = verts;
-    mesh.m_indices = indices;
-    mesh
+    let mesh_files = [
+        format!("minecraft_chunk_{}_{}.mesh", chunk_cx, chunk_cy),
+        format!("minecraft_chunk_{}_{}_cutout.mesh", chunk_cx, chunk_cy),
+        format!("minecraft_chunk_{}_{}_blend.mesh", chunk_cx, chunk_cy),
+    ];
+    let mut parts = verts.into_iter().zip(indices).zip(mesh_files);
+    std::array::from_fn(|_| {
+        let ((verts, indices), mesh_file) = parts.next().unwrap();
+        let mut mesh = GameObjectDynamicMeshDesc::default();
+        mesh.m_is_dirty = true;
+        mesh.m_mesh_file = mesh_file;
+        mesh.m_vertices = verts;
+        mesh.m_indices = indices;
+        mesh
+    })
 }
 
 struct ChunkRenderEntry {
     object_id: GObjectID,
-    mesh: Rc<RefCell<GameObjectDynamicMeshDesc>>,
+    meshes: [Rc<RefCell<GameObjectDynamicMeshDesc>>; CHUNK_PART_COUNT],
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -778,14 +815,16 @@ pub struct VoxelWorld {
 
 impl VoxelWorld {
     fn create_chunk_object(&mut self, engine: &Engine, scene: &mut Scene, cx: i32, cy: i32) {
-        let mesh = Rc::new(RefCell::new(build_chunk_mesh(cx, cy, &self.overrides)));
+        let meshes =
+            build_chunk_mesh(cx, cy, &self.overrides).map(|mesh| Rc::new(RefCell::new(mesh)));
         let mut mesh_component = Box::new(MeshComponent::default());
         mesh_component.post_load_resource(engine.asset_manager(), &self.mesh_res);
         mesh_component
             .m_raw_meshes
-            .resize(1, GameObjectPartDesc::default());
-        mesh_component.m_raw_meshes[0].m_mesh_desc =
-            GameObjectMeshDesc::DynamicMesh(Rc::clone(&mesh));
+            .resize(CHUNK_PART_COUNT, GameObjectPartDesc::default());
+        for (raw_mesh, mesh) in mesh_component.m_raw_meshes.iter_mut().zip(&meshes) {
+            raw_mesh.m_mesh_desc = GameObjectMeshDesc::DynamicMesh(Rc::clone(mesh));
+        }
 
         let mut transform = Box::new(TransformComponent::default());
         transform.post_load_resource(Transform::new(
@@ -799,12 +838,15 @@ impl VoxelWorld {
             RefCell::new(transform),
         ]);
         self.loaded_chunks
-            .insert((cx, cy), ChunkRenderEntry { object_id, mesh });
+            .insert((cx, cy), ChunkRenderEntry { object_id, meshes });
     }
 
     fn rebuild_loaded_chunk(&mut self, cx: i32, cy: i32) {
         if let Some(entry) = self.loaded_chunks.get(&(cx, cy)) {
-            *entry.mesh.borrow_mut() = build_chunk_mesh(cx, cy, &self.overrides);
+            let meshes = build_chunk_mesh(cx, cy, &self.overrides);
+            for (entry_mesh, mesh) in entry.meshes.iter().zip(meshes) {
+                *entry_mesh.borrow_mut() = mesh;
+            }
         }
     }
 